@@ -1 +1,222 @@
 \echo Use "ALTER EXTENSION pg_search UPDATE TO '0.14.1'" to load this file. \quit
+/* <begin connected objects> */
+-- pg_search/src/bootstrap/create_bm25.rs:611
+-- pg_search::bootstrap::create_bm25::add_field
+CREATE  FUNCTION "add_field"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"field_name" TEXT, /* &str */
+	"field_config" jsonb /* pgrx::datum::json::JsonB */
+) RETURNS INT /* core::result::Result<i32, anyhow::Error> */
+STRICT
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'add_field_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/index.rs:142
+-- pg_search::api::index::stored_document
+CREATE  FUNCTION "stored_document"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"key" anyelement /* pgrx::datum::anyelement::AnyElement */
+) RETURNS jsonb /* core::option::Option<pgrx::datum::json::JsonB> */
+STABLE PARALLEL SAFE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'stored_document_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/postgres/customscan/pdbscan/projections/stored.rs:27
+-- pg_search::postgres::customscan::pdbscan::projections::stored::stored_from_relation
+CREATE  FUNCTION "stored"(
+	"field" anyelement /* pgrx::datum::anyelement::AnyElement */
+) RETURNS jsonb /* core::option::Option<pgrx::datum::json::JsonB> */
+STABLE PARALLEL SAFE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'stored_from_relation_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/postgres/customscan/pdbscan/projections/stored.rs:32
+-- requires:
+--   stored_from_relation
+--   placeholder_support
+
+ALTER FUNCTION stored SUPPORT placeholder_support;
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/index.rs:429
+-- pg_search::api::index::nested
+CREATE  FUNCTION "nested"(
+	"path" FieldName, /* pg_search::api::index::FieldName */
+	"query" SearchQueryInput /* pg_search::query::SearchQueryInput */
+) RETURNS SearchQueryInput /* pg_search::query::SearchQueryInput */
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'nested_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/index.rs:225
+-- pg_search::api::index::facet_counts
+CREATE  FUNCTION "facet_counts"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"field" FieldName, /* pg_search::api::index::FieldName */
+	"query" SearchQueryInput DEFAULT paradedb.all(), /* pg_search::query::SearchQueryInput */
+	"path" TEXT DEFAULT '/' /* alloc::string::String */
+) RETURNS TABLE (
+	"facet" TEXT,  /* alloc::string::String */
+	"count" bigint  /* i64 */
+)
+STABLE STRICT PARALLEL SAFE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'facet_counts_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/index.rs:403
+-- pg_search::api::index::facet
+CREATE  FUNCTION "facet"(
+	"field" FieldName, /* pg_search::api::index::FieldName */
+	"path" TEXT /* alloc::string::String */
+) RETURNS SearchQueryInput /* pg_search::query::SearchQueryInput */
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'facet_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/index.rs:757
+-- pg_search::api::index::search_after
+CREATE  FUNCTION "search_after"(
+	"sort_values" jsonb, /* pgrx::datum::json::JsonB */
+	"key" anyelement /* pgrx::datum::anyelement::AnyElement */
+) RETURNS SearchQueryInput /* pg_search::query::SearchQueryInput */
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'search_after_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/index.rs:558
+-- pg_search::api::index::with_index
+CREATE  FUNCTION "with_index"(
+	"index" oid, /* pgrx_pg_sys::submodules::oids::Oid */
+	"query" SearchQueryInput /* pg_search::query::SearchQueryInput */
+) RETURNS SearchQueryInput /* pg_search::query::SearchQueryInput */
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'with_index_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/operator/text.rs:45
+-- pg_search::api::operator::text::score_with_text
+CREATE  FUNCTION "score_with_text"(
+	"_element" anyelement, /* pgrx::datum::anyelement::AnyElement */
+	"query" TEXT /* &str */
+) RETURNS real /* f32 */
+IMMUTABLE STRICT PARALLEL SAFE COST 1000000000
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'score_with_text_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/operator/text.rs:107
+-- pg_search::api::operator::text::score_text_support
+CREATE  FUNCTION "score_text_support"(
+	"arg" internal /* pgrx::datum::internal::Internal */
+) RETURNS internal /* pg_search::api::operator::ReturnedNodePointer */
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'score_text_support_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/operator/searchqueryinput.rs:112
+-- pg_search::api::operator::searchqueryinput::score_with_query_input
+CREATE  FUNCTION "score_with_query_input"(
+	"element" anyelement, /* pgrx::datum::anyelement::AnyElement */
+	"query" SearchQueryInput /* pg_search::query::SearchQueryInput */
+) RETURNS real /* f32 */
+IMMUTABLE STRICT PARALLEL SAFE COST 1000000000
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'score_with_query_input_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/api/operator/searchqueryinput.rs:157
+-- pg_search::api::operator::searchqueryinput::score_query_input_support
+CREATE  FUNCTION "score_query_input_support"(
+	"arg" internal /* pgrx::datum::internal::Internal */
+) RETURNS internal /* pg_search::api::operator::ReturnedNodePointer */
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'score_query_input_support_wrapper';
+/* </end connected objects> */
+
+CREATE OPERATOR pg_catalog.<=>@@ (
+    PROCEDURE = score_with_text,
+    LEFTARG = anyelement,
+    RIGHTARG = text
+);
+
+CREATE OPERATOR pg_catalog.<=>@@ (
+    PROCEDURE = score_with_query_input,
+    LEFTARG = anyelement,
+    RIGHTARG = paradedb.searchqueryinput
+);
+
+ALTER FUNCTION paradedb.score_with_text SUPPORT paradedb.score_text_support;
+ALTER FUNCTION paradedb.score_with_query_input SUPPORT paradedb.score_query_input_support;
+
+ALTER OPERATOR FAMILY anyelement_bm25_ops USING bm25 ADD
+    OPERATOR 3 pg_catalog.<=>@@(anyelement, text) FOR ORDER BY pg_catalog.float_ops,
+    OPERATOR 4 pg_catalog.<=>@@(anyelement, paradedb.searchqueryinput) FOR ORDER BY pg_catalog.float_ops;
+/* <begin connected objects> */
+-- pg_search/src/bootstrap/create_bm25.rs:435
+-- pg_search::bootstrap::create_bm25::merge
+CREATE  FUNCTION "merge"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"max_segments" INT DEFAULT NULL, /* core::option::Option<i32> */
+	"expunge_deletes" bool DEFAULT false /* bool */
+) RETURNS TABLE (
+	"segments_before" INT,  /* i32 */
+	"segments_after" INT,  /* i32 */
+	"deleted_docs_removed" bigint  /* i64 */
+)
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'merge_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/bootstrap/create_bm25.rs:396
+-- pg_search::bootstrap::create_bm25::verify_index
+CREATE  FUNCTION "verify_index"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"heapallindexed" bool DEFAULT false /* bool */
+) RETURNS TABLE (
+	"blockno" bigint,  /* core::option::Option<i64> */
+	"problem" TEXT  /* alloc::string::String */
+)
+STRICT
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'verify_index_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/bootstrap/create_bm25.rs:530
+-- pg_search::bootstrap::create_bm25::export_index
+CREATE  FUNCTION "export_index"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"path" TEXT /* &str */
+) RETURNS TABLE (
+	"segments" INT,  /* i32 */
+	"num_docs" bigint,  /* i64 */
+	"bytes" bigint  /* i64 */
+)
+STRICT
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'export_index_wrapper';
+/* </end connected objects> */
+/* <begin connected objects> */
+-- pg_search/src/bootstrap/create_bm25.rs:566
+-- pg_search::bootstrap::create_bm25::import_index
+CREATE  FUNCTION "import_index"(
+	"index" regclass, /* pgrx::rel::PgRelation */
+	"path" TEXT /* &str */
+) RETURNS TABLE (
+	"segments" INT,  /* i32 */
+	"num_docs" bigint,  /* i64 */
+	"rows_replaced" bigint  /* i64 */
+)
+STRICT
+LANGUAGE c /* Rust */
+AS 'MODULE_PATHNAME', 'import_index_wrapper';
+/* </end connected objects> */
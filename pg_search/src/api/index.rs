@@ -20,10 +20,15 @@ use pgrx::{iter::TableIterator, *};
 
 use crate::index::reader::index::SearchIndexReader;
 use crate::index::BlockDirectoryType;
+use crate::postgres::customscan::pdbscan::is_block_all_visible;
 use crate::postgres::types::TantivyValue;
-use crate::query::{SearchQueryInput, TermInput};
+use crate::postgres::utils::u64_to_item_pointer;
+use crate::postgres::visibility_checker::VisibilityChecker;
+use crate::query::{facet_from_path, SearchQueryInput, TermInput};
 use crate::schema::AnyEnum;
 use crate::schema::IndexRecordOption;
+use crate::schema::SearchFieldType;
+use pgrx::itemptr::item_pointer_get_block_number;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::ffi::CStr;
@@ -108,6 +113,7 @@ pub fn schema(
                     )
                 }
                 FieldType::Date(_) => ("Date".to_string(), None, None, None, None),
+                FieldType::Facet(_) => ("Facet".to_string(), None, None, None, None),
                 _ => ("Other".to_string(), None, None, None, None),
             };
 
@@ -130,6 +136,170 @@ pub fn schema(
     TableIterator::new(field_rows)
 }
 
+/// Return the `stored: true` fields of the document whose key field is `key` as jsonb.
+///
+/// The values are read from the index rather than the heap.  The heap is only consulted to check
+/// the visibility of the matching row, and only when its page isn't all-visible.
+#[pg_extern(stable, parallel_safe)]
+pub fn stored_document(index: PgRelation, key: AnyElement) -> Option<JsonB> {
+    // # Safety
+    //
+    // Lock the index relation until the end of this function so it is not dropped or
+    // altered while we are reading it.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::AccessShareLock as _) };
+    let heaprel = index
+        .heap_relation()
+        .expect("index should belong to a heap relation");
+
+    let search_reader = SearchIndexReader::open(&index, BlockDirectoryType::Mvcc, false)
+        .expect("could not open search index reader");
+    let key_fields = search_reader.schema().key_fields();
+    let values = unsafe { TantivyValue::try_from_anyelement_key(key) }
+        .unwrap_or_else(|e| panic!("invalid value for key field: {e}"));
+    if values.len() != key_fields.len() {
+        panic!(
+            "the index's key_field has {} parts but {} values were given",
+            key_fields.len(),
+            values.len()
+        );
+    }
+    let query = SearchQueryInput::Boolean {
+        must: key_fields
+            .into_iter()
+            .zip(values)
+            .map(|(key_field, value)| SearchQueryInput::Term {
+                field: Some(key_field.name.0),
+                value: value.tantivy_schema_value(),
+                is_datetime: key_field.type_ == SearchFieldType::Date,
+            })
+            .collect(),
+        should: vec![],
+        must_not: vec![],
+    };
+
+    unsafe {
+        let mut visibility_checker =
+            VisibilityChecker::with_rel_and_snap(heaprel.as_ptr(), pg_sys::GetActiveSnapshot());
+        let slot = pg_sys::MakeSingleTupleTableSlot(
+            heaprel.rd_att,
+            pg_sys::table_slot_callbacks(heaprel.as_ptr()),
+        );
+        let mut vmbuff = pg_sys::InvalidBuffer as pg_sys::Buffer;
+
+        // the index may still hold dead versions of the row, so find the one that's visible to us
+        let found = search_reader
+            .search(false, false, &query, None)
+            .find(|(scored, _)| {
+                let mut tid = pg_sys::ItemPointerData::default();
+                u64_to_item_pointer(scored.ctid, &mut tid);
+                is_block_all_visible(
+                    heaprel.as_ptr(),
+                    &mut vmbuff,
+                    item_pointer_get_block_number(&tid),
+                ) || visibility_checker
+                    .exec_if_visible(scored.ctid, slot, |_| ())
+                    .is_some()
+            });
+
+        if vmbuff != pg_sys::InvalidBuffer as pg_sys::Buffer {
+            pg_sys::ReleaseBuffer(vmbuff);
+        }
+        pg_sys::ExecDropSingleTupleTableSlot(slot);
+
+        let (_, doc_address) = found?;
+        let fields = search_reader
+            .get_stored_fields(doc_address)
+            .expect("stored fields should be readable from the docstore");
+        Some(JsonB(serde_json::Value::Object(fields)))
+    }
+}
+
+/// Count the rows matching `query` under each direct child of the facet `path` of the facet
+/// `field`.  Drill down into the hierarchy by calling it again with one of the returned facets.
+///
+/// Only rows visible to the current snapshot are counted.
+#[pg_extern(stable, parallel_safe)]
+pub fn facet_counts(
+    index: PgRelation,
+    field: FieldName,
+    query: default!(SearchQueryInput, "paradedb.all()"),
+    path: default!(String, "'/'"),
+) -> TableIterator<'static, (name!(facet, String), name!(count, i64))> {
+    // # Safety
+    //
+    // Lock the index relation until the end of this function so it is not dropped or
+    // altered while we are reading it.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::AccessShareLock as _) };
+    let heaprel = index
+        .heap_relation()
+        .expect("index should belong to a heap relation");
+
+    let search_reader = SearchIndexReader::open(&index, BlockDirectoryType::Mvcc, false)
+        .expect("could not open search index reader");
+    let field = field.into_inner();
+    if !matches!(
+        search_reader
+            .schema()
+            .get_search_field(&field.clone().into())
+            .map(|search_field| search_field.type_),
+        Some(SearchFieldType::Facet)
+    ) {
+        panic!("'{field}' is not a facet field");
+    }
+    let parent = facet_from_path(&path).unwrap_or_else(|e| panic!("{e}"));
+
+    let counts = unsafe {
+        let mut visibility_checker =
+            VisibilityChecker::with_rel_and_snap(heaprel.as_ptr(), pg_sys::GetActiveSnapshot());
+        let slot = pg_sys::MakeSingleTupleTableSlot(
+            heaprel.rd_att,
+            pg_sys::table_slot_callbacks(heaprel.as_ptr()),
+        );
+        let mut vmbuff = pg_sys::InvalidBuffer as pg_sys::Buffer;
+
+        let visible = search_reader
+            .search(false, false, &query, None)
+            .filter(|(scored, _)| {
+                let mut tid = pg_sys::ItemPointerData::default();
+                u64_to_item_pointer(scored.ctid, &mut tid);
+                is_block_all_visible(
+                    heaprel.as_ptr(),
+                    &mut vmbuff,
+                    item_pointer_get_block_number(&tid),
+                ) || visibility_checker
+                    .exec_if_visible(scored.ctid, slot, |_| ())
+                    .is_some()
+            })
+            .map(|(_, doc_address)| doc_address);
+        let counts = search_reader
+            .facet_counts(&field, &parent, visible)
+            .expect("facet counts should be readable from the index");
+
+        if vmbuff != pg_sys::InvalidBuffer as pg_sys::Buffer {
+            pg_sys::ReleaseBuffer(vmbuff);
+        }
+        pg_sys::ExecDropSingleTupleTableSlot(slot);
+        counts
+    };
+
+    let mut counts = counts
+        .into_iter()
+        .map(|(facet, count)| (facet.to_path_string(), count as i64))
+        .collect::<Vec<_>>();
+    counts.sort_by(|(a_facet, a_count), (b_facet, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_facet.cmp(b_facet))
+    });
+    TableIterator::new(counts)
+}
+
 #[pg_extern(immutable, parallel_safe)]
 pub fn all() -> SearchQueryInput {
     SearchQueryInput::All
@@ -230,6 +400,14 @@ pub fn fast_field_range_weight(
     }
 }
 
+#[pg_extern(immutable, parallel_safe)]
+pub fn facet(field: FieldName, path: String) -> SearchQueryInput {
+    SearchQueryInput::Facet {
+        field: field.into_inner(),
+        path,
+    }
+}
+
 #[pg_extern(immutable, parallel_safe)]
 pub fn fuzzy_term(
     field: FieldName,
@@ -325,18 +503,28 @@ pub fn more_like_this_id(
         stop_words,
         document_fields: None,
         document_id: unsafe {
-            Some(
-                TantivyValue::try_from_datum(
-                    document_id.datum(),
-                    PgOid::from_untagged(document_id.oid()),
-                )
-                .unwrap_or_else(|err| panic!("could not read more_like_this document_id: {err}"))
-                .0,
-            )
+            let mut values = TantivyValue::try_from_anyelement_key(document_id)
+                .unwrap_or_else(|err| panic!("could not read more_like_this document_id: {err}"));
+            if values.len() == 1 {
+                values.pop().map(|value| value.0)
+            } else {
+                // a composite key: the query matches each of its parts
+                Some(OwnedValue::Array(
+                    values.into_iter().map(|value| value.0).collect(),
+                ))
+            }
         },
     }
 }
 
+#[pg_extern(immutable, parallel_safe)]
+pub fn nested(path: FieldName, query: SearchQueryInput) -> SearchQueryInput {
+    SearchQueryInput::Nested {
+        path: path.into_inner(),
+        query: Box::new(query),
+    }
+}
+
 #[pg_extern(immutable, parallel_safe)]
 pub fn parse(
     query_string: String,
@@ -365,6 +553,16 @@ pub fn parse_with_field(
     }
 }
 
+/// Ties a query to the bm25 index it should be run against.  The `<=>@@` operator uses this when
+/// its query isn't known until execution, so that it can still score rows on its own.
+#[pg_extern(immutable, parallel_safe)]
+pub fn with_index(index: pg_sys::Oid, query: SearchQueryInput) -> SearchQueryInput {
+    SearchQueryInput::WithIndex {
+        oid: index,
+        query: Box::new(query),
+    }
+}
+
 #[pg_extern(immutable, parallel_safe)]
 pub fn phrase(
     field: FieldName,
@@ -566,6 +764,27 @@ pub fn regex_phrase(
     }
 }
 
+#[pg_extern(immutable, parallel_safe)]
+pub fn search_after(sort_values: JsonB, key: AnyElement) -> SearchQueryInput {
+    let sort_values = match sort_values.0 {
+        serde_json::Value::Array(values) => values
+            .into_iter()
+            .map(|value| {
+                serde_json::from_value::<OwnedValue>(value)
+                    .unwrap_or_else(|err| panic!("invalid search_after sort value: {err}"))
+            })
+            .collect(),
+        _ => panic!("search_after sort_values must be a JSON array"),
+    };
+    let key = unsafe { TantivyValue::try_from_anyelement_key(key) }
+        .unwrap_or_else(|err| panic!("invalid value for search_after key: {err}"))
+        .into_iter()
+        .map(|value| value.0)
+        .collect();
+
+    SearchQueryInput::SearchAfter { sort_values, key }
+}
+
 macro_rules! term_fn {
     ($func_name:ident, $value_type:ty) => {
         #[pg_extern(name = "term", immutable, parallel_safe)]
@@ -20,13 +20,15 @@ mod text;
 
 use crate::api::index::{fieldname_typoid, FieldName};
 use crate::index::reader::index::SearchIndexReader;
+use crate::index::statistics::load_statistics;
 use crate::index::BlockDirectoryType;
 use crate::nodecast;
+use crate::postgres::options::SearchIndexCreateOptions;
 use crate::postgres::utils::locate_bm25_index;
 use crate::query::SearchQueryInput;
 use pgrx::callconv::{BoxRet, FcInfo};
 use pgrx::datum::Datum;
-use pgrx::pg_sys::expression_tree_walker;
+use pgrx::pg_sys::{expression_tree_walker, AsPgCStr};
 use pgrx::pgrx_sql_entity_graph::metadata::{
     ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
 };
@@ -110,6 +112,46 @@ pub fn anyelement_query_input_opoid() -> pg_sys::Oid {
     }
 }
 
+fn with_index_procoid() -> pg_sys::Oid {
+    unsafe {
+        direct_function_call::<pg_sys::Oid>(
+            pg_sys::regprocedurein,
+            &[c"paradedb.with_index(oid, paradedb.searchqueryinput)".into_datum()],
+        )
+        .expect("the `paradedb.with_index(oid, paradedb.searchqueryinput)` function should exist")
+    }
+}
+
+pub fn anyelement_text_score_opoid() -> pg_sys::Oid {
+    unsafe {
+        direct_function_call::<pg_sys::Oid>(
+            pg_sys::regoperatorin,
+            &[c"<=>@@(anyelement, text)".into_datum()],
+        )
+        .expect("the `<=>@@(anyelement, text)` operator should exist")
+    }
+}
+
+pub fn anyelement_query_input_score_opoid() -> pg_sys::Oid {
+    unsafe {
+        direct_function_call::<pg_sys::Oid>(
+            pg_sys::regoperatorin,
+            &[c"<=>@@(anyelement, paradedb.searchqueryinput)".into_datum()],
+        )
+        .expect("the `<=>@@(anyelement, paradedb.searchqueryinput)` operator should exist")
+    }
+}
+
+fn anyelement_query_input_score_procoid() -> pg_sys::Oid {
+    unsafe {
+        direct_function_call::<pg_sys::Oid>(
+            pg_sys::regprocedurein,
+            &[c"paradedb.score_with_query_input(anyelement, paradedb.searchqueryinput)".into_datum()],
+        )
+        .expect("the `paradedb.score_with_query_input(anyelement, paradedb.searchqueryinput)` function should exist")
+    }
+}
+
 pub fn searchqueryinput_typoid() -> pg_sys::Oid {
     unsafe {
         let oid = direct_function_call::<pg_sys::Oid>(
@@ -138,6 +180,13 @@ pub(crate) fn estimate_selectivity(
         return None;
     }
 
+    // statistics gathered by ANALYZE are much cheaper to consult than the index itself
+    if let Some(selectivity) = load_statistics(indexrel.oid())
+        .and_then(|statistics| statistics.selectivity(search_query_input))
+    {
+        return Some(selectivity);
+    }
+
     let search_reader = SearchIndexReader::open(indexrel, BlockDirectoryType::Mvcc, false)
         .expect("estimate_selectivity: should be able to open a SearchIndexReader");
     let estimate = search_reader.estimate_docs(search_query_input).unwrap_or(1) as f64;
@@ -149,16 +198,13 @@ pub(crate) fn estimate_selectivity(
     Some(selectivity)
 }
 
-unsafe fn make_search_query_input_opexpr_node(
-    srs: *mut pg_sys::SupportRequestSimplify,
-    input_args: &mut PgList<pg_sys::Node>,
+/// Find the `USING bm25` index on the relation `var` comes from, and make `var` refer to the
+/// index's first column, the key_field, instead of whatever column it was
+unsafe fn retarget_var_to_key_field(
     var: *mut pg_sys::Var,
-    query: Option<SearchQueryInput>,
-    parse_with_field: Option<(*mut pg_sys::Node, String)>,
-    opoid: pg_sys::Oid,
-    procoid: pg_sys::Oid,
-) -> ReturnedNodePointer {
-    let (relid, _varattno, targetlist) = find_var_relation(var, (*srs).root);
+    root: *mut pg_sys::PlannerInfo,
+) -> (PgRelation, Option<PgList<pg_sys::TargetEntry>>) {
+    let (relid, _varattno, targetlist) = find_var_relation(var, root);
     if relid == pg_sys::Oid::INVALID {
         panic!("could not determine relation for var");
     }
@@ -211,6 +257,23 @@ unsafe fn make_search_query_input_opexpr_node(
     (*var).vartypmod = att.atttypmod;
     (*var).varcollid = att.attcollation;
 
+    (indexrel, targetlist)
+}
+
+unsafe fn make_search_query_input_opexpr_node(
+    srs: *mut pg_sys::SupportRequestSimplify,
+    input_args: &mut PgList<pg_sys::Node>,
+    var: *mut pg_sys::Var,
+    query: Option<SearchQueryInput>,
+    parse_with_field: Option<(*mut pg_sys::Node, String)>,
+    opoid: pg_sys::Oid,
+    procoid: pg_sys::Oid,
+) -> ReturnedNodePointer {
+    let (indexrel, targetlist) = retarget_var_to_key_field(var, (*srs).root);
+    let keys = &(*indexrel.rd_index).indkey;
+    let keys = keys.values.as_slice(keys.dim1 as usize);
+    let tupdesc = PgTupleDesc::from_pg_unchecked(indexrel.rd_att);
+
     // we're about to fabricate a new pg_sys::OpExpr node to return
     // that represents the `@@@(anyelement, paradedb.searchqueryinput)` operator
     let mut newopexpr = PgBox::<pg_sys::OpExpr>::alloc_node(pg_sys::NodeTag::T_OpExpr);
@@ -241,55 +304,7 @@ unsafe fn make_search_query_input_opexpr_node(
         newopexpr.opfuncid = anyelement_query_input_procoid();
     } else if let Some((param, attname)) = parse_with_field {
         // rewrite the rhs to be a function call to our `paradedb.parse_with_field(...)` function
-        let mut parse_with_field_args = PgList::<pg_sys::Node>::new();
-
-        parse_with_field_args.push(
-            pg_sys::makeConst(
-                fieldname_typoid(),
-                -1,
-                pg_sys::Oid::INVALID,
-                -1,
-                FieldName::from(attname).into_datum().unwrap(),
-                false,
-                false,
-            )
-            .cast(),
-        );
-        parse_with_field_args.push(param.cast());
-        parse_with_field_args.push(
-            pg_sys::makeConst(
-                pg_sys::BOOLOID,
-                -1,
-                pg_sys::Oid::INVALID,
-                size_of::<bool>() as _,
-                pg_sys::Datum::from(false),
-                false,
-                true,
-            )
-            .cast(),
-        );
-        parse_with_field_args.push(
-            pg_sys::makeConst(
-                pg_sys::BOOLOID,
-                -1,
-                pg_sys::Oid::INVALID,
-                size_of::<bool>() as _,
-                pg_sys::Datum::from(false),
-                false,
-                true,
-            )
-            .cast(),
-        );
-
-        let funcexpr = pg_sys::makeFuncExpr(
-            parse_with_field_procoid(),
-            searchqueryinput_typoid(),
-            parse_with_field_args.into_pg(),
-            pg_sys::Oid::INVALID,
-            pg_sys::DEFAULT_COLLATION_OID,
-            pg_sys::CoercionForm::COERCE_EXPLICIT_CALL,
-        );
-
+        let funcexpr = make_parse_with_field_funcexpr(param, attname);
         input_args.replace_ptr(1, funcexpr.cast());
         newopexpr.opno = anyelement_query_input_opoid();
         newopexpr.opfuncid = anyelement_query_input_procoid();
@@ -308,9 +323,211 @@ unsafe fn make_search_query_input_opexpr_node(
 
     let newopexpr = newopexpr.into_pg();
 
+    let num_keys = if indexrel.rd_options.is_null() {
+        1
+    } else {
+        PgBox::from_pg(indexrel.rd_options as *mut SearchIndexCreateOptions)
+            .get_key_fields()
+            .len()
+    };
+    if num_keys > 1 && (*newopexpr).opno == anyelement_query_input_opoid() {
+        // the Var only holds the first part of a composite key, which isn't enough to tell rows
+        // apart when the operator is evaluated row by row.  pair it with the same search over
+        // a `ROW(...)` of every key part.  index scans can still use the first clause, and our
+        // custom scan ignores the second
+        let key_vars = (0..num_keys).map(|i| {
+            let key_var = pg_sys::copyObjectImpl(var.cast()).cast::<pg_sys::Var>();
+            let attno = match &targetlist {
+                Some(targetlist) => targetlist
+                    .iter_ptr()
+                    .position(|te| !te.is_null() && (*te).resorigcol == keys[i])
+                    .map(|position| (position + 1) as pg_sys::AttrNumber)
+                    .unwrap_or_else(|| {
+                        panic!("every column of the index's composite key_field must be in the var's targetlist")
+                    }),
+                None => keys[i],
+            };
+            let att = tupdesc
+                .get(i)
+                .unwrap_or_else(|| panic!("attribute `{}` not found", keys[i]));
+            (*key_var).varattno = attno;
+            (*key_var).varattnosyn = attno;
+            (*key_var).vartype = att.atttypid;
+            (*key_var).vartypmod = att.atttypmod;
+            (*key_var).varcollid = att.attcollation;
+            (key_var, att.name().to_string())
+        });
+
+        let mut row_args = PgList::<pg_sys::Node>::new();
+        let mut row_colnames = PgList::<pg_sys::Node>::new();
+        for (key_var, name) in key_vars {
+            row_args.push(key_var.cast());
+            row_colnames.push(pg_sys::makeString(name.as_pg_cstr()).cast());
+        }
+        let mut row = PgBox::<pg_sys::RowExpr>::alloc_node(pg_sys::NodeTag::T_RowExpr);
+        row.args = row_args.into_pg();
+        row.row_typeid = pg_sys::RECORDOID;
+        row.row_format = pg_sys::CoercionForm::COERCE_IMPLICIT_CAST;
+        row.colnames = row_colnames.into_pg();
+        row.location = -1;
+
+        let mut key_args = PgList::<pg_sys::Node>::new();
+        key_args.push(row.into_pg().cast());
+        key_args.push(pg_sys::copyObjectImpl(input_args.get_ptr(1).unwrap().cast()).cast());
+        let key_opexpr = pg_sys::copyObjectImpl(newopexpr.cast()).cast::<pg_sys::OpExpr>();
+        (*key_opexpr).args = key_args.into_pg();
+
+        let mut and_args = PgList::<pg_sys::Node>::new();
+        and_args.push(newopexpr.cast());
+        and_args.push(key_opexpr.cast());
+        let and = pg_sys::makeBoolExpr(
+            pg_sys::BoolExprType::AND_EXPR,
+            and_args.into_pg(),
+            (*newopexpr).location,
+        );
+        return ReturnedNodePointer(NonNull::new(and.cast()));
+    }
+
     ReturnedNodePointer(NonNull::new(newopexpr.cast()))
 }
 
+/// Rewrite a `<=>@@` operator into `<=>@@(key_field, paradedb.searchqueryinput)`, with the query
+/// tied to the index it's run against.  That's the form a bm25 index scan orders by, and also
+/// what lets the operator score a row when it's evaluated on its own, as the key_field and the
+/// index are enough to find the row's document.
+///
+/// `rhs` is the query, when it's not known until execution.
+unsafe fn make_score_opexpr_node(
+    srs: *mut pg_sys::SupportRequestSimplify,
+    input_args: &mut PgList<pg_sys::Node>,
+    var: *mut pg_sys::Var,
+    query: Option<SearchQueryInput>,
+    rhs: *mut pg_sys::Node,
+) -> ReturnedNodePointer {
+    let (indexrel, _) = retarget_var_to_key_field(var, (*srs).root);
+
+    let rhs: *mut pg_sys::Node = match query {
+        Some(query) => pg_sys::makeConst(
+            searchqueryinput_typoid(),
+            -1,
+            pg_sys::Oid::INVALID,
+            -1,
+            SearchQueryInput::WithIndex {
+                oid: indexrel.oid(),
+                query: Box::new(query),
+            }
+            .into_datum()
+            .unwrap(),
+            false,
+            false,
+        )
+        .cast(),
+        None => {
+            let mut with_index_args = PgList::<pg_sys::Node>::new();
+            with_index_args.push(
+                pg_sys::makeConst(
+                    pg_sys::OIDOID,
+                    -1,
+                    pg_sys::Oid::INVALID,
+                    size_of::<pg_sys::Oid>() as _,
+                    indexrel.oid().into_datum().unwrap(),
+                    false,
+                    true,
+                )
+                .cast(),
+            );
+            with_index_args.push(rhs);
+            pg_sys::makeFuncExpr(
+                with_index_procoid(),
+                searchqueryinput_typoid(),
+                with_index_args.into_pg(),
+                pg_sys::Oid::INVALID,
+                pg_sys::DEFAULT_COLLATION_OID,
+                pg_sys::CoercionForm::COERCE_EXPLICIT_CALL,
+            )
+            .cast()
+        }
+    };
+    input_args.replace_ptr(1, rhs);
+
+    let mut newopexpr = PgBox::<pg_sys::OpExpr>::alloc_node(pg_sys::NodeTag::T_OpExpr);
+    newopexpr.opno = anyelement_query_input_score_opoid();
+    newopexpr.opfuncid = anyelement_query_input_score_procoid();
+    newopexpr.opresulttype = pg_sys::FLOAT4OID;
+    newopexpr.opcollid = pg_sys::Oid::INVALID;
+    newopexpr.inputcollid = pg_sys::DEFAULT_COLLATION_OID;
+    newopexpr.location = (*(*srs).fcall).location;
+    newopexpr.args = input_args.as_ptr();
+
+    ReturnedNodePointer(NonNull::new(newopexpr.into_pg().cast()))
+}
+
+/// Make a call to `paradedb.parse_with_field(attname, param)`, for a query string that isn't known
+/// until execution
+unsafe fn make_parse_with_field_funcexpr(
+    param: *mut pg_sys::Node,
+    attname: String,
+) -> *mut pg_sys::FuncExpr {
+    let mut parse_with_field_args = PgList::<pg_sys::Node>::new();
+
+    parse_with_field_args.push(
+        pg_sys::makeConst(
+            fieldname_typoid(),
+            -1,
+            pg_sys::Oid::INVALID,
+            -1,
+            FieldName::from(attname).into_datum().unwrap(),
+            false,
+            false,
+        )
+        .cast(),
+    );
+    parse_with_field_args.push(param.cast());
+    parse_with_field_args.push(
+        pg_sys::makeConst(
+            pg_sys::BOOLOID,
+            -1,
+            pg_sys::Oid::INVALID,
+            size_of::<bool>() as _,
+            pg_sys::Datum::from(false),
+            false,
+            true,
+        )
+        .cast(),
+    );
+    parse_with_field_args.push(
+        pg_sys::makeConst(
+            pg_sys::BOOLOID,
+            -1,
+            pg_sys::Oid::INVALID,
+            size_of::<bool>() as _,
+            pg_sys::Datum::from(false),
+            false,
+            true,
+        )
+        .cast(),
+    );
+
+    pg_sys::makeFuncExpr(
+        parse_with_field_procoid(),
+        searchqueryinput_typoid(),
+        parse_with_field_args.into_pg(),
+        pg_sys::Oid::INVALID,
+        pg_sys::DEFAULT_COLLATION_OID,
+        pg_sys::CoercionForm::COERCE_EXPLICIT_CALL,
+    )
+}
+
+/// Is `node` the `ROW(...)` of every key part that [`make_search_query_input_opexpr_node`] pairs
+/// with the `@@@` operator for an index with a composite key_field?
+pub unsafe fn is_composite_key_row(node: *mut pg_sys::Node) -> bool {
+    nodecast!(RowExpr, T_RowExpr, node).is_some_and(|row| {
+        PgList::<pg_sys::Node>::from_pg((*row).args)
+            .iter_ptr()
+            .all(|arg| nodecast!(Var, T_Var, arg).is_some())
+    })
+}
+
 /// Given a [`pg_sys::Var`] and a [`pg_sys::PlannerInfo`], attempt to find the relation Oid that
 /// contains the var.
 ///
@@ -453,6 +670,8 @@ extension_sql!(
     r#"
 ALTER FUNCTION paradedb.search_with_text SUPPORT paradedb.text_support;
 ALTER FUNCTION paradedb.search_with_query_input SUPPORT paradedb.query_input_support;
+ALTER FUNCTION paradedb.score_with_text SUPPORT paradedb.score_text_support;
+ALTER FUNCTION paradedb.score_with_query_input SUPPORT paradedb.score_query_input_support;
 
 CREATE OPERATOR pg_catalog.@@@ (
     PROCEDURE = search_with_text,
@@ -468,9 +687,23 @@ CREATE OPERATOR pg_catalog.@@@ (
     RESTRICT = query_input_restrict
 );
 
+CREATE OPERATOR pg_catalog.<=>@@ (
+    PROCEDURE = score_with_text,
+    LEFTARG = anyelement,
+    RIGHTARG = text
+);
+
+CREATE OPERATOR pg_catalog.<=>@@ (
+    PROCEDURE = score_with_query_input,
+    LEFTARG = anyelement,
+    RIGHTARG = paradedb.searchqueryinput
+);
+
 CREATE OPERATOR CLASS anyelement_bm25_ops DEFAULT FOR TYPE anyelement USING bm25 AS
     OPERATOR 1 pg_catalog.@@@(anyelement, text),                         /* for querying with a tantivy-compatible text query */
     OPERATOR 2 pg_catalog.@@@(anyelement, paradedb.searchqueryinput),    /* for querying with a paradedb.searchqueryinput structure */
+    OPERATOR 3 pg_catalog.<=>@@(anyelement, text) FOR ORDER BY pg_catalog.float_ops,                       /* for ordering by relevance to a text query */
+    OPERATOR 4 pg_catalog.<=>@@(anyelement, paradedb.searchqueryinput) FOR ORDER BY pg_catalog.float_ops,  /* for ordering by relevance to a paradedb.searchqueryinput */
     STORAGE anyelement;
 "#,
     name = "bm25_ops_anyelement_operator",
@@ -479,9 +712,13 @@ CREATE OPERATOR CLASS anyelement_bm25_ops DEFAULT FOR TYPE anyelement USING bm25
         text::search_with_text,
         text::text_restrict,
         text::text_support,
+        text::score_with_text,
+        text::score_text_support,
         // for using SearchQueryInput on the rhs
         searchqueryinput::search_with_query_input,
         searchqueryinput::query_input_restrict,
         searchqueryinput::query_input_support,
+        searchqueryinput::score_with_query_input,
+        searchqueryinput::score_query_input_support,
     ]
 );
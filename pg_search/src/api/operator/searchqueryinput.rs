@@ -15,12 +15,14 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 use super::{
-    anyelement_query_input_opoid, anyelement_query_input_procoid,
+    anyelement_query_input_opoid, anyelement_query_input_procoid, make_score_opexpr_node,
     make_search_query_input_opexpr_node,
 };
-use crate::api::operator::{estimate_selectivity, find_var_relation, ReturnedNodePointer};
+use crate::api::operator::{
+    estimate_selectivity, find_var_relation, is_composite_key_row, ReturnedNodePointer,
+};
 use crate::gucs::per_tuple_cost;
-use crate::index::fast_fields_helper::FFHelper;
+use crate::index::fast_fields_helper::{FFHelper, FastFieldType};
 use crate::index::reader::index::SearchIndexReader;
 use crate::index::BlockDirectoryType;
 use crate::postgres::types::TantivyValue;
@@ -29,9 +31,9 @@ use crate::query::SearchQueryInput;
 use crate::{nodecast, UNKNOWN_SELECTIVITY};
 use pgrx::{
     check_for_interrupts, pg_extern, pg_func_extra, pg_sys, AnyElement, FromDatum, Internal,
-    PgList, PgOid, PgRelation,
+    PgList, PgRelation,
 };
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
 use std::ptr::NonNull;
 
 #[pg_extern(immutable, parallel_safe, cost = 1000000000)]
@@ -57,42 +59,157 @@ pub fn search_with_query_input(
             SearchIndexReader::open(&index_relation, BlockDirectoryType::Mvcc, false)
                 .expect("search_with_query_input: should be able to open a SearchIndexReader");
 
-        let key_field = search_reader.key_field();
-        let key_field_name = key_field.name.0;
-        let key_field_type = key_field.type_.into();
+        let key_fields = search_reader.schema().key_fields();
         let fast_fields = FFHelper::with_fields(
             &search_reader,
-            &[(key_field_name.clone(), key_field_type).into()],
+            &key_fields
+                .iter()
+                .map(|key_field| {
+                    let key_field_type: FastFieldType = key_field.type_.into();
+                    (key_field.name.0.clone(), key_field_type).into()
+                })
+                .collect::<Vec<_>>(),
         );
         let top_docs = search_reader.search(query.contains_more_like_this(), false, &query, None);
-        let mut hs = FxHashSet::default();
+
+        // for a composite key, `element` is either a `ROW(...)` of every key part or, where the
+        // operator was paired with one of those, just the first part
+        let mut keys = FxHashSet::default();
+        let mut first_parts = FxHashSet::default();
         for (_, doc_address) in top_docs {
             check_for_interrupts!();
-            hs.insert(
-                fast_fields
-                    .value(0, doc_address)
-                    .expect("key_field value should not be null"),
-            );
+            let key = (0..key_fields.len())
+                .map(|i| {
+                    fast_fields
+                        .value(i, doc_address)
+                        .expect("key_field value should not be null")
+                })
+                .collect::<Vec<_>>();
+            if key_fields.len() > 1 {
+                first_parts.insert(key[0].clone());
+            }
+            keys.insert(key);
         }
 
-        (key_field_name, hs)
+        (key_fields.len(), keys, first_parts)
     };
 
-    let cached = unsafe { pg_func_extra(fcinfo, default_hash_set) };
-    let key_field = &cached.0;
-    let hash_set = &cached.1;
-
-    let key_field_value = match unsafe {
-        TantivyValue::try_from_datum(element.datum(), PgOid::from_untagged(element.oid()))
-    } {
-        Err(err) => panic!(
-            "no value present in key_field {} in tuple: {err}",
-            key_field
-        ),
-        Ok(value) => value,
+    let (num_keys, keys, first_parts) = unsafe { pg_func_extra(fcinfo, default_hash_set) };
+
+    let key = unsafe { TantivyValue::try_from_anyelement_key(element) }
+        .unwrap_or_else(|err| panic!("no value present in key_field in tuple: {err}"));
+    if key.len() == *num_keys {
+        keys.contains(&key)
+    } else {
+        first_parts.contains(&key[0])
+    }
+}
+
+/// This is the function behind the `<=>@@(anyelement, searchqueryinput)` operator.  It's what
+/// `<=>@@` is evaluated with outside of an ordered bm25 index scan, such as under a Sort, and
+/// returns the same distance that scan would: the negated bm25 score of the row's document, or
+/// zero for rows the query doesn't match.
+#[pg_extern(immutable, parallel_safe, cost = 1000000000)]
+pub fn score_with_query_input(
+    element: AnyElement,
+    query: SearchQueryInput,
+    fcinfo: pg_sys::FunctionCallInfo,
+) -> f32 {
+    // the query can change from one call to the next, such as on the inner side of a LATERAL
+    // join, so the scores are only reused while it stays the same
+    let (scored_query, scores) =
+        unsafe { pg_func_extra(fcinfo, || (query.clone(), score_map(&query))) };
+    if *scored_query != query {
+        *scores = score_map(&query);
+        *scored_query = query;
+    }
+
+    let key = unsafe { TantivyValue::try_from_anyelement_key(element) }
+        .unwrap_or_else(|err| panic!("no value present in key_field in tuple: {err}"));
+    // the most relevant rows are the "closest", so they sort first
+    -scores.get(&key[0]).copied().unwrap_or(0.0)
+}
+
+/// The bm25 score of every key_field value whose document matches `query`
+fn score_map(query: &SearchQueryInput) -> FxHashMap<TantivyValue, f32> {
+    let index_oid = match query {
+        SearchQueryInput::WithIndex { oid, .. } => *oid,
+        _ => panic!("the SearchQueryInput must be wrapped in a WithIndex variant"),
     };
+    let index_relation =
+        unsafe { PgRelation::with_lock(index_oid, pg_sys::AccessShareLock as pg_sys::LOCKMODE) };
+    let search_reader = SearchIndexReader::open(&index_relation, BlockDirectoryType::Mvcc, false)
+        .expect("score_with_query_input: should be able to open a SearchIndexReader");
+
+    let key_field = search_reader.schema().key_field();
+    let key_field_type: FastFieldType = key_field.type_.into();
+    let fast_fields =
+        FFHelper::with_fields(&search_reader, &[(key_field.name.0, key_field_type).into()]);
 
-    hash_set.contains(&key_field_value)
+    // the lhs is only ever the key_field, or the first part of a composite one.  with the
+    // latter, rows that share it are given the best score among them
+    let mut scores = FxHashMap::<TantivyValue, f32>::default();
+    for (scored, doc_address) in search_reader.search(true, false, query, None) {
+        check_for_interrupts!();
+        let key = fast_fields
+            .value(0, doc_address)
+            .expect("key_field value should not be null");
+        let score = scores.entry(key).or_insert(scored.bm25);
+        *score = score.max(scored.bm25);
+    }
+    scores
+}
+
+#[pg_extern(immutable, parallel_safe)]
+pub unsafe fn score_query_input_support(arg: Internal) -> ReturnedNodePointer {
+    let datum = match arg.unwrap() {
+        Some(d) => d,
+        None => return ReturnedNodePointer(None),
+    };
+
+    if let Some(node) = score_query_input_support_request_simplify(datum) {
+        return node;
+    }
+
+    if let Some(node) = search_query_input_request_cost(datum) {
+        return node;
+    }
+
+    ReturnedNodePointer(None)
+}
+
+fn score_query_input_support_request_simplify(arg: pg_sys::Datum) -> Option<ReturnedNodePointer> {
+    unsafe {
+        let srs = nodecast!(
+            SupportRequestSimplify,
+            T_SupportRequestSimplify,
+            arg.cast_mut_ptr::<pg_sys::Node>()
+        )?;
+        if (*srs).root.is_null() {
+            return None;
+        }
+
+        let mut input_args = PgList::<pg_sys::Node>::from_pg((*(*srs).fcall).args);
+        let var = nodecast!(Var, T_Var, input_args.get_ptr(0)?)?;
+
+        let rhs = input_args.get_ptr(1)?;
+        let query = nodecast!(Const, T_Const, rhs)
+            .and_then(|const_| {
+                SearchQueryInput::from_datum((*const_).constvalue, (*const_).constisnull)
+            })
+            .map(|query| match query {
+                // already tied to an index, such as by a previous rewrite
+                SearchQueryInput::WithIndex { query, .. } => *query,
+                query => query,
+            });
+        Some(make_score_opexpr_node(
+            srs,
+            &mut input_args,
+            var,
+            query,
+            rhs,
+        ))
+    }
 }
 
 #[pg_extern(immutable, parallel_safe)]
@@ -167,6 +284,10 @@ pub fn query_input_restrict(
             let args =
                 PgList::<pg_sys::Node>::from_pg(args.unwrap()?.cast_mut_ptr::<pg_sys::List>());
 
+            if is_composite_key_row(args.get_ptr(0)?) {
+                // the clause this was paired with already accounts for the query
+                return Some(1.0);
+            }
             let var = nodecast!(Var, T_Var, args.get_ptr(0)?)?;
             let const_ = nodecast!(Const, T_Const, args.get_ptr(1)?)?;
 
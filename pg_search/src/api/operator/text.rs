@@ -17,7 +17,8 @@
 
 use crate::api::operator::{
     anyelement_text_opoid, anyelement_text_procoid, attname_from_var, estimate_selectivity,
-    make_search_query_input_opexpr_node, ReturnedNodePointer,
+    make_parse_with_field_funcexpr, make_score_opexpr_node, make_search_query_input_opexpr_node,
+    ReturnedNodePointer,
 };
 use crate::postgres::utils::locate_bm25_index;
 use crate::query::SearchQueryInput;
@@ -38,6 +39,14 @@ pub fn search_with_text(
     panic!("query is incompatible with pg_search's `@@@(key_field, TEXT)` operator: `{query}`")
 }
 
+/// This is the function behind the `<=>@@(anyelement, text)` operator, which orders rows by their
+/// relevance to the query.  Like `@@@(anyelement, text)`, it's rewritten to its
+/// `paradedb.searchqueryinput` counterpart, so this is only called when that wasn't possible.
+#[pg_extern(immutable, parallel_safe, cost = 1000000000)]
+pub fn score_with_text(_element: AnyElement, query: &str) -> f32 {
+    panic!("query is incompatible with pg_search's `<=>@@(key_field, TEXT)` operator: `{query}`")
+}
+
 #[pg_extern(immutable, parallel_safe)]
 pub unsafe fn text_support(arg: Internal) -> ReturnedNodePointer {
     text_support_request_simplify(arg).unwrap_or(ReturnedNodePointer(None))
@@ -95,6 +104,49 @@ fn text_support_request_simplify(arg: Internal) -> Option<ReturnedNodePointer> {
     }
 }
 
+#[pg_extern(immutable, parallel_safe)]
+pub unsafe fn score_text_support(arg: Internal) -> ReturnedNodePointer {
+    score_text_support_request_simplify(arg).unwrap_or(ReturnedNodePointer(None))
+}
+
+fn score_text_support_request_simplify(arg: Internal) -> Option<ReturnedNodePointer> {
+    unsafe {
+        let srs = nodecast!(
+            SupportRequestSimplify,
+            T_SupportRequestSimplify,
+            arg.unwrap()?.cast_mut_ptr::<pg_sys::Node>()
+        )?;
+        if (*srs).root.is_null() {
+            return None;
+        }
+        let mut input_args = PgList::<pg_sys::Node>::from_pg((*(*srs).fcall).args);
+
+        let lhs = input_args.get_ptr(0)?;
+        let rhs = input_args.get_ptr(1)?;
+
+        let var = nodecast!(Var, T_Var, lhs)?;
+        let (query, rhs) = if let Some(const_) = nodecast!(Const, T_Const, rhs) {
+            let (_, query) = make_query_from_var_and_const((*srs).root, var, const_);
+            (Some(query), rhs)
+        } else {
+            // the query isn't known until execution, such as when it comes from the outer side
+            // of a LATERAL join, so it's parsed then
+            let attname = attname_from_var((*srs).root, var)
+                .1
+                .expect("should be able to determine Var name");
+            (None, make_parse_with_field_funcexpr(rhs, attname).cast())
+        };
+
+        Some(make_score_opexpr_node(
+            srs,
+            &mut input_args,
+            var,
+            query,
+            rhs,
+        ))
+    }
+}
+
 #[pg_extern(immutable, parallel_safe)]
 pub fn text_restrict(
     planner_info: Internal, // <pg_sys::PlannerInfo>,
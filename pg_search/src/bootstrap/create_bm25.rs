@@ -16,15 +16,21 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::HashMap;
+use std::path::Path;
 
+use crate::index::merge_policy::{claim_for_force_merge, set_num_segments, ForceMergePolicy};
 use crate::index::reader::index::SearchIndexReader;
-use crate::index::BlockDirectoryType;
+use crate::index::utils::load_schema;
+use crate::index::writer::index::SearchIndexWriter;
+use crate::index::{sync_index_schema, BlockDirectoryType, WriterResources};
+use crate::postgres::merge_worker::ForceMergeGuard;
 use crate::postgres::options::SearchIndexCreateOptions;
+use crate::postgres::portable;
 use crate::postgres::storage::block::{
     LinkedList, MVCCEntry, SegmentMetaEntry, SEGMENT_METAS_START,
 };
 use crate::postgres::storage::LinkedItemList;
-use crate::schema::IndexRecordOption;
+use crate::postgres::verify;
 use crate::schema::SearchFieldConfig;
 use crate::schema::SearchFieldName;
 use crate::schema::SearchFieldType;
@@ -32,12 +38,10 @@ use anyhow::bail;
 use anyhow::Result;
 use pgrx::prelude::*;
 use pgrx::JsonB;
+use pgrx::PgOid;
 use pgrx::PgRelation;
 use serde_json::Map;
 use serde_json::Value;
-use tokenizers::manager::SearchTokenizerFilters;
-use tokenizers::SearchNormalizer;
-use tokenizers::SearchTokenizer;
 
 #[allow(clippy::too_many_arguments)]
 #[pg_extern]
@@ -202,58 +206,30 @@ pub unsafe fn index_fields(index: PgRelation) -> JsonB {
             _ => panic!("'{name}' cannot be indexed as a datetime field"),
         });
 
-    let key_field = rdopts.get_key_field().expect("must specify key_field");
-    let key_field_type = match name_type_map.get(&key_field) {
-        Some(field_type) => field_type,
-        None => panic!("key field does not exist"),
-    };
-    let key_config = match key_field_type {
-        SearchFieldType::I64 | SearchFieldType::U64 | SearchFieldType::F64 => {
-            SearchFieldConfig::Numeric {
-                indexed: true,
-                fast: true,
-                stored: false,
-                column: None,
+    let facet_fields = rdopts.get_facet_fields().into_iter().map(|(name, config)| {
+        match name_type_map.get(&name) {
+            Some(SearchFieldType::Text | SearchFieldType::Facet) => {
+                (name, config, SearchFieldType::Facet)
             }
+            _ => panic!("'{name}' cannot be indexed as a facet field"),
         }
-        SearchFieldType::Text => SearchFieldConfig::Text {
-            indexed: true,
-            fast: true,
-            stored: false,
-            fieldnorms: false,
-            tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
-            record: IndexRecordOption::Basic,
-            normalizer: SearchNormalizer::Raw,
-            column: None,
-        },
-        SearchFieldType::Json => SearchFieldConfig::Json {
-            indexed: true,
-            fast: true,
-            stored: false,
-            expand_dots: false,
-            tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
-            record: IndexRecordOption::Basic,
-            normalizer: SearchNormalizer::Raw,
-            fieldnorms: true,
-            column: None,
-        },
-        SearchFieldType::Range => SearchFieldConfig::Range {
-            stored: false,
-            column: None,
-        },
-        SearchFieldType::Bool => SearchFieldConfig::Boolean {
-            indexed: true,
-            fast: true,
-            stored: false,
-            column: None,
-        },
-        SearchFieldType::Date => SearchFieldConfig::Date {
-            indexed: true,
-            fast: true,
-            stored: false,
-            column: None,
-        },
-    };
+    });
+
+    let key_fields = rdopts
+        .get_key_fields()
+        .into_iter()
+        .map(|key_field| {
+            let key_field_type = match name_type_map.get(&key_field) {
+                Some(field_type) => *field_type,
+                None => panic!("key field '{key_field}' does not exist"),
+            };
+            let key_config = SearchFieldConfig::key_field(&key_field_type);
+            (key_field, key_config, key_field_type)
+        })
+        .collect::<Vec<_>>();
+    if key_fields.is_empty() {
+        panic!("must specify key_field");
+    }
 
     // Concatenate the separate lists of fields.
     let fields = text_fields
@@ -262,11 +238,8 @@ pub unsafe fn index_fields(index: PgRelation) -> JsonB {
         .chain(json_fields)
         .chain(range_fields)
         .chain(datetime_fields)
-        .chain(std::iter::once((
-            key_field.clone(),
-            key_config,
-            *key_field_type,
-        )))
+        .chain(facet_fields)
+        .chain(key_fields)
         .map(|(name, config, _)| {
             (
                 name.0,
@@ -360,9 +333,312 @@ fn validate_checksum(index: PgRelation) -> Result<SetOfIterator<'static, String>
     ))
 }
 
+/// Check the index's block structure for cycles, orphaned or doubly-owned blocks and
+/// transaction id anomalies, returning whatever problems are found.
+///
+/// With `heapallindexed`, also check that every heap tuple the index should contain has exactly
+/// one live document.
+#[allow(clippy::type_complexity)]
+#[pg_extern]
+fn verify_index(
+    index: PgRelation,
+    heapallindexed: default!(bool, "false"),
+) -> Result<TableIterator<'static, (name!(blockno, Option<i64>), name!(problem, String))>> {
+    // # Safety
+    //
+    // Lock the relations until the end of this function so they are not dropped or altered
+    // while we are reading them.  ShareLock also keeps out writers and VACUUM, so neither can
+    // change underneath us.  Like amcheck, we lock the heap first, as everything else does.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let heap = heapallindexed.then(|| unsafe {
+        let heap_oid = pg_sys::IndexGetRelation(index.oid(), false);
+        PgRelation::with_lock(heap_oid, pg_sys::ShareLock as _)
+    });
+    let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::ShareLock as _) };
+
+    let mut problems = unsafe { verify::verify_structure(&index)? };
+    if let Some(heap) = heap {
+        // a broken index can't be searched reliably, so only check it against the heap if
+        // its structure is sound
+        if problems.is_empty() {
+            problems.extend(unsafe { verify::verify_heap_all_indexed(&heap, &index)? });
+        }
+    }
+
+    Ok(TableIterator::new(problems.into_iter().map(
+        |(blockno, problem)| (blockno.map(|blockno| blockno as i64), problem),
+    )))
+}
+
+/// Merge the index's segments down to at most `max_segments`, and/or rewrite every segment with
+/// deleted documents so that they no longer take up space.
+///
+/// Waits for any merge that's already in progress, and can be cancelled while it runs.
+#[allow(clippy::type_complexity)]
+#[pg_extern]
+fn merge(
+    index: PgRelation,
+    max_segments: default!(Option<i32>, "NULL"),
+    expunge_deletes: default!(bool, "false"),
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(segments_before, i32),
+            name!(segments_after, i32),
+            name!(deleted_docs_removed, i64),
+        ),
+    >,
+> {
+    let max_segments = match max_segments {
+        Some(max_segments) if max_segments < 1 => {
+            bail!("max_segments must be at least 1, received: {max_segments}")
+        }
+        Some(max_segments) => Some(max_segments as usize),
+        None if !expunge_deletes => {
+            bail!("nothing to merge: specify max_segments and/or expunge_deletes => true")
+        }
+        None => None,
+    };
+
+    // # Safety
+    //
+    // Lock the index relation until the end of this function so it is not dropped or
+    // altered while we are merging it.  This is the same lock an INSERT takes.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::RowExclusiveLock as _) };
+
+    let segment_stats = |index: &PgRelation| -> Result<(i32, i64)> {
+        let search_reader = SearchIndexReader::open(index, BlockDirectoryType::Mvcc, false)?;
+        let segment_readers = search_reader.segment_readers();
+        let num_deleted = segment_readers
+            .iter()
+            .map(|segment_reader| segment_reader.num_deleted_docs() as i64)
+            .sum();
+        Ok((segment_readers.len() as i32, num_deleted))
+    };
+
+    unsafe {
+        // no other merge can start until we commit, and we've waited for any that was running
+        claim_for_force_merge(index.oid());
+
+        // see everything that committed before we claimed the merge, including that merge
+        pg_sys::PushActiveSnapshot(pg_sys::GetLatestSnapshot());
+
+        let (segments_before, deleted_before) = segment_stats(&index)?;
+        pgrx::notice!(
+            "merging {segments_before} segments with {deleted_before} deleted documents in index \"{}\"",
+            index.name()
+        );
+
+        // merge one candidate at a time, so we can report progress in between.  our own commits
+        // are visible to our snapshot, and it keeps out everyone else's, so we're done once a
+        // commit changes nothing
+        {
+            let _guard = ForceMergeGuard::enter();
+            let policy = ForceMergePolicy {
+                max_segments,
+                deleted_docs_ratio: expunge_deletes.then_some(0.0),
+                max_merges: Some(1),
+            };
+            let (mut segments, mut deleted) = (segments_before, deleted_before);
+            loop {
+                SearchIndexWriter::open(
+                    &index,
+                    BlockDirectoryType::Mvcc,
+                    WriterResources::ForceMerge(policy.clone()),
+                )?
+                .commit()?;
+
+                let (segments_now, deleted_now) = segment_stats(&index)?;
+                if (segments_now, deleted_now) == (segments, deleted) {
+                    break;
+                }
+                (segments, deleted) = (segments_now, deleted_now);
+                pgrx::notice!(
+                    "index \"{}\" has {segments} segments with {deleted} deleted documents",
+                    index.name()
+                );
+            }
+        }
+
+        // a merged index shouldn't immediately be merged back up by the next insert
+        if let Some(max_segments) = max_segments {
+            set_num_segments(index.oid(), max_segments as u32);
+        }
+        pg_sys::PopActiveSnapshot();
+
+        pg_sys::PushActiveSnapshot(pg_sys::GetLatestSnapshot());
+        let (segments_after, deleted_after) = segment_stats(&index)?;
+        pg_sys::PopActiveSnapshot();
+
+        Ok(TableIterator::once((
+            segments_before,
+            segments_after,
+            deleted_before - deleted_after,
+        )))
+    }
+}
+
+/// Write the index's visible segments and a `meta.json` to `path`, a directory on the database
+/// server, as a standard tantivy index.  The pending list is flushed into a segment first, so
+/// that its documents are included.
+#[allow(clippy::type_complexity)]
+#[pg_extern]
+fn export_index(
+    index: PgRelation,
+    path: &str,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(segments, i32),
+            name!(num_docs, i64),
+            name!(bytes, i64),
+        ),
+    >,
+> {
+    // # Safety
+    //
+    // Lock the index relation until the end of this function so it is not dropped or
+    // altered while we are reading it.  RowExclusiveLock, like an INSERT, because the
+    // pending list is flushed into a segment first.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::RowExclusiveLock as _) };
+
+    let stats = unsafe { portable::export_index(&index, Path::new(path))? };
+    Ok(TableIterator::once((
+        stats.segments,
+        stats.num_docs,
+        stats.bytes,
+    )))
+}
+
+/// Attach the segments of the tantivy index at `path`, a directory on the database server, to
+/// the index.  Each document is matched to its row by key, replacing whatever the index already
+/// had for that row.
+#[allow(clippy::type_complexity)]
+#[pg_extern]
+fn import_index(
+    index: PgRelation,
+    path: &str,
+) -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(segments, i32),
+            name!(num_docs, i64),
+            name!(rows_replaced, i64),
+        ),
+    >,
+> {
+    // # Safety
+    //
+    // Lock the relations until the end of this function so they are not dropped or altered
+    // while we are working.  ShareLock keeps writers out of the heap, like CREATE INDEX, so
+    // the rows can't move out from under the ctids we find for them.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let heap = unsafe {
+        let heap_oid = pg_sys::IndexGetRelation(index.oid(), false);
+        PgRelation::with_lock(heap_oid, pg_sys::ShareLock as _)
+    };
+    let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::RowExclusiveLock as _) };
+
+    let stats = unsafe { portable::import_index(&heap, &index, Path::new(path))? };
+    Ok(TableIterator::once((
+        stats.segments,
+        stats.num_docs,
+        stats.rows_replaced,
+    )))
+}
+
 #[pg_extern(sql = "")]
 fn create_bm25_jsonb() {}
 
+/// Add a new field to an existing index without a REINDEX.
+///
+/// The field must be sourced from a column the index already contains, either by sharing its
+/// name or through the `column` key of `field_config`.  Documents indexed from now on will
+/// populate the field, while existing segments treat it as absent until they're rewritten.
+#[pg_extern]
+fn add_field(index: PgRelation, field_name: &str, field_config: JsonB) -> Result<i32> {
+    // # Safety
+    //
+    // Lock the index relation until the end of this function so it is not dropped or
+    // altered while we are modifying it.  This is the same lock ALTER INDEX ... SET takes.
+    //
+    // Because we accept a PgRelation above, we have confidence that Postgres has already
+    // validated the existence of the relation. We are safe calling the function below as
+    // long we do not pass pg_sys::NoLock without any other locking mechanism of our own.
+    let index = unsafe { PgRelation::with_lock(index.oid(), pg_sys::AccessExclusiveLock as _) };
+    let Value::Object(config) = field_config.0 else {
+        bail!("Expected a JSON object, received: {}", field_config.0);
+    };
+
+    let current = unsafe { load_schema(index.oid())? };
+    if current.schema.get_field(field_name).is_ok() {
+        bail!(
+            "field '{field_name}' already exists in index '{}'",
+            index.name()
+        );
+    }
+
+    let column = config
+        .get("column")
+        .and_then(|column| column.as_str())
+        .unwrap_or(field_name)
+        .to_string();
+    let tupdesc = index.tuple_desc();
+    let Some(attribute) = tupdesc.iter().find(|attribute| attribute.name() == column) else {
+        bail!(
+            "'{column}' is not a column of index '{}', so it cannot be the source of field '{field_name}'",
+            index.name()
+        );
+    };
+    let attribute_type_oid = attribute.type_oid();
+    let array_type = unsafe { pg_sys::get_element_type(attribute_type_oid.value()) };
+    let base_oid = if array_type != pg_sys::InvalidOid {
+        PgOid::from(array_type)
+    } else {
+        attribute_type_oid
+    };
+    let field_type = SearchFieldType::try_from(&base_oid)?;
+    drop(tupdesc);
+
+    let rdopts = unsafe { PgBox::from_pg(index.rd_options as *mut SearchIndexCreateOptions) };
+    let (option_name, mut fields) = rdopts.get_fields_option(&field_type);
+    fields.insert(field_name.to_string(), Value::Object(config));
+
+    // ALTER INDEX validates the new configuration and records it in the catalog
+    Spi::run(&format!(
+        "ALTER INDEX {}.{} SET ({option_name} = {})",
+        spi::quote_identifier(index.namespace()),
+        spi::quote_identifier(index.name()),
+        spi::quote_literal(&serde_json::to_string(&fields)?)
+    ))?;
+
+    // reopen the index so we see its new options, and then append the new field to the schema
+    // that's stored in the index itself
+    let indexrelid = index.oid();
+    drop(index);
+    let index = unsafe { PgRelation::with_lock(indexrelid, pg_sys::AccessExclusiveLock as _) };
+    let version = sync_index_schema(&index)?.unwrap_or(current.version);
+
+    Ok(version as i32)
+}
+
 #[allow(clippy::type_complexity)]
 #[pg_extern]
 fn storage_info(
@@ -57,6 +57,17 @@ static STATEMENT_PARALLELISM: GucSetting<i32> = GucSetting::<i32>::new(0);
 /// thread.  So if there's 10 threads and this value is 100MB, then a total of 1GB will be allocated.
 static STATEMENT_MEMORY_BUDGET: GucSetting<i32> = GucSetting::<i32>::new(0);
 
+/// How long, in milliseconds, should the background merge worker sleep each time it exceeds
+/// `MERGE_COST_LIMIT`?  Like `vacuum_cost_delay`, zero disables the throttling.
+static MERGE_COST_DELAY: GucSetting<f64> = GucSetting::<f64>::new(2.0);
+
+/// The accumulated buffer access cost, as counted by `vacuum_cost_page_hit` and friends, that
+/// puts the background merge worker to sleep for `MERGE_COST_DELAY`
+static MERGE_COST_LIMIT: GucSetting<i32> = GucSetting::<i32>::new(200);
+
+/// The fraction of a segment's documents that must be deleted before VACUUM rewrites it
+static VACUUM_DELETED_DOCS_RATIO: GucSetting<f64> = GucSetting::<f64>::new(0.2);
+
 pub fn init() {
     // Note that Postgres is very specific about the naming convention of variables.
     // They must be namespaced... we use 'paradedb.<variable>' below.
@@ -147,6 +158,39 @@ pub fn init() {
         GucContext::Suset,
         GucFlags::UNIT_MB,
     );
+
+    GucRegistry::define_float_guc(
+        "paradedb.merge_cost_delay",
+        "Cost-based delay, in milliseconds, for the background merge worker",
+        "Default is 2ms.  A value of zero lets merges run unthrottled",
+        &MERGE_COST_DELAY,
+        0.0,
+        100.0,
+        GucContext::Sighup,
+        GucFlags::UNIT_MS,
+    );
+
+    GucRegistry::define_int_guc(
+        "paradedb.merge_cost_limit",
+        "Cost amount available to the background merge worker before it sleeps",
+        "Default is 200, the same as `vacuum_cost_limit`",
+        &MERGE_COST_LIMIT,
+        1,
+        10000,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "paradedb.vacuum_deleted_docs_ratio",
+        "Fraction of a segment's documents that must be deleted for VACUUM to rewrite it",
+        "Default is 0.2.  A value of 1 only drops segments whose documents are all deleted",
+        &VACUUM_DELETED_DOCS_RATIO,
+        0.0,
+        1.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
 }
 
 pub fn telemetry_enabled() -> bool {
@@ -183,6 +227,18 @@ pub fn statement_memory_budget() -> usize {
     adjust_budget(STATEMENT_MEMORY_BUDGET.get(), statement_parallelism())
 }
 
+pub fn merge_cost_delay() -> f64 {
+    MERGE_COST_DELAY.get()
+}
+
+pub fn merge_cost_limit() -> i32 {
+    MERGE_COST_LIMIT.get()
+}
+
+pub fn vacuum_deleted_docs_ratio() -> f64 {
+    VACUUM_DELETED_DOCS_RATIO.get()
+}
+
 fn adjust_nthreads(nthreads: i32) -> NonZeroUsize {
     let nthreads = if nthreads <= 0 {
         std::thread::available_parallelism()
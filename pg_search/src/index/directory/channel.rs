@@ -273,6 +273,7 @@ impl ChannelRequestHandler {
                             Err(e) => return Err(e),
                         }
                     }
+                    crate::postgres::merge_worker::delay_point();
                 }
 
                 // the reply channel was closed, so lets just return that as the error
@@ -0,0 +1,224 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use serde::Deserialize;
+use std::any::Any;
+use std::collections::HashSet;
+use std::fs::File;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::{io, result};
+use tantivy::directory::error::{DeleteError, LockError, OpenReadError, OpenWriteError};
+use tantivy::directory::{
+    DirectoryLock, FileHandle, Lock, OwnedBytes, WatchCallback, WatchHandle, WritePtr,
+};
+use tantivy::index::{DeleteMeta, InnerSegmentMeta, SegmentId, SegmentMetaInventory};
+use tantivy::merge_policy::{MergePolicy, NoMergePolicy};
+use tantivy::schema::Schema;
+use tantivy::{Directory, HasLen, IndexMeta, IndexSettings, Opstamp};
+
+pub const META_FILEPATH: &str = "meta.json";
+
+/// The parts of a tantivy `meta.json` we need to attach its segments somewhere else
+#[derive(Deserialize)]
+struct ExternalMeta {
+    index_settings: IndexSettings,
+    segments: Vec<ExternalSegmentMeta>,
+    schema: Schema,
+    opstamp: Opstamp,
+}
+
+#[derive(Deserialize)]
+struct ExternalSegmentMeta {
+    segment_id: SegmentId,
+    max_doc: u32,
+    deletes: Option<DeleteMeta>,
+}
+
+/// Read-only Tantivy Directory implementation over a directory on the database server's
+/// filesystem, such as one written by `paradedb.export_index()` or by standard tantivy tooling
+///
+/// Nothing here touches Postgres, so it's safe to use from any thread.
+#[derive(Clone, Debug)]
+pub struct ExternalDirectory {
+    root: PathBuf,
+}
+
+impl ExternalDirectory {
+    pub fn open(root: &Path) -> io::Result<Self> {
+        if !root.join(META_FILEPATH).is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("`{}` does not contain a {META_FILEPATH}", root.display()),
+            ));
+        }
+        Ok(Self {
+            root: root.to_path_buf(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ExternalFileHandle {
+    file: File,
+    len: usize,
+}
+
+impl FileHandle for ExternalFileHandle {
+    fn read_bytes(&self, range: Range<usize>) -> io::Result<OwnedBytes> {
+        let mut bytes = vec![0; range.len()];
+        self.file.read_exact_at(&mut bytes, range.start as u64)?;
+        Ok(OwnedBytes::new(bytes))
+    }
+}
+
+impl HasLen for ExternalFileHandle {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+fn read_only_error(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("cannot write `{}` to a read-only directory", path.display()),
+    )
+}
+
+impl Directory for ExternalDirectory {
+    fn get_file_handle(&self, path: &Path) -> Result<Arc<dyn FileHandle>, OpenReadError> {
+        let full_path = self.root.join(path);
+        let file = File::open(&full_path).map_err(|io_error| match io_error.kind() {
+            io::ErrorKind::NotFound => OpenReadError::FileDoesNotExist(path.to_path_buf()),
+            _ => OpenReadError::wrap_io_error(io_error, path.to_path_buf()),
+        })?;
+        let len = file
+            .metadata()
+            .map_err(|io_error| OpenReadError::wrap_io_error(io_error, path.to_path_buf()))?
+            .len() as usize;
+        Ok(Arc::new(ExternalFileHandle { file, len }))
+    }
+
+    fn delete(&self, path: &Path) -> result::Result<(), DeleteError> {
+        Err(DeleteError::IoError {
+            io_error: read_only_error(path).into(),
+            filepath: path.to_path_buf(),
+        })
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool, OpenReadError> {
+        Ok(self.root.join(path).exists())
+    }
+
+    fn open_write(&self, path: &Path) -> result::Result<WritePtr, OpenWriteError> {
+        Err(OpenWriteError::wrap_io_error(
+            read_only_error(path),
+            path.to_path_buf(),
+        ))
+    }
+
+    fn atomic_read(&self, path: &Path) -> result::Result<Vec<u8>, OpenReadError> {
+        std::fs::read(self.root.join(path)).map_err(|io_error| match io_error.kind() {
+            io::ErrorKind::NotFound => OpenReadError::FileDoesNotExist(path.to_path_buf()),
+            _ => OpenReadError::wrap_io_error(io_error, path.to_path_buf()),
+        })
+    }
+
+    fn atomic_write(&self, path: &Path, _data: &[u8]) -> io::Result<()> {
+        Err(read_only_error(path))
+    }
+
+    fn sync_directory(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Nothing writes to this directory, so there's nothing to lock out
+    fn acquire_lock(&self, lock: &Lock) -> result::Result<DirectoryLock, LockError> {
+        Ok(DirectoryLock::from(Box::new(Lock {
+            filepath: lock.filepath.clone(),
+            is_blocking: true,
+        })))
+    }
+
+    fn watch(&self, _watch_callback: WatchCallback) -> tantivy::Result<WatchHandle> {
+        unimplemented!("OnCommitWithDelay ReloadPolicy not supported");
+    }
+
+    fn list_managed_files(&self) -> tantivy::Result<HashSet<PathBuf>> {
+        let mut files = HashSet::new();
+        for entry in std::fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.insert(PathBuf::from(entry.file_name()));
+            }
+        }
+        Ok(files)
+    }
+
+    fn register_files_as_managed(
+        &self,
+        _files: Vec<PathBuf>,
+        _overwrite: bool,
+    ) -> tantivy::Result<()> {
+        Ok(())
+    }
+
+    fn save_metas(
+        &self,
+        _meta: &IndexMeta,
+        _previous_meta: &IndexMeta,
+        _payload: &mut (dyn Any + '_),
+    ) -> tantivy::Result<()> {
+        Err(read_only_error(Path::new(META_FILEPATH)).into())
+    }
+
+    fn load_metas(&self, inventory: &SegmentMetaInventory) -> tantivy::Result<IndexMeta> {
+        let meta_json = self.atomic_read(Path::new(META_FILEPATH))?;
+        let meta: ExternalMeta = serde_json::from_slice(&meta_json)?;
+
+        Ok(IndexMeta {
+            segments: meta
+                .segments
+                .into_iter()
+                .map(|segment| {
+                    InnerSegmentMeta {
+                        max_doc: segment.max_doc,
+                        segment_id: segment.segment_id,
+                        deletes: segment.deletes,
+                        include_temp_doc_store: Arc::new(AtomicBool::new(false)),
+                    }
+                    .track(inventory)
+                })
+                .collect(),
+            schema: meta.schema,
+            index_settings: meta.index_settings,
+            opstamp: meta.opstamp,
+            payload: None,
+        })
+    }
+
+    fn reconsider_merge_policy(
+        &self,
+        _meta: &IndexMeta,
+        _previous_meta: &IndexMeta,
+    ) -> Option<Box<dyn MergePolicy>> {
+        Some(Box::new(NoMergePolicy))
+    }
+}
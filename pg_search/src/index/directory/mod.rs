@@ -1,3 +1,4 @@
 pub mod channel;
+pub mod external;
 pub mod mvcc;
 pub mod utils;
@@ -17,31 +17,33 @@
 
 use super::utils::{list_managed_files, load_metas, save_new_metas, save_schema, save_settings};
 use crate::index::merge_policy::{
-    set_num_segments, AllowedMergePolicy, MergeLock, NPlusOneMergePolicy,
+    set_num_segments, try_get_num_segments, try_set_num_segments, AllowedMergePolicy, MergeLock,
+    MergePolicyConfig,
 };
+use crate::index::pending::{pending_segment_id, PendingSegment};
 use crate::index::reader::segment_component::SegmentComponentReader;
+use crate::postgres::options::SearchIndexCreateOptions;
 use crate::postgres::storage::block::{
     FileEntry, SegmentFileDetails, SegmentMetaEntry, SEGMENT_METAS_START,
 };
 use crate::postgres::storage::LinkedItemList;
 use anyhow::{anyhow, Result};
 use parking_lot::Mutex;
-use pgrx::pg_sys;
+use pgrx::{pg_sys, PgRelation};
 use rustc_hash::{FxHashMap, FxHashSet};
 use std::any::Any;
 use std::collections::hash_map::Entry;
 use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, OnceLock};
 use std::{io, result};
 use tantivy::directory::error::{DeleteError, LockError, OpenReadError, OpenWriteError};
 use tantivy::directory::{DirectoryLock, FileHandle, Lock, WatchCallback, WatchHandle, WritePtr};
+use tantivy::index::{InnerSegmentMeta, SegmentMetaInventory};
 use tantivy::merge_policy::{MergePolicy, NoMergePolicy};
-use tantivy::{index::SegmentMetaInventory, Directory, IndexMeta};
-
-// Minimum number of segments for the NPlusOneMergePolicy to maintain
-const MIN_NUM_SEGMENTS: usize = 2;
+use tantivy::{Directory, IndexMeta};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum MvccSatisfies {
@@ -63,6 +65,10 @@ pub struct MVCCDirectory {
     // cloned, we don't lose all the work we did originally creating the FileHandler impls.  And
     // it's cloned a lot!
     readers: Arc<Mutex<FxHashMap<PathBuf, Arc<dyn FileHandle>>>>,
+
+    // the index's pending list, indexed into a segment of its own the first time the metas are
+    // loaded.  only searches ask for this -- nothing may ever write to that segment
+    pending: Option<Arc<OnceLock<Option<PendingSegment>>>>,
 }
 
 impl MVCCDirectory {
@@ -73,6 +79,7 @@ impl MVCCDirectory {
             mvcc_style: MvccSatisfies::Snapshot,
             readers: Arc::new(Mutex::new(FxHashMap::default())),
             merge_lock: Default::default(),
+            pending: None,
         }
     }
 
@@ -83,7 +90,27 @@ impl MVCCDirectory {
             mvcc_style: MvccSatisfies::Any,
             readers: Arc::new(Mutex::new(FxHashMap::default())),
             merge_lock: Default::default(),
+            pending: None,
+        }
+    }
+
+    /// Also search the documents waiting in the index's pending list
+    pub fn with_pending_list(mut self) -> Self {
+        self.pending = Some(Default::default());
+        self
+    }
+
+    fn pending_segment(&self) -> Option<&PendingSegment> {
+        self.pending.as_ref()?.get()?.as_ref()
+    }
+
+    /// The index's `merge_policy` option
+    unsafe fn merge_policy_config(&self) -> MergePolicyConfig {
+        let index_relation = PgRelation::open(self.relation_oid);
+        if index_relation.rd_options.is_null() {
+            return MergePolicyConfig::default();
         }
+        (*(index_relation.rd_options as *const SearchIndexCreateOptions)).get_merge_policy()
     }
 
     pub unsafe fn directory_lookup(&self, path: &Path) -> Result<FileEntry> {
@@ -113,6 +140,12 @@ impl MVCCDirectory {
 impl Directory for MVCCDirectory {
     /// Returns a segment reader that implements std::io::Read
     fn get_file_handle(&self, path: &Path) -> Result<Arc<dyn FileHandle>, OpenReadError> {
+        if let Some(pending_segment) = self.pending_segment() {
+            if path.segment_id() == Some(pending_segment_id()) {
+                return pending_segment.get_file_handle(path);
+            }
+        }
+
         match self.readers.lock().entry(path.to_path_buf()) {
             Entry::Occupied(reader) => Ok(reader.get().clone()),
             Entry::Vacant(vacant) => {
@@ -232,14 +265,34 @@ impl Directory for MVCCDirectory {
     }
 
     fn load_metas(&self, inventory: &SegmentMetaInventory) -> tantivy::Result<IndexMeta> {
-        unsafe {
-            load_metas(
-                self.relation_oid,
-                inventory,
-                pg_sys::GetActiveSnapshot(),
-                self.mvcc_style,
-            )
+        let snapshot = unsafe { pg_sys::GetActiveSnapshot() };
+        let mut meta =
+            unsafe { load_metas(self.relation_oid, inventory, snapshot, self.mvcc_style)? };
+
+        if let Some(pending) = &self.pending {
+            if pending.get().is_none() {
+                let pending_segment = unsafe {
+                    PendingSegment::build(
+                        self.relation_oid,
+                        snapshot,
+                        &meta.schema,
+                        &meta.index_settings,
+                    )?
+                };
+                let _ = pending.set(pending_segment);
+            }
+            if let Some(pending_segment) = self.pending_segment() {
+                let inner_segment_meta = InnerSegmentMeta {
+                    max_doc: pending_segment.max_doc,
+                    segment_id: pending_segment_id(),
+                    deletes: None,
+                    include_temp_doc_store: Arc::new(AtomicBool::new(false)),
+                };
+                meta.segments.push(inner_segment_meta.track(inventory));
+            }
         }
+
+        Ok(meta)
     }
 
     fn reconsider_merge_policy(
@@ -266,27 +319,56 @@ impl Directory for MVCCDirectory {
             return Some(Box::new(NoMergePolicy));
         }
 
+        if let AllowedMergePolicy::Force(policy) = &self.merge_policy {
+            // whoever asked for this merge already holds the merge lock
+            return Some(Box::new(policy.clone()));
+        }
+
+        let config = unsafe { self.merge_policy_config() };
+        if matches!(config, MergePolicyConfig::NoMerge) {
+            return Some(Box::new(NoMergePolicy));
+        }
+
         //
         // if more than 1 segment was created, that means a bulk insert occurred
         // we should not merge these new segments because that would be a very expensive operation
         // instead, we should just increase the target segment count for the next merge
-        if segments_created > 1 {
+        let bulk_insert =
+            segments_created > 1 && matches!(config, MergePolicyConfig::NPlusOne { .. });
+
+        if matches!(self.merge_policy, AllowedMergePolicy::Background) {
+            // the background merge worker can hold the merge lock for as long as a merge takes,
+            // so never wait on it from here
+            if bulk_insert {
+                unsafe { try_set_num_segments(self.relation_oid, new_ids.len() as u32 - 1) };
+            } else {
+                let wants_merge = match unsafe { try_get_num_segments(self.relation_oid) } {
+                    Some(num_segments) => {
+                        !unsafe { config.merge_policy(self.relation_oid, num_segments) }
+                            .compute_merge_candidates(&meta.segments)
+                            .is_empty()
+                    }
+
+                    // a merge is running right now.  let the worker decide after it finishes
+                    None => true,
+                };
+                if wants_merge {
+                    unsafe { crate::postgres::merge_worker::request_merge(self.relation_oid) };
+                }
+            }
+            return Some(Box::new(NoMergePolicy));
+        }
+
+        if bulk_insert {
             unsafe { set_num_segments(self.relation_oid, new_ids.len() as u32 - 1) };
             return Some(Box::new(NoMergePolicy));
         }
 
         // try to acquire merge lock and do merge
         if let Some(mut merge_lock) = unsafe { MergeLock::acquire_for_merge(self.relation_oid) } {
-            if matches!(&self.merge_policy, &AllowedMergePolicy::NPlusOne) {
+            if matches!(&self.merge_policy, &AllowedMergePolicy::Configured) {
                 let num_segments = unsafe { merge_lock.num_segments() };
-                let parallelism = std::thread::available_parallelism()
-                    .expect("failed to get available_parallelism")
-                    .get();
-                let target_segments = std::cmp::max(parallelism, num_segments as usize);
-                let merge_policy: Box<dyn MergePolicy> = Box::new(NPlusOneMergePolicy {
-                    n: target_segments,
-                    min_num_segments: MIN_NUM_SEGMENTS,
-                });
+                let merge_policy = unsafe { config.merge_policy(self.relation_oid, num_segments) };
 
                 let mut lock = self.merge_lock.lock();
                 *lock = Some(merge_lock);
@@ -1,7 +1,7 @@
 use crate::index::mvcc::MvccSatisfies;
 use crate::postgres::storage::block::{
-    DeleteEntry, FileEntry, LinkedList, MVCCEntry, PgItem, SegmentFileDetails, SegmentMetaEntry,
-    SCHEMA_START, SEGMENT_METAS_START, SETTINGS_START,
+    DeleteEntry, FileEntry, LinkedList, MVCCEntry, PgItem, SchemaEntry, SegmentFileDetails,
+    SegmentMetaEntry, StoredSchema, SCHEMA_START, SEGMENT_METAS_START, SETTINGS_START,
 };
 use crate::postgres::storage::{LinkedBytesList, LinkedItemList};
 use anyhow::Result;
@@ -44,10 +44,41 @@ pub unsafe fn list_managed_files(relation_oid: pg_sys::Oid) -> tantivy::Result<H
     Ok(files)
 }
 
+/// The byte size of every segment in the index, visible or not
+pub unsafe fn segment_byte_sizes(relation_oid: pg_sys::Oid) -> FxHashMap<SegmentId, u64> {
+    let segment_metas = LinkedItemList::<SegmentMetaEntry>::open(relation_oid, SEGMENT_METAS_START);
+    let bman = segment_metas.bman();
+    let mut blockno = segment_metas.get_start_blockno();
+    let mut sizes = FxHashMap::default();
+
+    while blockno != pg_sys::InvalidBlockNumber {
+        let buffer = bman.get_buffer(blockno);
+        let page = buffer.page();
+        let max_offset = page.max_offset_number();
+        let mut offsetno = pg_sys::FirstOffsetNumber;
+
+        while offsetno <= max_offset {
+            if let Some((entry, _)) = page.read_item::<SegmentMetaEntry>(offsetno) {
+                sizes.insert(entry.segment_id, entry.byte_size());
+            }
+            offsetno += 1;
+        }
+
+        blockno = page.next_blockno();
+    }
+
+    sizes
+}
+
 pub fn save_schema(relation_oid: pg_sys::Oid, tantivy_schema: &Schema) -> Result<()> {
     let mut schema = LinkedBytesList::open(relation_oid, SCHEMA_START);
     if schema.is_empty() {
-        let bytes = serde_json::to_vec(tantivy_schema)?;
+        let entry = SchemaEntry {
+            version: 1,
+            xmin: pg_sys::FrozenTransactionId,
+            schema: tantivy_schema.clone(),
+        };
+        let bytes = serde_json::to_vec(&entry)?;
         unsafe {
             let _ = schema.write(&bytes)?;
         }
@@ -55,6 +86,40 @@ pub fn save_schema(relation_oid: pg_sys::Oid, tantivy_schema: &Schema) -> Result
     Ok(())
 }
 
+/// Load the most recent usable version of the index's schema
+pub unsafe fn load_schema(relation_oid: pg_sys::Oid) -> Result<SchemaEntry> {
+    let schema = LinkedBytesList::open(relation_oid, SCHEMA_START);
+    let bytes = schema.read_all();
+    let mut latest = None;
+    for stored in serde_json::Deserializer::from_slice(&bytes).into_iter::<StoredSchema>() {
+        let entry = SchemaEntry::from(stored?);
+        if entry.is_usable() {
+            latest = Some(entry);
+        }
+    }
+    latest.ok_or_else(|| anyhow::anyhow!("index {relation_oid:?} has no usable schema"))
+}
+
+/// Append a new version of the index's schema, returning its version number.
+///
+/// Callers must ensure `tantivy_schema` only adds fields to the end of the current schema,
+/// and must hold a lock that prevents other backends from doing the same concurrently.
+pub unsafe fn append_schema_version(
+    relation_oid: pg_sys::Oid,
+    tantivy_schema: &Schema,
+) -> Result<u32> {
+    let current = load_schema(relation_oid)?;
+    let entry = SchemaEntry {
+        version: current.version + 1,
+        xmin: pg_sys::GetCurrentTransactionId(),
+        schema: tantivy_schema.clone(),
+    };
+    let bytes = serde_json::to_vec(&entry)?;
+    let mut schema = LinkedBytesList::open(relation_oid, SCHEMA_START);
+    let _ = schema.write(&bytes)?;
+    Ok(entry.version)
+}
+
 pub fn save_settings(relation_oid: pg_sys::Oid, tantivy_settings: &IndexSettings) -> Result<()> {
     let mut settings = LinkedBytesList::open(relation_oid, SETTINGS_START);
     if settings.is_empty() {
@@ -362,9 +427,10 @@ pub unsafe fn load_metas(
 
     pg_sys::RelationClose(heap_relation);
 
-    let schema = LinkedBytesList::open(relation_oid, SCHEMA_START);
     let settings = LinkedBytesList::open(relation_oid, SETTINGS_START);
-    let deserialized_schema = serde_json::from_slice(&schema.read_all())?;
+    let deserialized_schema = load_schema(relation_oid)
+        .map_err(|err| tantivy::TantivyError::SchemaError(err.to_string()))?
+        .schema;
     let deserialized_settings = serde_json::from_slice(&settings.read_all())?;
 
     Ok(IndexMeta {
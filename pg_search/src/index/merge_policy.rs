@@ -1,13 +1,228 @@
+use crate::index::utils::segment_byte_sizes;
 use crate::postgres::storage::block::{MergeLockData, MERGE_LOCK};
 use crate::postgres::storage::buffer::{BufferManager, BufferMut};
+use anyhow::{anyhow, Result};
 use pgrx::pg_sys;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use tantivy::index::SegmentId;
 use tantivy::indexer::{MergeCandidate, MergePolicy};
+use tantivy::merge_policy::NoMergePolicy;
 use tantivy::SegmentMeta;
 
+// Minimum number of segments for the NPlusOneMergePolicy to maintain
+pub const MIN_NUM_SEGMENTS: usize = 2;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
 #[derive(Debug, Clone)]
 pub enum AllowedMergePolicy {
     None,
-    NPlusOne,
+
+    /// Merge in this backend, with the policy chosen by the index's `merge_policy` option
+    Configured,
+
+    /// Never merge in this backend, but ask the background merge worker to merge once the
+    /// writing transaction commits, if the index's merge policy has something to do
+    Background,
+
+    /// Merge with the given policy, regardless of the index's own.  The caller is responsible
+    /// for holding the [`MergeLock`], or for having called [`claim_for_force_merge`]
+    Force(ForceMergePolicy),
+}
+
+/// The `merge_policy` index option, written as either the bare policy name or a JSON object
+/// whose `type` key names the policy, alongside its tunables:
+///
+/// ```sql
+/// merge_policy = '{"type": "size_tiered", "merge_factor": 8, "max_segment_size_mb": 1024}'
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MergePolicyConfig {
+    NPlusOne {
+        /// Target this many segments, rather than one per core.  Bulk inserts that create more
+        /// segments than this raise the target
+        #[serde(default)]
+        n: Option<usize>,
+
+        #[serde(default = "MergePolicyConfig::default_min_num_segments")]
+        min_num_segments: usize,
+
+        /// Also rewrite any segment with at least this fraction of its docs deleted
+        #[serde(default)]
+        deleted_docs_ratio: Option<f64>,
+    },
+    SizeTiered {
+        #[serde(default = "MergePolicyConfig::default_merge_factor")]
+        merge_factor: usize,
+
+        /// Segments smaller than this all belong to the lowest tier
+        #[serde(default = "MergePolicyConfig::default_floor_segment_size_mb")]
+        floor_segment_size_mb: u64,
+
+        #[serde(default = "MergePolicyConfig::default_max_segment_size_mb")]
+        max_segment_size_mb: u64,
+
+        #[serde(default = "MergePolicyConfig::default_deleted_docs_ratio")]
+        deleted_docs_ratio: f64,
+    },
+    NoMerge,
+}
+
+impl Default for MergePolicyConfig {
+    fn default() -> Self {
+        MergePolicyConfig::NPlusOne {
+            n: None,
+            min_num_segments: Self::default_min_num_segments(),
+            deleted_docs_ratio: None,
+        }
+    }
+}
+
+impl MergePolicyConfig {
+    fn default_min_num_segments() -> usize {
+        MIN_NUM_SEGMENTS
+    }
+
+    fn default_merge_factor() -> usize {
+        10
+    }
+
+    fn default_floor_segment_size_mb() -> u64 {
+        2
+    }
+
+    fn default_max_segment_size_mb() -> u64 {
+        5 * 1024
+    }
+
+    fn default_deleted_docs_ratio() -> f64 {
+        0.2
+    }
+
+    pub fn from_option(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let config: Self = if value.starts_with('{') {
+            serde_json::from_str(value)?
+        } else {
+            serde_json::from_value(serde_json::json!({ "type": value }))?
+        };
+
+        let valid_ratio = |ratio: f64| ratio > 0.0 && ratio <= 1.0;
+        match &config {
+            MergePolicyConfig::NPlusOne {
+                n,
+                min_num_segments,
+                deleted_docs_ratio,
+            } => {
+                if *n == Some(0) {
+                    return Err(anyhow!("n must be at least 1"));
+                }
+                if *min_num_segments < 2 {
+                    return Err(anyhow!("min_num_segments must be at least 2"));
+                }
+                if deleted_docs_ratio.is_some_and(|ratio| !valid_ratio(ratio)) {
+                    return Err(anyhow!(
+                        "deleted_docs_ratio must be greater than 0 and at most 1"
+                    ));
+                }
+            }
+            MergePolicyConfig::SizeTiered {
+                merge_factor,
+                floor_segment_size_mb,
+                max_segment_size_mb,
+                deleted_docs_ratio,
+            } => {
+                if *merge_factor < 2 {
+                    return Err(anyhow!("merge_factor must be at least 2"));
+                }
+                if *floor_segment_size_mb == 0 || floor_segment_size_mb >= max_segment_size_mb {
+                    return Err(anyhow!(
+                        "floor_segment_size_mb must be at least 1 and less than max_segment_size_mb"
+                    ));
+                }
+                if !valid_ratio(*deleted_docs_ratio) {
+                    return Err(anyhow!(
+                        "deleted_docs_ratio must be greater than 0 and at most 1"
+                    ));
+                }
+            }
+            MergePolicyConfig::NoMerge => {}
+        }
+
+        Ok(config)
+    }
+
+    /// Build the tantivy [`MergePolicy`] for an index whose target segment count, as recorded in
+    /// its [`MergeLockData`], is `num_segments`.  Must be called from the main thread, as the
+    /// size-tiered policy reads segment sizes from the index
+    pub unsafe fn merge_policy(
+        &self,
+        relation_oid: pg_sys::Oid,
+        num_segments: u32,
+    ) -> Box<dyn MergePolicy> {
+        match self {
+            MergePolicyConfig::NPlusOne {
+                n,
+                min_num_segments,
+                deleted_docs_ratio,
+            } => {
+                // unless told otherwise, never target fewer segments than we have cores
+                let n = n.unwrap_or_else(|| {
+                    std::thread::available_parallelism()
+                        .expect("failed to get available_parallelism")
+                        .get()
+                });
+                Box::new(NPlusOneMergePolicy {
+                    n: std::cmp::max(n, num_segments as usize),
+                    min_num_segments: *min_num_segments,
+                    deleted_docs_ratio: *deleted_docs_ratio,
+                })
+            }
+            MergePolicyConfig::SizeTiered {
+                merge_factor,
+                floor_segment_size_mb,
+                max_segment_size_mb,
+                deleted_docs_ratio,
+            } => Box::new(SizeTieredMergePolicy {
+                merge_factor: *merge_factor,
+                floor_segment_size: floor_segment_size_mb * BYTES_PER_MB,
+                max_segment_size: max_segment_size_mb * BYTES_PER_MB,
+                deleted_docs_ratio: *deleted_docs_ratio,
+                segment_sizes: segment_byte_sizes(relation_oid),
+            }),
+            MergePolicyConfig::NoMerge => Box::new(NoMergePolicy),
+        }
+    }
+}
+
+fn deleted_ratio(meta: &SegmentMeta) -> f64 {
+    if meta.max_doc() == 0 {
+        0.0
+    } else {
+        meta.num_deleted_docs() as f64 / meta.max_doc() as f64
+    }
+}
+
+/// One single-segment candidate for each segment not already being merged that has deleted docs
+/// making up at least `ratio` of it.  Merging a segment by itself rewrites it without them
+fn expunge_deletes_candidates(
+    segments: &[SegmentMeta],
+    candidates: &[MergeCandidate],
+    ratio: f64,
+) -> Vec<MergeCandidate> {
+    let merging = candidates
+        .iter()
+        .flat_map(|candidate| candidate.0.iter())
+        .collect::<FxHashSet<_>>();
+    segments
+        .iter()
+        .filter(|meta| {
+            !merging.contains(&meta.id()) && meta.has_deletes() && deleted_ratio(meta) >= ratio
+        })
+        .map(|meta| MergeCandidate(vec![meta.id()]))
+        .collect()
 }
 
 /// A tantivy [`MergePolicy`] that endeavours to keep a maximum number of segments "N", plus
@@ -22,10 +237,23 @@ pub struct NPlusOneMergePolicy {
     // the minimum number of segments to merge together
     // if we don't have this many, no merge is performed
     pub min_num_segments: usize,
+
+    // rewrite segments with at least this fraction of deleted docs, even if they're not merged
+    pub deleted_docs_ratio: Option<f64>,
 }
 
 impl MergePolicy for NPlusOneMergePolicy {
     fn compute_merge_candidates(&self, segments: &[SegmentMeta]) -> Vec<MergeCandidate> {
+        let mut candidates = self.compute_n_plus_one_candidates(segments);
+        if let Some(ratio) = self.deleted_docs_ratio {
+            candidates.extend(expunge_deletes_candidates(segments, &candidates, ratio));
+        }
+        candidates
+    }
+}
+
+impl NPlusOneMergePolicy {
+    fn compute_n_plus_one_candidates(&self, segments: &[SegmentMeta]) -> Vec<MergeCandidate> {
         let n = self.n;
         let min_num_segments = self.min_num_segments;
 
@@ -52,6 +280,132 @@ impl MergePolicy for NPlusOneMergePolicy {
     }
 }
 
+/// A tantivy [`MergePolicy`] that groups segments into tiers by their on-disk size, each tier
+/// `merge_factor` times larger than the one below it, and merges `merge_factor` segments of a
+/// tier together once it has that many.  Every document is rewritten about once per tier, so
+/// write amplification grows with the log of the index size rather than with its segment count.
+#[derive(Debug)]
+pub struct SizeTieredMergePolicy {
+    pub merge_factor: usize,
+
+    // segments smaller than this, in bytes, all belong to the lowest tier
+    pub floor_segment_size: u64,
+
+    // never produce a segment larger than this, in bytes
+    pub max_segment_size: u64,
+
+    pub deleted_docs_ratio: f64,
+
+    // the byte size of each segment, as recorded in its `SegmentMetaEntry`
+    pub segment_sizes: FxHashMap<SegmentId, u64>,
+}
+
+impl SizeTieredMergePolicy {
+    /// The segment's size, discounting the space taken by its deleted docs
+    fn live_size(&self, meta: &SegmentMeta) -> u64 {
+        let size = self.segment_sizes.get(&meta.id()).copied().unwrap_or(0);
+        (size as f64 * (1.0 - deleted_ratio(meta))) as u64
+    }
+
+    fn tier(&self, size: u64) -> usize {
+        let mut tier = 0;
+        let mut bound = self.floor_segment_size;
+        while size >= bound {
+            tier += 1;
+            bound = bound.saturating_mul(self.merge_factor as u64);
+        }
+        tier
+    }
+}
+
+impl MergePolicy for SizeTieredMergePolicy {
+    fn compute_merge_candidates(&self, segments: &[SegmentMeta]) -> Vec<MergeCandidate> {
+        // segments at least half the max size are finished, as merging any two of them would
+        // exceed it.  sort the rest smallest-to-largest
+        let mut sized = segments
+            .iter()
+            .map(|meta| (meta.id(), self.live_size(meta)))
+            .filter(|(_, size)| *size < self.max_segment_size / 2)
+            .collect::<Vec<_>>();
+        sized.sort_unstable_by_key(|(_, size)| *size);
+
+        let mut tiers: Vec<Vec<(SegmentId, u64)>> = vec![];
+        let mut current_tier = None;
+        for (id, size) in sized {
+            let tier = self.tier(size);
+            if current_tier != Some(tier) {
+                tiers.push(vec![]);
+                current_tier = Some(tier);
+            }
+            tiers.last_mut().unwrap().push((id, size));
+        }
+
+        let mut candidates = vec![];
+        for tier in tiers {
+            for group in tier.chunks_exact(self.merge_factor) {
+                // take the group's smallest segments that fit within the max size together
+                let mut candidate = MergeCandidate(vec![]);
+                let mut total_size = 0;
+                for (id, size) in group {
+                    if total_size + size > self.max_segment_size {
+                        break;
+                    }
+                    total_size += size;
+                    candidate.0.push(*id);
+                }
+
+                if candidate.0.len() >= 2 {
+                    candidates.push(candidate);
+                }
+            }
+        }
+
+        candidates.extend(expunge_deletes_candidates(
+            segments,
+            &candidates,
+            self.deleted_docs_ratio,
+        ));
+        candidates
+    }
+}
+
+/// The tantivy [`MergePolicy`] behind `paradedb.merge()` and VACUUM, which merges the index down
+/// to at most `max_segments` segments by merging all but the largest together, and/or rewrites
+/// every segment with at least `deleted_docs_ratio` of its docs deleted.  At most `max_merges`
+/// of those merges happen per commit
+#[derive(Debug, Clone)]
+pub struct ForceMergePolicy {
+    pub max_segments: Option<usize>,
+    pub deleted_docs_ratio: Option<f64>,
+    pub max_merges: Option<usize>,
+}
+
+impl MergePolicy for ForceMergePolicy {
+    fn compute_merge_candidates(&self, segments: &[SegmentMeta]) -> Vec<MergeCandidate> {
+        let mut candidates = vec![];
+
+        if let Some(max_segments) = self.max_segments {
+            if segments.len() > max_segments {
+                // keep the largest `max_segments - 1` segments as they are
+                let mut by_size = segments.iter().collect::<Vec<_>>();
+                by_size.sort_unstable_by(|a, b| a.num_docs().cmp(&b.num_docs()).reverse());
+                let rest = by_size.split_off(max_segments - 1);
+                candidates.push(MergeCandidate(rest.iter().map(|meta| meta.id()).collect()));
+            }
+        }
+
+        if let Some(ratio) = self.deleted_docs_ratio {
+            candidates.extend(expunge_deletes_candidates(segments, &candidates, ratio));
+        }
+
+        if let Some(max_merges) = self.max_merges {
+            candidates.truncate(max_merges);
+        }
+
+        candidates
+    }
+}
+
 /// Only one merge can happen at a time, so we need to lock the merge process
 #[derive(Debug)]
 pub struct MergeLock(BufferMut);
@@ -87,8 +441,10 @@ impl MergeLock {
     }
 
     // This lock must be acquired before ambulkdelete calls commit() on the index
-    // We ask for an exclusive lock because ambulkdelete must delete all dead ctids
+    // We ask for an exclusive lock because ambulkdelete must delete all dead ctids.  A
+    // `paradedb.merge()` doesn't hold the block while it merges, so we wait for it separately
     pub unsafe fn acquire_for_delete(relation_oid: pg_sys::Oid) -> Self {
+        crate::postgres::merge_worker::lock_merges_for_transaction(relation_oid);
         let mut bman = BufferManager::new(relation_oid);
         let merge_lock = bman.get_buffer_mut(MERGE_LOCK);
         MergeLock(merge_lock)
@@ -113,6 +469,46 @@ impl Drop for MergeLock {
     }
 }
 
+/// Claim the index's merges for `paradedb.merge()` until the current transaction ends, once any
+/// merge in progress has finished and committed.  Unlike [`MergeLock`], the claim doesn't hold
+/// the [`MERGE_LOCK`] block, so the merges that follow can be cancelled.  Merge workers and
+/// ambulkdelete wait on the same heavyweight lock, and every other merge finds our transaction
+/// in `last_merge` and leaves the index alone
+pub unsafe fn claim_for_force_merge(relation_oid: pg_sys::Oid) {
+    crate::postgres::merge_worker::lock_merges_for_transaction(relation_oid);
+
+    let mut bman = BufferManager::new(relation_oid);
+    loop {
+        let Some(mut merge_lock) = bman.get_buffer_conditional(MERGE_LOCK) else {
+            // a merge is running
+            pgrx::check_for_interrupts!();
+            pg_sys::pg_usleep(10_000);
+            continue;
+        };
+
+        let mut page = merge_lock.page_mut();
+        let metadata = page.contents_mut::<MergeLockData>();
+        let last_merge = metadata.last_merge;
+        if last_merge != pg_sys::InvalidTransactionId
+            && !pg_sys::TransactionIdIsCurrentTransactionId(last_merge)
+            && pg_sys::TransactionIdIsInProgress(last_merge)
+        {
+            // a merge has finished, but we wouldn't see it until its transaction commits
+            drop(merge_lock);
+            pg_sys::XactLockTableWait(
+                last_merge,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                pg_sys::XLTW_Oper::XLTW_None,
+            );
+            continue;
+        }
+
+        metadata.last_merge = pg_sys::GetCurrentTransactionId();
+        return;
+    }
+}
+
 pub unsafe fn set_num_segments(relation_oid: pg_sys::Oid, num_segments: u32) {
     let mut bman = BufferManager::new(relation_oid);
     let mut buffer = bman.get_buffer_mut(MERGE_LOCK);
@@ -120,3 +516,26 @@ pub unsafe fn set_num_segments(relation_oid: pg_sys::Oid, num_segments: u32) {
     let metadata = page.contents_mut::<MergeLockData>();
     metadata.num_segments = num_segments;
 }
+
+/// Like [`set_num_segments`], but gives up instead of waiting if a merge currently holds the
+/// [`MERGE_LOCK`] block.  Returns `true` if the new value was written
+pub unsafe fn try_set_num_segments(relation_oid: pg_sys::Oid, num_segments: u32) -> bool {
+    let mut bman = BufferManager::new(relation_oid);
+    match bman.get_buffer_conditional(MERGE_LOCK) {
+        Some(mut buffer) => {
+            let mut page = buffer.page_mut();
+            let metadata = page.contents_mut::<MergeLockData>();
+            metadata.num_segments = num_segments;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Read the target segment count without waiting on a merge that holds the [`MERGE_LOCK`] block
+pub unsafe fn try_get_num_segments(relation_oid: pg_sys::Oid) -> Option<u32> {
+    let mut bman = BufferManager::new(relation_oid);
+    let buffer = bman.get_buffer_conditional(MERGE_LOCK)?;
+    let metadata = buffer.page().contents::<MergeLockData>();
+    Some(metadata.num_segments)
+}
@@ -18,8 +18,10 @@
 pub mod directory;
 pub mod fast_fields_helper;
 pub mod merge_policy;
+pub mod pending;
 pub mod reader;
 pub mod search;
+pub mod statistics;
 pub mod writer;
 
 pub use directory::*;
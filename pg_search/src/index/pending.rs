@@ -0,0 +1,373 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! The pending list of an index with `fastupdate` enabled.
+//!
+//! Rather than writing a new segment for every transaction, inserts append their documents to a
+//! [`LinkedItemList`] of [`PendingEntry`]s.  Scans index whatever is visible to them into an
+//! in-memory segment, and once the list grows past `pending_list_limit`, or when the index is
+//! vacuumed, its entries are flushed into a real segment.
+//!
+//! Flushing doesn't remove entries, it sets their `xmax` to the flushing transaction, which is
+//! also the `xmin` of the segment they're written to.  So any snapshot sees each document exactly
+//! once: either in the pending list or in that segment.  VACUUM recycles flushed entries once no
+//! one can see them anymore.
+
+use crate::index::setup_tokenizers;
+use crate::index::writer::index::SearchIndexWriter;
+use crate::index::{BlockDirectoryType, WriterResources};
+use crate::postgres::options::SearchIndexCreateOptions;
+use crate::postgres::storage::block::{
+    bm25_max_free_space, CleanupLockData, LinkedList, MVCCEntry, PendingEntry, PgItem, CLEANUP_LOCK,
+};
+use crate::postgres::storage::buffer::BufferManager;
+use crate::postgres::storage::LinkedItemList;
+use crate::schema::SearchDocument;
+use anyhow::Result;
+use pgrx::{check_for_interrupts, pg_sys, PgRelation};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tantivy::directory::error::OpenReadError;
+use tantivy::directory::{FileHandle, RamDirectory};
+use tantivy::index::SegmentId;
+use tantivy::indexer::UserOperation;
+use tantivy::merge_policy::NoMergePolicy;
+use tantivy::schema::Schema;
+use tantivy::{Directory, Index, IndexSettings, IndexWriter, TantivyDocument};
+
+/// The segment id the pending list is searched under.  It's the same in every backend, so that
+/// the workers of a parallel scan agree on which segment is which
+const PENDING_SEGMENT_UUID: &str = "ffffffffffffffffffffffffffffffff";
+
+/// Tantivy's indexer needs at least 15MB.  This is plenty for the default `pending_list_limit`,
+/// and a pending list that needs more is still indexed, just into more than one segment
+const PENDING_MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+
+pub fn pending_segment_id() -> SegmentId {
+    SegmentId::from_uuid_string(PENDING_SEGMENT_UUID).expect("pending segment id should be valid")
+}
+
+/// Whether the index was created, or altered, with `fastupdate = true`, and if so how large its
+/// pending list can grow before it's flushed
+pub fn pending_list_limit(index_relation: &PgRelation) -> Option<usize> {
+    if index_relation.rd_options.is_null() {
+        return None;
+    }
+    let options = unsafe { &*(index_relation.rd_options as *const SearchIndexCreateOptions) };
+    options
+        .get_fastupdate()
+        .then(|| options.get_pending_list_limit())
+}
+
+/// The index's pending list, if anything has ever been added to it
+pub fn pending_list(relation_oid: pg_sys::Oid) -> Option<LinkedItemList<PendingEntry>> {
+    let bman = BufferManager::new(relation_oid);
+    let pending_blockno = bman
+        .get_buffer(CLEANUP_LOCK)
+        .page_contents::<CleanupLockData>()
+        .pending_blockno;
+    if pending_blockno == 0 || pending_blockno == pg_sys::InvalidBlockNumber {
+        return None;
+    }
+    Some(LinkedItemList::open(relation_oid, pending_blockno))
+}
+
+/// Make a pending list entry for `document`, unless it's too large to fit on a page or wouldn't
+/// come back out the same, in which case it needs to be written to a segment directly
+pub fn pending_entry(
+    schema: &Schema,
+    document: &SearchDocument,
+    ctid: u64,
+) -> Option<PendingEntry> {
+    // the entry is parsed back into a document when it's flushed, which had better give the
+    // same document, so make sure it serializes the same way again
+    let to_json = |doc: &TantivyDocument| {
+        let json = doc.to_json(schema);
+        let reparsed = TantivyDocument::parse_json(schema, &json).ok()?;
+        (reparsed.to_json(schema) == json).then_some(json)
+    };
+
+    let entry = PendingEntry {
+        xmin: unsafe { pg_sys::GetCurrentTransactionId() },
+        xmax: pg_sys::InvalidTransactionId,
+        ctid,
+        doc: to_json(&document.doc)?,
+        nested: document
+            .nested
+            .iter()
+            .map(to_json)
+            .collect::<Option<Vec<_>>>()?,
+    };
+
+    let fits = unsafe { pg_sys::MAXALIGN(entry.item_size()) }
+        + std::mem::size_of::<pg_sys::ItemIdData>()
+        <= bm25_max_free_space();
+    fits.then_some(entry)
+}
+
+/// The document a pending list entry was made from, without its ctid
+pub fn pending_document(schema: &Schema, entry: &PendingEntry) -> Result<SearchDocument> {
+    Ok(SearchDocument {
+        doc: TantivyDocument::parse_json(schema, &entry.doc)?,
+        nested: entry
+            .nested
+            .iter()
+            .map(|json| TantivyDocument::parse_json(schema, json))
+            .collect::<std::result::Result<_, _>>()?,
+    })
+}
+
+/// Add `entries` to the end of the index's pending list, creating it if need be, and return
+/// roughly how many bytes of documents are now waiting to be flushed
+pub unsafe fn append_pending(relation_oid: pg_sys::Oid, entries: Vec<PendingEntry>) -> Result<u64> {
+    let bytes = entries
+        .iter()
+        .map(|entry| entry.item_size() as u64)
+        .sum::<u64>();
+
+    let mut bman = BufferManager::new(relation_oid);
+    let (pending_blockno, pending_bytes) = {
+        let mut cleanup_lock = bman.get_buffer_mut(CLEANUP_LOCK);
+        let mut page = cleanup_lock.page_mut();
+        let metadata = page.contents_mut::<CleanupLockData>();
+        if metadata.pending_blockno == 0 || metadata.pending_blockno == pg_sys::InvalidBlockNumber {
+            let list = LinkedItemList::<PendingEntry>::create(relation_oid);
+            metadata.pending_blockno = list.header_blockno;
+        }
+        metadata.pending_bytes = metadata.pending_bytes.saturating_add(bytes);
+        (metadata.pending_blockno, metadata.pending_bytes)
+    };
+
+    LinkedItemList::<PendingEntry>::open(relation_oid, pending_blockno).add_items(entries, None)?;
+    Ok(pending_bytes)
+}
+
+/// Write everything in the pending list into a new segment.  If `wait` is false and someone else
+/// is already flushing it, we leave it to them.
+///
+/// Returns the number of documents flushed.
+pub unsafe fn flush_pending(index_relation: &PgRelation, wait: bool) -> Result<usize> {
+    let Some(list) = pending_list(index_relation.oid()) else {
+        return Ok(0);
+    };
+
+    if !lock_pending_list(index_relation, &list, wait) {
+        return Ok(0);
+    }
+    let flushed = flush_locked(index_relation, &list)?;
+    unlock_pending_list(index_relation, &list);
+    Ok(flushed)
+}
+
+/// Flush the pending list, then recycle the entries that earlier flushes left behind
+pub unsafe fn vacuum_pending(index_relation: &PgRelation) -> Result<()> {
+    let Some(mut list) = pending_list(index_relation.oid()) else {
+        return Ok(());
+    };
+
+    lock_pending_list(index_relation, &list, true);
+    flush_locked(index_relation, &list)?;
+    list.garbage_collect(pg_sys::GetAccessStrategy(
+        pg_sys::BufferAccessStrategyType::BAS_VACUUM,
+    ))?;
+    unlock_pending_list(index_relation, &list);
+    Ok(())
+}
+
+/// Only one backend flushes the pending list at a time.  It's also what stops VACUUM from
+/// compacting the list's pages while a flush is holding onto the offsets of its entries
+unsafe fn lock_pending_list(
+    index_relation: &PgRelation,
+    list: &LinkedItemList<PendingEntry>,
+    wait: bool,
+) -> bool {
+    let lockmode = pg_sys::ExclusiveLock as pg_sys::LOCKMODE;
+    if wait {
+        pg_sys::LockPage(index_relation.as_ptr(), list.header_blockno, lockmode);
+        true
+    } else {
+        pg_sys::ConditionalLockPage(index_relation.as_ptr(), list.header_blockno, lockmode)
+    }
+}
+
+unsafe fn unlock_pending_list(index_relation: &PgRelation, list: &LinkedItemList<PendingEntry>) {
+    pg_sys::UnlockPage(
+        index_relation.as_ptr(),
+        list.header_blockno,
+        pg_sys::ExclusiveLock as pg_sys::LOCKMODE,
+    );
+}
+
+unsafe fn flush_locked(
+    index_relation: &PgRelation,
+    list: &LinkedItemList<PendingEntry>,
+) -> Result<usize> {
+    // every entry that hasn't been flushed yet, whether or not the transaction that added it has
+    // finished.  the heap decides which of them anyone gets to see
+    let mut entries = vec![];
+    let mut locations = BTreeMap::<pg_sys::BlockNumber, Vec<pg_sys::OffsetNumber>>::new();
+    let mut blockno = list.get_start_blockno();
+    while blockno != pg_sys::InvalidBlockNumber {
+        check_for_interrupts!();
+        let buffer = list.bman().get_buffer(blockno);
+        let page = buffer.page();
+        for offsetno in pg_sys::FirstOffsetNumber..=page.max_offset_number() {
+            if let Some((entry, _)) = page.read_item::<PendingEntry>(offsetno) {
+                if entry.xmax == pg_sys::InvalidTransactionId {
+                    locations.entry(blockno).or_default().push(offsetno);
+                    entries.push(entry);
+                }
+            }
+        }
+        blockno = page.next_blockno();
+    }
+
+    if !entries.is_empty() {
+        let mut writer = SearchIndexWriter::open(
+            index_relation,
+            BlockDirectoryType::Mvcc,
+            WriterResources::Statement,
+        )?;
+        let schema = writer.schema.schema.clone();
+        for entry in &entries {
+            check_for_interrupts!();
+            writer.insert(pending_document(&schema, entry)?, entry.ctid)?;
+        }
+        writer.commit()?;
+
+        // the segment those documents were just written to has us as its xmin
+        let xid = pg_sys::GetCurrentTransactionId();
+        let mut bman = BufferManager::new(index_relation.oid());
+        for (blockno, offsets) in locations {
+            let mut buffer = bman.get_buffer_mut(blockno);
+            let mut page = buffer.page_mut();
+            for offsetno in offsets {
+                let (entry, _) = page
+                    .read_item::<PendingEntry>(offsetno)
+                    .expect("pending entry should not have moved");
+                let PgItem(item, size) = PendingEntry { xmax: xid, ..entry }.into();
+                let did_replace = page.replace_item(offsetno, item, size);
+                assert!(did_replace);
+            }
+        }
+    }
+
+    // whatever was added while we were flushing will be counted again by the next flush
+    let mut bman = BufferManager::new(index_relation.oid());
+    let mut cleanup_lock = bman.get_buffer_mut(CLEANUP_LOCK);
+    let mut page = cleanup_lock.page_mut();
+    page.contents_mut::<CleanupLockData>().pending_bytes = 0;
+
+    Ok(entries.len())
+}
+
+/// The pending list entries visible to a scan, indexed into a segment that only lives in memory
+#[derive(Clone, Debug)]
+pub struct PendingSegment {
+    directory: RamDirectory,
+    segment_id: SegmentId,
+    pub max_doc: u32,
+}
+
+impl PendingSegment {
+    /// Index the entries of the pending list that are visible to `snapshot`, if there are any
+    pub unsafe fn build(
+        relation_oid: pg_sys::Oid,
+        snapshot: pg_sys::Snapshot,
+        schema: &Schema,
+        settings: &IndexSettings,
+    ) -> tantivy::Result<Option<Self>> {
+        let Some(list) = pending_list(relation_oid) else {
+            return Ok(None);
+        };
+
+        let ctid_field = schema.get_field("ctid")?;
+        let mut operations = vec![];
+        let mut blockno = list.get_start_blockno();
+        while blockno != pg_sys::InvalidBlockNumber {
+            check_for_interrupts!();
+            let buffer = list.bman().get_buffer(blockno);
+            let page = buffer.page();
+            for offsetno in pg_sys::FirstOffsetNumber..=page.max_offset_number() {
+                let Some((entry, _)) = page.read_item::<PendingEntry>(offsetno) else {
+                    continue;
+                };
+                if !entry.visible(snapshot) {
+                    continue;
+                }
+
+                let SearchDocument { mut doc, nested } = pending_document(schema, &entry)
+                    .map_err(|err| tantivy::TantivyError::InternalError(err.to_string()))?;
+                for mut child in nested {
+                    child.add_u64(ctid_field, entry.ctid);
+                    operations.push(UserOperation::Add(child));
+                }
+                doc.add_u64(ctid_field, entry.ctid);
+                operations.push(UserOperation::Add(doc));
+            }
+            blockno = page.next_blockno();
+        }
+
+        if operations.is_empty() {
+            return Ok(None);
+        }
+
+        let directory = RamDirectory::create();
+        let mut index = Index::create(directory.clone(), schema.clone(), settings.clone())?;
+        setup_tokenizers(&mut index, &PgRelation::open(relation_oid));
+
+        let mut writer: IndexWriter = index.writer_with_num_threads(1, PENDING_MEMORY_BUDGET)?;
+        writer.set_merge_policy(Box::new(NoMergePolicy));
+        writer.run(operations)?;
+        writer.commit()?;
+
+        // the pending list is searched as a single segment, so if the memory budget split it
+        // across several, they're merged back together
+        let segment_ids = index.searchable_segment_ids()?;
+        if segment_ids.len() > 1 {
+            writer.merge(&segment_ids).wait()?;
+        }
+        writer.wait_merging_threads()?;
+
+        let mut segments = index.searchable_segment_metas()?;
+        assert!(
+            segments.len() == 1,
+            "pending list should have been written to a single segment, not {}",
+            segments.len()
+        );
+        let segment = segments.pop().unwrap();
+        Ok(Some(Self {
+            directory,
+            segment_id: segment.id(),
+            max_doc: segment.max_doc(),
+        }))
+    }
+
+    /// Open one of the segment's files, by its name under [`pending_segment_id`]
+    pub fn get_file_handle(&self, path: &Path) -> Result<Arc<dyn FileHandle>, OpenReadError> {
+        let file_name = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .unwrap_or_default();
+        let extension = file_name
+            .strip_prefix(PENDING_SEGMENT_UUID)
+            .ok_or_else(|| OpenReadError::FileDoesNotExist(path.to_path_buf()))?;
+        let ram_path = PathBuf::from(format!("{}{extension}", self.segment_id.uuid_string()));
+        self.directory.get_file_handle(&ram_path)
+    }
+}
@@ -21,21 +21,27 @@ use crate::index::reader::index::scorer_iter::DeferredScorer;
 use crate::index::{setup_tokenizers, BlockDirectoryType};
 use crate::postgres::storage::block::CLEANUP_LOCK;
 use crate::postgres::storage::buffer::{BufferManager, PinnedBuffer};
+use crate::postgres::utils::categorize_fields;
 use crate::query::SearchQueryInput;
 use crate::schema::SearchField;
 use crate::schema::{SearchFieldName, SearchIndexSchema};
 use anyhow::Result;
-use pgrx::{pg_sys, PgRelation};
+use pgrx::{pg_sys, PgRelation, PgTupleDesc};
 use rustc_hash::FxHashMap;
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::fmt::Debug;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tantivy::collector::{Collector, TopDocs};
+use tantivy::columnar::{Cardinality, Column, ColumnType};
 use tantivy::index::{Index, SegmentId};
-use tantivy::query::{EnableScoring, QueryParser, Weight};
-use tantivy::schema::FieldType;
+use tantivy::query::{
+    BooleanQuery, ConstScoreQuery, EnableScoring, ExistsQuery, QueryParser, Weight,
+};
+use tantivy::query_grammar::Occur;
+use tantivy::schema::{Facet, FieldType, OwnedValue, DATE_TIME_PRECISION_INDEXED};
 use tantivy::termdict::TermOrdinal;
 use tantivy::{
     query::Query, DocAddress, DocId, DocSet, IndexReader, Order, ReloadPolicy, Score, Searcher,
@@ -103,6 +109,11 @@ pub enum SearchResults {
         FastFieldCache,
         std::vec::IntoIter<(TermOrdinal, DocAddress)>,
     ),
+    TopNByCompound(
+        Searcher,
+        FastFieldCache,
+        std::vec::IntoIter<(CompoundScore, DocAddress)>,
+    ),
     SingleSegment(
         Searcher,
         SegmentOrdinal,
@@ -129,6 +140,187 @@ impl PartialOrd for TweakedScore {
     }
 }
 
+/// One key of a compound "top N" sort:  the named fast field, or the document's score if `None`
+pub type SortKey = (Option<String>, SortDirection);
+
+/// A single document's value for one [`SortKey`]
+#[derive(Debug, Clone)]
+pub enum SortValue {
+    Null,
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl SortValue {
+    #[inline(always)]
+    fn from_fast_field(ff: &FFType, doc: DocId) -> Self {
+        match ff {
+            FFType::Junk => SortValue::Null,
+            FFType::Text(_) => {
+                let mut s = String::new();
+                ff.string(doc, &mut s)
+                    .map(|_| SortValue::Str(s))
+                    .unwrap_or(SortValue::Null)
+            }
+            FFType::I64(ff) => ff.first(doc).map(SortValue::I64).unwrap_or(SortValue::Null),
+            FFType::F64(ff) => ff.first(doc).map(SortValue::F64).unwrap_or(SortValue::Null),
+            FFType::U64(ff) => ff.first(doc).map(SortValue::U64).unwrap_or(SortValue::Null),
+            FFType::Bool(ff) => ff
+                .first(doc)
+                .map(SortValue::Bool)
+                .unwrap_or(SortValue::Null),
+            FFType::Date(ff) => ff
+                .first(doc)
+                .map(|date| SortValue::I64(date.into_timestamp_nanos()))
+                .unwrap_or(SortValue::Null),
+        }
+    }
+
+    /// Convert a `paradedb.search_after()` cursor value to the kind of value `ff` holds, or to a
+    /// score if there's no `ff`
+    fn from_search_after(value: &OwnedValue, ff: Option<&FFType>) -> Self {
+        fn mismatch<T>(value: &OwnedValue) -> T {
+            panic!("search_after value {value:?} doesn't match the type of its ORDER BY column")
+        }
+
+        match (value, ff) {
+            (OwnedValue::Null, _) => SortValue::Null,
+            // scores are `f32`s, and the cursor is usually a score as Postgres printed it, so it
+            // must be rounded the same way to compare equal to the score it came from
+            (value, None) => match value {
+                OwnedValue::F64(f) => SortValue::F64((*f as f32) as f64),
+                OwnedValue::I64(i) => SortValue::F64((*i as f32) as f64),
+                OwnedValue::U64(u) => SortValue::F64((*u as f32) as f64),
+                _ => mismatch(value),
+            },
+            (value, Some(FFType::F64(_))) => match value {
+                OwnedValue::F64(f) => SortValue::F64(*f),
+                OwnedValue::I64(i) => SortValue::F64(*i as f64),
+                OwnedValue::U64(u) => SortValue::F64(*u as f64),
+                _ => mismatch(value),
+            },
+            (value, Some(FFType::I64(_))) => match value {
+                OwnedValue::I64(i) => SortValue::I64(*i),
+                OwnedValue::U64(u) => {
+                    SortValue::I64(i64::try_from(*u).unwrap_or_else(|_| mismatch(value)))
+                }
+                _ => mismatch(value),
+            },
+            (value, Some(FFType::U64(_))) => match value {
+                OwnedValue::U64(u) => SortValue::U64(*u),
+                OwnedValue::I64(i) => {
+                    SortValue::U64(u64::try_from(*i).unwrap_or_else(|_| mismatch(value)))
+                }
+                _ => mismatch(value),
+            },
+            (OwnedValue::Bool(b), Some(FFType::Bool(_))) => SortValue::Bool(*b),
+            (OwnedValue::Str(s), Some(FFType::Text(_))) => SortValue::Str(s.clone()),
+            (OwnedValue::Date(date), Some(FFType::Date(_))) => SortValue::I64(
+                date.truncate(DATE_TIME_PRECISION_INDEXED)
+                    .into_timestamp_nanos(),
+            ),
+            (OwnedValue::Str(s), Some(FFType::Date(_))) => parse_search_after_date(s)
+                .map(|date| {
+                    SortValue::I64(
+                        date.truncate(DATE_TIME_PRECISION_INDEXED)
+                            .into_timestamp_nanos(),
+                    )
+                })
+                .unwrap_or_else(|| mismatch(value)),
+            _ => mismatch(value),
+        }
+    }
+
+    /// Compares the way Postgres does by default:  NULLs and NaNs sort after every other value
+    fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortValue::Null, SortValue::Null) => Ordering::Equal,
+            (SortValue::Null, _) => Ordering::Greater,
+            (_, SortValue::Null) => Ordering::Less,
+            (SortValue::I64(a), SortValue::I64(b)) => a.cmp(b),
+            (SortValue::U64(a), SortValue::U64(b)) => a.cmp(b),
+            (SortValue::Bool(a), SortValue::Bool(b)) => a.cmp(b),
+            (SortValue::Str(a), SortValue::Str(b)) => a.cmp(b),
+            (SortValue::F64(a), SortValue::F64(b)) => match (a.is_nan(), b.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            },
+            // every document of a segment has the same kind of value for a given key
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// Dates in a `paradedb.search_after()` cursor are usually copied from `row_to_json()` output,
+/// which is ISO 8601 with an offset for `timestamptz` and without one for `timestamp`
+fn parse_search_after_date(s: &str) -> Option<tantivy::DateTime> {
+    let nanos = match chrono::DateTime::parse_from_rfc3339(s) {
+        Ok(datetime) => datetime.timestamp_nanos_opt()?,
+        Err(_) => chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+            .or_else(|_| chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f"))
+            .ok()
+            .or_else(|| {
+                chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                    .ok()?
+                    .and_hms_opt(0, 0, 0)
+            })?
+            .and_utc()
+            .timestamp_nanos_opt()?,
+    };
+    Some(tantivy::DateTime::from_timestamp_nanos(nanos))
+}
+
+/// The "score" of a document when sorting by several [`SortKey`]s at once.  It orders
+/// lexicographically by each key's value, in that key's direction, such that the "greatest" score
+/// is the document that sorts first
+#[derive(Clone)]
+pub struct CompoundScore {
+    dirs: Arc<[SortDirection]>,
+    values: Vec<SortValue>,
+    score: Score,
+    excluded: bool,
+}
+
+impl PartialEq for CompoundScore {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(self.partial_cmp(other), Some(Ordering::Equal))
+    }
+}
+
+impl PartialOrd for CompoundScore {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.excluded != other.excluded {
+            return Some(if self.excluded {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            });
+        }
+
+        for ((a, b), dir) in self
+            .values
+            .iter()
+            .zip(other.values.iter())
+            .zip(self.dirs.iter())
+        {
+            let cmp = match dir {
+                SortDirection::Desc => a.compare(b),
+                SortDirection::Asc => a.compare(b).reverse(),
+                SortDirection::None => Ordering::Equal,
+            };
+            if cmp != Ordering::Equal {
+                return Some(cmp);
+            }
+        }
+        Some(Ordering::Equal)
+    }
+}
+
 impl Iterator for SearchResults {
     type Item = (SearchIndexScore, DocAddress);
 
@@ -147,6 +339,10 @@ impl Iterator for SearchResults {
                 let (_, doc_id) = iter.next()?;
                 (searcher, ff_lookup, (1.0, doc_id))
             }
+            SearchResults::TopNByCompound(searcher, ff_lookup, iter) => {
+                let (score, doc_id) = iter.next()?;
+                (searcher, ff_lookup, (score.score, doc_id))
+            }
             SearchResults::SingleSegment(searcher, segment_ord, fftype, iter) => {
                 let (score, doc_address) = iter.next()?;
                 let ctid_ff = fftype.get_or_insert_with(|| {
@@ -214,6 +410,7 @@ impl Iterator for SearchResults {
             SearchResults::TopNByScore(_, _, iter) => iter.size_hint(),
             SearchResults::TopNByTweakedScore(_, _, iter) => iter.size_hint(),
             SearchResults::TopNByField(_, _, iter) => iter.size_hint(),
+            SearchResults::TopNByCompound(_, _, iter) => iter.size_hint(),
             SearchResults::SingleSegment(_, _, _, iter) => iter.size_hint(),
             SearchResults::AllSegments(_, _, iters) => {
                 let hint = iters
@@ -234,6 +431,7 @@ impl Iterator for SearchResults {
             SearchResults::TopNByScore(_, _, iter) => iter.count(),
             SearchResults::TopNByTweakedScore(_, _, iter) => iter.count(),
             SearchResults::TopNByField(_, _, iter) => iter.count(),
+            SearchResults::TopNByCompound(_, _, iter) => iter.count(),
             SearchResults::SingleSegment(_, _, _, iter) => iter.count(),
             SearchResults::AllSegments(_, _, iters) => {
                 iters.into_iter().map(|iter| iter.count()).sum()
@@ -250,6 +448,10 @@ pub struct SearchIndexReader {
     underlying_reader: IndexReader,
     underlying_index: Index,
 
+    // fields whose source column is an array, and are returned as arrays no matter how many
+    // values they hold
+    array_fields: Arc<HashSet<SearchFieldName>>,
+
     // [`PinnedBuffer`] has a Drop impl, so we hold onto it but don't otherwise use it
     //
     // also, it's an Arc b/c if we're clone'd (we do derive it, after all), we only want this
@@ -280,9 +482,21 @@ impl SearchIndexReader {
             None
         };
 
-        let directory = directory_type.directory(index_relation, AllowedMergePolicy::None);
+        // documents waiting in the pending list are found like any others.  BulkDelete readers
+        // don't need them, as VACUUM flushes the pending list first
+        let search_pending = matches!(directory_type, BlockDirectoryType::Mvcc);
+        let mut directory = directory_type.directory(index_relation, AllowedMergePolicy::None);
+        if search_pending {
+            directory = directory.with_pending_list();
+        }
         let mut index = Index::open(directory)?;
         let schema = SearchIndexSchema::open(index.schema(), index_relation);
+        let tupdesc = unsafe { PgTupleDesc::from_pg_unchecked(index_relation.rd_att) };
+        let array_fields = categorize_fields(&tupdesc, &schema)
+            .into_iter()
+            .filter(|(_, categorized)| categorized.is_array)
+            .map(|(search_field, _)| search_field.name)
+            .collect();
 
         setup_tokenizers(&mut index, index_relation);
         let reader = index
@@ -297,6 +511,7 @@ impl SearchIndexReader {
             schema,
             underlying_reader: reader,
             underlying_index: index,
+            array_fields: Arc::new(array_fields),
             _cleanup_lock: Arc::new(cleanup_lock),
         })
     }
@@ -314,7 +529,7 @@ impl SearchIndexReader {
                 .map(|search_field| search_field.id.0)
                 .collect::<Vec<_>>(),
         );
-        search_query_input
+        let query = search_query_input
             .clone()
             .into_tantivy_query(
                 &(
@@ -324,7 +539,24 @@ impl SearchIndexReader {
                 &mut parser,
                 &self.searcher,
             )
-            .expect("must be able to parse query")
+            .expect("must be able to parse query");
+
+        if self.schema.has_nested_fields() {
+            // nested child documents don't have a key, and must never be returned on their own
+            let key_field_name = self.schema.key_field().name.0;
+            Box::new(BooleanQuery::new(vec![
+                (Occur::Must, query),
+                (
+                    Occur::Must,
+                    Box::new(ConstScoreQuery::new(
+                        Box::new(ExistsQuery::new(key_field_name, false)),
+                        0.0,
+                    )),
+                ),
+            ]))
+        } else {
+            query
+        }
     }
 
     fn weight(&self, need_scores: bool, search_query_input: &SearchQueryInput) -> Box<dyn Weight> {
@@ -337,6 +569,81 @@ impl SearchIndexReader {
         self.searcher.doc(doc_address)
     }
 
+    /// Read the `stored: true` fields of the document at `doc_address` from the docstore, as a
+    /// JSON object keyed by field name.
+    ///
+    /// Fields that are read from an array column are returned as arrays, and all others as
+    /// scalars.
+    pub fn get_stored_fields(
+        &self,
+        doc_address: DocAddress,
+    ) -> tantivy::Result<serde_json::Map<String, serde_json::Value>> {
+        let doc = self.get_doc(doc_address)?;
+        let json = doc.to_json(&self.schema.schema);
+        let fields: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&json)
+            .map_err(|e| tantivy::TantivyError::InternalError(e.to_string()))?;
+
+        Ok(fields
+            .into_iter()
+            .map(|(name, value)| match value {
+                serde_json::Value::Array(mut values)
+                    if values.len() == 1
+                        && !self.array_fields.contains(&SearchFieldName(name.clone())) =>
+                {
+                    (name, values.pop().unwrap())
+                }
+                other => (name, other),
+            })
+            .collect())
+    }
+
+    /// Count the documents in `docs` under each direct child of the `parent` facet of the facet
+    /// `field`.  A document is counted once per child, no matter how many of its facets fall
+    /// under that child.
+    pub fn facet_counts(
+        &self,
+        field: &str,
+        parent: &Facet,
+        docs: impl IntoIterator<Item = DocAddress>,
+    ) -> tantivy::Result<HashMap<Facet, u64>> {
+        let parent_path = if parent.is_root() {
+            vec![]
+        } else {
+            parent.to_path()
+        };
+        let mut facet_readers = HashMap::new();
+        let mut counts = HashMap::new();
+        let mut facet = Facet::root();
+
+        for doc_address in docs {
+            let facet_reader = match facet_readers.entry(doc_address.segment_ord) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    self.searcher
+                        .segment_reader(doc_address.segment_ord)
+                        .facet_reader(field)?,
+                ),
+            };
+
+            let mut children = HashSet::new();
+            for ord in facet_reader.facet_ords(doc_address.doc_id) {
+                facet_reader.facet_from_ord(ord, &mut facet)?;
+                if facet.is_root() {
+                    continue;
+                }
+                let path = facet.to_path();
+                if path.len() > parent_path.len() && path[..parent_path.len()] == parent_path[..] {
+                    children.insert(Facet::from_path(&path[..=parent_path.len()]));
+                }
+            }
+            for child in children {
+                *counts.entry(child).or_default() += 1;
+            }
+        }
+
+        Ok(counts)
+    }
+
     /// Returns the index size, in bytes, according to tantivy
     pub fn byte_size(&self) -> Result<u64> {
         Ok(self
@@ -350,6 +657,29 @@ impl SearchIndexReader {
         self.searcher.segment_readers()
     }
 
+    /// The number of live rows in the segment.  Unlike [`SegmentReader::num_docs`], this doesn't
+    /// count the child documents of `nested` fields, as they're not rows of their own.
+    pub fn segment_num_rows(&self, segment_reader: &SegmentReader) -> u32 {
+        if !self.schema.has_nested_fields() {
+            return segment_reader.num_docs();
+        }
+
+        // every row has a key, while no nested child does
+        let key_field_name = self.schema.key_field().name.0;
+        ExistsQuery::new(key_field_name, false)
+            .weight(EnableScoring::disabled_from_searcher(&self.searcher))
+            .and_then(|weight| weight.count(segment_reader))
+            .expect("counting the rows in a segment should not fail")
+    }
+
+    /// The number of live rows in the index, across all of its segments
+    pub fn num_rows(&self) -> u64 {
+        self.segment_readers()
+            .iter()
+            .map(|segment_reader| self.segment_num_rows(segment_reader) as u64)
+            .sum()
+    }
+
     pub fn schema(&self) -> &SearchIndexSchema {
         &self.schema
     }
@@ -495,6 +825,182 @@ impl SearchIndexReader {
         }
     }
 
+    /// Search the given index segments for the "top N" matching documents, most relevant first.
+    ///
+    /// It has no understanding of Postgres MVCC visibility.  It is the caller's responsibility to
+    /// handle that, if it's necessary.
+    pub fn search_top_n_in_segments(
+        &self,
+        segment_ids: &[SegmentId],
+        query: &SearchQueryInput,
+        n: usize,
+    ) -> SearchResults {
+        let weight = self.weight(true, query);
+        let collector = TopDocs::with_limit(n);
+        let fruits = self
+            .searcher
+            .segment_readers()
+            .iter()
+            .enumerate()
+            .filter(|(_, reader)| segment_ids.contains(&reader.segment_id()))
+            .map(|(segment_ord, segment_reader)| {
+                collector
+                    .collect_segment(
+                        weight.as_ref(),
+                        segment_ord as SegmentOrdinal,
+                        segment_reader,
+                    )
+                    .expect("should be able to collect top-n in segment")
+            })
+            .collect::<Vec<_>>();
+        let top_docs = collector
+            .merge_fruits(fruits)
+            .expect("should be able to merge top-n in segments");
+        SearchResults::TopNByScore(
+            self.searcher.clone(),
+            Default::default(),
+            top_docs.into_iter(),
+        )
+    }
+
+    /// Search the Tantivy index for the "top N" matching documents, ordered by several keys.
+    ///
+    /// The documents are returned ordered by the first of the `sort_keys`, with ties broken by the
+    /// next, and so on.  NULL values sort after every other value, as they do in Postgres by default.
+    ///
+    /// It has no understanding of Postgres MVCC visibility.  It is the caller's responsibility to
+    /// handle that, if it's necessary.
+    pub fn search_top_n_compound(
+        &self,
+        query: &SearchQueryInput,
+        sort_keys: &[SortKey],
+        search_after: Option<&[OwnedValue]>,
+        n: usize,
+        need_scores: bool,
+    ) -> SearchResults {
+        let collector = Self::compound_collector(sort_keys, search_after, n);
+        let need_scores = need_scores || sort_keys.iter().any(|(field, _)| field.is_none());
+        let top_docs = self.collect(query, collector, need_scores);
+        SearchResults::TopNByCompound(
+            self.searcher.clone(),
+            Default::default(),
+            top_docs
+                .into_iter()
+                .filter(|(score, _)| !score.excluded)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    }
+
+    /// Search the Tantivy index for the "top N" matching documents, ordered by several keys, in a
+    /// specific segment.
+    ///
+    /// It has no understanding of Postgres MVCC visibility.  It is the caller's responsibility to
+    /// handle that, if it's necessary.
+    pub fn search_top_n_compound_in_segment(
+        &self,
+        segment_id: SegmentId,
+        query: &SearchQueryInput,
+        sort_keys: &[SortKey],
+        search_after: Option<&[OwnedValue]>,
+        n: usize,
+        need_scores: bool,
+    ) -> SearchResults {
+        let (segment_ord, segment_reader) = self
+            .searcher
+            .segment_readers()
+            .iter()
+            .enumerate()
+            .find(|(_, reader)| reader.segment_id() == segment_id)
+            .expect("segment {segment_id} should exist");
+
+        let collector = Self::compound_collector(sort_keys, search_after, n);
+        let need_scores = need_scores || sort_keys.iter().any(|(field, _)| field.is_none());
+        let weight = self.weight(need_scores, query);
+        let top_docs = collector
+            .collect_segment(
+                weight.as_ref(),
+                segment_ord as SegmentOrdinal,
+                segment_reader,
+            )
+            .expect("should be able to collect top-n in segment");
+        let top_docs = collector
+            .merge_fruits(vec![top_docs])
+            .expect("should be able to merge top-n in segment");
+        SearchResults::TopNByCompound(
+            self.searcher.clone(),
+            Default::default(),
+            top_docs
+                .into_iter()
+                .filter(|(score, _)| !score.excluded)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    }
+
+    fn compound_collector(
+        sort_keys: &[SortKey],
+        search_after: Option<&[OwnedValue]>,
+        n: usize,
+    ) -> impl Collector<Fruit = Vec<(CompoundScore, DocAddress)>> {
+        let search_after = search_after.map(|values| values.to_vec());
+        let fields = sort_keys
+            .iter()
+            .map(|(field, _)| field.clone())
+            .collect::<Vec<_>>();
+        let dirs = sort_keys
+            .iter()
+            .map(|(_, dir)| *dir)
+            .collect::<Arc<[SortDirection]>>();
+
+        TopDocs::with_limit(n).tweak_score(move |segment_reader: &SegmentReader| {
+            let columns = fields
+                .iter()
+                .map(|field| {
+                    field
+                        .as_ref()
+                        .map(|field| FFType::new(segment_reader.fast_fields(), field))
+                })
+                .collect::<Vec<_>>();
+            let dirs = dirs.clone();
+
+            // the cursor's values depend on the type of each key's fast field in this segment
+            let search_after = search_after.as_ref().map(|values| CompoundScore {
+                dirs: dirs.clone(),
+                values: values
+                    .iter()
+                    .zip(columns.iter())
+                    .map(|(value, column)| SortValue::from_search_after(value, column.as_ref()))
+                    .collect(),
+                score: 0.0,
+                excluded: false,
+            });
+
+            move |doc: DocId, original_score: Score| {
+                let mut score = CompoundScore {
+                    dirs: dirs.clone(),
+                    values: columns
+                        .iter()
+                        .map(|column| match column {
+                            Some(ff) => SortValue::from_fast_field(ff, doc),
+                            None => SortValue::F64(original_score as f64),
+                        })
+                        .collect(),
+                    score: original_score,
+                    excluded: false,
+                };
+
+                // only documents that sort after the cursor are wanted.  the rest still go through
+                // the collector, as the lowest possible scores, and are removed afterwards
+                if let Some(search_after) = &search_after {
+                    score.excluded =
+                        !matches!(score.partial_cmp(search_after), Some(Ordering::Less));
+                }
+                score
+            }
+        })
+    }
+
     fn top_by_field(
         &self,
         query: &SearchQueryInput,
@@ -509,7 +1015,77 @@ impl SearchIndexReader {
 
         let collector =
             TopDocs::with_limit(n).order_by_u64_field(sort_field.name.0.clone(), sortdir.into());
-        let top_docs = self.collect(query, collector, true);
+        let weight = self.weight(true, query);
+
+        // visit the segments most likely to hold the top values first, so that once we have `n`
+        // documents, every remaining segment whose range can't beat the n-th one is skipped
+        // without being searched.  this is what keeps "newest first" queries cheap on tables
+        // where new rows land in new segments
+        let mut segments = self
+            .searcher
+            .segment_readers()
+            .iter()
+            .enumerate()
+            .map(|(segment_ord, segment_reader)| {
+                let column = segment_reader
+                    .fast_fields()
+                    .u64_lenient(&sort_field.name.0)
+                    .expect("sort field should be a fast field")
+                    // a text column holds term ordinals, which only mean something within their
+                    // own segment, so they can't be compared against another segment's
+                    .and_then(|(column, column_type)| {
+                        (!matches!(column_type, ColumnType::Str | ColumnType::Bytes))
+                            .then_some(column)
+                    });
+                (segment_ord as SegmentOrdinal, segment_reader, column)
+            })
+            .collect::<Vec<_>>();
+
+        // values are compared as keys that sort the best first, whichever the direction
+        let sort_key = |value: u64| match sortdir {
+            SortDirection::Asc => value,
+            _ => u64::MAX - value,
+        };
+        let best_key = |column: Option<&Column<u64>>| {
+            let (min, max) = segment_sort_bounds(column);
+            match sortdir {
+                SortDirection::Asc => sort_key(min),
+                _ => sort_key(max),
+            }
+        };
+        segments.sort_by_key(|(_, _, column)| best_key(column.as_ref()));
+
+        // the keys of the best `n` documents collected so far, the n-th at the top.  the sort
+        // values the collector hands back aren't necessarily the column's own values, so they're
+        // read from the column instead
+        let mut best_keys = BinaryHeap::new();
+        let mut fruits = Vec::with_capacity(segments.len());
+        for (segment_ord, segment_reader, column) in &segments {
+            if best_keys.len() == n
+                && best_keys
+                    .peek()
+                    .is_some_and(|threshold| best_key(column.as_ref()) > *threshold)
+            {
+                break;
+            }
+
+            let fruit = collector
+                .collect_segment(weight.as_ref(), *segment_ord, segment_reader)
+                .expect("should be able to collect top-n in segment");
+            if let Some(column) = column {
+                for (_, doc_address) in &fruit {
+                    best_keys.push(sort_key(column.first(doc_address.doc_id).unwrap_or(0)));
+                    if best_keys.len() > n {
+                        best_keys.pop();
+                    }
+                }
+            }
+            fruits.push(fruit);
+        }
+
+        let top_docs = collector
+            .merge_fruits(fruits)
+            .expect("should be able to merge top-n in segments");
         SearchResults::TopNByField(
             self.searcher.clone(),
             Default::default(),
@@ -734,9 +1310,14 @@ impl SearchIndexReader {
             .segment_readers()
             .iter()
             .max_by_key(|reader| reader.num_docs())?;
+
+        // a `paradedb.search_after()` cursor can't be evaluated on its own, so estimate without it
+        let mut search_query_input = search_query_input.clone();
+        search_query_input.take_search_after();
+
         let weight = self.weight(
             search_query_input.contains_more_like_this(),
-            search_query_input,
+            &search_query_input,
         );
         let count = weight
             .scorer(largest_reader, 1.0)
@@ -780,6 +1361,20 @@ impl SearchIndexReader {
     }
 }
 
+/// The smallest and largest value a document in the segment can be sorted by.
+///
+/// Documents without a value sort as zero, so a column that doesn't cover every document can't
+/// promise anything above that.  Without a comparable column, the segment can't be skipped at all.
+fn segment_sort_bounds(column: Option<&Column<u64>>) -> (u64, u64) {
+    match column {
+        Some(column) if matches!(column.get_cardinality(), Cardinality::Full) => {
+            (column.min_value(), column.max_value())
+        }
+        Some(column) => (0, column.max_value()),
+        None => (0, u64::MAX),
+    }
+}
+
 mod scorer_iter {
     use tantivy::query::{Scorer, Weight};
     use tantivy::{DocAddress, DocId, DocSet, Score, SegmentOrdinal, SegmentReader};
@@ -17,13 +17,14 @@
 
 use crate::gucs;
 use crate::index::channel::{ChannelRequest, ChannelRequestHandler};
-use crate::index::merge_policy::AllowedMergePolicy;
+use crate::index::merge_policy::{AllowedMergePolicy, ForceMergePolicy};
 use crate::index::mvcc::MVCCDirectory;
+use crate::index::utils::{append_schema_version, load_schema};
 use crate::postgres::index::get_fields;
 use crate::schema::{SearchFieldConfig, SearchIndexSchema};
 use anyhow::Result;
 use crossbeam::channel::Receiver;
-use pgrx::PgRelation;
+use pgrx::{pg_sys, PgRelation};
 use std::num::NonZeroUsize;
 use tantivy::Index;
 use tokenizers::{create_normalizer_manager, create_tokenizer_manager};
@@ -32,6 +33,8 @@ pub enum WriterResources {
     CreateIndex,
     Statement,
     Vacuum,
+    Merge,
+    ForceMerge(ForceMergePolicy),
 }
 pub type Parallelism = NonZeroUsize;
 pub type MemoryBudget = usize;
@@ -81,13 +84,23 @@ impl WriterResources {
             WriterResources::Statement => (
                 gucs::statement_parallelism(),
                 gucs::statement_memory_budget(),
-                AllowedMergePolicy::NPlusOne,
+                AllowedMergePolicy::Background,
             ),
             WriterResources::Vacuum => (
                 gucs::statement_parallelism(),
                 gucs::statement_memory_budget(),
                 AllowedMergePolicy::None,
             ),
+            WriterResources::Merge => (
+                gucs::statement_parallelism(),
+                gucs::statement_memory_budget(),
+                AllowedMergePolicy::Configured,
+            ),
+            WriterResources::ForceMerge(policy) => (
+                gucs::statement_parallelism(),
+                gucs::statement_memory_budget(),
+                AllowedMergePolicy::Force(policy.clone()),
+            ),
         }
     }
 }
@@ -101,6 +114,31 @@ pub fn get_index_schema(index_relation: &PgRelation) -> Result<SearchIndexSchema
     Ok(schema)
 }
 
+/// Append any fields that have been added to the index's configuration since its stored schema
+/// was last written, returning the new schema version if one was created.
+///
+/// New segments will populate these fields while existing segments treat them as absent.
+pub fn sync_index_schema(index_relation: &PgRelation) -> Result<Option<u32>> {
+    let relation_oid = index_relation.oid();
+    let (fields, _) = unsafe { get_fields(index_relation) };
+    let current = unsafe { load_schema(relation_oid)? };
+    if SearchIndexSchema::with_new_fields(&current.schema, &fields).is_none() {
+        return Ok(None);
+    }
+
+    unsafe {
+        // serialize with any other backend that has noticed the same new fields, and look again
+        // now that we have the lock
+        pg_sys::LockRelationOid(relation_oid, pg_sys::ShareUpdateExclusiveLock as _);
+
+        let current = load_schema(relation_oid)?;
+        match SearchIndexSchema::with_new_fields(&current.schema, &fields) {
+            Some(schema) => Ok(Some(append_schema_version(relation_oid, &schema)?)),
+            None => Ok(None),
+        }
+    }
+}
+
 pub fn setup_tokenizers(underlying_index: &mut Index, index_relation: &PgRelation) {
     let (fields, _) = unsafe { get_fields(index_relation) };
     let tokenizers = fields
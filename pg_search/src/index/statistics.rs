@@ -0,0 +1,542 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Planner statistics for a bm25 index.
+//!
+//! They're gathered whenever Postgres runs `ANALYZE` or `VACUUM` on the indexed table and are
+//! stored in the index itself, so the planner can estimate how selective an `@@@` query is
+//! without opening a tantivy reader.
+
+use crate::index::reader::index::SearchIndexReader;
+use crate::postgres::storage::block::{CleanupLockData, CLEANUP_LOCK};
+use crate::postgres::storage::buffer::BufferManager;
+use crate::postgres::storage::LinkedBytesList;
+use crate::query::SearchQueryInput;
+use crate::schema::{SearchField, SearchFieldType};
+use anyhow::Result;
+use pgrx::{check_for_interrupts, pg_sys};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::ops::Bound;
+use tantivy::schema::{Field, OwnedValue};
+use tantivy::{DocId, Searcher, SegmentReader, Term};
+
+/// How many of a text field's most common terms we keep track of
+const MOST_COMMON_TERMS: usize = 100;
+
+/// How many buckets a fast field's histogram is divided into
+const HISTOGRAM_BUCKETS: usize = 100;
+
+/// The number of documents sampled to build a fast field's histogram, which is the same number
+/// of rows `ANALYZE` samples with the default `default_statistics_target`
+const HISTOGRAM_SAMPLE_SIZE: usize = 30_000;
+
+/// The fraction of the index's rows that must have been added or removed since the statistics
+/// were gathered before a plain `VACUUM` gathers them again.  This is the default
+/// `autovacuum_analyze_scale_factor`.
+const STALE_FRACTION: f64 = 0.1;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IndexStatistics {
+    /// The number of live documents in the index when the statistics were gathered
+    pub num_docs: u64,
+    pub fields: HashMap<String, FieldStatistics>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FieldStatistics {
+    /// A text field's most common terms, and how many documents contain each of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub most_common_terms: Vec<(String, u64)>,
+
+    /// An estimate of how many other distinct terms the field has, and how many documents they
+    /// appear in, in total
+    #[serde(default)]
+    pub other_terms: u64,
+    #[serde(default)]
+    pub other_doc_freq: u64,
+
+    /// The bounds of an equi-depth histogram over a sample of a numeric, boolean, or date fast
+    /// field's values.  Dates are in nanoseconds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub histogram: Vec<f64>,
+
+    /// The fraction of the sampled documents without a value
+    #[serde(default)]
+    pub null_frac: f64,
+
+    /// The number of distinct values in the sample
+    #[serde(default)]
+    pub n_distinct: u64,
+}
+
+impl IndexStatistics {
+    /// Gather statistics for every indexed text field and every numeric, boolean, and date fast
+    /// field in the index
+    pub fn collect(search_reader: &SearchIndexReader) -> Result<Self> {
+        let searcher = search_reader.searcher();
+        let schema = search_reader.schema();
+        let mut fields = HashMap::new();
+
+        for search_field in &schema.fields {
+            let name = &search_field.name.0;
+            if name == "ctid" {
+                continue;
+            }
+
+            let field_entry = schema.schema.get_field_entry(search_field.id.0);
+            let statistics = match search_field.type_ {
+                SearchFieldType::Text if field_entry.is_indexed() => {
+                    FieldStatistics::from_terms(searcher, search_field.id.0)?
+                }
+                SearchFieldType::I64
+                | SearchFieldType::U64
+                | SearchFieldType::F64
+                | SearchFieldType::Bool
+                | SearchFieldType::Date
+                    if field_entry.is_fast() =>
+                {
+                    FieldStatistics::from_fast_field(searcher, search_field)
+                }
+                _ => continue,
+            };
+            fields.insert(name.clone(), statistics);
+        }
+
+        Ok(Self {
+            num_docs: search_reader.num_rows(),
+            fields,
+        })
+    }
+
+    /// Have enough rows been added to or removed from the index since these statistics were
+    /// gathered that they're likely to mislead the planner?
+    pub fn is_stale(&self, num_rows: u64) -> bool {
+        num_rows.abs_diff(self.num_docs) as f64 > self.num_docs as f64 * STALE_FRACTION
+    }
+
+    /// Estimate the fraction of the index's documents that match `query`, or `None` if it's not
+    /// a query these statistics can answer
+    pub fn selectivity(&self, query: &SearchQueryInput) -> Option<f64> {
+        if self.num_docs == 0 {
+            // we know nothing about an index that was empty when it was analyzed
+            return None;
+        }
+
+        let selectivity = match query {
+            SearchQueryInput::All => 1.0,
+            SearchQueryInput::Empty => 0.0,
+            SearchQueryInput::WithIndex { query, .. }
+            | SearchQueryInput::Boost { query, .. }
+            | SearchQueryInput::ConstScore { query, .. } => self.selectivity(query)?,
+            SearchQueryInput::Boolean {
+                must,
+                should,
+                must_not,
+            } => {
+                let mut selectivity = 1.0;
+                for query in must {
+                    selectivity *= self.selectivity(query)?;
+                }
+                if !should.is_empty() {
+                    selectivity *= self.any_selectivity(should.iter())?;
+                }
+                for query in must_not {
+                    selectivity *= 1.0 - self.selectivity(query)?;
+                }
+                selectivity
+            }
+            SearchQueryInput::DisjunctionMax { disjuncts, .. } => {
+                self.any_selectivity(disjuncts.iter())?
+            }
+            SearchQueryInput::Exists { field } => {
+                let field = self.fields.get(field)?;
+                if field.histogram.is_empty() {
+                    return None;
+                }
+                1.0 - field.null_frac
+            }
+            SearchQueryInput::Term {
+                field: Some(field),
+                value,
+                ..
+            } => self
+                .fields
+                .get(field)?
+                .term_selectivity(value, self.num_docs)?,
+            SearchQueryInput::TermSet { terms } => {
+                let mut none_match = 1.0;
+                for term in terms {
+                    let field = self.fields.get(&term.field)?;
+                    none_match *= 1.0 - field.term_selectivity(&term.value, self.num_docs)?;
+                }
+                1.0 - none_match
+            }
+            SearchQueryInput::Range {
+                field,
+                lower_bound,
+                upper_bound,
+                ..
+            } => self
+                .fields
+                .get(field)?
+                .range_selectivity(lower_bound, upper_bound)?,
+            SearchQueryInput::ParseWithField {
+                field,
+                query_string,
+                conjunction_mode,
+                ..
+            } => {
+                // only a plain list of words can be estimated.  anything using the query
+                // language's syntax is left to the index
+                let field = self.fields.get(field)?;
+                let words = query_string.split_whitespace().collect::<Vec<_>>();
+                if words.is_empty()
+                    || !words
+                        .iter()
+                        .all(|word| word.chars().all(char::is_alphanumeric))
+                {
+                    return None;
+                }
+
+                let mut selectivities = Vec::with_capacity(words.len());
+                for word in words {
+                    let term = OwnedValue::Str(word.to_lowercase());
+                    selectivities.push(field.term_selectivity(&term, self.num_docs)?);
+                }
+                if conjunction_mode.unwrap_or(false) {
+                    selectivities.into_iter().product()
+                } else {
+                    1.0 - selectivities
+                        .into_iter()
+                        .map(|selectivity| 1.0 - selectivity)
+                        .product::<f64>()
+                }
+            }
+            _ => return None,
+        };
+
+        Some(selectivity.clamp(0.0, 1.0))
+    }
+
+    /// The selectivity of matching any one of `queries`, assuming they're independent
+    fn any_selectivity<'a>(
+        &self,
+        queries: impl Iterator<Item = &'a SearchQueryInput>,
+    ) -> Option<f64> {
+        let mut none_match = 1.0;
+        for query in queries {
+            none_match *= 1.0 - self.selectivity(query)?;
+        }
+        Some(1.0 - none_match)
+    }
+}
+
+impl FieldStatistics {
+    fn from_terms(searcher: &Searcher, field: Field) -> Result<Self> {
+        // the most common terms of each segment are the candidates for the most common terms of
+        // the whole index, and we total up their document frequencies once we know them all
+        let mut candidates = HashSet::new();
+        let mut num_terms = 0u64;
+        let mut total_doc_freq = 0u64;
+
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(field)?;
+            let mut most_common = BinaryHeap::new();
+            let mut stream = inverted_index.terms().stream()?;
+            while stream.advance() {
+                if num_terms % 10_000 == 0 {
+                    check_for_interrupts!();
+                }
+
+                let doc_freq = stream.value().doc_freq;
+                num_terms += 1;
+                total_doc_freq += doc_freq as u64;
+
+                if most_common.len() < MOST_COMMON_TERMS {
+                    most_common.push(Reverse((doc_freq, stream.key().to_vec())));
+                } else if most_common
+                    .peek()
+                    .is_some_and(|Reverse((least, _))| *least < doc_freq)
+                {
+                    most_common.pop();
+                    most_common.push(Reverse((doc_freq, stream.key().to_vec())));
+                }
+            }
+            candidates.extend(most_common.into_iter().map(|Reverse((_, term))| term));
+        }
+
+        let mut most_common_terms = candidates
+            .into_iter()
+            .map(|bytes| {
+                let term = String::from_utf8_lossy(&bytes).into_owned();
+                let doc_freq = searcher.doc_freq(&Term::from_field_text(field, &term))?;
+                Ok((term, doc_freq))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        most_common_terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        most_common_terms.truncate(MOST_COMMON_TERMS);
+
+        // terms that appear in more than one segment are counted more than once, so this is only
+        // an approximation, but then so is everything else here
+        let most_common_doc_freq = most_common_terms.iter().map(|(_, n)| n).sum::<u64>();
+        Ok(Self {
+            other_terms: num_terms.saturating_sub(most_common_terms.len() as u64),
+            other_doc_freq: total_doc_freq.saturating_sub(most_common_doc_freq),
+            most_common_terms,
+            ..Default::default()
+        })
+    }
+
+    fn from_fast_field(searcher: &Searcher, search_field: &SearchField) -> Self {
+        let max_doc = searcher
+            .segment_readers()
+            .iter()
+            .map(|segment_reader| segment_reader.max_doc() as usize)
+            .sum::<usize>();
+        let step = (max_doc / HISTOGRAM_SAMPLE_SIZE).max(1);
+
+        let mut values = Vec::with_capacity(max_doc.min(HISTOGRAM_SAMPLE_SIZE + 1));
+        let mut sampled = 0usize;
+        for segment_reader in searcher.segment_readers() {
+            let Some(column) = numeric_column(segment_reader, search_field) else {
+                continue;
+            };
+            let alive_bitset = segment_reader.alive_bitset();
+            for doc in (0..segment_reader.max_doc()).step_by(step) {
+                if alive_bitset.is_some_and(|alive_bitset| alive_bitset.is_deleted(doc)) {
+                    continue;
+                }
+                sampled += 1;
+                if let Some(value) = column(doc) {
+                    values.push(value);
+                }
+            }
+        }
+
+        values.sort_by(f64::total_cmp);
+        let null_frac = if sampled == 0 {
+            0.0
+        } else {
+            (sampled - values.len()) as f64 / sampled as f64
+        };
+        let mut n_distinct = values.len() as u64;
+        if !values.is_empty() {
+            n_distinct = 1 + values.windows(2).filter(|pair| pair[0] != pair[1]).count() as u64;
+        }
+        let histogram = if values.is_empty() {
+            vec![]
+        } else {
+            (0..=HISTOGRAM_BUCKETS)
+                .map(|i| values[i * (values.len() - 1) / HISTOGRAM_BUCKETS])
+                .collect()
+        };
+
+        Self {
+            histogram,
+            null_frac,
+            n_distinct,
+            ..Default::default()
+        }
+    }
+
+    fn term_selectivity(&self, value: &OwnedValue, num_docs: u64) -> Option<f64> {
+        if !self.histogram.is_empty() {
+            let value = as_f64(value)?;
+            let (first, last) = (self.histogram[0], self.histogram[self.histogram.len() - 1]);
+            if value < first || value > last {
+                return Some(0.0);
+            }
+
+            // a value that spans more than one histogram bucket is that much more common than a
+            // typical distinct value
+            let buckets = self
+                .histogram
+                .iter()
+                .filter(|bound| **bound == value)
+                .count()
+                .saturating_sub(1);
+            let frequent = buckets as f64 / (self.histogram.len() - 1).max(1) as f64;
+            let typical = (1.0 - self.null_frac) / self.n_distinct.max(1) as f64;
+            return Some(frequent.max(typical));
+        }
+
+        if self.most_common_terms.is_empty() && self.other_terms == 0 {
+            return None;
+        }
+
+        let OwnedValue::Str(value) = value else {
+            return None;
+        };
+        let doc_freq = match self
+            .most_common_terms
+            .iter()
+            .find(|(term, _)| term == value)
+        {
+            Some((_, doc_freq)) => *doc_freq as f64,
+            None => {
+                // anything else is assumed to be as common as the average of the other terms,
+                // but never more common than the least common of the most common terms
+                let average = self.other_doc_freq as f64 / self.other_terms.max(1) as f64;
+                match self.most_common_terms.last() {
+                    Some((_, least_common)) => average.min(*least_common as f64),
+                    None => average,
+                }
+            }
+        };
+        Some(doc_freq / num_docs as f64)
+    }
+
+    fn range_selectivity(
+        &self,
+        lower: &Bound<OwnedValue>,
+        upper: &Bound<OwnedValue>,
+    ) -> Option<f64> {
+        if self.histogram.is_empty() {
+            return None;
+        }
+
+        let below_lower = match lower {
+            Bound::Included(value) => self.fraction_below(as_f64(value)?, false),
+            Bound::Excluded(value) => self.fraction_below(as_f64(value)?, true),
+            Bound::Unbounded => 0.0,
+        };
+        let below_upper = match upper {
+            Bound::Included(value) => self.fraction_below(as_f64(value)?, true),
+            Bound::Excluded(value) => self.fraction_below(as_f64(value)?, false),
+            Bound::Unbounded => 1.0,
+        };
+        Some((below_upper - below_lower).max(0.0) * (1.0 - self.null_frac))
+    }
+
+    /// The fraction of values less than (or equal to) `value`, interpolating within the histogram
+    /// bucket it falls into
+    fn fraction_below(&self, value: f64, or_equal: bool) -> f64 {
+        let histogram = &self.histogram;
+        let nbuckets = histogram.len() - 1;
+
+        // the first bound past `value`
+        let i = if or_equal {
+            histogram.partition_point(|bound| *bound <= value)
+        } else {
+            histogram.partition_point(|bound| *bound < value)
+        };
+        if i == 0 {
+            return 0.0;
+        } else if i > nbuckets {
+            return 1.0;
+        }
+
+        let (low, high) = (histogram[i - 1], histogram[i]);
+        let within = if high > low {
+            (value - low) / (high - low)
+        } else {
+            0.5
+        };
+        ((i - 1) as f64 + within) / nbuckets as f64
+    }
+}
+
+type NumericColumn = Box<dyn Fn(DocId) -> Option<f64>>;
+
+/// A fast field's values as `f64`s, which is how our histograms represent them
+fn numeric_column(
+    segment_reader: &SegmentReader,
+    search_field: &SearchField,
+) -> Option<NumericColumn> {
+    let fast_fields = segment_reader.fast_fields();
+    let name = &search_field.name.0;
+    let column: NumericColumn = match search_field.type_ {
+        SearchFieldType::I64 => {
+            let column = fast_fields.i64(name).ok()?;
+            Box::new(move |doc| column.first(doc).map(|value| value as f64))
+        }
+        SearchFieldType::U64 => {
+            let column = fast_fields.u64(name).ok()?;
+            Box::new(move |doc| column.first(doc).map(|value| value as f64))
+        }
+        SearchFieldType::F64 => {
+            let column = fast_fields.f64(name).ok()?;
+            Box::new(move |doc| column.first(doc))
+        }
+        SearchFieldType::Bool => {
+            let column = fast_fields.bool(name).ok()?;
+            Box::new(move |doc| column.first(doc).map(|value| value as u8 as f64))
+        }
+        SearchFieldType::Date => {
+            let column = fast_fields.date(name).ok()?;
+            Box::new(move |doc| {
+                column
+                    .first(doc)
+                    .map(|value| value.into_timestamp_nanos() as f64)
+            })
+        }
+        _ => return None,
+    };
+    Some(column)
+}
+
+fn as_f64(value: &OwnedValue) -> Option<f64> {
+    match value {
+        OwnedValue::I64(value) => Some(*value as f64),
+        OwnedValue::U64(value) => Some(*value as f64),
+        OwnedValue::F64(value) => Some(*value),
+        OwnedValue::Bool(value) => Some(*value as u8 as f64),
+        OwnedValue::Date(value) => Some(value.into_timestamp_nanos() as f64),
+        _ => None,
+    }
+}
+
+/// Replace the index's statistics with `statistics`
+pub unsafe fn save_statistics(
+    relation_oid: pg_sys::Oid,
+    statistics: &IndexStatistics,
+) -> Result<()> {
+    let bytes = serde_json::to_vec(statistics)?;
+    let mut list = LinkedBytesList::create(relation_oid);
+    list.write(&bytes)?;
+
+    let mut bman = BufferManager::new(relation_oid);
+    let previous = {
+        let mut cleanup_lock = bman.get_buffer_mut(CLEANUP_LOCK);
+        let mut page = cleanup_lock.page_mut();
+        let metadata = page.contents_mut::<CleanupLockData>();
+        std::mem::replace(&mut metadata.statistics_blockno, list.header_blockno)
+    };
+
+    if previous != 0 && previous != pg_sys::InvalidBlockNumber {
+        // the old statistics are recycled once no one could still be reading them
+        LinkedBytesList::open(relation_oid, previous).mark_deleted();
+    }
+    Ok(())
+}
+
+/// Load the index's statistics, if they've ever been gathered
+pub fn load_statistics(relation_oid: pg_sys::Oid) -> Option<IndexStatistics> {
+    let bman = BufferManager::new(relation_oid);
+    let statistics_blockno = bman
+        .get_buffer(CLEANUP_LOCK)
+        .page_contents::<CleanupLockData>()
+        .statistics_blockno;
+    if statistics_blockno == 0 || statistics_blockno == pg_sys::InvalidBlockNumber {
+        return None;
+    }
+
+    let bytes = unsafe { LinkedBytesList::open(relation_oid, statistics_blockno).read_all() };
+    serde_json::from_slice(&bytes).ok()
+}
@@ -19,12 +19,14 @@ use anyhow::Result;
 use pgrx::PgRelation;
 use std::sync::Arc;
 use tantivy::indexer::UserOperation;
+use tantivy::query::Query;
 use tantivy::schema::Field;
-use tantivy::{Index, IndexSettings, IndexWriter, Opstamp, TantivyDocument, TantivyError, Term};
+use tantivy::{Index, IndexSettings, IndexWriter, Opstamp, TantivyError};
 use thiserror::Error;
 
 use crate::index::channel::{ChannelDirectory, ChannelRequestHandler};
 use crate::index::{get_index_schema, setup_tokenizers, BlockDirectoryType, WriterResources};
+use crate::postgres::options::SearchIndexCreateOptions;
 use crate::{
     postgres::types::TantivyValueError,
     schema::{SearchDocument, SearchIndexSchema},
@@ -33,7 +35,7 @@ use crate::{
 // NB:  should this be a GUC?  Could be useful or could just complicate things for the user
 /// How big should our insert queue get before we go ahead and add them to the tantivy index?
 const MAX_INSERT_QUEUE_SIZE: usize = 1000;
-const CHANNEL_QUEUE_LEN: usize = 1000;
+pub const CHANNEL_QUEUE_LEN: usize = 1000;
 
 /// The entity that interfaces with Tantivy indexes.
 pub struct SearchIndexWriter {
@@ -105,9 +107,20 @@ impl SearchIndexWriter {
 
         let mut index = {
             let schema = schema.clone();
-            let settings = IndexSettings {
-                docstore_compress_dedicated_thread: false,
-                ..IndexSettings::default()
+            let settings = if index_relation.rd_options.is_null() {
+                IndexSettings {
+                    docstore_compress_dedicated_thread: false,
+                    ..IndexSettings::default()
+                }
+            } else {
+                let options =
+                    unsafe { &*(index_relation.rd_options as *const SearchIndexCreateOptions) };
+                IndexSettings {
+                    docstore_compression: options.get_docstore_compression(),
+                    docstore_blocksize: options.get_docstore_blocksize(),
+                    docstore_compress_dedicated_thread: false,
+                    ..IndexSettings::default()
+                }
             };
 
             handler
@@ -136,20 +149,29 @@ impl SearchIndexWriter {
         })
     }
 
-    pub fn get_ctid_field(&self) -> Field {
-        self.ctid_field
-    }
-
-    pub fn delete_term(&mut self, term: Term) -> Result<()> {
-        self.insert_queue.push(UserOperation::Delete(term));
-        if self.insert_queue.len() >= MAX_INSERT_QUEUE_SIZE {
-            self.drain_insert_queue()?;
-        }
+    /// Delete every document matching `query`, as of this point in the writer's operations
+    pub fn delete_query(&mut self, query: Box<dyn Query>) -> Result<()> {
+        self.drain_insert_queue()?;
+        let writer = self.writer.clone();
+        self.handler
+            .wait_for(move || writer.delete_query(query))
+            .expect("spawned thread should not fail")?;
         Ok(())
     }
 
     pub fn insert(&mut self, document: SearchDocument, ctid: u64) -> Result<()> {
-        let mut tantivy_document: TantivyDocument = document.into();
+        let SearchDocument {
+            doc: mut tantivy_document,
+            nested,
+        } = document;
+
+        // nested children go first, and in the same batch as their parent, so that they're
+        // assigned the doc ids immediately preceding it.  They share the parent's ctid so that
+        // they're deleted along with it
+        for mut child in nested {
+            child.add_u64(self.ctid_field, ctid);
+            self.insert_queue.push(UserOperation::Add(child));
+        }
 
         tantivy_document.add_u64(self.ctid_field, ctid);
 
@@ -92,6 +92,7 @@ pub unsafe extern "C" fn _PG_init() {
     #[allow(static_mut_refs)]
     #[allow(deprecated)]
     customscan::register_rel_pathlist(customscan::pdbscan::PdbScan);
+    customscan::register_upper_paths(customscan::aggregatescan::AggregateScan);
 }
 
 /// This module is required by `cargo pgrx test` invocations.
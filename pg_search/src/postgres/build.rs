@@ -40,14 +40,19 @@ struct BuildState {
     start: Instant,
     writer: SearchIndexWriter,
     categorized_fields: Vec<(SearchField, CategorizedFieldData)>,
-    key_field_name: String,
+    key_field_names: Vec<String>,
 }
 
 impl BuildState {
     fn new(indexrel: &PgRelation, writer: SearchIndexWriter) -> Self {
         let tupdesc = unsafe { PgTupleDesc::from_pg_unchecked(indexrel.rd_att) };
         let categorized_fields = categorize_fields(&tupdesc, &writer.schema);
-        let key_field_name = writer.schema.key_field().name.0;
+        let key_field_names = writer
+            .schema
+            .key_fields()
+            .into_iter()
+            .map(|field| field.name.0)
+            .collect();
 
         BuildState {
             count: 0,
@@ -55,7 +60,7 @@ impl BuildState {
             start: Instant::now(),
             writer,
             categorized_fields,
-            key_field_name,
+            key_field_names,
         }
     }
 }
@@ -157,7 +162,7 @@ unsafe extern "C" fn build_callback(
         .expect("BuildState pointer should not be null");
 
     let categorized_fields = &build_state.categorized_fields;
-    let key_field_name = &build_state.key_field_name;
+    let key_field_names = &build_state.key_field_names;
     let writer = &mut build_state.writer;
     // In the block below, we switch to the memory context we've defined on our build
     // state, resetting it before and after. We do this because we're looking up a
@@ -172,7 +177,7 @@ unsafe extern "C" fn build_callback(
         build_state.memctx.switch_to(|_| {
             let mut search_document = writer.schema.new_document();
 
-            row_to_search_document(values, isnull, key_field_name, categorized_fields, &mut search_document).unwrap_or_else(|err| {
+            row_to_search_document(values, isnull, key_field_names, categorized_fields, &mut search_document).unwrap_or_else(|err| {
                 panic!(
                     "error creating index entries for index '{}': {err}",
                     CStr::from_ptr((*(*indexrel).rd_rel).relname.data.as_ptr())
@@ -62,13 +62,20 @@ pub unsafe extern "C" fn amcostestimate(
     // it does cost a little bit for us to startup, which is spawning the tantivy query
     *index_startup_cost = DEFAULT_STARTUP_COST;
 
-    // choose the smallest selectivity from the RestrictInfo clauses that have already done their estimations
-    *index_selectivity = index_clauses
-        .iter_ptr()
-        .map(|clause| (*(*clause).rinfo).norm_selec)
-        .filter(|norm| *norm > 0.0)
-        .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Greater))
-        .unwrap_or(UNKNOWN_SELECTIVITY);
+    // the index clauses are ANDed together, so combine the selectivities the RestrictInfo clauses
+    // have already estimated, which come from the index's statistics when it's been analyzed, as
+    // if they were independent
+    *index_selectivity = if index_clauses.is_empty() {
+        // a scan that only orders by `<=>@@` returns every row
+        1.0
+    } else {
+        index_clauses
+            .iter_ptr()
+            .map(|clause| (*(*clause).rinfo).norm_selec)
+            .filter(|norm| *norm > 0.0 && *norm <= 1.0)
+            .reduce(|a, b| a * b)
+            .unwrap_or(UNKNOWN_SELECTIVITY)
+    };
 
     // use the selectivity to further estimate how many postgres pages we'd read,
     // if in fact we were based on Postgres' block storage
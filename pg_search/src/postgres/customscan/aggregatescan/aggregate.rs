@@ -0,0 +1,522 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::nodecast;
+use crate::schema::{SearchFieldConfig, SearchFieldName, SearchFieldType, SearchIndexSchema};
+use pgrx::{pg_sys, AnyNumeric, IntoDatum, PgBuiltInOids, PgList, PgOid, PgRelation};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::ffi::CStr;
+use tantivy::schema::OwnedValue;
+use tokenizers::SearchNormalizer;
+
+/// A `GROUP BY` column, read from its fast field
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupingColumn {
+    pub field: String,
+    pub attno: pg_sys::AttrNumber,
+    pub typoid: u32,
+}
+
+impl GroupingColumn {
+    pub fn is_text(&self) -> bool {
+        is_text_type(self.typoid.into())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AggregateKind {
+    CountStar,
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateKind {
+    fn from_name(name: &str, aggstar: bool) -> Option<Self> {
+        match name {
+            "count" if aggstar => Some(AggregateKind::CountStar),
+            "count" => Some(AggregateKind::Count),
+            "sum" => Some(AggregateKind::Sum),
+            "avg" => Some(AggregateKind::Avg),
+            "min" => Some(AggregateKind::Min),
+            "max" => Some(AggregateKind::Max),
+            _ => None,
+        }
+    }
+
+    /// The type Postgres' own aggregate returns for an argument of type `argtype`
+    fn result_type(&self, argtype: pg_sys::Oid) -> Option<pg_sys::Oid> {
+        let is_int = matches!(
+            PgOid::from(argtype),
+            PgOid::BuiltIn(
+                PgBuiltInOids::INT2OID | PgBuiltInOids::INT4OID | PgBuiltInOids::INT8OID
+            )
+        );
+        let is_float = matches!(
+            PgOid::from(argtype),
+            PgOid::BuiltIn(PgBuiltInOids::FLOAT4OID | PgBuiltInOids::FLOAT8OID)
+        );
+
+        match self {
+            AggregateKind::CountStar | AggregateKind::Count => Some(pg_sys::INT8OID),
+            AggregateKind::Sum if argtype == pg_sys::INT8OID => Some(pg_sys::NUMERICOID),
+            AggregateKind::Sum if is_int => Some(pg_sys::INT8OID),
+            AggregateKind::Sum if is_float => Some(argtype),
+            AggregateKind::Avg if is_int => Some(pg_sys::NUMERICOID),
+            AggregateKind::Avg if is_float => Some(pg_sys::FLOAT8OID),
+            AggregateKind::Min | AggregateKind::Max if is_int || is_float => Some(argtype),
+            _ => None,
+        }
+    }
+}
+
+/// An aggregate function computed from the fast field of its argument
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub kind: AggregateKind,
+    pub field: Option<String>,
+    pub argtype: u32,
+}
+
+/// One column of the tuples the scan produces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Target {
+    GroupingColumn(usize),
+    Aggregate(Aggregate),
+}
+
+/// Everything the scan needs to know to compute the groups of a query
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AggregateSpec {
+    pub grouping_columns: Vec<GroupingColumn>,
+    pub targets: Vec<Target>,
+}
+
+impl AggregateSpec {
+    /// Can the grouping and aggregation of `root`'s query, whose grouped output is `exprs`, be
+    /// computed from the fast fields of the relation at `rti`?
+    pub unsafe fn new(
+        root: *mut pg_sys::PlannerInfo,
+        rti: pg_sys::Index,
+        heaprel: &PgRelation,
+        schema: &SearchIndexSchema,
+        exprs: *mut pg_sys::List,
+    ) -> Option<Self> {
+        let grouping_columns = grouping_columns(root, rti, heaprel, schema)?;
+        let mut targets = Vec::new();
+        for node in scan_targets(exprs).iter_ptr() {
+            if let Some(var) = nodecast!(Var, T_Var, node) {
+                if (*var).varno as i32 != rti as i32 || (*var).varlevelsup != 0 {
+                    return None;
+                }
+                let idx = grouping_columns
+                    .iter()
+                    .position(|column| column.attno == (*var).varattno)?;
+                targets.push(Target::GroupingColumn(idx));
+            } else if let Some(aggref) = nodecast!(Aggref, T_Aggref, node) {
+                targets.push(Target::Aggregate(aggregate(rti, heaprel, schema, aggref)?));
+            } else {
+                return None;
+            }
+        }
+
+        Some(AggregateSpec {
+            grouping_columns,
+            targets,
+        })
+    }
+
+    /// The fast fields to read, with the grouping columns first and then the argument of each
+    /// aggregate, in [`Target`] order
+    pub fn fast_fields(&self) -> Vec<String> {
+        self.grouping_columns
+            .iter()
+            .map(|column| column.field.clone())
+            .chain(self.targets.iter().filter_map(|target| match target {
+                Target::Aggregate(Aggregate {
+                    field: Some(field), ..
+                }) => Some(field.clone()),
+                _ => None,
+            }))
+            .collect()
+    }
+}
+
+/// The distinct Vars and Aggrefs of `exprs`, which are the columns the scan produces.  The
+/// planner's expressions above them are computed by the scan's projection.
+pub unsafe fn scan_targets(exprs: *mut pg_sys::List) -> PgList<pg_sys::Node> {
+    let pulled = PgList::<pg_sys::Node>::from_pg(pg_sys::pull_var_clause(
+        exprs.cast(),
+        (pg_sys::PVC_INCLUDE_AGGREGATES | pg_sys::PVC_RECURSE_PLACEHOLDERS) as _,
+    ));
+
+    let mut targets = PgList::<pg_sys::Node>::new();
+    for node in pulled.iter_ptr() {
+        if !targets
+            .iter_ptr()
+            .any(|existing| pg_sys::equal(existing.cast(), node.cast()))
+        {
+            targets.push(node);
+        }
+    }
+    targets
+}
+
+unsafe fn grouping_columns(
+    root: *mut pg_sys::PlannerInfo,
+    rti: pg_sys::Index,
+    heaprel: &PgRelation,
+    schema: &SearchIndexSchema,
+) -> Option<Vec<GroupingColumn>> {
+    let parse = (*root).parse;
+    let group_clause = PgList::<pg_sys::SortGroupClause>::from_pg((*parse).groupClause);
+
+    let mut columns = Vec::new();
+    for sgc in group_clause.iter_ptr() {
+        let expr = pg_sys::get_sortgroupclause_expr(sgc, (*root).processed_tlist);
+        let var = nodecast!(Var, T_Var, expr)?;
+        if (*var).varno as i32 != rti as i32 || (*var).varlevelsup != 0 || (*var).varattno <= 0 {
+            return None;
+        }
+
+        // a case-insensitive collation would put values the index keeps apart in one group
+        if (*var).varcollid != pg_sys::Oid::INVALID
+            && !pg_sys::get_collation_isdeterministic((*var).varcollid)
+        {
+            return None;
+        }
+
+        let field = fast_field(heaprel, schema, var, true)?;
+        columns.push(GroupingColumn {
+            field,
+            attno: (*var).varattno,
+            typoid: (*var).vartype.as_u32(),
+        });
+    }
+
+    Some(columns)
+}
+
+unsafe fn aggregate(
+    rti: pg_sys::Index,
+    heaprel: &PgRelation,
+    schema: &SearchIndexSchema,
+    aggref: *mut pg_sys::Aggref,
+) -> Option<Aggregate> {
+    if (*aggref).aggsplit != pg_sys::AggSplit::AGGSPLIT_SIMPLE
+        || (*aggref).aggkind as u8 != pg_sys::AGGKIND_NORMAL as u8
+        || (*aggref).agglevelsup != 0
+        || !(*aggref).aggdistinct.is_null()
+        || !(*aggref).aggorder.is_null()
+        || !(*aggref).aggfilter.is_null()
+        || (*aggref).aggvariadic
+    {
+        return None;
+    }
+
+    let namespace = pg_sys::get_namespace_name(pg_sys::get_func_namespace((*aggref).aggfnoid));
+    if namespace.is_null() || CStr::from_ptr(namespace) != c"pg_catalog" {
+        return None;
+    }
+    let name = pg_sys::get_func_name((*aggref).aggfnoid);
+    if name.is_null() {
+        return None;
+    }
+    let kind = AggregateKind::from_name(CStr::from_ptr(name).to_str().ok()?, (*aggref).aggstar)?;
+
+    let args = PgList::<pg_sys::TargetEntry>::from_pg((*aggref).args);
+    let (field, argtype) = if kind == AggregateKind::CountStar {
+        (None, pg_sys::Oid::INVALID)
+    } else {
+        if args.len() != 1 {
+            return None;
+        }
+        let te = args.get_ptr(0)?;
+        let var = nodecast!(Var, T_Var, (*te).expr)?;
+        if (*var).varno as i32 != rti as i32 || (*var).varlevelsup != 0 || (*var).varattno <= 0 {
+            return None;
+        }
+        (
+            Some(fast_field(heaprel, schema, var, false)?),
+            (*var).vartype,
+        )
+    };
+
+    if kind != AggregateKind::CountStar
+        && !matches!(
+            PgOid::from(argtype),
+            PgOid::BuiltIn(
+                PgBuiltInOids::INT2OID
+                    | PgBuiltInOids::INT4OID
+                    | PgBuiltInOids::INT8OID
+                    | PgBuiltInOids::FLOAT4OID
+                    | PgBuiltInOids::FLOAT8OID
+                    | PgBuiltInOids::BOOLOID
+            )
+        )
+    {
+        return None;
+    }
+    if kind.result_type(argtype)? != (*aggref).aggtype {
+        return None;
+    }
+
+    Some(Aggregate {
+        kind,
+        field,
+        argtype: argtype.as_u32(),
+    })
+}
+
+/// The name of the fast field holding exactly the values of `var`.  Text is only accepted when
+/// `allow_text` is set and the field keeps its values unchanged with the `raw` normalizer.
+unsafe fn fast_field(
+    heaprel: &PgRelation,
+    schema: &SearchIndexSchema,
+    var: *mut pg_sys::Var,
+    allow_text: bool,
+) -> Option<String> {
+    let tupdesc = heaprel.tuple_desc();
+    let attname = tupdesc
+        .get((*var).varattno as usize - 1)?
+        .name()
+        .to_string();
+    let search_field = schema.get_search_field(&SearchFieldName(attname.clone()))?;
+    if search_field
+        .config
+        .column()
+        .is_some_and(|column| *column != attname)
+    {
+        return None;
+    }
+
+    let exact = match PgOid::from((*var).vartype) {
+        PgOid::BuiltIn(
+            PgBuiltInOids::INT2OID | PgBuiltInOids::INT4OID | PgBuiltInOids::INT8OID,
+        ) => {
+            search_field.type_ == SearchFieldType::I64
+                && matches!(
+                    search_field.config,
+                    SearchFieldConfig::Numeric { fast: true, .. }
+                )
+        }
+        PgOid::BuiltIn(PgBuiltInOids::FLOAT4OID | PgBuiltInOids::FLOAT8OID) => {
+            search_field.type_ == SearchFieldType::F64
+                && matches!(
+                    search_field.config,
+                    SearchFieldConfig::Numeric { fast: true, .. }
+                )
+        }
+        PgOid::BuiltIn(PgBuiltInOids::BOOLOID) => {
+            search_field.type_ == SearchFieldType::Bool
+                && matches!(
+                    search_field.config,
+                    SearchFieldConfig::Boolean { fast: true, .. }
+                )
+        }
+        PgOid::BuiltIn(PgBuiltInOids::TEXTOID | PgBuiltInOids::VARCHAROID) => {
+            allow_text
+                && matches!(
+                    search_field.config,
+                    SearchFieldConfig::Text {
+                        fast: true,
+                        normalizer: SearchNormalizer::Raw,
+                        ..
+                    }
+                )
+        }
+        _ => false,
+    };
+
+    exact.then_some(attname)
+}
+
+fn is_text_type(typoid: pg_sys::Oid) -> bool {
+    typoid == pg_sys::TEXTOID || typoid == pg_sys::VARCHAROID
+}
+
+/// The value of a grouping column.  Floats are kept as bits, with `-0` and every `NaN` made the
+/// same, as Postgres considers them equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GroupValue {
+    Null,
+    I64(i64),
+    F64(u64),
+    Bool(bool),
+    Str(String),
+}
+
+impl From<OwnedValue> for GroupValue {
+    fn from(value: OwnedValue) -> Self {
+        match value {
+            OwnedValue::I64(v) => GroupValue::I64(v),
+            OwnedValue::F64(v) if v.is_nan() => GroupValue::F64(f64::NAN.to_bits()),
+            OwnedValue::F64(v) if v == 0.0 => GroupValue::F64(0.0f64.to_bits()),
+            OwnedValue::F64(v) => GroupValue::F64(v.to_bits()),
+            OwnedValue::Bool(v) => GroupValue::Bool(v),
+            OwnedValue::Str(v) => GroupValue::Str(v),
+            _ => GroupValue::Null,
+        }
+    }
+}
+
+impl GroupValue {
+    pub unsafe fn into_datum(self, typoid: pg_sys::Oid) -> Option<pg_sys::Datum> {
+        match (self, PgOid::from(typoid)) {
+            (GroupValue::Null, _) => None,
+            (GroupValue::I64(v), PgOid::BuiltIn(PgBuiltInOids::INT2OID)) => (v as i16).into_datum(),
+            (GroupValue::I64(v), PgOid::BuiltIn(PgBuiltInOids::INT4OID)) => (v as i32).into_datum(),
+            (GroupValue::I64(v), _) => v.into_datum(),
+            (GroupValue::F64(v), PgOid::BuiltIn(PgBuiltInOids::FLOAT4OID)) => {
+                (f64::from_bits(v) as f32).into_datum()
+            }
+            (GroupValue::F64(v), _) => f64::from_bits(v).into_datum(),
+            (GroupValue::Bool(v), _) => v.into_datum(),
+            (GroupValue::Str(v), _) => v.into_datum(),
+        }
+    }
+}
+
+/// The running state of one [`Aggregate`] for one group
+#[derive(Debug, Clone)]
+pub enum Accumulator {
+    Count(i64),
+    SumInt(Option<i128>),
+    SumFloat(Option<f64>),
+    AvgInt(i128, i64),
+    AvgFloat(f64, i64),
+    Min(Option<OwnedValue>),
+    Max(Option<OwnedValue>),
+}
+
+impl Accumulator {
+    pub fn new(aggregate: &Aggregate) -> Self {
+        let is_float = matches!(
+            PgOid::from(pg_sys::Oid::from(aggregate.argtype)),
+            PgOid::BuiltIn(PgBuiltInOids::FLOAT4OID | PgBuiltInOids::FLOAT8OID)
+        );
+        match aggregate.kind {
+            AggregateKind::CountStar | AggregateKind::Count => Accumulator::Count(0),
+            AggregateKind::Sum if is_float => Accumulator::SumFloat(None),
+            AggregateKind::Sum => Accumulator::SumInt(None),
+            AggregateKind::Avg if is_float => Accumulator::AvgFloat(0.0, 0),
+            AggregateKind::Avg => Accumulator::AvgInt(0, 0),
+            AggregateKind::Min => Accumulator::Min(None),
+            AggregateKind::Max => Accumulator::Max(None),
+        }
+    }
+
+    /// Add a document, whose argument is `value`.  `count(*)` is given [`OwnedValue::Null`] and
+    /// counts every document.  Everything else skips nulls, as Postgres does.
+    pub fn accumulate(&mut self, value: OwnedValue, is_count_star: bool) {
+        if is_count_star {
+            if let Accumulator::Count(count) = self {
+                *count += 1;
+            }
+            return;
+        }
+
+        match (self, value) {
+            (_, OwnedValue::Null) => {}
+            (Accumulator::Count(count), _) => *count += 1,
+            (Accumulator::SumInt(sum), OwnedValue::I64(v)) => {
+                *sum = Some(sum.unwrap_or_default() + v as i128)
+            }
+            (Accumulator::SumFloat(sum), OwnedValue::F64(v)) => {
+                *sum = Some(sum.unwrap_or_default() + v)
+            }
+            (Accumulator::AvgInt(sum, count), OwnedValue::I64(v)) => {
+                *sum += v as i128;
+                *count += 1;
+            }
+            (Accumulator::AvgFloat(sum, count), OwnedValue::F64(v)) => {
+                *sum += v;
+                *count += 1;
+            }
+            (Accumulator::Min(min), value) => {
+                let replace = match min {
+                    None => true,
+                    Some(min) => compare(&value, min) == Ordering::Less,
+                };
+                if replace {
+                    *min = Some(value);
+                }
+            }
+            (Accumulator::Max(max), value) => {
+                let replace = match max {
+                    None => true,
+                    Some(max) => compare(&value, max) == Ordering::Greater,
+                };
+                if replace {
+                    *max = Some(value);
+                }
+            }
+            (accumulator, value) => {
+                panic!("unexpected value for aggregate {accumulator:?}: {value:?}")
+            }
+        }
+    }
+
+    /// The aggregate's result, as a Datum of the type Postgres' own aggregate returns
+    pub unsafe fn into_datum(self, argtype: pg_sys::Oid) -> Option<pg_sys::Datum> {
+        match self {
+            Accumulator::Count(count) => count.into_datum(),
+            Accumulator::SumInt(sum) => {
+                let sum = sum?;
+                if argtype == pg_sys::INT8OID {
+                    AnyNumeric::from(sum).into_datum()
+                } else {
+                    i64::try_from(sum)
+                        .unwrap_or_else(|_| panic!("bigint out of range"))
+                        .into_datum()
+                }
+            }
+            Accumulator::SumFloat(sum) => {
+                if argtype == pg_sys::FLOAT4OID {
+                    sum.map(|sum| sum as f32).into_datum()
+                } else {
+                    sum.into_datum()
+                }
+            }
+            Accumulator::AvgInt(_, 0) | Accumulator::AvgFloat(_, 0) => None,
+            Accumulator::AvgInt(sum, count) => {
+                (AnyNumeric::from(sum) / AnyNumeric::from(count)).into_datum()
+            }
+            Accumulator::AvgFloat(sum, count) => (sum / count as f64).into_datum(),
+            Accumulator::Min(value) | Accumulator::Max(value) => {
+                GroupValue::from(value?).into_datum(argtype)
+            }
+        }
+    }
+}
+
+/// Compare two values the way Postgres does, where `NaN` is larger than every other float
+fn compare(a: &OwnedValue, b: &OwnedValue) -> Ordering {
+    match (a, b) {
+        (OwnedValue::I64(a), OwnedValue::I64(b)) => a.cmp(b),
+        (OwnedValue::F64(a), OwnedValue::F64(b)) => match (a.is_nan(), b.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        },
+        _ => Ordering::Equal,
+    }
+}
@@ -0,0 +1,573 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! A custom scan for the `GROUP BY` / aggregate stage of a query over a single table that has a
+//! `USING bm25` index.  The groups are computed from the index's fast fields, so the matching
+//! heap tuples never need to be returned to Postgres' own aggregation.
+
+#![allow(clippy::unnecessary_cast)] // helps with integer casting differences between postgres versions
+mod aggregate;
+mod privdat;
+mod scan_state;
+
+use crate::api::operator::anyelement_query_input_opoid;
+use crate::index::fast_fields_helper::{FFHelper, FastFieldType, WhichFastField};
+use crate::index::merge_policy::AllowedMergePolicy;
+use crate::index::mvcc::MVCCDirectory;
+use crate::index::reader::index::SearchIndexReader;
+use crate::index::BlockDirectoryType;
+use crate::postgres::customscan::aggregatescan::aggregate::{
+    scan_targets, Accumulator, Aggregate, AggregateKind, AggregateSpec, GroupValue, Target,
+};
+use crate::postgres::customscan::aggregatescan::privdat::PrivateData;
+use crate::postgres::customscan::aggregatescan::scan_state::{AggregateScanState, Group};
+use crate::postgres::customscan::builders::custom_path::CustomPathBuilder;
+use crate::postgres::customscan::builders::custom_scan::CustomScanBuilder;
+use crate::postgres::customscan::builders::custom_state::{
+    CustomScanStateBuilder, CustomScanStateWrapper,
+};
+use crate::postgres::customscan::explainer::Explainer;
+use crate::postgres::customscan::pdbscan::is_block_all_visible;
+use crate::postgres::customscan::pdbscan::qual_inspect::extract_quals;
+use crate::postgres::customscan::{CustomScan, ExecMethod, PlainExecCapable};
+use crate::postgres::rel_get_bm25_index;
+use crate::postgres::utils::u64_to_item_pointer;
+use crate::postgres::visibility_checker::VisibilityChecker;
+use crate::query::SearchQueryInput;
+use crate::schema::SearchIndexSchema;
+use crate::DEFAULT_STARTUP_COST;
+use pgrx::itemptr::item_pointer_get_block_number;
+use pgrx::pg_sys::CustomExecMethods;
+use pgrx::{pg_sys, FromDatum, PgList, PgMemoryContexts, PgRelation};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use tantivy::schema::OwnedValue;
+use tantivy::Index;
+
+#[derive(Default)]
+pub struct AggregateScan;
+
+impl ExecMethod for AggregateScan {
+    fn exec_methods() -> *const CustomExecMethods {
+        <AggregateScan as PlainExecCapable>::exec_methods()
+    }
+}
+
+impl PlainExecCapable for AggregateScan {}
+
+impl CustomScan for AggregateScan {
+    const NAME: &'static CStr = c"ParadeDB Aggregate Scan";
+
+    type State = AggregateScanState;
+    type PrivateData = PrivateData;
+
+    fn callback(mut builder: CustomPathBuilder<Self::PrivateData>) -> Option<pg_sys::CustomPath> {
+        unsafe {
+            let root = builder.args().root;
+            let parse = (*root).parse;
+            if !(*parse).groupingSets.is_null()
+                || !(*parse).havingQual.is_null()
+                || (*parse).hasTargetSRFs
+            {
+                return None;
+            }
+
+            let rti = builder.args().rti;
+            let (table, bm25_index) = {
+                let rte = builder.args().rte();
+
+                // we only work on plain relations, without inheritance children
+                if rte.rtekind != pg_sys::RTEKind::RTE_RELATION || rte.inh {
+                    return None;
+                }
+                let relkind = pg_sys::get_rel_relkind(rte.relid) as u8;
+                if relkind != pg_sys::RELKIND_RELATION && relkind != pg_sys::RELKIND_MATVIEW {
+                    return None;
+                }
+
+                // and that relation must have a `USING bm25` index
+                rel_get_bm25_index(rte.relid)?
+            };
+
+            let directory = MVCCDirectory::snapshot(bm25_index.oid(), AllowedMergePolicy::None);
+            let index =
+                Index::open(directory).expect("aggregate_scan: should be able to open index");
+            let schema = SearchIndexSchema::open(index.schema(), &bm25_index);
+
+            // every restriction on the scanned relation has to become part of the search, and at
+            // least one of them must be a `@@@` query
+            let input_rel = *(*root).simple_rel_array.add(rti as usize);
+            let restrict_info =
+                PgList::<pg_sys::RestrictInfo>::from_pg((*input_rel).baserestrictinfo);
+            if restrict_info.is_empty() {
+                return None;
+            }
+            let quals = extract_quals(
+                root,
+                rti,
+                restrict_info.as_ptr().cast(),
+                anyelement_query_input_opoid(),
+                &schema,
+            )
+            .filter(|quals| !quals.is_pushdown_only())?;
+
+            let path_target = builder.path_target();
+            let spec = AggregateSpec::new(root, rti, &table, &schema, (*path_target).exprs)?;
+
+            builder.custom_private().set_heaprelid(table.oid());
+            builder.custom_private().set_indexrelid(bm25_index.oid());
+            builder.custom_private().set_range_table_index(rti);
+            builder.custom_private().set_quals(quals);
+            builder.custom_private().set_spec(&spec);
+
+            // the grouped relation's estimate comes from the paths Postgres already made for it
+            let output_rel = builder.args().rel;
+            let rows = PgList::<pg_sys::Path>::from_pg((*output_rel).pathlist)
+                .get_ptr(0)
+                .map(|path| (*path).rows)
+                .unwrap_or(1.0)
+                .max(1.0);
+
+            // all the groups are computed before the first one is returned, and like the fast
+            // field exec methods, matching documents are read from fast fields and not the heap
+            let per_tuple_cost = pg_sys::cpu_index_tuple_cost / 100.0;
+            let cost = DEFAULT_STARTUP_COST + (*input_rel).rows * per_tuple_cost;
+
+            builder = builder.set_rows(rows);
+            builder = builder.set_startup_cost(cost);
+            builder = builder.set_total_cost(cost + rows * pg_sys::cpu_tuple_cost);
+
+            Some(builder.build())
+        }
+    }
+
+    fn plan_custom_path(builder: CustomScanBuilder<Self::PrivateData>) -> pg_sys::CustomScan {
+        unsafe {
+            // the scan produces the grouping columns and aggregates that the planner's targetlist
+            // refers to, in the same order the path's spec was made from
+            let mut exprs = PgList::<pg_sys::Node>::new();
+            for te in builder.args().tlist.iter_ptr() {
+                exprs.push((*te).expr.cast());
+            }
+
+            let mut scan_tlist = PgList::<pg_sys::TargetEntry>::new();
+            for (i, node) in scan_targets(exprs.into_pg()).iter_ptr().enumerate() {
+                scan_tlist.push(pg_sys::makeTargetEntry(
+                    node.cast(),
+                    (i + 1) as pg_sys::AttrNumber,
+                    std::ptr::null_mut(),
+                    false,
+                ));
+            }
+
+            builder.set_custom_scan_tlist(scan_tlist.into_pg()).build()
+        }
+    }
+
+    fn create_custom_scan_state(
+        mut builder: CustomScanStateBuilder<Self, Self::PrivateData>,
+    ) -> *mut CustomScanStateWrapper<Self> {
+        builder.custom_state().heaprelid = builder
+            .custom_private()
+            .heaprelid()
+            .expect("heaprelid should have a value");
+        builder.custom_state().indexrelid = builder
+            .custom_private()
+            .indexrelid()
+            .expect("indexrelid should have a value");
+        builder.custom_state().rti = builder
+            .custom_private()
+            .range_table_index()
+            .expect("range table index should have been set");
+        builder.custom_state().spec = builder
+            .custom_private()
+            .spec()
+            .expect("aggregate spec should have been set");
+
+        let quals = builder
+            .custom_private()
+            .quals()
+            .expect("should have a Qual structure");
+        if !quals.contains_expressions() {
+            builder.custom_state().search_query_input = SearchQueryInput::from(&quals);
+        }
+        builder.custom_state().quals = Some(quals);
+
+        builder.build()
+    }
+
+    fn explain_custom_scan(
+        state: &CustomScanStateWrapper<Self>,
+        ancestors: *mut pg_sys::List,
+        explainer: &mut Explainer,
+    ) {
+        unsafe {
+            if let Some(heaprel) = state.custom_state().heaprel {
+                explainer.add_text("Table", PgRelation::from_pg(heaprel).name());
+            }
+            if let Some(indexrel) = state.custom_state().indexrel {
+                explainer.add_text("Index", PgRelation::from_pg(indexrel).name());
+            }
+        }
+
+        let spec = &state.custom_state().spec;
+        if !spec.grouping_columns.is_empty() {
+            explainer.add_text(
+                "Group By",
+                spec.grouping_columns
+                    .iter()
+                    .map(|column| column.field.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+        }
+        explainer.add_text(
+            "Aggregates",
+            spec.targets
+                .iter()
+                .filter_map(|target| match target {
+                    Target::Aggregate(aggregate) => Some(describe(aggregate)),
+                    Target::GroupingColumn(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+
+        if !explainer.is_analyze()
+            && state
+                .custom_state()
+                .quals
+                .as_ref()
+                .is_some_and(|quals| quals.contains_expressions())
+        {
+            // the query depends on parameters that are only evaluated when the scan starts
+            explainer.add_text("Tantivy Query", "<evaluated at execution time>");
+            return;
+        }
+
+        let query = &state.custom_state().search_query_input;
+        let pretty_json = if explainer.is_verbose() {
+            serde_json::to_string_pretty(&query)
+        } else {
+            serde_json::to_string(&query)
+        }
+        .expect("query should serialize to json");
+        explainer.add_text("Tantivy Query", &pretty_json);
+    }
+
+    fn begin_custom_scan(
+        state: &mut CustomScanStateWrapper<Self>,
+        estate: *mut pg_sys::EState,
+        eflags: i32,
+    ) {
+        unsafe {
+            // open the heap and index relations with the proper locks
+            let rte = pg_sys::exec_rt_fetch(state.custom_state().rti, estate);
+            assert!(!rte.is_null());
+            let lockmode = (*rte).rellockmode as pg_sys::LOCKMODE;
+
+            let (heaprel, indexrel) = if lockmode == pg_sys::NoLock as pg_sys::LOCKMODE {
+                (
+                    pg_sys::RelationIdGetRelation(state.custom_state().heaprelid),
+                    pg_sys::RelationIdGetRelation(state.custom_state().indexrelid),
+                )
+            } else {
+                (
+                    pg_sys::relation_open(state.custom_state().heaprelid, lockmode),
+                    pg_sys::relation_open(state.custom_state().indexrelid, lockmode),
+                )
+            };
+
+            state.custom_state_mut().heaprel = Some(heaprel);
+            state.custom_state_mut().indexrel = Some(indexrel);
+            state.custom_state_mut().lockmode = lockmode;
+
+            // prepare the parts of our query that are only known now, like prepared statement
+            // parameters.  they're evaluated at every (re)scan
+            let planstate = state.planstate();
+            if let Some(quals) = state.custom_state_mut().quals.as_mut() {
+                quals.init_expressions(planstate);
+            }
+        }
+
+        if eflags & (pg_sys::EXEC_FLAG_EXPLAIN_ONLY as i32) != 0 {
+            // don't do anything else if we're only explaining the query
+            return;
+        }
+
+        AggregateScan::rescan_custom_scan(state)
+    }
+
+    fn rescan_custom_scan(state: &mut CustomScanStateWrapper<Self>) {
+        let runtime_query = state
+            .custom_state()
+            .quals
+            .as_ref()
+            .filter(|quals| quals.contains_expressions())
+            .map(SearchQueryInput::from);
+        if let Some(query) = runtime_query {
+            state.custom_state_mut().search_query_input = query;
+        }
+
+        let groups = unsafe { compute_groups(state.custom_state()) };
+        state.custom_state_mut().groups = groups;
+        state.custom_state_mut().next_group = 0;
+    }
+
+    fn exec_custom_scan(state: &mut CustomScanStateWrapper<Self>) -> *mut pg_sys::TupleTableSlot {
+        let next_group = state.custom_state().next_group;
+        if next_group >= state.custom_state().groups.len() {
+            return std::ptr::null_mut();
+        }
+        state.custom_state_mut().next_group += 1;
+        let (key, accumulators) = std::mem::take(&mut state.custom_state_mut().groups[next_group]);
+
+        unsafe {
+            let slot = state.scanslot();
+            if let Some(clear) = (*(*slot).tts_ops).clear {
+                clear(slot);
+            }
+
+            let natts = (*(*slot).tts_tupleDescriptor).natts as usize;
+            let datums = std::slice::from_raw_parts_mut((*slot).tts_values, natts);
+            let isnull = std::slice::from_raw_parts_mut((*slot).tts_isnull, natts);
+
+            let mut per_tuple_context =
+                PgMemoryContexts::For((*(*state.planstate()).ps_ExprContext).ecxt_per_tuple_memory);
+            per_tuple_context.reset();
+
+            let spec = &state.custom_state().spec;
+            debug_assert!(natts == spec.targets.len());
+            per_tuple_context.switch_to(|_| {
+                let mut key = key.into_iter().map(Some).collect::<Vec<_>>();
+                let mut accumulators = accumulators.into_iter();
+                for (i, target) in spec.targets.iter().enumerate() {
+                    let datum = match target {
+                        Target::GroupingColumn(idx) => key[*idx]
+                            .take()
+                            .expect("grouping column should only be returned once")
+                            .into_datum(spec.grouping_columns[*idx].typoid.into()),
+                        Target::Aggregate(aggregate) => accumulators
+                            .next()
+                            .expect("every aggregate should have an accumulator")
+                            .into_datum(aggregate.argtype.into()),
+                    };
+
+                    match datum {
+                        None => {
+                            datums[i] = pg_sys::Datum::null();
+                            isnull[i] = true;
+                        }
+                        Some(datum) => {
+                            datums[i] = datum;
+                            isnull[i] = false;
+                        }
+                    }
+                }
+            });
+            pg_sys::ExecStoreVirtualTuple(slot);
+
+            let projection_info = state.projection_info();
+            if projection_info.is_null() {
+                // the scan's tuples are exactly what's wanted
+                return slot;
+            }
+            (*(*projection_info).pi_exprContext).ecxt_scantuple = slot;
+            pg_sys::ExecProject(projection_info)
+        }
+    }
+
+    fn shutdown_custom_scan(state: &mut CustomScanStateWrapper<Self>) {}
+
+    fn end_custom_scan(state: &mut CustomScanStateWrapper<Self>) {
+        drop(std::mem::take(&mut state.custom_state_mut().groups));
+
+        if let Some(heaprel) = state.custom_state_mut().heaprel.take() {
+            unsafe {
+                pg_sys::relation_close(heaprel, state.custom_state().lockmode);
+            }
+        }
+        if let Some(indexrel) = state.custom_state_mut().indexrel.take() {
+            unsafe {
+                pg_sys::relation_close(indexrel, state.custom_state().lockmode);
+            }
+        }
+    }
+}
+
+fn describe(aggregate: &Aggregate) -> String {
+    let name = match aggregate.kind {
+        AggregateKind::CountStar | AggregateKind::Count => "count",
+        AggregateKind::Sum => "sum",
+        AggregateKind::Avg => "avg",
+        AggregateKind::Min => "min",
+        AggregateKind::Max => "max",
+    };
+    format!("{name}({})", aggregate.field.as_deref().unwrap_or("*"))
+}
+
+/// Search the index and fold every visible matching document into its group.  Without a
+/// `GROUP BY` there's always exactly one group, even when nothing matches.
+unsafe fn compute_groups(state: &AggregateScanState) -> Vec<Group> {
+    let heaprel = state.heaprel();
+    let indexrel = PgRelation::from_pg(
+        state
+            .indexrel
+            .expect("custom_state.indexrel should already be open"),
+    );
+    let search_reader = SearchIndexReader::open(&indexrel, BlockDirectoryType::Mvcc, false)
+        .expect("should be able to open the search index reader");
+
+    let spec = &state.spec;
+    let aggregates = spec
+        .targets
+        .iter()
+        .filter_map(|target| match target {
+            Target::Aggregate(aggregate) => Some(aggregate),
+            Target::GroupingColumn(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let which_fast_fields = spec
+        .fast_fields()
+        .into_iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let is_text = spec
+                .grouping_columns
+                .get(i)
+                .is_some_and(|column| column.is_text());
+            WhichFastField::Named(
+                field,
+                if is_text {
+                    FastFieldType::String
+                } else {
+                    FastFieldType::Numeric
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    let ffhelper = FFHelper::with_fields(&search_reader, &which_fast_fields);
+
+    let mut visibility_checker =
+        VisibilityChecker::with_rel_and_snap(heaprel, pg_sys::GetActiveSnapshot());
+    let slot =
+        pg_sys::MakeSingleTupleTableSlot((*heaprel).rd_att, pg_sys::table_slot_callbacks(heaprel));
+    let mut vmbuff = pg_sys::InvalidBuffer as pg_sys::Buffer;
+
+    let mut lookup = HashMap::<Vec<GroupValue>, usize>::new();
+    let mut groups = Vec::<Group>::new();
+    let mut strbuf = String::new();
+
+    'docs: for (scored, doc_address) in
+        search_reader.search(false, false, &state.search_query_input, None)
+    {
+        let mut tid = pg_sys::ItemPointerData::default();
+        u64_to_item_pointer(scored.ctid, &mut tid);
+
+        let mut in_slot = false;
+        if !is_block_all_visible(heaprel, &mut vmbuff, item_pointer_get_block_number(&tid)) {
+            if visibility_checker
+                .exec_if_visible(scored.ctid, slot, |_| ())
+                .is_none()
+            {
+                continue;
+            }
+            in_slot = true;
+        }
+
+        let mut key = Vec::with_capacity(spec.grouping_columns.len());
+        for (i, column) in spec.grouping_columns.iter().enumerate() {
+            let value = if column.is_text() {
+                if ffhelper.string(i, doc_address, &mut strbuf).is_some() {
+                    GroupValue::Str(strbuf.clone())
+                } else {
+                    // the fast field doesn't keep values longer than the normalizer allows, so
+                    // tell those apart from NULL by looking at the heap tuple
+                    if !in_slot {
+                        if visibility_checker
+                            .exec_if_visible(scored.ctid, slot, |_| ())
+                            .is_none()
+                        {
+                            continue 'docs;
+                        }
+                        in_slot = true;
+                    }
+                    heap_text(slot, column.attno)
+                        .map(GroupValue::Str)
+                        .unwrap_or(GroupValue::Null)
+                }
+            } else {
+                ffhelper
+                    .value(i, doc_address)
+                    .map(|value| GroupValue::from(value.0))
+                    .unwrap_or(GroupValue::Null)
+            };
+            key.push(value);
+        }
+
+        let idx = match lookup.get(&key) {
+            Some(idx) => *idx,
+            None => {
+                groups.push((
+                    key.clone(),
+                    aggregates.iter().map(|a| Accumulator::new(a)).collect(),
+                ));
+                lookup.insert(key, groups.len() - 1);
+                groups.len() - 1
+            }
+        };
+
+        let mut ff_idx = spec.grouping_columns.len();
+        for (accumulator, aggregate) in groups[idx].1.iter_mut().zip(&aggregates) {
+            if aggregate.field.is_some() {
+                let value = ffhelper
+                    .value(ff_idx, doc_address)
+                    .map(|value| value.0)
+                    .unwrap_or(OwnedValue::Null);
+                ff_idx += 1;
+                accumulator.accumulate(value, false);
+            } else {
+                accumulator.accumulate(OwnedValue::Null, true);
+            }
+        }
+    }
+
+    if vmbuff != pg_sys::InvalidBuffer as pg_sys::Buffer {
+        pg_sys::ReleaseBuffer(vmbuff);
+    }
+    pg_sys::ExecDropSingleTupleTableSlot(slot);
+
+    if groups.is_empty() && spec.grouping_columns.is_empty() {
+        groups.push((
+            vec![],
+            aggregates.iter().map(|a| Accumulator::new(a)).collect(),
+        ));
+    }
+    groups
+}
+
+/// The text value of attribute `attno` of the heap tuple in `slot`
+unsafe fn heap_text(
+    slot: *mut pg_sys::TupleTableSlot,
+    attno: pg_sys::AttrNumber,
+) -> Option<String> {
+    if ((*slot).tts_nvalid as i32) < attno as i32 {
+        pg_sys::slot_getsomeattrs_int(slot, attno as i32);
+    }
+    let idx = (attno - 1) as usize;
+    String::from_datum(*(*slot).tts_values.add(idx), *(*slot).tts_isnull.add(idx))
+}
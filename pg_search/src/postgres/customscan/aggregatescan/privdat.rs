@@ -0,0 +1,112 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::nodecast;
+use crate::postgres::customscan::aggregatescan::aggregate::AggregateSpec;
+use crate::postgres::customscan::pdbscan::privdat::deserialize::{decodeInteger, decodeString};
+use crate::postgres::customscan::pdbscan::privdat::serialize::{makeInteger, makeString};
+use crate::postgres::customscan::pdbscan::qual_inspect::Qual;
+use pgrx::{pg_sys, PgList};
+
+#[derive(Default, Debug)]
+pub struct PrivateData {
+    heaprelid: Option<pg_sys::Oid>,
+    indexrelid: Option<pg_sys::Oid>,
+    range_table_index: Option<pg_sys::Index>,
+    quals: Option<*mut pg_sys::List>,
+    spec: Option<String>,
+}
+
+impl From<*mut pg_sys::List> for PrivateData {
+    fn from(list: *mut pg_sys::List) -> Self {
+        unsafe {
+            let input = PgList::<pg_sys::Node>::from_pg(list);
+            PrivateData {
+                heaprelid: input.get_ptr(0).and_then(|n| decodeInteger(n)),
+                indexrelid: input.get_ptr(1).and_then(|n| decodeInteger(n)),
+                range_table_index: input.get_ptr(2).and_then(|n| decodeInteger(n)),
+                quals: input.get_ptr(3).and_then(|n| nodecast!(List, T_List, n)),
+                spec: input.get_ptr(4).and_then(|n| decodeString(n)),
+            }
+        }
+    }
+}
+
+impl From<PrivateData> for *mut pg_sys::List {
+    fn from(value: PrivateData) -> Self {
+        unsafe {
+            let mut ser = PgList::<pg_sys::Node>::new();
+            ser.push(makeInteger(value.heaprelid));
+            ser.push(makeInteger(value.indexrelid));
+            ser.push(makeInteger(value.range_table_index));
+            ser.push(
+                value
+                    .quals
+                    .map(|l| l.cast())
+                    .unwrap_or_else(|| makeString(None::<String>)),
+            );
+            ser.push(makeString(value.spec));
+            ser.into_pg()
+        }
+    }
+}
+
+impl PrivateData {
+    pub fn set_heaprelid(&mut self, oid: pg_sys::Oid) {
+        self.heaprelid = Some(oid);
+    }
+
+    pub fn set_indexrelid(&mut self, oid: pg_sys::Oid) {
+        self.indexrelid = Some(oid);
+    }
+
+    pub fn set_range_table_index(&mut self, rti: pg_sys::Index) {
+        self.range_table_index = Some(rti);
+    }
+
+    pub fn set_quals(&mut self, quals: Qual) {
+        let serialized: PgList<pg_sys::Node> = quals.into();
+        self.quals = Some(serialized.into_pg().cast())
+    }
+
+    pub fn set_spec(&mut self, spec: &AggregateSpec) {
+        self.spec = Some(serde_json::to_string(spec).expect("aggregate spec should serialize"));
+    }
+
+    pub fn heaprelid(&self) -> Option<pg_sys::Oid> {
+        self.heaprelid
+    }
+
+    pub fn indexrelid(&self) -> Option<pg_sys::Oid> {
+        self.indexrelid
+    }
+
+    pub fn range_table_index(&self) -> Option<pg_sys::Index> {
+        self.range_table_index
+    }
+
+    pub fn quals(&self) -> Option<Qual> {
+        self.quals
+            .map(|ri| unsafe { Qual::from(PgList::<pg_sys::Node>::from_pg(ri)) })
+    }
+
+    pub fn spec(&self) -> Option<AggregateSpec> {
+        self.spec
+            .as_ref()
+            .map(|spec| serde_json::from_str(spec).expect("aggregate spec should deserialize"))
+    }
+}
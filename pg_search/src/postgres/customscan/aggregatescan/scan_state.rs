@@ -0,0 +1,58 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::postgres::customscan::aggregatescan::aggregate::{
+    Accumulator, AggregateSpec, GroupValue,
+};
+use crate::postgres::customscan::pdbscan::qual_inspect::Qual;
+use crate::postgres::customscan::CustomScanState;
+use crate::query::SearchQueryInput;
+use pgrx::pg_sys;
+
+/// The values of a group's `GROUP BY` columns and its aggregates, in target order
+pub type Group = (Vec<GroupValue>, Vec<Accumulator>);
+
+#[derive(Default)]
+pub struct AggregateScanState {
+    pub rti: pg_sys::Index,
+
+    pub quals: Option<Qual>,
+    pub search_query_input: SearchQueryInput,
+    pub spec: AggregateSpec,
+
+    pub groups: Vec<Group>,
+    pub next_group: usize,
+
+    pub heaprelid: pg_sys::Oid,
+    pub heaprel: Option<pg_sys::Relation>,
+    pub indexrel: Option<pg_sys::Relation>,
+    pub indexrelid: pg_sys::Oid,
+    pub lockmode: pg_sys::LOCKMODE,
+}
+
+impl CustomScanState for AggregateScanState {
+    fn init_exec_method(&mut self, _cstate: *mut pg_sys::CustomScanState) {
+        // there's only one way to compute the groups
+    }
+}
+
+impl AggregateScanState {
+    #[inline(always)]
+    pub fn heaprel(&self) -> pg_sys::Relation {
+        self.heaprel.unwrap()
+    }
+}
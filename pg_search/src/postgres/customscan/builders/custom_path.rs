@@ -78,6 +78,7 @@ impl From<SortDirection> for u32 {
     }
 }
 
+#[derive(Clone)]
 pub enum OrderByStyle {
     Score(*mut pg_sys::PathKey),
     Field(*mut pg_sys::PathKey, String),
@@ -99,6 +100,17 @@ impl OrderByStyle {
             (*self.pathkey()).pk_strategy.into()
         }
     }
+
+    /// Does this pathkey want NULLs where Postgres puts them by default:  last when ascending and
+    /// first when descending?
+    pub fn has_default_nulls_order(&self) -> bool {
+        unsafe {
+            let pathkey = self.pathkey();
+            assert!(!pathkey.is_null());
+
+            (*pathkey).pk_nulls_first == matches!(self.direction(), SortDirection::Desc)
+        }
+    }
 }
 
 #[derive(Debug)]
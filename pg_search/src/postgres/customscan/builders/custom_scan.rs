@@ -112,6 +112,14 @@ impl<P: Into<*mut pg_sys::List> + From<*mut pg_sys::List> + Default> CustomScanB
         &mut self.custom_private
     }
 
+    /// Describe the tuples this scan produces when it doesn't scan a base relation, such as for
+    /// a join or an upper relation.  The plan's targetlist will reference these entries by
+    /// position.
+    pub fn set_custom_scan_tlist(mut self, tlist: *mut pg_sys::List) -> Self {
+        self.custom_scan_node.custom_scan_tlist = tlist;
+        self
+    }
+
     pub fn build(self) -> pg_sys::CustomScan {
         let mut node = self.custom_scan_node;
         node.custom_private = self.custom_private.into();
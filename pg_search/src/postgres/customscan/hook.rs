@@ -58,6 +58,42 @@ pub fn register_rel_pathlist<CS: CustomScan + 'static>(_: CS) {
     }
 }
 
+pub fn register_upper_paths<CS: CustomScan + 'static>(_: CS) {
+    unsafe {
+        static mut PREV_HOOKS: Lazy<
+            FxHashMap<std::any::TypeId, pg_sys::create_upper_paths_hook_type>,
+        > = Lazy::new(Default::default);
+
+        #[pg_guard]
+        extern "C" fn __priv_callback<CS: CustomScan + 'static>(
+            root: *mut pg_sys::PlannerInfo,
+            stage: pg_sys::UpperRelationKind::Type,
+            input_rel: *mut pg_sys::RelOptInfo,
+            output_rel: *mut pg_sys::RelOptInfo,
+            extra: *mut std::ffi::c_void,
+        ) {
+            unsafe {
+                #[allow(static_mut_refs)]
+                if let Some(Some(prev_hook)) = PREV_HOOKS.get(&std::any::TypeId::of::<CS>()) {
+                    (*prev_hook)(root, stage, input_rel, output_rel, extra);
+                }
+
+                paradedb_upper_paths_callback::<CS>(root, stage, input_rel, output_rel, extra);
+            }
+        }
+
+        #[allow(static_mut_refs)]
+        match PREV_HOOKS.entry(std::any::TypeId::of::<CS>()) {
+            Entry::Occupied(_) => panic!("{} is already registered", std::any::type_name::<CS>()),
+            Entry::Vacant(entry) => entry.insert(pg_sys::create_upper_paths_hook),
+        };
+
+        pg_sys::create_upper_paths_hook = Some(__priv_callback::<CS>);
+
+        pg_sys::RegisterCustomScanMethods(CS::custom_scan_methods())
+    }
+}
+
 /// Although this hook function can be used to examine, modify, or remove paths generated by the
 /// core system, a custom scan provider will typically confine itself to generating CustomPath
 /// objects and adding them to rel using add_path. The custom scan provider is responsible for
@@ -111,3 +147,41 @@ pub extern "C" fn paradedb_rel_pathlist_callback<CS: CustomScan>(
         }
     }
 }
+
+/// Offer a CustomPath that computes the grouping and aggregation of a single base relation.
+///
+/// Only the [`pg_sys::UpperRelationKind::UPPERREL_GROUP_AGG`] stage is considered, and the
+/// [`CustomPathBuilder`] is made for the grouped `output_rel`, with the range table index and
+/// entry of the scanned `input_rel`.
+#[pg_guard]
+pub extern "C" fn paradedb_upper_paths_callback<CS: CustomScan>(
+    root: *mut pg_sys::PlannerInfo,
+    stage: pg_sys::UpperRelationKind::Type,
+    input_rel: *mut pg_sys::RelOptInfo,
+    output_rel: *mut pg_sys::RelOptInfo,
+    extra: *mut std::ffi::c_void,
+) {
+    unsafe {
+        if !gucs::enable_custom_scan() {
+            return;
+        }
+
+        if stage != pg_sys::UpperRelationKind::UPPERREL_GROUP_AGG
+            || (*input_rel).reloptkind != pg_sys::RelOptKind::RELOPT_BASEREL
+        {
+            return;
+        }
+
+        let rti = (*input_rel).relid;
+        let rte = *(*root).simple_rte_array.add(rti as usize);
+        if let Some(mut path) =
+            CS::callback(CustomPathBuilder::new::<CS>(root, output_rel, rti, rte))
+        {
+            path.flags &= !(Flags::Force as u32);
+
+            let custom_path = PgMemoryContexts::CurrentMemoryContext
+                .copy_ptr_into(&mut path, std::mem::size_of_val(&path));
+            pg_sys::add_path(output_rel, custom_path.cast());
+        }
+    }
+}
@@ -31,6 +31,7 @@ pub enum ExecState {
     },
     Virtual {
         slot: *mut pg_sys::TupleTableSlot,
+        doc_address: DocAddress,
     },
     Eof,
 }
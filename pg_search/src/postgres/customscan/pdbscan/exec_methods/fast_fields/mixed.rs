@@ -15,7 +15,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::index::fast_fields_helper::{FFHelper, WhichFastField};
+use crate::index::fast_fields_helper::{FFHelper, FastFieldType, WhichFastField};
 use crate::index::reader::index::SearchResults;
 use crate::postgres::customscan::pdbscan::exec_methods::fast_fields::{
     ff_to_datum, FastFieldExecState,
@@ -28,11 +28,13 @@ use pgrx::itemptr::item_pointer_get_block_number;
 use pgrx::pg_sys::CustomScanState;
 use pgrx::{pg_sys, PgTupleDesc};
 
-pub struct NumericFastFieldExecState {
+/// Emits any mix of numeric and string fast fields, `paradedb.score()`, `ctid`, and `tableoid`
+/// straight from the index for rows on all-visible heap pages.
+pub struct MixedFastFieldExecState {
     inner: FastFieldExecState,
 }
 
-impl NumericFastFieldExecState {
+impl MixedFastFieldExecState {
     pub fn new(which_fast_fields: Vec<WhichFastField>) -> Self {
         Self {
             inner: FastFieldExecState::new(which_fast_fields),
@@ -40,7 +42,7 @@ impl NumericFastFieldExecState {
     }
 }
 
-impl ExecMethod for NumericFastFieldExecState {
+impl ExecMethod for MixedFastFieldExecState {
     fn init(&mut self, state: &mut PdbScanState, cstate: *mut CustomScanState) {
         unsafe {
             self.inner.heaprel = state.heaprel();
@@ -132,6 +134,19 @@ impl ExecMethod for NumericFastFieldExecState {
                         for (i, att) in self.inner.tupdesc.as_ref().unwrap().iter().enumerate() {
                             let which_fast_field = &which_fast_fields[i];
 
+                            // a string field is read into our buffer before it's converted
+                            if matches!(
+                                which_fast_field,
+                                WhichFastField::Named(_, FastFieldType::String)
+                            ) && fast_fields
+                                .string(i, doc_address, &mut self.inner.strbuf)
+                                .is_none()
+                            {
+                                datums[i] = pg_sys::Datum::null();
+                                isnull[i] = true;
+                                continue;
+                            }
+
                             match ff_to_datum(
                                 (which_fast_field, i),
                                 att.atttypid,
@@ -152,7 +167,7 @@ impl ExecMethod for NumericFastFieldExecState {
                             }
                         }
 
-                        ExecState::Virtual { slot }
+                        ExecState::Virtual { slot, doc_address }
                     } else {
                         ExecState::RequiresVisibilityCheck {
                             ctid: scored.ctid,
@@ -15,7 +15,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-pub mod numeric;
+pub mod mixed;
 pub mod string;
 
 use crate::index::fast_fields_helper::{FFHelper, FastFieldType, WhichFastField};
@@ -27,7 +27,7 @@ use crate::postgres::customscan::builders::custom_state::{
     CustomScanStateBuilder, CustomScanStateWrapper,
 };
 use crate::postgres::customscan::explainer::Explainer;
-use crate::postgres::customscan::pdbscan::exec_methods::fast_fields::numeric::NumericFastFieldExecState;
+use crate::postgres::customscan::pdbscan::exec_methods::fast_fields::mixed::MixedFastFieldExecState;
 use crate::postgres::customscan::pdbscan::exec_methods::fast_fields::string::StringFastFieldExecState;
 use crate::postgres::customscan::pdbscan::exec_methods::normal::NormalScanExecState;
 use crate::postgres::customscan::pdbscan::privdat::PrivateData;
@@ -118,8 +118,8 @@ unsafe fn ff_to_datum(
     ) {
         strbuf.as_str().into_datum()
     } else if typid == pg_sys::TEXTOID || typid == pg_sys::VARCHAROID {
-        // NB:  we don't actually support text-based fast fields... yet
-        // but if we did, we'd want to do it this way
+        // string fast fields were read into `strbuf` by the exec method before we were called.
+        // this is any other text column, which is read the same way
         ff_helper
             .string(field_index, doc_address, strbuf)
             .and_then(|s| strbuf.as_str().into_datum())
@@ -235,18 +235,6 @@ pub unsafe fn pullup_fast_fields(
         return None;
     }
 
-    if matches
-        .iter()
-        .sorted()
-        .dedup()
-        .filter(|ff| matches!(ff, WhichFastField::Named(_, FastFieldType::String)))
-        .count()
-        > 1
-    {
-        // we cannot support more than 1 different String fast field
-        return None;
-    }
-
     Some(matches)
 }
 
@@ -254,7 +242,7 @@ pub unsafe fn pullup_fast_fields(
 /// [`NormalScanExecState`] if not.
 ///
 /// We support [`StringFastFieldExecState`] when there's 1 fast field and it's a string, or
-/// [`NumericFastFieldExecState`] when there's one or more numeric fast fields
+/// [`MixedFastFieldExecState`] for any other mix of numeric and string fast fields
 ///
 /// `paradedb.score()`, `ctid`, and `tableoid` are considered fast fields for the purposes of
 /// these specialized [`ExecMethod`]s.
@@ -264,11 +252,11 @@ pub fn assign_exec_method(builder: &mut CustomScanStateBuilder<PdbScan, PrivateD
         builder
             .custom_state()
             .assign_exec_method(StringFastFieldExecState::new(field, which_fast_fields));
-    } else if is_numeric_fast_field_capable(builder.custom_state()) {
+    } else if is_mixed_fast_field_capable(builder.custom_state()) {
         let which_fast_fields = builder.custom_state().which_fast_fields.clone().unwrap();
         builder
             .custom_state()
-            .assign_exec_method(NumericFastFieldExecState::new(which_fast_fields));
+            .assign_exec_method(MixedFastFieldExecState::new(which_fast_fields));
     } else {
         builder
             .custom_state()
@@ -306,17 +294,8 @@ pub fn is_string_agg_capable_ex(
     string_field
 }
 
-fn is_numeric_fast_field_capable(state: &PdbScanState) -> bool {
-    if state.which_fast_fields.is_none() || state.targetlist_len == 0 {
-        return false;
-    }
-
-    for ff in state.which_fast_fields.iter().flatten() {
-        if matches!(ff, WhichFastField::Named(_, FastFieldType::String)) {
-            return false;
-        }
-    }
-    true
+fn is_mixed_fast_field_capable(state: &PdbScanState) -> bool {
+    state.which_fast_fields.is_some() && state.targetlist_len != 0
 }
 
 /// Add nodes to `EXPLAIN` output to describe the "fast fields" being used by the query, if any
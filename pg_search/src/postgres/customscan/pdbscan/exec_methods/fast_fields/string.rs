@@ -169,7 +169,7 @@ impl ExecMethod for StringFastFieldExecState {
                             }
                         }
 
-                        ExecState::Virtual { slot }
+                        ExecState::Virtual { slot, doc_address }
                     } else {
                         ExecState::RequiresVisibilityCheck {
                             ctid: scored.ctid,
@@ -134,7 +134,7 @@ impl ExecMethod for NormalScanExecState {
                     (*slot).tts_flags |= pg_sys::TTS_FLAG_SHOULDFREE as u16;
                     (*slot).tts_nvalid = 0;
 
-                    ExecState::Virtual { slot }
+                    ExecState::Virtual { slot, doc_address }
                 } else {
                     // not sure about the block visibility so the tuple requires a heap check
                     ExecState::RequiresVisibilityCheck {
@@ -15,7 +15,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::index::reader::index::{SearchIndexReader, SearchResults};
+use crate::index::reader::index::{
+    SearchIndexReader, SearchResults, SortDirection as IndexSortDirection, SortKey,
+};
 use crate::postgres::customscan::builders::custom_path::SortDirection;
 use crate::postgres::customscan::pdbscan::exec_methods::{ExecMethod, ExecState};
 use crate::postgres::customscan::pdbscan::parallel::checkout_segment;
@@ -23,6 +25,7 @@ use crate::postgres::customscan::pdbscan::scan_state::PdbScanState;
 use crate::query::SearchQueryInput;
 use pgrx::{direct_function_call, pg_sys, IntoDatum};
 use tantivy::index::SegmentId;
+use tantivy::schema::OwnedValue;
 
 // TODO:  should these be GUCs?  I think yes, probably
 const SUBSEQUENT_RETRY_SCALE_FACTOR: usize = 2;
@@ -40,6 +43,8 @@ pub struct TopNScanExecState {
     search_query_input: Option<SearchQueryInput>,
     search_reader: Option<SearchIndexReader>,
     sort_field: Option<String>,
+    sort_keys: Vec<SortKey>,
+    search_after: Option<Vec<OwnedValue>>,
     search_results: SearchResults,
     did_query: bool,
 
@@ -67,6 +72,12 @@ impl TopNScanExecState {
         }
     }
 
+    /// How many documents to ask for.  This starts at the limit, which already includes the
+    /// query's OFFSET, and grows each time we retry because too many of them weren't visible
+    fn chunk_size(&self) -> usize {
+        self.limit.max(self.chunk_size)
+    }
+
     fn query_more_results(
         &mut self,
         state: &mut PdbScanState,
@@ -82,14 +93,25 @@ impl TopNScanExecState {
                 self.current_segment = segment_id;
 
                 let search_reader = state.search_reader.as_ref().unwrap();
-                search_reader.search_top_n_in_segment(
-                    segment_id,
-                    self.search_query_input.as_ref().unwrap(),
-                    self.sort_field.clone(),
-                    self.sort_direction.into(),
-                    self.limit,
-                    self.need_scores,
-                )
+                if self.sort_keys.is_empty() {
+                    search_reader.search_top_n_in_segment(
+                        segment_id,
+                        self.search_query_input.as_ref().unwrap(),
+                        self.sort_field.clone(),
+                        self.sort_direction.into(),
+                        self.chunk_size(),
+                        self.need_scores,
+                    )
+                } else {
+                    search_reader.search_top_n_compound_in_segment(
+                        segment_id,
+                        self.search_query_input.as_ref().unwrap(),
+                        &self.sort_keys,
+                        self.search_after.as_deref(),
+                        self.chunk_size(),
+                        self.need_scores,
+                    )
+                }
             } else {
                 // no more segments to query
                 SearchResults::None
@@ -100,13 +122,23 @@ impl TopNScanExecState {
         } else {
             // not parallel, first time query
             let search_reader = state.search_reader.as_ref().unwrap();
-            search_reader.search_top_n(
-                self.search_query_input.as_ref().unwrap(),
-                self.sort_field.clone(),
-                self.sort_direction.into(),
-                self.limit,
-                self.need_scores,
-            )
+            if self.sort_keys.is_empty() {
+                search_reader.search_top_n(
+                    self.search_query_input.as_ref().unwrap(),
+                    self.sort_field.clone(),
+                    self.sort_direction.into(),
+                    self.chunk_size(),
+                    self.need_scores,
+                )
+            } else {
+                search_reader.search_top_n_compound(
+                    self.search_query_input.as_ref().unwrap(),
+                    &self.sort_keys,
+                    self.search_after.as_deref(),
+                    self.chunk_size(),
+                    self.need_scores,
+                )
+            }
         }
     }
 
@@ -124,10 +156,65 @@ impl ExecMethod for TopNScanExecState {
 
         self.search_query_input = Some(state.search_query_input.clone());
         self.sort_field = sort_field;
+        self.sort_keys = state
+            .sort_keys
+            .iter()
+            .map(|(field, direction)| (field.clone(), (*direction).into()))
+            .collect();
         self.search_reader = state.search_reader.clone();
+        self.search_after = None;
+
+        if let Some((sort_values, key)) = state.search_after.clone() {
+            // a `paradedb.search_after()` cursor is compared against the `ORDER BY` keys with the
+            // index's key field(s) breaking ties, so always sort by those too
+            if self.sort_keys.is_empty() && !matches!(self.sort_direction, SortDirection::None) {
+                self.sort_keys = vec![(self.sort_field.clone(), self.sort_direction.into())];
+            }
+
+            let key_fields = self
+                .search_reader
+                .as_ref()
+                .expect("search reader should be open")
+                .schema()
+                .key_fields()
+                .into_iter()
+                .map(|field| field.name.0)
+                .collect::<Vec<_>>();
+            if key.len() != key_fields.len() {
+                panic!(
+                    "the index's key_field has {} parts but {} values were given to search_after",
+                    key_fields.len(),
+                    key.len()
+                );
+            }
+
+            let ends_with_key = key_fields.len() == 1
+                && self
+                    .sort_keys
+                    .last()
+                    .is_some_and(|(field, _)| field.as_ref() == Some(&key_fields[0]));
+            if !ends_with_key {
+                self.sort_keys.extend(
+                    key_fields
+                        .into_iter()
+                        .map(|field| (Some(field), IndexSortDirection::Asc)),
+                );
+            }
+
+            let expected = self.sort_keys.len() - key.len();
+            if sort_values.len() != expected {
+                panic!(
+                    "search_after expects {expected} sort values, one for each ORDER BY column before the key field, but {} were given",
+                    sort_values.len()
+                );
+            }
+            self.search_after = Some(sort_values.into_iter().chain(key).collect());
+        }
     }
 
     fn query(&mut self, state: &mut PdbScanState) -> bool {
+        self.reset();
+        state.top_n_chunk_size = state.top_n_chunk_size.max(self.chunk_size());
         let search_results = self.query_more_results(state, None);
 
         self.did_query = true;
@@ -136,7 +223,6 @@ impl ExecMethod for TopNScanExecState {
             false
         } else {
             self.search_results = search_results;
-            self.reset();
             true
         }
     }
@@ -151,12 +237,14 @@ impl ExecMethod for TopNScanExecState {
                         return ExecState::Eof;
                     }
                     None => {
-                        if self.found <= self.limit {
+                        if self.found < self.chunk_size() {
                             // we found all the matching rows
                             return ExecState::Eof;
                         }
                     }
                     Some((scored, doc_address)) => {
+                        self.found += 1;
+                        self.last_ctid = scored.ctid;
                         return ExecState::RequiresVisibilityCheck {
                             ctid: scored.ctid,
                             score: scored.bm25,
@@ -167,6 +255,7 @@ impl ExecMethod for TopNScanExecState {
 
                 // we underflowed our tuples, so go get some more, if there are any
                 self.retry_count += 1;
+                state.retry_count += 1;
 
                 // calculate a scaling factor to use against the limit
                 let factor = if self.chunk_size == 0 {
@@ -191,10 +280,13 @@ impl ExecMethod for TopNScanExecState {
                     SUBSEQUENT_RETRY_SCALE_FACTOR
                 };
 
-                // set the chunk size to the scaling factor times the limit
+                // set the chunk size to the scaling factor times the limit, growing by at most
+                // MAX_CHUNK_SIZE each time.  it always grows, otherwise the retry would just see the
+                // same documents again
                 self.chunk_size = (self.chunk_size * factor)
                     .max(self.limit * factor)
-                    .min(MAX_CHUNK_SIZE);
+                    .min(self.chunk_size() + MAX_CHUNK_SIZE);
+                state.top_n_chunk_size = state.top_n_chunk_size.max(self.chunk_size());
 
                 let mut results = self.query_more_results(state, Some(self.current_segment));
 
@@ -18,13 +18,14 @@
 #![allow(clippy::unnecessary_cast)] // helps with integer casting differences between postgres versions
 mod exec_methods;
 pub mod parallel;
-mod privdat;
+pub mod privdat;
 mod projections;
-mod qual_inspect;
+pub mod qual_inspect;
 mod scan_state;
 
 use crate::api::operator::{
-    anyelement_query_input_opoid, attname_from_var, estimate_selectivity, find_var_relation,
+    anyelement_query_input_opoid, anyelement_query_input_score_opoid, anyelement_text_score_opoid,
+    attname_from_var, estimate_selectivity, find_var_relation,
 };
 use crate::api::{AsCStr, AsInt, Cardinality};
 use crate::index::merge_policy::AllowedMergePolicy;
@@ -50,24 +51,27 @@ use crate::postgres::customscan::pdbscan::projections::score::{
 use crate::postgres::customscan::pdbscan::projections::snippet::{
     snippet_funcoid, uses_snippets, SnippetInfo,
 };
+use crate::postgres::customscan::pdbscan::projections::stored::{stored_funcoid, uses_stored};
 use crate::postgres::customscan::pdbscan::projections::{
     inject_placeholders, maybe_needs_const_projections, pullout_funcexprs,
 };
 use crate::postgres::customscan::pdbscan::qual_inspect::extract_quals;
 use crate::postgres::customscan::pdbscan::scan_state::PdbScanState;
 use crate::postgres::customscan::{CustomScan, CustomScanState, ExecMethod};
-use crate::postgres::rel_get_bm25_index;
 use crate::postgres::visibility_checker::VisibilityChecker;
+use crate::postgres::{rel_get_bm25_index, SegmentStats};
 use crate::query::SearchQueryInput;
 use crate::schema::SearchIndexSchema;
 use crate::{nodecast, DEFAULT_STARTUP_COST, UNKNOWN_SELECTIVITY};
 use exec_methods::top_n::TopNScanExecState;
 use exec_methods::ExecState;
 use pgrx::pg_sys::{AsPgCStr, CustomExecMethods};
-use pgrx::{direct_function_call, pg_sys, IntoDatum, PgList, PgMemoryContexts, PgRelation};
-use std::collections::HashMap;
+use pgrx::{direct_function_call, pg_sys, IntoDatum, JsonB, PgList, PgMemoryContexts, PgRelation};
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::CStr;
 use std::ptr::addr_of_mut;
+use std::time::Instant;
+use tantivy::index::SegmentId;
 use tantivy::snippet::SnippetGenerator;
 use tantivy::Index;
 
@@ -113,11 +117,16 @@ impl CustomScan for PdbScan {
             };
 
             let root = builder.args().root;
+            if orders_by_distance_operator(root) {
+                // a plain bm25 index scan returns rows already in `<=>@@` order
+                return None;
+            }
 
             let directory = MVCCDirectory::snapshot(bm25_index.oid(), AllowedMergePolicy::None);
             let index = Index::open(directory).expect("custom_scan: should be able to open index");
             let schema = SearchIndexSchema::open(index.schema(), &bm25_index);
-            let pathkey = pullup_orderby_pathkey(&mut builder, rti, &schema, root);
+            let orderby = pullup_orderby_pathkeys(&mut builder, rti, &schema, root);
+            let pathkey = orderby.first().cloned();
 
             #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
             let baserels = (*builder.args().root).all_baserels;
@@ -140,7 +149,15 @@ impl CustomScan for PdbScan {
             let ff_cnt =
                 exec_methods::fast_fields::count(&mut builder, rti, &table, &schema, target_list);
             let maybe_ff = builder.custom_private().maybe_ff();
-            let is_topn = limit.is_some() && pathkey.is_some();
+            // a "top n" query needs to sort by every `ORDER BY` pathkey.  if we could only sort by
+            // some of them, we might pick the wrong rows when the leading keys tie
+            let is_topn = limit.is_some()
+                && !orderby.is_empty()
+                && orderby.len()
+                    == PgList::<pg_sys::PathKey>::from_pg((*builder.args().root).query_pathkeys)
+                        .len()
+                && (orderby.len() == 1
+                    || orderby.iter().all(|style| style.has_default_nulls_order()));
             let which_fast_fields = exec_methods::fast_fields::collect(
                 builder.custom_private().maybe_ff(),
                 target_list,
@@ -154,10 +171,14 @@ impl CustomScan for PdbScan {
             //
             let restrict_info = builder.restrict_info();
             if let Some(quals) = extract_quals(
+                root,
                 rti,
                 restrict_info.as_ptr().cast(),
                 anyelement_query_input_opoid(),
-            ) {
+                &schema,
+            )
+            .filter(|quals| !quals.is_pushdown_only())
+            {
                 let selectivity = if let Some(limit) = limit {
                     // use the limit
                     limit
@@ -165,6 +186,9 @@ impl CustomScan for PdbScan {
                             .reltuples()
                             .map(|n| n as Cardinality)
                             .unwrap_or(UNKNOWN_SELECTIVITY)
+                } else if quals.contains_expressions() {
+                    // the query isn't known until execution time
+                    UNKNOWN_SELECTIVITY
                 } else if restrict_info.len() == 1 {
                     // we can use the norm_selec that already happened
                     (*restrict_info.get_ptr(0).unwrap()).norm_selec
@@ -183,7 +207,12 @@ impl CustomScan for PdbScan {
                 // we must use this path if we need to do const projections for scores or snippets
                 builder = builder.set_force_path(maybe_needs_const_projections || is_topn);
 
-                if is_topn {
+                if is_topn && orderby.len() > 1 {
+                    // a compound sort key is collected alongside the score, so it works whether
+                    // or not we're doing const projections
+                    builder.custom_private().set_sort_info(&pathkey);
+                    builder.custom_private().set_sort_keys(&orderby);
+                } else if is_topn {
                     // sorting by a field only works if we're not doing const projections
                     // the reason for this is that tantivy can't do both scoring and ordering by
                     // a fast field at the same time.
@@ -312,9 +341,13 @@ impl CustomScan for PdbScan {
             let mut attname_lookup = PgList::<pg_sys::Node>::new();
             let score_funcoid = score_funcoid();
             let snippet_funcoid = snippet_funcoid();
+            let stored_funcoid = stored_funcoid();
             for te in processed_tlist.iter_ptr() {
-                let func_vars_at_level =
-                    pullout_funcexprs(te.cast(), &[score_funcoid, snippet_funcoid], rti);
+                let func_vars_at_level = pullout_funcexprs(
+                    te.cast(),
+                    &[score_funcoid, snippet_funcoid, stored_funcoid],
+                    rti,
+                );
 
                 for (funcexpr, var) in func_vars_at_level {
                     // if we have a tlist, then we need to add the specific function that uses
@@ -392,13 +425,19 @@ impl CustomScan for PdbScan {
             builder.custom_state().limit = builder.custom_private().limit();
             builder.custom_state().sort_field = builder.custom_private().sort_field();
             builder.custom_state().sort_direction = builder.custom_private().sort_direction();
+            builder.custom_state().sort_keys = builder.custom_private().sort_keys();
 
             // store our query quals into our custom state too
             let quals = builder
                 .custom_private()
                 .quals()
                 .expect("should have a Qual structure");
-            builder.custom_state().search_query_input = SearchQueryInput::from(&quals);
+            if !quals.contains_expressions() {
+                builder
+                    .custom_state()
+                    .set_search_query_input(SearchQueryInput::from(&quals));
+            }
+            builder.custom_state().quals = Some(quals);
 
             builder.custom_state().segment_count = builder.custom_private().segment_count();
 
@@ -447,9 +486,11 @@ impl CustomScan for PdbScan {
 
             let score_funcoid = score_funcoid();
             let snippet_funcoid = snippet_funcoid();
+            let stored_funcoid = stored_funcoid();
 
             builder.custom_state().score_funcoid = score_funcoid;
             builder.custom_state().snippet_funcoid = snippet_funcoid;
+            builder.custom_state().stored_funcoid = stored_funcoid;
 
             builder.custom_state().need_scores = uses_scores(
                 builder.target_list().as_ptr().cast(),
@@ -465,6 +506,9 @@ impl CustomScan for PdbScan {
                     .into_iter()
                     .map(|field| (field, None))
                     .collect();
+            let attname_lookup = &builder.custom_state().var_attname_lookup;
+            builder.custom_state().stored_fields =
+                uses_stored(rti, attname_lookup, node, stored_funcoid);
 
             let need_snippets = builder.custom_state().need_snippets();
             let need_scores = builder.custom_state().need_scores();
@@ -512,6 +556,17 @@ impl CustomScan for PdbScan {
         );
 
         if explainer.is_analyze() {
+            explain_segments(state, explainer);
+
+            // every matching document is checked for visibility, either with a heap fetch or,
+            // for virtual tuples, with the visibility map alone
+            explainer.add_unsigned_integer(
+                "Visibility Checks",
+                (state.custom_state().heap_tuple_check_count
+                    + state.custom_state().invisible_tuple_count
+                    + state.custom_state().virtual_tuple_count) as u64,
+                None,
+            );
             explainer.add_unsigned_integer(
                 "Heap Fetches",
                 state.custom_state().heap_tuple_check_count as u64,
@@ -529,6 +584,20 @@ impl CustomScan for PdbScan {
                     None,
                 );
             }
+            if explainer.is_timing() {
+                explainer.add_float(
+                    "Tantivy Time",
+                    state.custom_state().tantivy_time.as_secs_f64() * 1000.0,
+                    Some("ms"),
+                    3,
+                );
+                explainer.add_float(
+                    "Heap Time",
+                    state.custom_state().heap_time.as_secs_f64() * 1000.0,
+                    Some("ms"),
+                    3,
+                );
+            }
         }
 
         explainer.add_text(
@@ -544,7 +613,21 @@ impl CustomScan for PdbScan {
 
         explainer.add_bool("Scores", state.custom_state().need_scores());
         if let Some(sort_direction) = state.custom_state().sort_direction {
-            if !matches!(sort_direction, SortDirection::None) {
+            if !state.custom_state().sort_keys.is_empty() {
+                let sort_keys = state
+                    .custom_state()
+                    .sort_keys
+                    .iter()
+                    .map(|(field, direction)| {
+                        format!(
+                            "{} {direction}",
+                            field.as_deref().unwrap_or("paradedb.score()")
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                explainer.add_text("   Sort Keys", sort_keys);
+            } else if !matches!(sort_direction, SortDirection::None) {
                 if let Some(sort_field) = &state.custom_state().sort_field {
                     explainer.add_text("   Sort Field", sort_field);
                 } else {
@@ -555,14 +638,38 @@ impl CustomScan for PdbScan {
 
             if let Some(limit) = state.custom_state().limit {
                 explainer.add_unsigned_integer("   Top N Limit", limit as u64, None);
-                if explainer.is_analyze() && state.custom_state().retry_count > 0 {
+                if explainer.is_analyze() {
                     explainer.add_unsigned_integer(
                         "   Invisible Tuple Retries",
                         state.custom_state().retry_count as u64,
                         None,
                     );
+                    explainer.add_unsigned_integer(
+                        "   Final Chunk Size",
+                        state.custom_state().top_n_chunk_size as u64,
+                        None,
+                    );
                 }
             }
+
+            if let Some(search_after) = &state.custom_state().search_after {
+                explainer.add_text(
+                    "   Search After",
+                    serde_json::to_string(search_after).expect("search_after should serialize"),
+                );
+            }
+        }
+
+        if !explainer.is_analyze()
+            && state
+                .custom_state()
+                .quals
+                .as_ref()
+                .is_some_and(|quals| quals.contains_expressions())
+        {
+            // the query depends on parameters that are only evaluated when the scan starts
+            explainer.add_text("Tantivy Query", "<evaluated at execution time>");
+            return;
         }
 
         let query = &state.custom_state().search_query_input;
@@ -602,6 +709,11 @@ impl CustomScan for PdbScan {
             state.custom_state_mut().indexrel = Some(indexrel);
             state.custom_state_mut().lockmode = lockmode;
 
+            // the node's own Instrumentation isn't allocated until after we return, so ask the EState
+            state.custom_state_mut().instrument = (*estate).es_instrument != 0;
+            state.custom_state_mut().instrument_timer =
+                ((*estate).es_instrument & pg_sys::InstrumentOption::INSTRUMENT_TIMER as i32) != 0;
+
             state.custom_state_mut().heaprel_namespace =
                 PgRelation::from_pg(heaprel).namespace().to_string();
             state.custom_state_mut().heaprel_relname =
@@ -625,6 +737,13 @@ impl CustomScan for PdbScan {
                 state.planstate(),
                 (*state.csstate.ss.ss_ScanTupleSlot).tts_tupleDescriptor,
             );
+
+            // prepare the parts of our query that are only known now, like prepared statement
+            // parameters.  they're evaluated at every (re)scan
+            let planstate = state.planstate();
+            if let Some(quals) = state.custom_state_mut().quals.as_mut() {
+                quals.init_expressions(planstate);
+            }
         }
 
         if eflags & (pg_sys::EXEC_FLAG_EXPLAIN_ONLY as i32) != 0 {
@@ -636,6 +755,21 @@ impl CustomScan for PdbScan {
     }
 
     fn rescan_custom_scan(state: &mut CustomScanStateWrapper<Self>) {
+        let runtime_query = state
+            .custom_state()
+            .quals
+            .as_ref()
+            .filter(|quals| quals.contains_expressions())
+            .map(SearchQueryInput::from);
+        if let Some(query) = runtime_query {
+            state.custom_state_mut().set_search_query_input(query);
+        }
+        if state.custom_state().search_after.is_some()
+            && state.custom_state().is_top_n_capable().is_none()
+        {
+            panic!("paradedb.search_after() requires an ORDER BY ... LIMIT over fast fields or paradedb.score() that the index can sort by");
+        }
+
         let need_snippets = state.custom_state().need_snippets();
 
         // Open the index and query it
@@ -699,7 +833,13 @@ impl CustomScan for PdbScan {
             let exec_method = state.custom_state_mut().exec_method_mut();
 
             // get the next matching document from our search results and look for it in the heap
-            match exec_method.next(state.custom_state_mut()) {
+            let started = state.custom_state().instrument_timer.then(Instant::now);
+            let next = exec_method.next(state.custom_state_mut());
+            if let Some(started) = started {
+                state.custom_state_mut().tantivy_time += started.elapsed();
+            }
+
+            match next {
                 // reached the end of the SearchResults
                 ExecState::Eof => {
                     return std::ptr::null_mut();
@@ -712,7 +852,16 @@ impl CustomScan for PdbScan {
                     doc_address,
                 } => {
                     unsafe {
-                        let slot = match check_visibility(state, ctid, state.scanslot().cast()) {
+                        let started = state.custom_state().instrument_timer.then(Instant::now);
+                        let visible = check_visibility(state, ctid, state.scanslot().cast());
+                        if let Some(started) = started {
+                            state.custom_state_mut().heap_time += started.elapsed();
+                        }
+                        if state.custom_state().instrument {
+                            state.custom_state_mut().record_segment_match(doc_address);
+                        }
+
+                        let slot = match visible {
                             // the ctid is visible
                             Some(slot) => {
                                 state.custom_state_mut().heap_tuple_check_count += 1;
@@ -728,9 +877,10 @@ impl CustomScan for PdbScan {
 
                         if !state.custom_state().need_scores()
                             && !state.custom_state().need_snippets()
+                            && !state.custom_state().need_stored()
                         {
                             //
-                            // we don't need scores or snippets
+                            // we don't need scores, snippets, or stored fields
                             // do the projection and return
                             //
 
@@ -738,7 +888,7 @@ impl CustomScan for PdbScan {
                             return pg_sys::ExecProject(state.projection_info());
                         } else {
                             //
-                            // we do need scores or snippets
+                            // we do need scores, snippets, or stored fields
                             //
                             // replace their placeholder values and then rebuild the ProjectionInfo
                             // and project it
@@ -778,6 +928,34 @@ impl CustomScan for PdbScan {
                                 });
                             }
 
+                            if state.custom_state().need_stored() {
+                                let stored = state
+                                    .custom_state()
+                                    .search_reader
+                                    .as_ref()
+                                    .expect("search_reader should be initialized")
+                                    .get_stored_fields(doc_address)
+                                    .expect("stored fields should be readable from the index");
+                                per_tuple_context.switch_to(|_| {
+                                    for (field, const_stored_node) in
+                                        &state.custom_state().const_stored_nodes
+                                    {
+                                        match stored.get(field) {
+                                            Some(value) if !value.is_null() => {
+                                                (**const_stored_node).constvalue =
+                                                    JsonB(value.clone()).into_datum().unwrap();
+                                                (**const_stored_node).constisnull = false;
+                                            }
+                                            _ => {
+                                                (**const_stored_node).constvalue =
+                                                    pg_sys::Datum::null();
+                                                (**const_stored_node).constisnull = true;
+                                            }
+                                        }
+                                    }
+                                });
+                            }
+
                             // finally, do the projection
                             return per_tuple_context.switch_to(|_| {
                                 let planstate = state.planstate();
@@ -799,7 +977,10 @@ impl CustomScan for PdbScan {
                     }
                 }
 
-                ExecState::Virtual { slot } => {
+                ExecState::Virtual { slot, doc_address } => {
+                    if state.custom_state().instrument {
+                        state.custom_state_mut().record_segment_match(doc_address);
+                    }
                     state.custom_state_mut().virtual_tuple_count += 1;
                     return slot;
                 }
@@ -807,7 +988,22 @@ impl CustomScan for PdbScan {
         }
     }
 
-    fn shutdown_custom_scan(state: &mut CustomScanStateWrapper<Self>) {}
+    fn shutdown_custom_scan(state: &mut CustomScanStateWrapper<Self>) {
+        // Postgres can shut a node down more than once, such as when a Limit shuts down its Gather
+        // early and then the whole plan is shut down at the end, and by then the parallel shared
+        // memory is gone.  So only the first shutdown may look at it
+        let Some(parallel_state) = state.custom_state_mut().parallel_state.take() else {
+            return;
+        };
+
+        // every participant publishes its matches as it finds them, so everything the leader has
+        // received from the workers is already accounted for.  The leader, whose children are
+        // shut down before the parallel shared memory goes away, keeps a copy for EXPLAIN
+        if state.custom_state().instrument && unsafe { pg_sys::ParallelWorkerNumber } == -1 {
+            state.custom_state_mut().parallel_segment_stats =
+                unsafe { (*parallel_state).segment_stats() };
+        }
+    }
 
     fn end_custom_scan(state: &mut CustomScanStateWrapper<Self>) {
         // get some things dropped now
@@ -831,6 +1027,46 @@ impl CustomScan for PdbScan {
     }
 }
 
+/// Report how many documents matched in each segment.  For a parallel scan, the segments are
+/// broken out by the participant that claimed them.
+fn explain_segments(state: &CustomScanStateWrapper<PdbScan>, explainer: &mut Explainer) {
+    let describe = |segment_id: &SegmentId, matches: u64| {
+        format!("{}: {matches}", segment_id.short_uuid_string())
+    };
+
+    let parallel_segment_stats = &state.custom_state().parallel_segment_stats;
+    if !parallel_segment_stats.is_empty() {
+        let mut by_participant = BTreeMap::<i32, Vec<String>>::new();
+        for (segment_id, stats) in parallel_segment_stats {
+            by_participant
+                .entry(stats.claimed_by)
+                .or_default()
+                .push(describe(segment_id, stats.matches));
+        }
+
+        for (claimed_by, segments) in by_participant {
+            let key = match claimed_by {
+                SegmentStats::UNCLAIMED => "Unclaimed Segments".to_string(),
+                -1 => "Leader Segments".to_string(),
+                worker => format!("Worker {worker} Segments"),
+            };
+            explainer.add_text(&key, segments.join(", "));
+        }
+    } else if let Some(search_reader) = state.custom_state().search_reader.as_ref() {
+        let segment_matches = &state.custom_state().segment_matches;
+        let segments = search_reader
+            .segment_readers()
+            .iter()
+            .map(|segment_reader| {
+                let segment_id = segment_reader.segment_id();
+                let matches = segment_matches.get(&segment_id).copied().unwrap_or(0);
+                describe(&segment_id, matches as u64)
+            })
+            .collect::<Vec<_>>();
+        explainer.add_text("Segment Matches", segments.join(", "));
+    }
+}
+
 /// Use the [`VisibilityChecker`] to lookup the [`SearchIndexScore`] document in the underlying heap
 /// and if it exists return a formed [`TupleTableSlot`].
 #[inline(always)]
@@ -846,83 +1082,119 @@ fn check_visibility(
 }
 
 unsafe fn inject_score_and_snippet_placeholders(state: &mut CustomScanStateWrapper<PdbScan>) {
-    if !state.custom_state().need_scores() && !state.custom_state().need_snippets() {
-        // scores/snippets aren't necessary so we use whatever we originally setup as our ProjectionInfo
+    if !state.custom_state().need_scores()
+        && !state.custom_state().need_snippets()
+        && !state.custom_state().need_stored()
+    {
+        // scores/snippets/stored fields aren't necessary so we use whatever we originally setup as our ProjectionInfo
         return;
     }
 
-    // inject score and/or snippet placeholder [`pg_sys::Const`] nodes into what is a copy of the Plan's
+    // inject score, snippet, and/or stored field placeholder [`pg_sys::Const`] nodes into what is a copy of the Plan's
     // targetlist.  We store this in our custom state's "placeholder_targetlist" for use during the
     // forced projection we must do later.
 
     let planstate = state.planstate();
-    let (targetlist, const_score_node, const_snippet_nodes) = inject_placeholders(
-        (*(*planstate).plan).targetlist,
-        state.custom_state().rti,
-        state.custom_state().score_funcoid,
-        state.custom_state().snippet_funcoid,
-        &state.custom_state().var_attname_lookup,
-        &state.custom_state().snippet_generators,
-    );
+    let (targetlist, const_score_node, const_snippet_nodes, const_stored_nodes) =
+        inject_placeholders(
+            (*(*planstate).plan).targetlist,
+            state.custom_state().rti,
+            state.custom_state().score_funcoid,
+            state.custom_state().snippet_funcoid,
+            state.custom_state().stored_funcoid,
+            &state.custom_state().var_attname_lookup,
+            &state.custom_state().snippet_generators,
+        );
 
     state.custom_state_mut().placeholder_targetlist = Some(targetlist);
     state.custom_state_mut().const_score_node = Some(const_score_node);
     state.custom_state_mut().const_snippet_nodes = const_snippet_nodes;
+    state.custom_state_mut().const_stored_nodes = const_stored_nodes;
 }
 
-unsafe fn pullup_orderby_pathkey<P: Into<*mut pg_sys::List> + Default>(
+/// Does the query `ORDER BY` the `<=>@@` operator?
+unsafe fn orders_by_distance_operator(root: *mut pg_sys::PlannerInfo) -> bool {
+    let pathkeys = PgList::<pg_sys::PathKey>::from_pg((*root).query_pathkeys);
+    if pathkeys.is_empty() {
+        return false;
+    }
+    let opoids = [
+        anyelement_text_score_opoid(),
+        anyelement_query_input_score_opoid(),
+    ];
+
+    pathkeys.iter_ptr().any(|pathkey| {
+        let members =
+            PgList::<pg_sys::EquivalenceMember>::from_pg((*(*pathkey).pk_eclass).ec_members);
+        members.iter_ptr().any(|member| {
+            nodecast!(OpExpr, T_OpExpr, (*member).em_expr)
+                .is_some_and(|opexpr| opoids.contains(&(*opexpr).opno))
+        })
+    })
+}
+
+/// Pull up the leading `ORDER BY` pathkeys we're able to sort by ourselves, in order, stopping at
+/// the first one we can't
+unsafe fn pullup_orderby_pathkeys<P: Into<*mut pg_sys::List> + Default>(
     builder: &mut CustomPathBuilder<P>,
     rti: pg_sys::Index,
     schema: &SearchIndexSchema,
     root: *mut pg_sys::PlannerInfo,
-) -> Option<OrderByStyle> {
+) -> Vec<OrderByStyle> {
     let pathkeys = PgList::<pg_sys::PathKey>::from_pg((*builder.args().root).query_pathkeys);
 
-    if let Some(first_pathkey) = pathkeys.get_ptr(0) {
-        let equivclass = (*first_pathkey).pk_eclass;
-        let members = PgList::<pg_sys::EquivalenceMember>::from_pg((*equivclass).ec_members);
-
-        for member in members.iter_ptr() {
-            let expr = (*member).em_expr;
+    pathkeys
+        .iter_ptr()
+        .map_while(|pathkey| pullup_orderby_pathkey(pathkey, rti, schema, root))
+        .collect()
+}
 
-            if is_score_func(expr.cast(), rti as _) {
-                return Some(OrderByStyle::Score(first_pathkey));
-            } else if let Some(var) = is_lower_func(expr.cast(), rti as _) {
-                let (heaprelid, attno, _) = find_var_relation(var, root);
-                let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
-                let tupdesc = heaprel.tuple_desc();
-                if let Some(att) = tupdesc.get(attno as usize - 1) {
-                    if schema.is_field_lower_sortable(att.name()) {
-                        return Some(OrderByStyle::Field(first_pathkey, att.name().to_string()));
-                    }
-                }
-            } else if let Some(relabel) = nodecast!(RelabelType, T_RelabelType, expr) {
-                if let Some(var) = nodecast!(Var, T_Var, (*relabel).arg) {
-                    let (heaprelid, attno, _) = find_var_relation(var, root);
-                    let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
-                    let tupdesc = heaprel.tuple_desc();
-                    if let Some(att) = tupdesc.get(attno as usize - 1) {
-                        if schema.is_field_raw_sortable(att.name()) {
-                            return Some(OrderByStyle::Field(
-                                first_pathkey,
-                                att.name().to_string(),
-                            ));
-                        }
-                    }
+unsafe fn pullup_orderby_pathkey(
+    pathkey: *mut pg_sys::PathKey,
+    rti: pg_sys::Index,
+    schema: &SearchIndexSchema,
+    root: *mut pg_sys::PlannerInfo,
+) -> Option<OrderByStyle> {
+    let equivclass = (*pathkey).pk_eclass;
+    let members = PgList::<pg_sys::EquivalenceMember>::from_pg((*equivclass).ec_members);
+
+    for member in members.iter_ptr() {
+        let expr = (*member).em_expr;
+
+        if is_score_func(expr.cast(), rti as _) {
+            return Some(OrderByStyle::Score(pathkey));
+        } else if let Some(var) = is_lower_func(expr.cast(), rti as _) {
+            let (heaprelid, attno, _) = find_var_relation(var, root);
+            let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
+            let tupdesc = heaprel.tuple_desc();
+            if let Some(att) = tupdesc.get(attno as usize - 1) {
+                if schema.is_field_lower_sortable(att.name()) {
+                    return Some(OrderByStyle::Field(pathkey, att.name().to_string()));
                 }
-            } else if let Some(var) = nodecast!(Var, T_Var, expr) {
+            }
+        } else if let Some(relabel) = nodecast!(RelabelType, T_RelabelType, expr) {
+            if let Some(var) = nodecast!(Var, T_Var, (*relabel).arg) {
                 let (heaprelid, attno, _) = find_var_relation(var, root);
-                if heaprelid == pg_sys::Oid::INVALID {
-                    return None;
-                }
                 let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
                 let tupdesc = heaprel.tuple_desc();
                 if let Some(att) = tupdesc.get(attno as usize - 1) {
                     if schema.is_field_raw_sortable(att.name()) {
-                        return Some(OrderByStyle::Field(first_pathkey, att.name().to_string()));
+                        return Some(OrderByStyle::Field(pathkey, att.name().to_string()));
                     }
                 }
             }
+        } else if let Some(var) = nodecast!(Var, T_Var, expr) {
+            let (heaprelid, attno, _) = find_var_relation(var, root);
+            if heaprelid == pg_sys::Oid::INVALID {
+                return None;
+            }
+            let heaprel = PgRelation::with_lock(heaprelid, pg_sys::AccessShareLock as _);
+            let tupdesc = heaprel.tuple_desc();
+            if let Some(att) = tupdesc.get(attno as usize - 1) {
+                if schema.is_field_raw_sortable(att.name()) {
+                    return Some(OrderByStyle::Field(pathkey, att.name().to_string()));
+                }
+            }
         }
     }
     None
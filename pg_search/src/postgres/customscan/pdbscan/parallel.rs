@@ -63,7 +63,9 @@ pub unsafe fn checkout_segment(pscan_state: *mut ParallelScanState) -> Option<Se
     if (*pscan_state).remaining_segments > 0 {
         (*pscan_state).remaining_segments -= 1;
 
-        Some((*pscan_state).get_segment_id((*pscan_state).remaining_segments as usize))
+        let i = (*pscan_state).remaining_segments;
+        (*pscan_state).set_claimed_by_me(i);
+        Some((*pscan_state).get_segment_id(i))
     } else {
         None
     }
@@ -30,6 +30,7 @@ pub struct PrivateData {
     limit: Option<usize>,
     sort_field: Option<String>,
     sort_direction: Option<SortDirection>,
+    sort_keys: Vec<(Option<String>, SortDirection)>,
     var_attname_lookup: Option<*mut pg_sys::List>,
     maybe_ff: bool,
     segment_count: usize,
@@ -87,6 +88,17 @@ impl PrivateData {
         }
     }
 
+    /// Record every key of a multi-column `ORDER BY`, where a `None` field is `paradedb.score()`
+    pub fn set_sort_keys(&mut self, pathkeys: &[OrderByStyle]) {
+        self.sort_keys = pathkeys
+            .iter()
+            .map(|style| match style {
+                OrderByStyle::Score(_) => (None, style.direction()),
+                OrderByStyle::Field(_, name) => (Some(name.clone()), style.direction()),
+            })
+            .collect();
+    }
+
     pub fn set_var_attname_lookup(&mut self, var_attname_lookup: *mut pg_sys::List) {
         self.var_attname_lookup = Some(var_attname_lookup);
     }
@@ -134,6 +146,10 @@ impl PrivateData {
         self.sort_direction
     }
 
+    pub fn sort_keys(&self) -> Vec<(Option<String>, SortDirection)> {
+        self.sort_keys.clone()
+    }
+
     pub fn var_attname_lookup(&self) -> Option<PgList<pg_sys::Node>> {
         self.var_attname_lookup
             .map(|list| unsafe { PgList::from_pg(list) })
@@ -268,6 +284,14 @@ pub mod serialize {
         ser.push(makeString(privdat.limit));
         ser.push(makeString(privdat.sort_field));
         ser.push(makeInteger(privdat.sort_direction));
+        ser.push(unwrapOrNull({
+            let mut sort_keys = PgList::<pg_sys::Node>::new();
+            for (field, direction) in privdat.sort_keys {
+                sort_keys.push(makeString(field));
+                sort_keys.push(makeInteger(Some(direction)));
+            }
+            (!sort_keys.is_empty()).then(|| sort_keys.into_pg().cast())
+        }));
         ser.push(unwrapOrNull(
             privdat.var_attname_lookup.map(|v| v.cast::<pg_sys::Node>()),
         ));
@@ -314,14 +338,32 @@ pub mod deserialize {
             limit: input.get_ptr(4).and_then(|n| decodeString(n)),
             sort_field: input.get_ptr(5).and_then(|n| decodeString(n)),
             sort_direction: input.get_ptr(6).and_then(|n| decodeInteger(n)),
-            var_attname_lookup: input
+            sort_keys: input
                 .get_ptr(7)
+                .and_then(|n| nodecast!(List, T_List, n))
+                .map(|list| {
+                    let list = PgList::<pg_sys::Node>::from_pg(list);
+                    (0..list.len())
+                        .step_by(2)
+                        .map(|i| {
+                            (
+                                list.get_ptr(i).and_then(|n| decodeString(n)),
+                                list.get_ptr(i + 1)
+                                    .and_then(|n| decodeInteger(n))
+                                    .expect("sort key should have a direction"),
+                            )
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            var_attname_lookup: input
+                .get_ptr(8)
                 .and_then(|n| nodecast!(List, T_List, n, true)),
             maybe_ff: input
-                .get_ptr(8)
+                .get_ptr(9)
                 .and_then(|n| decodeBoolean(n))
                 .unwrap_or_default(),
-            segment_count: input.get_ptr(9).and_then(|n| decodeString(n)).unwrap_or(0),
+            segment_count: input.get_ptr(10).and_then(|n| decodeString(n)).unwrap_or(0),
         }
     }
 }
@@ -17,11 +17,13 @@
 
 pub mod score;
 pub mod snippet;
+pub mod stored;
 
 use crate::api::operator::{find_vars, ReturnedNodePointer};
 use crate::nodecast;
 use crate::postgres::customscan::pdbscan::projections::score::score_funcoid;
 use crate::postgres::customscan::pdbscan::projections::snippet::{snippet_funcoid, SnippetInfo};
+use crate::postgres::customscan::pdbscan::projections::stored::stored_funcoid;
 use pgrx::pg_sys::expression_tree_walker;
 use pgrx::{pg_extern, pg_guard, pg_sys, Internal, PgList};
 use std::collections::HashMap;
@@ -91,6 +93,7 @@ pub unsafe fn maybe_needs_const_projections(node: *mut pg_sys::Node) -> bool {
             let data = &*data.cast::<Data>();
             if (*funcexpr).funcid == data.score_funcoid
                 || (*funcexpr).funcid == data.snipped_funcoid
+                || (*funcexpr).funcid == data.stored_funcoid
             {
                 return true;
             }
@@ -102,11 +105,13 @@ pub unsafe fn maybe_needs_const_projections(node: *mut pg_sys::Node) -> bool {
     struct Data {
         score_funcoid: pg_sys::Oid,
         snipped_funcoid: pg_sys::Oid,
+        stored_funcoid: pg_sys::Oid,
     }
 
     let mut data = Data {
         score_funcoid: score_funcoid(),
         snipped_funcoid: snippet_funcoid(),
+        stored_funcoid: stored_funcoid(),
     };
 
     let data = addr_of_mut!(data).cast();
@@ -170,12 +175,14 @@ pub unsafe fn inject_placeholders(
     rti: pg_sys::Index,
     score_funcoid: pg_sys::Oid,
     snippet_funcoid: pg_sys::Oid,
+    stored_funcoid: pg_sys::Oid,
     attname_lookup: &HashMap<(i32, pg_sys::AttrNumber), String>,
     snippet_infos: &HashMap<SnippetInfo, Option<(tantivy::schema::Field, SnippetGenerator)>>,
 ) -> (
     *mut pg_sys::List,
     *mut pg_sys::Const,
     HashMap<SnippetInfo, *mut pg_sys::Const>,
+    HashMap<String, *mut pg_sys::Const>,
 ) {
     #[pg_guard]
     unsafe extern "C" fn walker(
@@ -214,6 +221,29 @@ pub unsafe fn inject_placeholders(
                         }
                     }
                 }
+            } else if (*funcexpr).funcid == data.stored_funcoid {
+                let var = nodecast!(Var, T_Var, args.get_ptr(0)?)?;
+                if (*var).varno as i32 != data.rti as i32 {
+                    // another relation's column, which that relation's scan answers for
+                    return None;
+                }
+                let key = (data.rti as i32, (*var).varattno);
+                let attname = data.attname_lookup.get(&key)?;
+                let const_ = *data
+                    .const_stored_nodes
+                    .entry(attname.clone())
+                    .or_insert_with(|| {
+                        pg_sys::makeConst(
+                            pg_sys::JSONBOID,
+                            -1,
+                            pg_sys::Oid::INVALID,
+                            -1,
+                            pg_sys::Datum::null(),
+                            true,
+                            false,
+                        )
+                    });
+                return Some(const_.cast());
             }
 
             None
@@ -247,6 +277,9 @@ pub unsafe fn inject_placeholders(
         attname_lookup: &'a HashMap<(i32, pg_sys::AttrNumber), String>,
         snippet_infos: &'a HashMap<SnippetInfo, Option<(tantivy::schema::Field, SnippetGenerator)>>,
         const_snippet_nodes: HashMap<SnippetInfo, *mut pg_sys::Const>,
+
+        stored_funcoid: pg_sys::Oid,
+        const_stored_nodes: HashMap<String, *mut pg_sys::Const>,
     }
 
     let mut data = Data {
@@ -267,11 +300,15 @@ pub unsafe fn inject_placeholders(
         attname_lookup,
         snippet_infos,
         const_snippet_nodes: Default::default(),
+
+        stored_funcoid,
+        const_stored_nodes: Default::default(),
     };
     let targetlist = walker(targetlist.cast(), addr_of_mut!(data).cast());
     (
         targetlist.cast(),
         data.const_score_node,
         data.const_snippet_nodes,
+        data.const_stored_nodes,
     )
 }
@@ -0,0 +1,113 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::nodecast;
+use pgrx::pg_sys::expression_tree_walker;
+use pgrx::{
+    direct_function_call, ereport, extension_sql, pg_extern, pg_guard, pg_sys, AnyElement,
+    IntoDatum, JsonB, PgList, PgLogLevel, PgSqlErrorCode,
+};
+use std::collections::HashMap;
+use std::ptr::addr_of_mut;
+
+#[pg_extern(name = "stored", stable, parallel_safe)]
+fn stored_from_relation(field: AnyElement) -> Option<JsonB> {
+    None
+}
+
+extension_sql!(
+    r#"
+ALTER FUNCTION stored SUPPORT placeholder_support;
+"#,
+    name = "stored_placeholder",
+    requires = [stored_from_relation, placeholder_support]
+);
+
+pub fn stored_funcoid() -> pg_sys::Oid {
+    unsafe {
+        direct_function_call::<pg_sys::Oid>(
+            pg_sys::regprocedurein,
+            &[c"paradedb.stored(anyelement)".into_datum()],
+        )
+        .expect("the `paradedb.stored(anyelement)` function should exist")
+    }
+}
+
+/// Find the names of the fields passed to `paradedb.stored()` calls at our `rti` level
+pub unsafe fn uses_stored(
+    rti: pg_sys::Index,
+    attname_lookup: &HashMap<(i32, pg_sys::AttrNumber), String>,
+    node: *mut pg_sys::Node,
+    stored_funcoid: pg_sys::Oid,
+) -> Vec<String> {
+    struct Context<'a> {
+        rti: pg_sys::Index,
+        attname_lookup: &'a HashMap<(i32, pg_sys::AttrNumber), String>,
+        stored_funcoid: pg_sys::Oid,
+        fields: Vec<String>,
+    }
+
+    #[pg_guard]
+    unsafe extern "C" fn walker(node: *mut pg_sys::Node, data: *mut core::ffi::c_void) -> bool {
+        if node.is_null() {
+            return false;
+        }
+
+        if let Some(funcexpr) = nodecast!(FuncExpr, T_FuncExpr, node) {
+            let context = data.cast::<Context>();
+
+            if (*funcexpr).funcid == (*context).stored_funcoid {
+                let args = PgList::<pg_sys::Node>::from_pg((*funcexpr).args);
+
+                // this should be equal to the number of args in the `stored()` function above
+                assert!(args.len() == 1);
+
+                if let Some(field_arg) = nodecast!(Var, T_Var, args.get_ptr(0).unwrap()) {
+                    // a column of some other relation in the query isn't ours to read
+                    if (*field_arg).varno as i32 == (*context).rti as i32 {
+                        if let Some(attname) = (*context)
+                            .attname_lookup
+                            .get(&((*context).rti as _, (*field_arg).varattno as _))
+                        {
+                            if !(*context).fields.contains(attname) {
+                                (*context).fields.push(attname.clone());
+                            }
+                        }
+                    }
+                } else {
+                    ereport!(
+                        PgLogLevel::ERROR,
+                        PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+                        "`paradedb.stored()`'s argument must be a column"
+                    );
+                }
+            }
+        }
+
+        expression_tree_walker(node, Some(walker), data)
+    }
+
+    let mut context = Context {
+        rti,
+        attname_lookup,
+        stored_funcoid,
+        fields: vec![],
+    };
+
+    walker(node, addr_of_mut!(context).cast());
+    context.fields
+}
@@ -15,13 +15,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::api::operator::{attname_from_var, is_composite_key_row};
 use crate::nodecast;
 use crate::postgres::customscan::pdbscan::privdat::deserialize::decodeString;
 use crate::postgres::customscan::pdbscan::privdat::serialize::{
     makeInteger, makeString, AsValueNode,
 };
-use crate::query::SearchQueryInput;
-use pgrx::{node_to_string, pg_sys, FromDatum, PgList};
+use crate::query::{SearchQueryInput, TermInput};
+use crate::schema::{SearchFieldConfig, SearchFieldName, SearchFieldType, SearchIndexSchema};
+use pgrx::pg_sys::expression_tree_walker;
+use pgrx::{
+    node_to_string, pg_guard, pg_sys, FromDatum, PgBuiltInOids, PgList, PgMemoryContexts, PgOid,
+};
+use std::ops::Bound;
+use tantivy::schema::OwnedValue;
+use tokenizers::SearchNormalizer;
 
 #[derive(Debug, Clone)]
 pub enum Qual {
@@ -31,11 +39,88 @@ pub enum Qual {
         opno: pg_sys::Oid,
         val: *mut pg_sys::Const,
     },
+    /// The rhs of an `@@@` operator that isn't known until the scan starts, such as a prepared
+    /// statement's `$1` parameter.  It's evaluated with `expr_state`, once that has been set up
+    /// by [`Qual::init_expressions`]
+    Expression {
+        node: *mut pg_sys::Node,
+        expr_state: *mut pg_sys::ExprState,
+    },
+    /// An ordinary predicate on an indexed field, such as `price < 100`, that has already been
+    /// translated into the equivalent query
+    PushdownExpr(SearchQueryInput),
+    /// An ordinary predicate on an indexed field whose value isn't known until the scan starts,
+    /// such as `price < $1`.  It's evaluated the same way as a [`Qual::Expression`], and then
+    /// translated into a query
+    PushdownExpression {
+        field: String,
+        vartype: pg_sys::Oid,
+        /// The btree strategy of the comparison, or [`pg_sys::BTEqualStrategyNumber`] for
+        /// `field = ANY($1)`
+        strategy: u32,
+        is_array: bool,
+        node: *mut pg_sys::Node,
+        expr_state: *mut pg_sys::ExprState,
+    },
     And(Vec<Qual>),
     Or(Vec<Qual>),
     Not(Box<Qual>),
 }
 
+impl Qual {
+    /// Is this made up of nothing but pushed down predicates?  Such quals don't involve the `@@@`
+    /// operator at all, so they aren't a reason to choose our custom scan
+    pub fn is_pushdown_only(&self) -> bool {
+        match self {
+            Qual::Ignore | Qual::OperatorExpression { .. } | Qual::Expression { .. } => false,
+            Qual::PushdownExpr(_) | Qual::PushdownExpression { .. } => true,
+            Qual::And(quals) | Qual::Or(quals) => quals.iter().all(Qual::is_pushdown_only),
+            Qual::Not(qual) => qual.is_pushdown_only(),
+        }
+    }
+
+    fn contains_pushdown(&self) -> bool {
+        match self {
+            Qual::Ignore | Qual::OperatorExpression { .. } | Qual::Expression { .. } => false,
+            Qual::PushdownExpr(_) | Qual::PushdownExpression { .. } => true,
+            Qual::And(quals) | Qual::Or(quals) => quals.iter().any(Qual::contains_pushdown),
+            Qual::Not(qual) => qual.contains_pushdown(),
+        }
+    }
+
+    /// Does this have a value that can only be evaluated at execution time?
+    pub fn contains_expressions(&self) -> bool {
+        match self {
+            Qual::Ignore | Qual::OperatorExpression { .. } | Qual::PushdownExpr(_) => false,
+            Qual::Expression { .. } | Qual::PushdownExpression { .. } => true,
+            Qual::And(quals) | Qual::Or(quals) => quals.iter().any(Qual::contains_expressions),
+            Qual::Not(qual) => qual.contains_expressions(),
+        }
+    }
+
+    /// Prepare every [`Qual::Expression`] and [`Qual::PushdownExpression`] for evaluation as
+    /// part of `planstate`
+    pub unsafe fn init_expressions(&mut self, planstate: *mut pg_sys::PlanState) {
+        match self {
+            Qual::Ignore | Qual::OperatorExpression { .. } | Qual::PushdownExpr(_) => {}
+            Qual::Expression { node, expr_state }
+            | Qual::PushdownExpression {
+                node, expr_state, ..
+            } => {
+                // the planner doesn't fill these in for the nodes we keep in our private data
+                pg_sys::fix_opfuncids(*node);
+                *expr_state = pg_sys::ExecInitExpr(node.cast(), planstate);
+            }
+            Qual::And(quals) | Qual::Or(quals) => {
+                for qual in quals {
+                    qual.init_expressions(planstate);
+                }
+            }
+            Qual::Not(qual) => qual.init_expressions(planstate),
+        }
+    }
+}
+
 impl From<&Qual> for SearchQueryInput {
     fn from(value: &Qual) -> Self {
         match value {
@@ -44,6 +129,48 @@ impl From<&Qual> for SearchQueryInput {
                 SearchQueryInput::from_datum((**val).constvalue, (**val).constisnull)
                     .expect("rhs of @@@ operator Qual must not be null")
             },
+            Qual::Expression { expr_state, .. } => unsafe {
+                let (datum, is_null) = evaluate(*expr_state);
+                SearchQueryInput::from_datum(datum, is_null)
+                    .expect("rhs of @@@ operator Qual must not be null")
+            },
+            Qual::PushdownExpr(query) => query.clone(),
+            Qual::PushdownExpression {
+                field,
+                vartype,
+                strategy,
+                is_array,
+                node,
+                expr_state,
+            } => unsafe {
+                let (datum, is_null) = evaluate(*expr_state);
+                if is_null {
+                    // nothing compares equal to NULL
+                    return SearchQueryInput::Empty;
+                }
+
+                // `comparison()` only plans these for types whose every value can be pushed down
+                let valtype = pg_sys::exprType(*node);
+                if *is_array {
+                    let elemtype = pg_sys::get_element_type(valtype);
+                    let values = Vec::<Option<pg_sys::Datum>>::from_datum(datum, false)
+                        .expect("array should not be null")
+                        .into_iter()
+                        .flatten()
+                        .map(|datum| {
+                            pushdown_value(*vartype, elemtype, datum)
+                                .expect("array element should be a pushdown value")
+                        })
+                        .collect();
+                    term_set(field, values)
+                } else {
+                    let value = pushdown_value(*vartype, valtype, datum)
+                        .expect("parameter should be a pushdown value");
+                    let type_ = field_type_of(*vartype).expect("field should have a pushdown type");
+                    comparison_query(field, type_, false, *strategy, value)
+                        .expect("comparison should be pushed down")
+                }
+            },
 
             Qual::And(quals) => {
                 let must = quals.iter().map(SearchQueryInput::from).collect::<Vec<_>>();
@@ -97,6 +224,32 @@ impl From<Qual> for PgList<pg_sys::Node> {
                     list.push(makeInteger(Some(opno)));
                     list.push(val.cast());
                 }
+                Qual::Expression { node, .. } => {
+                    list.push(makeString(Some("EXPRESSION")));
+                    list.push(node);
+                }
+                Qual::PushdownExpression {
+                    field,
+                    vartype,
+                    strategy,
+                    is_array,
+                    node,
+                    ..
+                } => {
+                    list.push(makeString(Some("PUSHDOWN_EXPRESSION")));
+                    list.push(makeString(Some(field)));
+                    list.push(makeInteger(Some(vartype)));
+                    list.push(makeInteger(Some(strategy)));
+                    list.push(makeInteger(Some(is_array as u32)));
+                    list.push(node);
+                }
+                Qual::PushdownExpr(query) => {
+                    list.push(makeString(Some("PUSHDOWN_EXPR")));
+                    list.push(makeString(Some(
+                        serde_json::to_string(&query)
+                            .expect("pushdown query should serialize to json"),
+                    )));
+                }
                 Qual::And(quals) => {
                     list.push(makeString(Some("AND")));
                     list.push(makeInteger(Some(quals.len())));
@@ -142,6 +295,25 @@ impl From<PgList<pg_sys::Node>> for Qual {
                             );
                             Some(Qual::OperatorExpression { var, opno, val })
                         }
+                        "EXPRESSION" => Some(Qual::Expression {
+                            node: value.get_ptr(1)?,
+                            expr_state: std::ptr::null_mut(),
+                        }),
+                        "PUSHDOWN_EXPRESSION" => Some(Qual::PushdownExpression {
+                            field: decodeString::<String>(value.get_ptr(1)?)?,
+                            vartype: pg_sys::Oid::from_value_node(value.get_ptr(2)?)?,
+                            strategy: u32::from_value_node(value.get_ptr(3)?)?,
+                            is_array: u32::from_value_node(value.get_ptr(4)?)? != 0,
+                            node: value.get_ptr(5)?,
+                            expr_state: std::ptr::null_mut(),
+                        }),
+                        "PUSHDOWN_EXPR" => {
+                            let json = decodeString::<String>(value.get_ptr(1)?)?;
+                            Some(Qual::PushdownExpr(
+                                serde_json::from_str(&json)
+                                    .expect("pushdown query should deserialize from json"),
+                            ))
+                        }
                         "AND" => {
                             let len = usize::from_value_node(value.get_ptr(1)?)?;
                             let mut quals = Vec::with_capacity(len);
@@ -188,14 +360,33 @@ impl From<PgList<pg_sys::Node>> for Qual {
     }
 }
 
+/// Evaluate an expression prepared by [`Qual::init_expressions`]
+unsafe fn evaluate(expr_state: *mut pg_sys::ExprState) -> (pg_sys::Datum, bool) {
+    assert!(
+        !expr_state.is_null(),
+        "Qual expressions must be initialized before they're evaluated"
+    );
+    let econtext = (*(*expr_state).parent).ps_ExprContext;
+    let mut is_null = false;
+    let datum = PgMemoryContexts::For((*econtext).ecxt_per_tuple_memory).switch_to(|_| {
+        let evalfunc = (*expr_state)
+            .evalfunc
+            .expect("ExprState should have an evalfunc");
+        evalfunc(expr_state, econtext, &mut is_null)
+    });
+    (datum, is_null)
+}
+
 pub unsafe fn extract_quals(
+    root: *mut pg_sys::PlannerInfo,
     rti: pg_sys::Index,
     node: *mut pg_sys::Node,
     pdbopoid: pg_sys::Oid,
+    schema: &SearchIndexSchema,
 ) -> Option<Qual> {
     match (*node).type_ {
         pg_sys::NodeTag::T_List => {
-            let mut quals = list(rti, node.cast(), pdbopoid)?;
+            let mut quals = list(root, rti, node.cast(), pdbopoid, schema)?;
             if quals.len() == 1 {
                 quals.pop()
             } else {
@@ -213,20 +404,46 @@ pub unsafe fn extract_quals(
             } else {
                 (*ri).clause
             };
-            extract_quals(rti, clause.cast(), pdbopoid)
+            extract_quals(root, rti, clause.cast(), pdbopoid, schema)
         }
 
-        pg_sys::NodeTag::T_OpExpr => opexpr(rti, node, pdbopoid),
+        pg_sys::NodeTag::T_OpExpr => opexpr(root, rti, node, pdbopoid, schema),
+
+        pg_sys::NodeTag::T_ScalarArrayOpExpr => scalar_array_opexpr(root, rti, node, schema),
+
+        pg_sys::NodeTag::T_NullTest => nulltest(root, rti, node, schema),
+
+        // a boolean column used directly as a predicate, as in `WHERE in_stock`
+        pg_sys::NodeTag::T_Var => {
+            let var = nodecast!(Var, T_Var, node)?;
+            bool_var(root, rti, var, schema, true)
+        }
 
         pg_sys::NodeTag::T_BoolExpr => {
             let boolexpr = nodecast!(BoolExpr, T_BoolExpr, node)?;
             let args = PgList::<pg_sys::Node>::from_pg((*boolexpr).args);
-            let mut quals = list(rti, (*boolexpr).args, pdbopoid)?;
+
+            // `WHERE NOT in_stock` doesn't match NULLs, so it's the same as `in_stock = false`
+            if (*boolexpr).boolop == pg_sys::BoolExprType::NOT_EXPR && args.len() == 1 {
+                if let Some(var) = nodecast!(Var, T_Var, args.get_ptr(0)?) {
+                    return bool_var(root, rti, var, schema, false);
+                }
+            }
+
+            let mut quals = list(root, rti, (*boolexpr).args, pdbopoid, schema)?;
 
             match (*boolexpr).boolop {
                 pg_sys::BoolExprType::AND_EXPR => Some(Qual::And(quals)),
                 pg_sys::BoolExprType::OR_EXPR => Some(Qual::Or(quals)),
-                pg_sys::BoolExprType::NOT_EXPR => Some(Qual::Not(Box::new(quals.pop()?))),
+                pg_sys::BoolExprType::NOT_EXPR => {
+                    let qual = quals.pop()?;
+                    if qual.contains_pushdown() {
+                        // a negated comparison would also need to match the rows where the
+                        // column is NULL, which the index can't do exactly
+                        return None;
+                    }
+                    Some(Qual::Not(Box::new(qual)))
+                }
                 _ => panic!("unexpected `BoolExprType`: {}", (*boolexpr).boolop),
             }
         }
@@ -240,50 +457,585 @@ pub unsafe fn extract_quals(
 }
 
 unsafe fn list(
+    root: *mut pg_sys::PlannerInfo,
     rti: pg_sys::Index,
     list: *mut pg_sys::List,
     pdbopoid: pg_sys::Oid,
+    schema: &SearchIndexSchema,
 ) -> Option<Vec<Qual>> {
     let args = PgList::<pg_sys::Node>::from_pg(list);
     let mut quals = Vec::new();
     for child in args.iter_ptr() {
-        quals.push(extract_quals(rti, child, pdbopoid)?)
+        quals.push(extract_quals(root, rti, child, pdbopoid, schema)?)
     }
     Some(quals)
 }
 
 unsafe fn opexpr(
+    root: *mut pg_sys::PlannerInfo,
     rti: pg_sys::Index,
     node: *mut pg_sys::Node,
     pdbopoid: pg_sys::Oid,
+    schema: &SearchIndexSchema,
 ) -> Option<Qual> {
     let opexpr = nodecast!(OpExpr, T_OpExpr, node)?;
+    if (*opexpr).opno != pdbopoid {
+        return comparison(root, rti, opexpr, schema);
+    }
+
     let args = PgList::<pg_sys::Node>::from_pg((*opexpr).args);
-    let (lhs, rhs) = (
-        nodecast!(Var, T_Var, args.get_ptr(0)?),
-        nodecast!(Const, T_Const, args.get_ptr(1)?),
-    );
+    if is_composite_key_row(args.get_ptr(0)?) {
+        // this only repeats the search of the `@@@` clause it was paired with, which is where
+        // we get the query from
+        return Some(Qual::Ignore);
+    }
+    let (lhs, rhs) = (nodecast!(Var, T_Var, args.get_ptr(0)?), args.get_ptr(1)?);
 
-    if lhs.is_none() || rhs.is_none() {
-        pgrx::debug1!(
-            "unrecognized `OpExpr`: {}",
-            node_to_string(opexpr.cast()).expect("node_to_string should not return null")
-        );
+    let lhs = match lhs {
+        Some(lhs) if nodecast!(Const, T_Const, rhs).is_some() || is_runtime_expression(rhs) => lhs,
+        _ => {
+            pgrx::debug1!(
+                "unrecognized `OpExpr`: {}",
+                node_to_string(opexpr.cast()).expect("node_to_string should not return null")
+            );
+            return None;
+        }
+    };
+
+    if (*lhs).varno as i32 != rti as i32 {
+        Some(Qual::Ignore)
+    } else if let Some(rhs) = nodecast!(Const, T_Const, rhs) {
+        Some(Qual::OperatorExpression {
+            var: lhs,
+            opno: (*opexpr).opno,
+            val: rhs,
+        })
+    } else {
+        Some(Qual::Expression {
+            node: rhs,
+            expr_state: std::ptr::null_mut(),
+        })
+    }
+}
+
+/// Can `node` be evaluated once, when the scan starts?  That's true of prepared statement
+/// parameters and of non-volatile expressions over them, like `paradedb.term('field', $1)`, but
+/// not of anything that references a column or a subquery.
+unsafe fn is_runtime_expression(node: *mut pg_sys::Node) -> bool {
+    #[pg_guard]
+    unsafe extern "C" fn walker(node: *mut pg_sys::Node, context: *mut core::ffi::c_void) -> bool {
+        if node.is_null() {
+            return false;
+        }
+
+        match (*node).type_ {
+            pg_sys::NodeTag::T_Var
+            | pg_sys::NodeTag::T_PlaceHolderVar
+            | pg_sys::NodeTag::T_Aggref
+            | pg_sys::NodeTag::T_WindowFunc
+            | pg_sys::NodeTag::T_SubLink
+            | pg_sys::NodeTag::T_SubPlan
+            | pg_sys::NodeTag::T_AlternativeSubPlan => return true,
+            pg_sys::NodeTag::T_Param => {
+                let param = node.cast::<pg_sys::Param>();
+                if (*param).paramkind != pg_sys::ParamKind::PARAM_EXTERN {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+
+        expression_tree_walker(node, Some(walker), context)
+    }
+
+    !pg_sys::contain_volatile_functions(node) && !walker(node, std::ptr::null_mut())
+}
+
+/// Translate `field <op> const` (or `const <op> field`), where `<op>` is one of the btree
+/// comparison operators or `<>`, into a query.  Integer and boolean fields can also be compared
+/// with a value that's only known when the scan starts, like `$1`.
+unsafe fn comparison(
+    root: *mut pg_sys::PlannerInfo,
+    rti: pg_sys::Index,
+    opexpr: *mut pg_sys::OpExpr,
+    schema: &SearchIndexSchema,
+) -> Option<Qual> {
+    let args = PgList::<pg_sys::Node>::from_pg((*opexpr).args);
+    if args.len() != 2 {
         return None;
     }
-    let (lhs, rhs) = (lhs?, rhs?);
+    let (lhs, rhs) = (args.get_ptr(0)?, args.get_ptr(1)?);
 
-    if (*opexpr).opno == pdbopoid {
-        if (*lhs).varno as i32 != rti as i32 {
-            Some(Qual::Ignore)
-        } else {
-            Some(Qual::OperatorExpression {
-                var: lhs,
-                opno: (*opexpr).opno,
-                val: rhs,
-            })
+    let (var, val, opno) = if let Some(var) = column(lhs) {
+        (var, rhs, (*opexpr).opno)
+    } else if let Some(var) = column(rhs) {
+        // `100 > price` is the same as `price < 100`
+        let commutator = pg_sys::get_commutator((*opexpr).opno);
+        if commutator == pg_sys::Oid::INVALID {
+            return None;
         }
+        (var, lhs, commutator)
+    } else {
+        return None;
+    };
+
+    let field = pushdown_field(root, rti, var, schema)?;
+    if !field.indexed || !is_deterministic((*opexpr).inputcollid) {
+        return None;
+    }
+    let strategy = btree_strategy(opno)?;
+
+    if let Some(val) = nodecast!(Const, T_Const, val) {
+        if (*val).constisnull {
+            return None;
+        }
+        let value = pushdown_value((*var).vartype, (*val).consttype, (*val).constvalue)?;
+        if !field.can_match(&value) {
+            return None;
+        }
+        let query = comparison_query(&field.name, field.type_, field.fast, strategy, value)?;
+        Some(Qual::PushdownExpr(query))
+    } else if is_runtime_expression(val)
+        && field_type_of(pg_sys::exprType(val)) == Some(field.type_)
+    {
+        // make sure the comparison can be pushed down, whatever the value turns out to be
+        comparison_query(
+            &field.name,
+            field.type_,
+            field.fast,
+            strategy,
+            any_runtime_value(field.type_)?,
+        )?;
+        Some(Qual::PushdownExpression {
+            field: field.name,
+            vartype: (*var).vartype,
+            strategy,
+            is_array: false,
+            node: val,
+            expr_state: std::ptr::null_mut(),
+        })
     } else {
         None
     }
 }
+
+/// The query for `field <strategy> value`, if there is one
+fn comparison_query(
+    field: &str,
+    type_: SearchFieldType,
+    fast: bool,
+    strategy: u32,
+    value: OwnedValue,
+) -> Option<SearchQueryInput> {
+    let query = if type_ == SearchFieldType::Bool {
+        // comparisons other than equality don't mean much for a boolean
+        match strategy {
+            pg_sys::BTEqualStrategyNumber => term(field, value),
+            pg_sys::RowCompareType::ROWCOMPARE_NE => match value {
+                OwnedValue::Bool(value) => term(field, OwnedValue::Bool(!value)),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    } else if type_ == SearchFieldType::Text {
+        // text sorts by collation, which the index doesn't know about, so only equality can be
+        // pushed down
+        match strategy {
+            pg_sys::BTEqualStrategyNumber => term(field, value),
+            // the column not being NULL has to come from the fast field
+            pg_sys::RowCompareType::ROWCOMPARE_NE if fast => SearchQueryInput::Boolean {
+                must: vec![SearchQueryInput::Exists {
+                    field: field.to_string(),
+                }],
+                should: Default::default(),
+                must_not: vec![term(field, value)],
+            },
+            _ => return None,
+        }
+    } else {
+        match strategy {
+            pg_sys::BTLessStrategyNumber => range(field, Bound::Unbounded, Bound::Excluded(value)),
+            pg_sys::BTLessEqualStrategyNumber => {
+                range(field, Bound::Unbounded, Bound::Included(value))
+            }
+            pg_sys::BTEqualStrategyNumber => term(field, value),
+            pg_sys::BTGreaterEqualStrategyNumber => {
+                range(field, Bound::Included(value), Bound::Unbounded)
+            }
+            pg_sys::BTGreaterStrategyNumber => {
+                range(field, Bound::Excluded(value), Bound::Unbounded)
+            }
+            pg_sys::RowCompareType::ROWCOMPARE_NE => SearchQueryInput::Boolean {
+                must: Default::default(),
+                // spelled as two ranges, rather than as not-equal, so NULLs don't match
+                should: vec![
+                    range(field, Bound::Unbounded, Bound::Excluded(value.clone())),
+                    range(field, Bound::Excluded(value), Bound::Unbounded),
+                ],
+                must_not: Default::default(),
+            },
+            _ => return None,
+        }
+    };
+
+    Some(query)
+}
+
+/// A stand-in for a value of `type_` that isn't known until the scan starts.  Only types where
+/// the index can compare every value exactly are allowed.  A float could be NaN, text could be
+/// too long to be indexed, and a timestamp could have fractional seconds, so those aren't.
+fn any_runtime_value(type_: SearchFieldType) -> Option<OwnedValue> {
+    match type_ {
+        SearchFieldType::I64 => Some(OwnedValue::I64(0)),
+        SearchFieldType::Bool => Some(OwnedValue::Bool(false)),
+        _ => None,
+    }
+}
+
+/// Translate `field = ANY(const array)`, which is also how Postgres represents `field IN (...)`,
+/// into a query.  Like [`comparison`], integer and boolean fields can also be compared with an
+/// array that's only known when the scan starts.
+unsafe fn scalar_array_opexpr(
+    root: *mut pg_sys::PlannerInfo,
+    rti: pg_sys::Index,
+    node: *mut pg_sys::Node,
+    schema: &SearchIndexSchema,
+) -> Option<Qual> {
+    let saop = nodecast!(ScalarArrayOpExpr, T_ScalarArrayOpExpr, node)?;
+    if !(*saop).useOr || btree_strategy((*saop).opno)? != pg_sys::BTEqualStrategyNumber {
+        return None;
+    }
+
+    let args = PgList::<pg_sys::Node>::from_pg((*saop).args);
+    let (var, val) = (column(args.get_ptr(0)?)?, args.get_ptr(1)?);
+    let field = pushdown_field(root, rti, var, schema)?;
+    if !field.indexed || !is_deterministic((*saop).inputcollid) {
+        return None;
+    }
+
+    let Some(val) = nodecast!(Const, T_Const, val) else {
+        let elemtype = pg_sys::get_element_type(pg_sys::exprType(val));
+        if !is_runtime_expression(val)
+            || field_type_of(elemtype) != Some(field.type_)
+            || any_runtime_value(field.type_).is_none()
+        {
+            return None;
+        }
+        return Some(Qual::PushdownExpression {
+            field: field.name,
+            vartype: (*var).vartype,
+            strategy: pg_sys::BTEqualStrategyNumber,
+            is_array: true,
+            node: val,
+            expr_state: std::ptr::null_mut(),
+        });
+    };
+    if (*val).constisnull {
+        return None;
+    }
+
+    let elemtype = pg_sys::get_element_type((*val).consttype);
+    // NULL elements can never be equal to anything, so they're skipped
+    let values = Vec::<Option<pg_sys::Datum>>::from_datum((*val).constvalue, false)?
+        .into_iter()
+        .flatten()
+        .map(|datum| pushdown_value((*var).vartype, elemtype, datum))
+        .collect::<Option<Vec<_>>>()?;
+    if !values.iter().all(|value| field.can_match(value)) {
+        return None;
+    }
+
+    Some(Qual::PushdownExpr(term_set(&field.name, values)))
+}
+
+/// Translate `field IS NULL` and `field IS NOT NULL` into a query.  These need the field to be
+/// fast.
+unsafe fn nulltest(
+    root: *mut pg_sys::PlannerInfo,
+    rti: pg_sys::Index,
+    node: *mut pg_sys::Node,
+    schema: &SearchIndexSchema,
+) -> Option<Qual> {
+    let nulltest = nodecast!(NullTest, T_NullTest, node)?;
+    if (*nulltest).argisrow {
+        return None;
+    }
+    let var = nodecast!(Var, T_Var, (*nulltest).arg.cast())?;
+    let field = pushdown_field(root, rti, var, schema)?;
+    if !field.fast {
+        return None;
+    }
+
+    let exists = SearchQueryInput::Exists { field: field.name };
+    let query = match (*nulltest).nulltesttype {
+        pg_sys::NullTestType::IS_NOT_NULL => exists,
+        pg_sys::NullTestType::IS_NULL => SearchQueryInput::Boolean {
+            must: vec![SearchQueryInput::All],
+            should: Default::default(),
+            must_not: vec![exists],
+        },
+        _ => return None,
+    };
+
+    Some(Qual::PushdownExpr(query))
+}
+
+unsafe fn bool_var(
+    root: *mut pg_sys::PlannerInfo,
+    rti: pg_sys::Index,
+    var: *mut pg_sys::Var,
+    schema: &SearchIndexSchema,
+    value: bool,
+) -> Option<Qual> {
+    let field = pushdown_field(root, rti, var, schema)?;
+    if field.type_ != SearchFieldType::Bool || !field.indexed {
+        return None;
+    }
+    Some(Qual::PushdownExpr(term(
+        &field.name,
+        OwnedValue::Bool(value),
+    )))
+}
+
+struct PushdownField {
+    name: String,
+    type_: SearchFieldType,
+    indexed: bool,
+    fast: bool,
+    /// The longest text value the index keeps as a term, for a text field
+    max_term_len: Option<usize>,
+}
+
+impl PushdownField {
+    /// Would a document with `value` in this field be found by searching for it?  Text values
+    /// that are too long aren't indexed at all.
+    fn can_match(&self, value: &OwnedValue) -> bool {
+        match (value, self.max_term_len) {
+            (OwnedValue::Str(value), Some(max_term_len)) => value.len() <= max_term_len,
+            (OwnedValue::Str(_), None) => false,
+            _ => true,
+        }
+    }
+}
+
+/// Can predicates on `var` be answered exactly by the index?  That's the case for columns that
+/// are indexed as themselves and are of type:
+///
+/// - integer, floating point or boolean
+/// - `text` or `varchar`, when the `raw` tokenizer has `lowercase` turned off.  Only equality is
+///   pushed down, and `<>` also needs a fast field with the `raw` normalizer
+/// - `date`, `timestamp(0)` or `timestamptz(0)`, as the index keeps dates to the second
+///
+/// `NUMERIC`, enums, timestamps with fractional seconds and every other kind of text field aren't
+/// pushed down, as the index doesn't keep their values exactly as Postgres compares them.
+unsafe fn pushdown_field(
+    root: *mut pg_sys::PlannerInfo,
+    rti: pg_sys::Index,
+    var: *mut pg_sys::Var,
+    schema: &SearchIndexSchema,
+) -> Option<PushdownField> {
+    if (*var).varno as i32 != rti as i32 {
+        return None;
+    }
+    let type_ = field_type_of((*var).vartype)?;
+    if type_ == SearchFieldType::Date
+        && PgOid::from((*var).vartype) != PgOid::BuiltIn(PgBuiltInOids::DATEOID)
+        && (*var).vartypmod != 0
+    {
+        // the index would truncate the fractional seconds this column can hold
+        return None;
+    }
+    let (_, attname) = attname_from_var(root, var);
+    let attname = attname?;
+    let search_field = schema.get_search_field(&SearchFieldName(attname.clone()))?;
+    if search_field.type_ != type_
+        || search_field
+            .config
+            .column()
+            .is_some_and(|column| *column != attname)
+    {
+        return None;
+    }
+
+    let (indexed, fast, max_term_len) = match &search_field.config {
+        SearchFieldConfig::Numeric { indexed, fast, .. }
+        | SearchFieldConfig::Boolean { indexed, fast, .. }
+        | SearchFieldConfig::Date { indexed, fast, .. } => (*indexed, *fast, None),
+        SearchFieldConfig::Text {
+            indexed,
+            fast,
+            tokenizer,
+            normalizer,
+            ..
+        } => {
+            let max_term_len = tokenizer.max_unchanged_token_len();
+            (
+                *indexed && max_term_len.is_some(),
+                *fast && *normalizer == SearchNormalizer::Raw,
+                max_term_len,
+            )
+        }
+        _ => return None,
+    };
+
+    Some(PushdownField {
+        name: attname,
+        type_,
+        indexed,
+        fast,
+        max_term_len,
+    })
+}
+
+fn field_type_of(typoid: pg_sys::Oid) -> Option<SearchFieldType> {
+    match PgOid::from(typoid) {
+        PgOid::BuiltIn(
+            PgBuiltInOids::INT2OID | PgBuiltInOids::INT4OID | PgBuiltInOids::INT8OID,
+        ) => Some(SearchFieldType::I64),
+        PgOid::BuiltIn(PgBuiltInOids::FLOAT4OID | PgBuiltInOids::FLOAT8OID) => {
+            Some(SearchFieldType::F64)
+        }
+        PgOid::BuiltIn(PgBuiltInOids::BOOLOID) => Some(SearchFieldType::Bool),
+        PgOid::BuiltIn(PgBuiltInOids::TEXTOID | PgBuiltInOids::VARCHAROID) => {
+            Some(SearchFieldType::Text)
+        }
+        PgOid::BuiltIn(
+            PgBuiltInOids::DATEOID | PgBuiltInOids::TIMESTAMPOID | PgBuiltInOids::TIMESTAMPTZOID,
+        ) => Some(SearchFieldType::Date),
+        // `NUMERIC` is indexed as an `f64`, and distinct values that round to the same `f64`
+        // compare equal in the index, so `price < 100` would miss `99.99999999999999999`
+        PgOid::BuiltIn(PgBuiltInOids::NUMERICOID) => None,
+        _ => None,
+    }
+}
+
+/// Convert a constant compared against a column of type `vartype` into the value to search for
+unsafe fn pushdown_value(
+    vartype: pg_sys::Oid,
+    consttype: pg_sys::Oid,
+    datum: pg_sys::Datum,
+) -> Option<OwnedValue> {
+    let type_ = field_type_of(vartype)?;
+    if type_ != field_type_of(consttype)? {
+        return None;
+    }
+    if type_ == SearchFieldType::Date && vartype != consttype {
+        // comparing a `date` with a `timestamptz`, say, depends on the session's time zone
+        return None;
+    }
+
+    match PgOid::from(consttype) {
+        PgOid::BuiltIn(PgBuiltInOids::INT2OID) => {
+            i16::from_datum(datum, false).map(|v| OwnedValue::I64(v as i64))
+        }
+        PgOid::BuiltIn(PgBuiltInOids::INT4OID) => {
+            i32::from_datum(datum, false).map(|v| OwnedValue::I64(v as i64))
+        }
+        PgOid::BuiltIn(PgBuiltInOids::INT8OID) => {
+            i64::from_datum(datum, false).map(OwnedValue::I64)
+        }
+        // Postgres sorts NaN above every other value, which the index doesn't
+        PgOid::BuiltIn(PgBuiltInOids::FLOAT4OID) => f32::from_datum(datum, false)
+            .filter(|v| !v.is_nan())
+            .map(|v| OwnedValue::F64(v as f64)),
+        PgOid::BuiltIn(PgBuiltInOids::FLOAT8OID) => f64::from_datum(datum, false)
+            .filter(|v| !v.is_nan())
+            .map(OwnedValue::F64),
+        PgOid::BuiltIn(PgBuiltInOids::BOOLOID) => {
+            bool::from_datum(datum, false).map(OwnedValue::Bool)
+        }
+        PgOid::BuiltIn(PgBuiltInOids::TEXTOID | PgBuiltInOids::VARCHAROID) => {
+            String::from_datum(datum, false).map(OwnedValue::Str)
+        }
+        PgOid::BuiltIn(PgBuiltInOids::DATEOID) => {
+            datetime_value((datum.value() as i32 as i64).checked_mul(USECS_PER_DAY)?)
+        }
+        PgOid::BuiltIn(PgBuiltInOids::TIMESTAMPOID | PgBuiltInOids::TIMESTAMPTZOID) => {
+            datetime_value(datum.value() as i64)
+        }
+        _ => None,
+    }
+}
+
+const USECS_PER_DAY: i64 = 86_400_000_000;
+const USECS_PER_SEC: i64 = 1_000_000;
+/// Microseconds between the Unix epoch and the Postgres epoch of 2000-01-01
+const POSTGRES_EPOCH_USECS: i64 = 946_684_800 * USECS_PER_SEC;
+
+/// Convert a Postgres timestamp, in microseconds since 2000-01-01, into the value the index
+/// stores.  The index only keeps whole seconds, and counts nanoseconds from 1970-01-01, which
+/// only reaches the years 1677 to 2262, so anything else (including `infinity`) isn't pushed down.
+fn datetime_value(usecs: i64) -> Option<OwnedValue> {
+    if usecs % USECS_PER_SEC != 0 {
+        return None;
+    }
+    let unix_usecs = usecs.checked_add(POSTGRES_EPOCH_USECS)?;
+    unix_usecs.checked_mul(1000)?;
+    Some(OwnedValue::Date(tantivy::DateTime::from_timestamp_micros(
+        unix_usecs,
+    )))
+}
+
+/// A column, possibly behind a binary-compatible cast such as the one that lets `varchar` use the
+/// `text` operators
+unsafe fn column(node: *mut pg_sys::Node) -> Option<*mut pg_sys::Var> {
+    match nodecast!(RelabelType, T_RelabelType, node) {
+        Some(relabel) => nodecast!(Var, T_Var, (*relabel).arg.cast()),
+        None => nodecast!(Var, T_Var, node),
+    }
+}
+
+/// Does the collation `collid` only consider identical strings equal, like the index does?
+unsafe fn is_deterministic(collid: pg_sys::Oid) -> bool {
+    collid == pg_sys::Oid::INVALID || pg_sys::get_collation_isdeterministic(collid)
+}
+
+/// The btree strategy number of a comparison operator, with `<>` reported as
+/// [`pg_sys::RowCompareType::ROWCOMPARE_NE`]
+unsafe fn btree_strategy(opno: pg_sys::Oid) -> Option<u32> {
+    let interpretations =
+        PgList::<pg_sys::OpBtreeInterpretation>::from_pg(pg_sys::get_op_btree_interpretation(opno));
+    interpretations
+        .get_ptr(0)
+        .map(|interpretation| (*interpretation).strategy as u32)
+}
+
+fn term(field: &str, value: OwnedValue) -> SearchQueryInput {
+    SearchQueryInput::Term {
+        field: Some(field.to_string()),
+        is_datetime: matches!(value, OwnedValue::Date(_)),
+        value,
+    }
+}
+
+fn term_set(field: &str, values: Vec<OwnedValue>) -> SearchQueryInput {
+    SearchQueryInput::TermSet {
+        terms: values
+            .into_iter()
+            .map(|value| TermInput {
+                field: field.to_string(),
+                is_datetime: matches!(value, OwnedValue::Date(_)),
+                value,
+            })
+            .collect(),
+    }
+}
+
+fn range(
+    field: &str,
+    lower_bound: Bound<OwnedValue>,
+    upper_bound: Bound<OwnedValue>,
+) -> SearchQueryInput {
+    let is_datetime = [&lower_bound, &upper_bound].into_iter().any(|bound| {
+        matches!(
+            bound,
+            Bound::Included(OwnedValue::Date(_)) | Bound::Excluded(OwnedValue::Date(_))
+        )
+    });
+    SearchQueryInput::Range {
+        field: field.to_string(),
+        lower_bound,
+        upper_bound,
+        is_datetime,
+    }
+}
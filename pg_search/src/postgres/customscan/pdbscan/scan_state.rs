@@ -20,18 +20,22 @@ use crate::index::reader::index::{SearchIndexReader, SearchResults};
 use crate::postgres::customscan::builders::custom_path::SortDirection;
 use crate::postgres::customscan::pdbscan::exec_methods::ExecMethod;
 use crate::postgres::customscan::pdbscan::projections::snippet::SnippetInfo;
+use crate::postgres::customscan::pdbscan::qual_inspect::Qual;
 use crate::postgres::customscan::CustomScanState;
 use crate::postgres::options::SearchIndexCreateOptions;
 use crate::postgres::utils::u64_to_item_pointer;
 use crate::postgres::visibility_checker::VisibilityChecker;
-use crate::postgres::ParallelScanState;
+use crate::postgres::{ParallelScanState, SegmentStats};
 use crate::query::SearchQueryInput;
 use pgrx::heap_tuple::PgHeapTuple;
 use pgrx::{name_data_to_str, pg_sys, PgRelation, PgTupleDesc};
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
+use std::time::Duration;
+use tantivy::index::SegmentId;
 use tantivy::schema::OwnedValue;
 use tantivy::snippet::SnippetGenerator;
+use tantivy::DocAddress;
 
 #[derive(Default)]
 pub struct PdbScanState {
@@ -39,7 +43,9 @@ pub struct PdbScanState {
 
     pub rti: pg_sys::Index,
 
+    pub quals: Option<Qual>,
     pub search_query_input: SearchQueryInput,
+    pub search_after: Option<(Vec<OwnedValue>, Vec<OwnedValue>)>,
     pub search_reader: Option<SearchIndexReader>,
 
     pub search_results: SearchResults,
@@ -49,11 +55,21 @@ pub struct PdbScanState {
     pub limit: Option<usize>,
     pub sort_field: Option<String>,
     pub sort_direction: Option<SortDirection>,
+    pub sort_keys: Vec<(Option<String>, SortDirection)>,
     pub retry_count: usize,
     pub heap_tuple_check_count: usize,
     pub virtual_tuple_count: usize,
     pub invisible_tuple_count: usize,
 
+    // only tracked when the scan is being instrumented by `EXPLAIN ANALYZE`
+    pub instrument: bool,
+    pub instrument_timer: bool,
+    pub segment_matches: HashMap<SegmentId, usize>,
+    pub parallel_segment_stats: Vec<(SegmentId, SegmentStats)>,
+    pub top_n_chunk_size: usize,
+    pub tantivy_time: Duration,
+    pub heap_time: Duration,
+
     pub heaprelid: pg_sys::Oid,
     pub heaprel: Option<pg_sys::Relation>,
     pub indexrel: Option<pg_sys::Relation>,
@@ -76,6 +92,10 @@ pub struct PdbScanState {
         HashMap<SnippetInfo, Option<(tantivy::schema::Field, SnippetGenerator)>>,
     pub var_attname_lookup: HashMap<(i32, pg_sys::AttrNumber), String>,
 
+    pub const_stored_nodes: HashMap<String, *mut pg_sys::Const>,
+    pub stored_funcoid: pg_sys::Oid,
+    pub stored_fields: Vec<String>,
+
     pub placeholder_targetlist: Option<*mut pg_sys::List>,
 
     exec_method: UnsafeCell<Box<dyn ExecMethod>>,
@@ -130,6 +150,30 @@ impl PdbScanState {
         &self.exec_method_name
     }
 
+    /// Count a document matched by the query against the segment it came from.  A parallel scan
+    /// counts it in shared memory right away, before the tuple can reach the leader.
+    pub fn record_segment_match(&mut self, doc_address: DocAddress) {
+        let segment_id = self
+            .search_reader
+            .as_ref()
+            .expect("search_reader should be initialized")
+            .segment_readers()[doc_address.segment_ord as usize]
+            .segment_id();
+        match self.parallel_state {
+            Some(parallel_state) => unsafe {
+                (*parallel_state).add_segment_matches(segment_id, 1);
+            },
+            None => *self.segment_matches.entry(segment_id).or_default() += 1,
+        }
+    }
+
+    /// Set the query we'll execute, pulling out any `paradedb.search_after()` cursor it has so
+    /// the "top N" exec method can honor it
+    pub fn set_search_query_input(&mut self, mut query: SearchQueryInput) {
+        self.search_after = query.take_search_after();
+        self.search_query_input = query;
+    }
+
     #[inline(always)]
     pub fn need_scores(&self) -> bool {
         self.need_scores || self.search_query_input.contains_more_like_this()
@@ -152,6 +196,11 @@ impl PdbScanState {
         !self.snippet_generators.is_empty()
     }
 
+    #[inline(always)]
+    pub fn need_stored(&self) -> bool {
+        !self.stored_fields.is_empty()
+    }
+
     #[track_caller]
     #[inline(always)]
     pub fn heaprel(&self) -> pg_sys::Relation {
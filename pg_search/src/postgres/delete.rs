@@ -16,14 +16,22 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use pgrx::{pg_sys::ItemPointerData, *};
-use tantivy::Term;
+use rustc_hash::FxHashMap;
+use std::sync::Arc;
+use tantivy::index::SegmentId;
+use tantivy::query::{
+    BitSetDocSet, ConstScorer, EmptyScorer, EnableScoring, Explanation, Query, Scorer, Weight,
+};
+use tantivy::{DocId, DocSet, Score, SegmentReader, TantivyError};
+use tantivy_common::BitSet;
 
 use super::storage::block::CLEANUP_LOCK;
-use crate::index::fast_fields_helper::FFType;
 use crate::index::merge_policy::MergeLock;
+use crate::index::pending::flush_pending;
 use crate::index::reader::index::SearchIndexReader;
 use crate::index::writer::index::SearchIndexWriter;
 use crate::index::{BlockDirectoryType, WriterResources};
+use crate::postgres::customscan::pdbscan::is_block_all_visible;
 use crate::postgres::storage::buffer::BufferManager;
 
 #[pg_guard]
@@ -44,37 +52,65 @@ pub extern "C" fn ambulkdelete(
         callback(&mut ctid, callback_state)
     };
 
+    if stats.is_null() {
+        stats = unsafe {
+            PgBox::from_pg(
+                pg_sys::palloc0(std::mem::size_of::<pg_sys::IndexBulkDeleteResult>()).cast(),
+            )
+        };
+    }
+
+    // documents in the pending list can only be deleted once they're in a segment
+    unsafe { flush_pending(&index_relation, true) }
+        .expect("ambulkdelete: should be able to flush the pending list");
+
+    // the doc ids we find below are only meaningful until the next merge, so no merge can
+    // happen until we've committed their deletion
     let merge_lock = unsafe { MergeLock::acquire_for_delete(index_relation.oid()) };
-    let mut writer = SearchIndexWriter::open(
-        &index_relation,
-        BlockDirectoryType::BulkDelete,
-        WriterResources::Vacuum,
-    )
-    .expect("ambulkdelete: should be able to open a SearchIndexWriter");
     let reader = SearchIndexReader::open(&index_relation, BlockDirectoryType::BulkDelete, false)
         .expect("ambulkdelete: should be able to open a SearchIndexReader");
 
-    let ctid_field = writer.get_ctid_field();
-    let mut did_delete = false;
+    let heap_relation = unsafe {
+        let heap_oid = pg_sys::IndexGetRelation(index_relation.oid(), false);
+        pg_sys::RelationIdGetRelation(heap_oid)
+    };
+    let mut vmbuff = pg_sys::InvalidBuffer as pg_sys::Buffer;
+    let mut dead_docs = FxHashMap::default();
 
     for segment_reader in reader.searcher().segment_readers() {
-        let ctid_ff = FFType::new_ctid(segment_reader.fast_fields());
+        check_for_interrupts!();
+        if let Some(dead) = find_dead_docs(segment_reader, heap_relation, &mut vmbuff, &callback) {
+            stats.tuples_removed += dead.len() as f64;
+            dead_docs.insert(segment_reader.segment_id(), dead);
+        }
+    }
 
-        for doc_id in 0..segment_reader.max_doc() {
-            check_for_interrupts!();
-            let ctid = ctid_ff.as_u64(doc_id).expect("ctid should be present");
-            if callback(ctid) {
-                did_delete = true;
-                writer
-                    .delete_term(Term::from_field_u64(ctid_field, ctid))
-                    .expect("ambulkdelete: deleting ctid Term should succeed");
-            }
+    unsafe {
+        if vmbuff != pg_sys::InvalidBuffer as pg_sys::Buffer {
+            pg_sys::ReleaseBuffer(vmbuff);
         }
+        pg_sys::RelationClose(heap_relation);
+    }
+    drop(reader);
+
+    // segments without dead docs are left untouched, and if there are none at all we don't
+    // need to write anything
+    let did_delete = !dead_docs.is_empty();
+    if did_delete {
+        let mut writer = SearchIndexWriter::open(
+            &index_relation,
+            BlockDirectoryType::BulkDelete,
+            WriterResources::Vacuum,
+        )
+        .expect("ambulkdelete: should be able to open a SearchIndexWriter");
+        writer
+            .delete_query(Box::new(DeadDocsQuery(Arc::new(dead_docs))))
+            .expect("ambulkdelete: deleting dead docs should succeed");
+        // Don't merge here, amvacuumcleanup will merge
+        writer
+            .commit()
+            .expect("ambulkdelete: commit should succeed");
     }
-    // Don't merge here, amvacuumcleanup will merge
-    writer
-        .commit()
-        .expect("ambulkdelete: commit should succeed");
 
     // we're done evaluating docs and no longer need to hold the merge_lock.
     //
@@ -82,15 +118,6 @@ pub extern "C" fn ambulkdelete(
     // across backends due to lock inversion issues
     drop(merge_lock);
 
-    if stats.is_null() {
-        stats = unsafe {
-            PgBox::from_pg(
-                pg_sys::palloc0(std::mem::size_of::<pg_sys::IndexBulkDeleteResult>()).cast(),
-            )
-        };
-        stats.pages_deleted = 0;
-    }
-
     // As soon as ambulkdelete returns, Postgres will update the visibility map
     // This can cause concurrent scans that have just read ctids, which are dead but
     // are about to be marked visible, to return wrong results. To guard against this,
@@ -108,3 +135,94 @@ pub extern "C" fn ambulkdelete(
 
     stats.into_pg()
 }
+
+/// Ask the vacuum `callback` about each live doc in the segment, returning the ones it reports
+/// dead, if any.
+///
+/// VACUUM prunes a heap page before it calls us, and a page with dead line pointers is never
+/// all-visible.  So we only need to ask about docs on pages that aren't all-visible, and can skip
+/// the segment entirely if every page in its range of blocks is.  The docs are visited in ctid
+/// order so that each heap page's visibility is only looked up once
+fn find_dead_docs(
+    segment_reader: &SegmentReader,
+    heap_relation: pg_sys::Relation,
+    vmbuff: &mut pg_sys::Buffer,
+    callback: &impl Fn(u64) -> bool,
+) -> Option<BitSet> {
+    let ctids = segment_reader
+        .fast_fields()
+        .u64("ctid")
+        .expect("ctid should be a u64 fast field");
+    let min_blockno = (ctids.min_value() >> 16) as pg_sys::BlockNumber;
+    let max_blockno = (ctids.max_value() >> 16) as pg_sys::BlockNumber;
+    if (min_blockno..=max_blockno)
+        .all(|blockno| is_block_all_visible(heap_relation, vmbuff, blockno))
+    {
+        return None;
+    }
+
+    let mut sorted = segment_reader
+        .doc_ids_alive()
+        .filter_map(|doc_id| ctids.first(doc_id).map(|ctid| (ctid, doc_id)))
+        .collect::<Vec<_>>();
+    sorted.sort_unstable();
+
+    let mut dead = BitSet::with_max_value(segment_reader.max_doc());
+    let mut current_blockno = pg_sys::InvalidBlockNumber;
+    let mut maybe_dead = false;
+    for (ctid, doc_id) in sorted {
+        let blockno = (ctid >> 16) as pg_sys::BlockNumber;
+        if blockno != current_blockno {
+            check_for_interrupts!();
+            current_blockno = blockno;
+            maybe_dead = !is_block_all_visible(heap_relation, vmbuff, blockno);
+        }
+
+        if maybe_dead && callback(ctid) {
+            dead.insert(doc_id);
+        }
+    }
+
+    (dead.len() > 0).then_some(dead)
+}
+
+/// Matches the given doc ids of each segment, so that deletes can be applied to each segment as
+/// a bitset rather than by looking up every ctid in every segment
+#[derive(Clone)]
+pub struct DeadDocsQuery(pub Arc<FxHashMap<SegmentId, BitSet>>);
+
+impl std::fmt::Debug for DeadDocsQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeadDocsQuery")
+            .field("segments", &self.0.len())
+            .finish()
+    }
+}
+
+impl Query for DeadDocsQuery {
+    fn weight(&self, _enable_scoring: EnableScoring<'_>) -> tantivy::Result<Box<dyn Weight>> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+impl Weight for DeadDocsQuery {
+    fn scorer(&self, reader: &SegmentReader, boost: Score) -> tantivy::Result<Box<dyn Scorer>> {
+        match self.0.get(&reader.segment_id()) {
+            Some(dead) => Ok(Box::new(ConstScorer::new(
+                BitSetDocSet::from(dead.clone()),
+                boost,
+            ))),
+            None => Ok(Box::new(EmptyScorer)),
+        }
+    }
+
+    fn explain(&self, reader: &SegmentReader, doc: DocId) -> tantivy::Result<Explanation> {
+        let mut scorer = self.scorer(reader, 1.0)?;
+        if scorer.seek(doc) != doc {
+            return Err(TantivyError::InvalidArgument(format!(
+                "Document #({doc}) does not match"
+            )));
+        }
+        Ok(Explanation::new("DeadDocsQuery", scorer.score()))
+    }
+}
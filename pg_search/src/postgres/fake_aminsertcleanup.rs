@@ -56,7 +56,7 @@ pub unsafe fn register() {
             pg_sys::standard_ProcessUtility(pstmt, query_string, read_only_tree, context, params, query_env, dest, qc)
         }
 
-        paradedb_aminsertcleanup(PENDING_TANTIVY_COMMIT.take().and_then(|state| state.writer));
+        paradedb_aminsertcleanup(PENDING_TANTIVY_COMMIT.take().as_mut());
     }
 
     #[cfg(feature = "pg13")]
@@ -78,12 +78,12 @@ pub unsafe fn register() {
             pg_sys::standard_ProcessUtility(pstmt, query_string, context, params, query_env, dest, qc)
         }
 
-        paradedb_aminsertcleanup(PENDING_TANTIVY_COMMIT.take().and_then(|state| state.writer));
+        paradedb_aminsertcleanup(PENDING_TANTIVY_COMMIT.take().as_mut());
     }
 
     #[pg_guard]
     unsafe extern "C" fn executor_finish_hook(query_desc: *mut pg_sys::QueryDesc) {
-        paradedb_aminsertcleanup(PENDING_TANTIVY_COMMIT.take().and_then(|state| state.writer));
+        paradedb_aminsertcleanup(PENDING_TANTIVY_COMMIT.take().as_mut());
 
         if let Some(prev_hook) = PREV_EXECUTOR_FINISH_HOOK {
             prev_hook(query_desc);
@@ -16,15 +16,13 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use crate::postgres::options::SearchIndexCreateOptions;
-use crate::schema::{IndexRecordOption, SearchFieldConfig, SearchFieldName, SearchFieldType};
+use crate::schema::{SearchFieldConfig, SearchFieldName, SearchFieldType};
 use pgrx::{pg_sys, PgBox, PgOid, PgRelation};
 use std::collections::HashMap;
-use tokenizers::manager::SearchTokenizerFilters;
-use tokenizers::{SearchNormalizer, SearchTokenizer};
 
 type Fields = Vec<(SearchFieldName, SearchFieldConfig, SearchFieldType)>;
-type KeyFieldIndex = usize;
-pub unsafe fn get_fields(index_relation: &PgRelation) -> (Fields, KeyFieldIndex) {
+type KeyFieldIndexes = Vec<usize>;
+pub unsafe fn get_fields(index_relation: &PgRelation) -> (Fields, KeyFieldIndexes) {
     let heap_relation = index_relation
         .heap_relation()
         .expect("index should belong to a heap");
@@ -102,82 +100,59 @@ pub unsafe fn get_fields(index_relation: &PgRelation) -> (Fields, KeyFieldIndex)
         }
     }
 
-    let key_field = rdopts.get_key_field().expect("must specify key_field");
-    let key_field_type = match name_type_map.get(&key_field) {
-        Some(field_type) => field_type,
-        None => panic!("key field does not exist"),
-    };
-    let key_config = match key_field_type {
-        SearchFieldType::I64 | SearchFieldType::U64 | SearchFieldType::F64 => {
-            SearchFieldConfig::Numeric {
-                indexed: true,
-                fast: true,
-                stored: false,
-                column: None,
-            }
+    for (name, config) in rdopts.get_facet_fields() {
+        let name = SearchFieldName(config.column().unwrap_or(&name.0).into());
+        if !matches!(
+            name_type_map.get(&name),
+            Some(SearchFieldType::Text | SearchFieldType::Facet)
+        ) {
+            panic!("'{name}' cannot be indexed as a facet field");
         }
-        SearchFieldType::Text => SearchFieldConfig::Text {
-            indexed: true,
-            fast: true,
-            stored: false,
-            fieldnorms: false,
-            tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
-            record: IndexRecordOption::Basic,
-            normalizer: SearchNormalizer::Raw,
-            column: None,
-        },
-        SearchFieldType::Json => SearchFieldConfig::Json {
-            indexed: true,
-            fast: true,
-            stored: false,
-            fieldnorms: false,
-            expand_dots: false,
-            tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
-            record: IndexRecordOption::Basic,
-            normalizer: SearchNormalizer::Raw,
-            column: None,
-        },
-        SearchFieldType::Range => SearchFieldConfig::Range {
-            stored: false,
-            column: None,
-        },
-        SearchFieldType::Bool => SearchFieldConfig::Boolean {
-            indexed: true,
-            fast: true,
-            stored: false,
-            column: None,
-        },
-        SearchFieldType::Date => SearchFieldConfig::Date {
-            indexed: true,
-            fast: true,
-            stored: false,
-            column: None,
-        },
-    };
+    }
+
+    let key_fields = rdopts.get_key_fields();
+    if key_fields.is_empty() {
+        panic!("must specify key_field");
+    }
+    let key_configs = key_fields
+        .iter()
+        .map(|key_field| {
+            let key_field_type = match name_type_map.get(key_field) {
+                Some(field_type) => field_type,
+                None => panic!("key field '{key_field}' does not exist"),
+            };
+            (
+                key_field.clone(),
+                SearchFieldConfig::key_field(key_field_type),
+                *key_field_type,
+            )
+        })
+        .collect::<Vec<_>>();
 
     // Concatenate the separate lists of fields.
     let index_info = unsafe { pg_sys::BuildIndexInfo(index_relation.as_ptr()) };
     let fields: Vec<_> = rdopts
         .get_fields(&heap_relation, index_info)
         .into_iter()
-        .filter(|(name, _, _)| name != &key_field) // Process key_field separately.
-        .chain(std::iter::once((
-            key_field.clone(),
-            key_config,
-            *key_field_type,
-        )))
+        .filter(|(name, _, _)| !key_fields.contains(name)) // Process key fields separately.
+        .chain(key_configs)
         .collect();
 
-    let key_field_index = fields
+    let key_field_indexes = key_fields
         .iter()
-        .position(|(name, _, _)| name == &key_field)
-        .expect("key field not found in columns"); // key field is already validated by now.
+        .map(|key_field| {
+            fields
+                .iter()
+                .position(|(name, _, _)| name == key_field)
+                .expect("key field not found in columns") // key field is already validated by now.
+        })
+        .collect::<Vec<_>>();
 
-    // If there's only one field in the vector, then it's just the key field
-    // which we added above, and the user has not specified any fields to index.
-    if fields.len() == 1 {
+    // If the vector only holds the key fields we added above, then the user has not specified
+    // any fields to index.
+    if fields.len() == key_fields.len() {
         panic!("no fields specified")
     }
 
-    (fields, key_field_index)
+    (fields, key_field_indexes)
 }
@@ -15,12 +15,17 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::index::pending::{
+    append_pending, flush_pending, pending_document, pending_entry, pending_list_limit,
+};
+use crate::index::utils::load_schema;
 use crate::index::writer::index::SearchIndexWriter;
 use crate::index::{BlockDirectoryType, WriterResources};
+use crate::postgres::storage::block::PendingEntry;
 use crate::postgres::utils::{
     categorize_fields, item_pointer_to_u64, row_to_search_document, CategorizedFieldData,
 };
-use crate::schema::SearchField;
+use crate::schema::{SearchDocument, SearchField, SearchIndexSchema};
 use pgrx::{pg_guard, pg_sys, PgRelation, PgTupleDesc};
 use std::ffi::CStr;
 use std::panic::{catch_unwind, resume_unwind};
@@ -31,8 +36,16 @@ extern "C" {
 
 pub struct InsertState {
     pub writer: Option<SearchIndexWriter>,
+    index_oid: pg_sys::Oid,
+    schema: SearchIndexSchema,
     categorized_fields: Vec<(SearchField, CategorizedFieldData)>,
-    key_field_name: String,
+    key_field_names: Vec<String>,
+
+    // with `fastupdate`, the statement's documents are collected here and added to the pending
+    // list when it finishes
+    pending: Option<Vec<PendingEntry>>,
+    pending_bytes: usize,
+    pending_list_limit: usize,
 }
 
 impl InsertState {
@@ -40,16 +53,69 @@ impl InsertState {
         indexrel: &PgRelation,
         writer_resources: WriterResources,
     ) -> anyhow::Result<Self> {
-        let writer = SearchIndexWriter::open(indexrel, BlockDirectoryType::Mvcc, writer_resources)?;
+        let pending_list_limit = pending_list_limit(indexrel);
+        let (writer, schema) = if pending_list_limit.is_some() {
+            // the writer is only opened if something can't go to the pending list
+            let schema = load_schema(indexrel.oid())?.schema;
+            (None, SearchIndexSchema::open(schema, indexrel))
+        } else {
+            let writer =
+                SearchIndexWriter::open(indexrel, BlockDirectoryType::Mvcc, writer_resources)?;
+            let schema = writer.schema.clone();
+            (Some(writer), schema)
+        };
         let tupdesc = unsafe { PgTupleDesc::from_pg_unchecked(indexrel.rd_att) };
-        let categorized_fields = categorize_fields(&tupdesc, &writer.schema);
-        let key_field_name = writer.schema.key_field().name.0;
+        let categorized_fields = categorize_fields(&tupdesc, &schema);
+        let key_field_names = schema
+            .key_fields()
+            .into_iter()
+            .map(|field| field.name.0)
+            .collect();
         Ok(Self {
-            writer: Some(writer),
+            writer,
+            index_oid: indexrel.oid(),
+            schema,
             categorized_fields,
-            key_field_name,
+            key_field_names,
+            pending: pending_list_limit.map(|_| vec![]),
+            pending_bytes: 0,
+            pending_list_limit: pending_list_limit.unwrap_or_default(),
         })
     }
+
+    fn writer(&mut self) -> anyhow::Result<&mut SearchIndexWriter> {
+        if self.writer.is_none() {
+            self.writer = Some(SearchIndexWriter::open(
+                &PgRelation::open(self.index_oid),
+                BlockDirectoryType::Mvcc,
+                WriterResources::Statement,
+            )?);
+        }
+        Ok(self.writer.as_mut().expect("writer should not be null"))
+    }
+
+    fn insert(&mut self, document: SearchDocument, ctid: u64) -> anyhow::Result<()> {
+        if let Some(pending) = self.pending.as_mut() {
+            if let Some(entry) = pending_entry(&self.schema.schema, &document, ctid) {
+                self.pending_bytes += entry.item_size();
+                pending.push(entry);
+
+                if self.pending_bytes > self.pending_list_limit {
+                    // this statement alone would fill the pending list, so it may as well write
+                    // its own segment
+                    let pending = self.pending.take().unwrap_or_default();
+                    let schema = self.schema.schema.clone();
+                    let writer = self.writer()?;
+                    for entry in pending {
+                        writer.insert(pending_document(&schema, &entry)?, entry.ctid)?;
+                    }
+                }
+                return Ok(());
+            }
+        }
+
+        self.writer()?.insert(document, ctid)
+    }
 }
 
 #[cfg(not(feature = "pg17"))]
@@ -153,17 +219,13 @@ unsafe fn aminsert_internal(
                 .expect("index_info argument must not be null"),
             WriterResources::Statement,
         );
-        let categorized_fields = &state.categorized_fields;
-        let key_field_name = &state.key_field_name;
-        let writer = state.writer.as_mut().expect("writer should not be null");
-
-        let mut search_document = writer.schema.new_document();
+        let mut search_document = state.schema.new_document();
 
         row_to_search_document(
             values,
             isnull,
-            key_field_name,
-            categorized_fields,
+            &state.key_field_names,
+            &state.categorized_fields,
             &mut search_document,
         )
         .unwrap_or_else(|err| {
@@ -172,7 +234,7 @@ unsafe fn aminsert_internal(
                 CStr::from_ptr((*(*index_relation).rd_rel).relname.data.as_ptr()).to_string_lossy()
             );
         });
-        writer
+        state
             .insert(search_document, item_pointer_to_u64(*ctid))
             .expect("insertion into index should succeed");
         true
@@ -195,13 +257,28 @@ pub unsafe extern "C" fn aminsertcleanup(
         return;
     }
 
-    paradedb_aminsertcleanup(state.as_mut().and_then(|state| state.writer.take()));
+    paradedb_aminsertcleanup(state.as_mut());
 }
 
-pub fn paradedb_aminsertcleanup(mut writer: Option<SearchIndexWriter>) {
-    if let Some(writer) = writer.take() {
+pub fn paradedb_aminsertcleanup(state: Option<&mut InsertState>) {
+    let Some(state) = state else {
+        return;
+    };
+
+    if let Some(writer) = state.writer.take() {
         writer
             .commit()
             .expect("must be able to commit inserts in fake_aminsertcleanup");
     }
+
+    if let Some(pending) = state.pending.take().filter(|pending| !pending.is_empty()) {
+        unsafe {
+            let pending_bytes = append_pending(state.index_oid, pending)
+                .expect("must be able to add inserts to the pending list");
+            if pending_bytes > state.pending_list_limit as u64 {
+                flush_pending(&PgRelation::open(state.index_oid), false)
+                    .expect("must be able to flush the pending list");
+            }
+        }
+    }
 }
@@ -0,0 +1,242 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Merging segments happens in a dynamic background worker rather than in the backend that
+//! wrote them, so that an `INSERT`/`UPDATE`/`COPY` only ever has to flush its own segments.
+//!
+//! A writing backend that would like its index merged calls [`request_merge`].  Once its
+//! transaction commits, it launches a worker for each such index unless one is already waiting
+//! to start, in which case that worker is guaranteed to see the commit.  Workers for the same
+//! index merge one at a time, and each is throttled with the same cost-based delay machinery
+//! that `VACUUM` uses.
+
+use crate::gucs;
+use crate::index::writer::index::SearchIndexWriter;
+use crate::index::{BlockDirectoryType, WriterResources};
+use pgrx::bgworkers::{BackgroundWorker, BackgroundWorkerBuilder, SignalWakeFlags};
+use pgrx::{pg_guard, pg_sys, FromDatum, IntoDatum, PgRelation, PgXactCallbackEvent};
+
+/// Indexes that the current transaction wants merged once it commits
+static mut MERGE_REQUESTS: Vec<pg_sys::Oid> = Vec::new();
+
+/// Set in the merge worker so that [`delay_point`] knows to throttle this process
+static mut IS_MERGE_WORKER: bool = false;
+
+/// Set while `paradedb.merge()` runs, so that [`delay_point`] lets the user cancel it
+static mut IS_FORCE_MERGE: bool = false;
+
+/// The advisory locks a merge worker holds, keyed on its database and index oid.  They only
+/// conflict with a user's own two-key advisory locks if those use the same index oid and key
+#[derive(Copy, Clone)]
+#[repr(u32)]
+enum WorkerLock {
+    /// Held from the moment a worker starts until just before it takes the snapshot it merges
+    /// with.  A committed transaction that finds it held can rely on that worker to see it
+    Pending = 0x7064_6201,
+
+    /// Held while merging, so that only one worker merges a given index at a time.  `paradedb.merge()`
+    /// and ambulkdelete hold it for the rest of their transaction, too
+    Running = 0x7064_6202,
+}
+
+impl WorkerLock {
+    fn tag(self, database_oid: pg_sys::Oid, index_oid: pg_sys::Oid) -> pg_sys::LOCKTAG {
+        pg_sys::LOCKTAG {
+            locktag_field1: database_oid.as_u32(),
+            locktag_field2: index_oid.as_u32(),
+            locktag_field3: self as u32,
+            locktag_field4: 2,
+            locktag_type: pg_sys::LockTagType::LOCKTAG_ADVISORY as u8,
+            locktag_lockmethodid: pg_sys::USER_LOCKMETHOD as u8,
+        }
+    }
+}
+
+unsafe fn try_lock(tag: &pg_sys::LOCKTAG) -> bool {
+    pg_sys::LockAcquire(tag, pg_sys::ExclusiveLock as pg_sys::LOCKMODE, true, true)
+        != pg_sys::LockAcquireResult::LOCKACQUIRE_NOT_AVAIL
+}
+
+unsafe fn lock(tag: &pg_sys::LOCKTAG) {
+    pg_sys::LockAcquire(tag, pg_sys::ExclusiveLock as pg_sys::LOCKMODE, true, false);
+}
+
+unsafe fn unlock(tag: &pg_sys::LOCKTAG) {
+    pg_sys::LockRelease(tag, pg_sys::ExclusiveLock as pg_sys::LOCKMODE, true);
+}
+
+/// Wait for any merge worker that's merging the index to finish, and keep any other from starting
+/// to until the current transaction ends.  Being a heavyweight lock, the wait can be cancelled
+pub unsafe fn lock_merges_for_transaction(index_oid: pg_sys::Oid) {
+    let running = WorkerLock::Running.tag(pg_sys::MyDatabaseId, index_oid);
+    pg_sys::LockAcquire(
+        &running,
+        pg_sys::ExclusiveLock as pg_sys::LOCKMODE,
+        false,
+        false,
+    );
+}
+
+/// Ask for the index to be merged in the background once the current transaction commits.  Until
+/// then the new segments aren't visible, so a worker started any sooner could miss them
+#[allow(static_mut_refs)]
+pub unsafe fn request_merge(index_oid: pg_sys::Oid) {
+    if MERGE_REQUESTS.is_empty() {
+        pgrx::register_xact_callback(PgXactCallbackEvent::Commit, || unsafe {
+            for index_oid in std::mem::take(&mut MERGE_REQUESTS) {
+                launch_merge_worker(index_oid);
+            }
+        });
+        pgrx::register_xact_callback(PgXactCallbackEvent::Abort, || unsafe {
+            MERGE_REQUESTS.clear()
+        });
+    }
+
+    if !MERGE_REQUESTS.contains(&index_oid) {
+        MERGE_REQUESTS.push(index_oid);
+    }
+}
+
+unsafe fn launch_merge_worker(index_oid: pg_sys::Oid) {
+    let database_oid = pg_sys::MyDatabaseId;
+    let pending = WorkerLock::Pending.tag(database_oid, index_oid);
+    if !try_lock(&pending) {
+        // a worker has yet to take its snapshot, and when it does it'll see what we just committed
+        return;
+    }
+    unlock(&pending);
+
+    let name = format!("pg_search merge worker for {index_oid}");
+    let launched = BackgroundWorkerBuilder::new(&name)
+        .set_function("merge_worker")
+        .set_library("pg_search")
+        .set_argument(index_oid.into_datum())
+        .set_extra(&database_oid.as_u32().to_string())
+        .enable_spi_access()
+        .set_restart_time(None)
+        .load_dynamic();
+
+    if launched.is_err() {
+        // most likely `max_worker_processes` is exhausted.  the next write will ask again
+        pgrx::debug1!("could not launch a pg_search merge worker for index {index_oid}");
+    }
+}
+
+/// Called by the [`ChannelRequestHandler`](crate::index::channel::ChannelRequestHandler) while
+/// it waits on tantivy, so that a merge worker can be throttled and stopped mid-merge, and so
+/// that `paradedb.merge()` can be cancelled.  It does nothing otherwise
+pub fn delay_point() {
+    unsafe {
+        if IS_FORCE_MERGE {
+            pgrx::check_for_interrupts!();
+        } else if IS_MERGE_WORKER {
+            if BackgroundWorker::sigterm_received() {
+                panic!("terminating pg_search merge worker due to administrator command");
+            }
+            pg_sys::vacuum_delay_point();
+        }
+    }
+}
+
+/// Makes the current backend honor query cancellation while tantivy merges, until dropped
+pub struct ForceMergeGuard(());
+
+impl ForceMergeGuard {
+    pub fn enter() -> Self {
+        unsafe { IS_FORCE_MERGE = true };
+        ForceMergeGuard(())
+    }
+}
+
+impl Drop for ForceMergeGuard {
+    fn drop(&mut self) {
+        unsafe { IS_FORCE_MERGE = false };
+    }
+}
+
+#[pg_guard]
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn merge_worker(index_oid: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGTERM | SignalWakeFlags::SIGHUP);
+
+    let index_oid =
+        pg_sys::Oid::from_datum(index_oid, false).expect("merge worker requires an index oid");
+    let database_oid = BackgroundWorker::get_extra()
+        .parse::<u32>()
+        .map(pg_sys::Oid::from)
+        .expect("merge worker requires a database oid");
+    BackgroundWorker::connect_worker_to_spi_by_oid(Some(database_oid), None);
+
+    let pending = WorkerLock::Pending.tag(database_oid, index_oid);
+    let running = WorkerLock::Running.tag(database_oid, index_oid);
+    let should_merge = BackgroundWorker::transaction(|| {
+        if !try_lock(&pending) {
+            // another worker is waiting to start, and it'll see everything we would have
+            return false;
+        }
+
+        // wait for any merge already in progress, then let the next request launch a worker
+        // of its own, because we're about to take our snapshot
+        lock(&running);
+        unlock(&pending);
+        true
+    });
+
+    if !should_merge || BackgroundWorker::sigterm_received() {
+        return;
+    }
+
+    IS_MERGE_WORKER = true;
+    let cost_delay = gucs::merge_cost_delay();
+    let cost_limit = gucs::merge_cost_limit();
+    pg_sys::VacuumCostDelay = cost_delay;
+    pg_sys::VacuumCostLimit = cost_limit;
+    #[cfg(any(feature = "pg16", feature = "pg17"))]
+    {
+        pg_sys::vacuum_cost_delay = cost_delay;
+        pg_sys::vacuum_cost_limit = cost_limit;
+    }
+    pg_sys::VacuumCostBalance = 0;
+    pg_sys::VacuumCostActive = cost_delay > 0.0;
+
+    BackgroundWorker::transaction(|| merge_index(index_oid));
+}
+
+unsafe fn merge_index(index_oid: pg_sys::Oid) {
+    let lockmode = pg_sys::RowExclusiveLock as pg_sys::LOCKMODE;
+    let relation = pg_sys::try_relation_open(index_oid, lockmode);
+    if relation.is_null() {
+        // the index was dropped after the merge was requested
+        return;
+    }
+
+    pg_sys::PushActiveSnapshot(pg_sys::GetTransactionSnapshot());
+
+    // an empty commit under the index's merge policy is all it takes to merge it
+    let index_relation = PgRelation::from_pg(relation);
+    SearchIndexWriter::open(
+        &index_relation,
+        BlockDirectoryType::Mvcc,
+        WriterResources::Merge,
+    )
+    .and_then(|writer| writer.commit())
+    .unwrap_or_else(|e| panic!("failed to merge index {index_oid}: {e}"));
+
+    pg_sys::PopActiveSnapshot();
+    pg_sys::relation_close(relation, lockmode);
+}
@@ -36,16 +36,21 @@ pub mod datetime;
 #[cfg(not(feature = "pg17"))]
 pub mod fake_aminsertcleanup;
 pub mod index;
+pub mod merge_worker;
 mod parallel;
+pub mod portable;
 pub mod storage;
 pub mod types;
 pub mod utils;
+pub mod verify;
 pub mod visibility_checker;
 
 #[repr(u16)] // b/c that's what [`pg_sys::StrategyNumber`] is
 pub enum ScanStrategy {
     TextQuery = 1,
     SearchQueryInput = 2,
+    TextScore = 3,
+    SearchQueryInputScore = 4,
     // NB:  Any additions here **mut** update the `amroutine.amstrategies` down below in [`bm25_handler`]
 }
 
@@ -57,6 +62,10 @@ impl TryFrom<pg_sys::StrategyNumber> for ScanStrategy {
             Ok(ScanStrategy::TextQuery)
         } else if value == 2 {
             Ok(ScanStrategy::SearchQueryInput)
+        } else if value == 3 {
+            Ok(ScanStrategy::TextScore)
+        } else if value == 4 {
+            Ok(ScanStrategy::SearchQueryInputScore)
         } else {
             Err(format!("`{value}` is an unknown `ScanStrategy` number"))
         }
@@ -72,10 +81,11 @@ fn bm25_handler(_fcinfo: pg_sys::FunctionCallInfo) -> PgBox<pg_sys::IndexAmRouti
     let mut amroutine =
         unsafe { PgBox::<pg_sys::IndexAmRoutine>::alloc_node(pg_sys::NodeTag::T_IndexAmRoutine) };
 
-    amroutine.amstrategies = 2;
+    amroutine.amstrategies = 4;
     amroutine.amsupport = 0;
     amroutine.amcanmulticol = true;
     amroutine.amsearcharray = true;
+    amroutine.amcanorderbyop = true;
 
     amroutine.amkeytype = pg_sys::InvalidOid;
 
@@ -124,14 +134,29 @@ pub struct ParallelScanState {
     pub mutex: Spinlock,
     pub segment_count: usize,
     pub remaining_segments: usize,
-    pub segment_uuids: [u8; 0], // dynamically sized, allocated after end
+    // dynamically sized, allocated after end, and followed by one [`SegmentStats`] per segment
+    pub segment_uuids: [u8; 0],
+}
+
+/// What happened to each segment of a parallel scan, so the leader can report it in `EXPLAIN ANALYZE`
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct SegmentStats {
+    /// The `ParallelWorkerNumber` of the process that claimed the segment, where -1 is the leader
+    pub claimed_by: i32,
+    /// How many documents matched the query in the segment
+    pub matches: u64,
+}
+
+impl SegmentStats {
+    pub const UNCLAIMED: i32 = i32::MIN;
 }
 
 impl ParallelScanState {
     #[inline]
     const fn size_of_with_segments(nsegments: usize) -> usize {
         // a SegmentId, in byte form, is 16 bytes
-        size_of::<Self>() + (nsegments * 16)
+        size_of::<Self>() + (nsegments * (16 + size_of::<SegmentStats>()))
     }
 
     unsafe fn get_segment_id(&self, i: usize) -> SegmentId {
@@ -153,9 +178,52 @@ impl ParallelScanState {
 
         for (i, segment_reader) in search_index_reader.segment_readers().iter().enumerate() {
             self.set_segment_id(i, segment_reader.segment_id());
+            self.segment_stats_ptr(i).write(SegmentStats {
+                claimed_by: SegmentStats::UNCLAIMED,
+                matches: 0,
+            });
+        }
+    }
+
+    unsafe fn segment_stats_ptr(&self, i: usize) -> *mut SegmentStats {
+        unsafe {
+            if i >= self.segment_count {
+                panic!(
+                    "index {i} out of bounds.  segment_count={}",
+                    self.segment_count
+                );
+            }
+            // the stats live immediately after the last segment id, and as the segment ids are
+            // 16 bytes each, they stay properly aligned
+            let ptr = addr_of!(self.segment_uuids) as *mut [u8; 16];
+            ptr.add(self.segment_count).cast::<SegmentStats>().add(i)
+        }
+    }
+
+    /// Record that the current process has claimed the segment at index `i`
+    unsafe fn set_claimed_by_me(&mut self, i: usize) {
+        unsafe { (*self.segment_stats_ptr(i)).claimed_by = pg_sys::ParallelWorkerNumber }
+    }
+
+    /// Add to the number of documents that matched in the specified segment
+    pub unsafe fn add_segment_matches(&mut self, segment_id: SegmentId, matches: u64) {
+        let _mutex = self.mutex.acquire();
+        for i in 0..self.segment_count {
+            if self.get_segment_id(i) == segment_id {
+                (*self.segment_stats_ptr(i)).matches += matches;
+                return;
+            }
         }
     }
 
+    /// Copy out the [`SegmentStats`] of every segment, in the order they were assigned
+    pub unsafe fn segment_stats(&mut self) -> Vec<(SegmentId, SegmentStats)> {
+        let _mutex = self.mutex.acquire();
+        (0..self.segment_count)
+            .map(|i| (self.get_segment_id(i), self.segment_stats_ptr(i).read()))
+            .collect()
+    }
+
     unsafe fn set_segment_id(&mut self, i: usize, segment_id: SegmentId) {
         unsafe {
             if i >= self.segment_count {
@@ -15,16 +15,17 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use memoffset::*;
 use pgrx::pg_sys::AsPgCStr;
 use pgrx::*;
 use serde_json::{json, Map};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CStr;
-use tokenizers::{manager::SearchTokenizerFilters, SearchNormalizer, SearchTokenizer};
+use tantivy::store::{Compressor, ZstdCompressor};
 
-use crate::schema::{IndexRecordOption, SearchFieldConfig, SearchFieldName, SearchFieldType};
+use crate::index::merge_policy::MergePolicyConfig;
+use crate::schema::{SearchFieldConfig, SearchFieldName, SearchFieldType};
 
 /* ADDING OPTIONS
  * in init(), call pg_sys::add_{type}_reloption (check postgres docs for what args you need)
@@ -55,8 +56,25 @@ pub struct SearchIndexCreateOptions {
     range_fields_offset: i32,
     datetime_fields_offset: i32,
     key_field_offset: i32,
+    facet_fields_offset: i32,
+    merge_policy_offset: i32,
+    docstore_compression_offset: i32,
+    docstore_blocksize: i32,
+    fastupdate: bool,
+    pending_list_limit: i32,
 }
 
+/// The docstore's default block size, in bytes, which is tantivy's own default
+const DEFAULT_DOCSTORE_BLOCKSIZE: i32 = 16_384;
+
+/// The default size, in kilobytes, the pending list can grow to before it's flushed.  This is
+/// also the default of Postgres' `gin_pending_list_limit`
+const DEFAULT_PENDING_LIST_LIMIT: i32 = 4096;
+
+/// The largest `pending_list_limit`, in kilobytes.  Every search indexes the pending list again,
+/// in memory, so it has to stay small enough for that to be reasonable
+const MAX_PENDING_LIST_LIMIT: i32 = 65_536;
+
 #[pg_guard]
 extern "C" fn validate_text_fields(value: *const std::os::raw::c_char) {
     let json_str = cstr_to_rust_str(value);
@@ -129,6 +147,18 @@ extern "C" fn validate_datetime_fields(value: *const std::os::raw::c_char) {
     );
 }
 
+#[pg_guard]
+extern "C" fn validate_facet_fields(value: *const std::os::raw::c_char) {
+    let json_str = cstr_to_rust_str(value);
+    if json_str.is_empty() {
+        return;
+    }
+    SearchIndexCreateOptions::deserialize_config_fields(
+        json_str,
+        &SearchFieldConfig::facet_from_json,
+    );
+}
+
 #[pg_guard]
 extern "C" fn validate_fields(value: *const std::os::raw::c_char) {
     let json_str = cstr_to_rust_str(value);
@@ -143,7 +173,82 @@ extern "C" fn validate_fields(value: *const std::os::raw::c_char) {
 
 #[pg_guard]
 extern "C" fn validate_key_field(value: *const std::os::raw::c_char) {
-    cstr_to_rust_str(value);
+    let key_field = cstr_to_rust_str(value);
+    if key_field.is_empty() {
+        return;
+    }
+
+    let parts = parse_key_field(&key_field);
+    if parts.iter().any(|part| part.is_empty()) {
+        panic!("invalid key_field '{key_field}': expected a column name or a list of column names like '(a, b)'");
+    }
+}
+
+#[pg_guard]
+extern "C" fn validate_merge_policy(value: *const std::os::raw::c_char) {
+    let merge_policy = cstr_to_rust_str(value);
+    if merge_policy.is_empty() {
+        return;
+    }
+
+    if let Err(err) = MergePolicyConfig::from_option(&merge_policy) {
+        panic!("invalid merge_policy '{merge_policy}': {err}");
+    }
+}
+
+#[pg_guard]
+extern "C" fn validate_docstore_compression(value: *const std::os::raw::c_char) {
+    let docstore_compression = cstr_to_rust_str(value);
+    if docstore_compression.is_empty() {
+        return;
+    }
+
+    if let Err(err) = parse_docstore_compression(&docstore_compression) {
+        panic!("invalid docstore_compression '{docstore_compression}': {err}");
+    }
+}
+
+/// Parse a `docstore_compression` option, which is one of `none`, `lz4`, `zstd`, or `zstd(<level>)`
+fn parse_docstore_compression(value: &str) -> Result<Compressor> {
+    let value = value.trim().to_lowercase();
+    match value.as_str() {
+        "none" => Ok(Compressor::None),
+        "lz4" => Ok(Compressor::Lz4),
+        "zstd" => Ok(Compressor::Zstd(ZstdCompressor {
+            compression_level: None,
+        })),
+        _ => {
+            let level = value
+                .strip_prefix("zstd(")
+                .and_then(|rest| rest.strip_suffix(')'))
+                .ok_or_else(|| anyhow!("expected one of none, lz4, zstd, or zstd(<level>)"))?
+                .trim()
+                .parse::<i32>()
+                .map_err(|err| anyhow!("invalid zstd level: {err}"))?;
+            if !(1..=22).contains(&level) {
+                return Err(anyhow!("zstd level must be between 1 and 22"));
+            }
+            Ok(Compressor::Zstd(ZstdCompressor {
+                compression_level: Some(level),
+            }))
+        }
+    }
+}
+
+/// Split a `key_field` option into its column names.  A composite key is written as a
+/// parenthesized, comma-separated list, such as `(tenant_id, doc_id)`.
+fn parse_key_field(key_field: &str) -> Vec<String> {
+    let key_field = key_field.trim();
+    match key_field
+        .strip_prefix('(')
+        .and_then(|inner| inner.strip_suffix(')'))
+    {
+        Some(inner) => inner
+            .split(',')
+            .map(|part| part.trim().trim_matches('"').to_string())
+            .collect(),
+        None => vec![key_field.to_string()],
+    }
 }
 
 #[inline]
@@ -158,7 +263,7 @@ fn cstr_to_rust_str(value: *const std::os::raw::c_char) -> String {
         .to_string()
 }
 
-const NUM_REL_OPTS: usize = 7;
+const NUM_REL_OPTS: usize = 13;
 #[pg_guard]
 pub unsafe extern "C" fn amoptions(
     reloptions: pg_sys::Datum,
@@ -200,6 +305,36 @@ pub unsafe extern "C" fn amoptions(
             opttype: pg_sys::relopt_type::RELOPT_TYPE_STRING,
             offset: offset_of!(SearchIndexCreateOptions, key_field_offset) as i32,
         },
+        pg_sys::relopt_parse_elt {
+            optname: "facet_fields".as_pg_cstr(),
+            opttype: pg_sys::relopt_type::RELOPT_TYPE_STRING,
+            offset: offset_of!(SearchIndexCreateOptions, facet_fields_offset) as i32,
+        },
+        pg_sys::relopt_parse_elt {
+            optname: "merge_policy".as_pg_cstr(),
+            opttype: pg_sys::relopt_type::RELOPT_TYPE_STRING,
+            offset: offset_of!(SearchIndexCreateOptions, merge_policy_offset) as i32,
+        },
+        pg_sys::relopt_parse_elt {
+            optname: "docstore_compression".as_pg_cstr(),
+            opttype: pg_sys::relopt_type::RELOPT_TYPE_STRING,
+            offset: offset_of!(SearchIndexCreateOptions, docstore_compression_offset) as i32,
+        },
+        pg_sys::relopt_parse_elt {
+            optname: "docstore_blocksize".as_pg_cstr(),
+            opttype: pg_sys::relopt_type::RELOPT_TYPE_INT,
+            offset: offset_of!(SearchIndexCreateOptions, docstore_blocksize) as i32,
+        },
+        pg_sys::relopt_parse_elt {
+            optname: "fastupdate".as_pg_cstr(),
+            opttype: pg_sys::relopt_type::RELOPT_TYPE_BOOL,
+            offset: offset_of!(SearchIndexCreateOptions, fastupdate) as i32,
+        },
+        pg_sys::relopt_parse_elt {
+            optname: "pending_list_limit".as_pg_cstr(),
+            opttype: pg_sys::relopt_type::RELOPT_TYPE_INT,
+            offset: offset_of!(SearchIndexCreateOptions, pending_list_limit) as i32,
+        },
     ];
     build_relopts(reloptions, validate, options)
 }
@@ -296,6 +431,41 @@ impl SearchIndexCreateOptions {
         Self::deserialize_config_fields(config, &SearchFieldConfig::date_from_json)
     }
 
+    pub fn get_facet_fields(&self) -> Vec<(SearchFieldName, SearchFieldConfig)> {
+        let config = self.get_str(self.facet_fields_offset, "".to_string());
+        if config.is_empty() {
+            return Vec::new();
+        }
+        Self::deserialize_config_fields(config, &SearchFieldConfig::facet_from_json)
+    }
+
+    /// The name of the index option that configures fields of `field_type`, along with its
+    /// current (possibly empty) JSON configuration
+    pub fn get_fields_option(
+        &self,
+        field_type: &SearchFieldType,
+    ) -> (&'static str, Map<String, serde_json::Value>) {
+        let (name, offset) = match field_type {
+            SearchFieldType::Text => ("text_fields", self.text_fields_offset),
+            SearchFieldType::I64 | SearchFieldType::F64 | SearchFieldType::U64 => {
+                ("numeric_fields", self.numeric_fields_offset)
+            }
+            SearchFieldType::Bool => ("boolean_fields", self.boolean_fields_offset),
+            SearchFieldType::Json => ("json_fields", self.json_fields_offset),
+            SearchFieldType::Range => ("range_fields", self.range_fields_offset),
+            SearchFieldType::Date => ("datetime_fields", self.datetime_fields_offset),
+            SearchFieldType::Facet => ("facet_fields", self.facet_fields_offset),
+        };
+
+        let config = self.get_str(offset, "".to_string());
+        if config.is_empty() {
+            return (name, Map::new());
+        }
+        let config = serde_json::from_str(&config)
+            .unwrap_or_else(|err| panic!("error in JSON field config: {err}: {config}"));
+        (name, config)
+    }
+
     fn json_value_to_search_field_config(
         field_type: &SearchFieldType,
         field_config: serde_json::Value,
@@ -309,22 +479,33 @@ impl SearchIndexCreateOptions {
             SearchFieldType::Json => SearchFieldConfig::json_from_json(field_config),
             SearchFieldType::Date => SearchFieldConfig::date_from_json(field_config),
             SearchFieldType::Range => SearchFieldConfig::range_from_json(field_config),
+            SearchFieldType::Facet => SearchFieldConfig::facet_from_json(field_config),
         }
         .expect("field config should be valid for SearchFieldConfig::{field_name}")
     }
 
+    /// The first (or only) column of the `key_field`
     pub fn get_key_field(&self) -> Option<SearchFieldName> {
+        self.get_key_fields().into_iter().next()
+    }
+
+    /// Every column of the `key_field`, in key order.  This is a single column unless the index
+    /// uses a composite key
+    pub fn get_key_fields(&self) -> Vec<SearchFieldName> {
         let key_field_name = self.get_str(self.key_field_offset, "".to_string());
         if key_field_name.is_empty() {
-            return None;
+            return vec![];
         }
-        Some(SearchFieldName(key_field_name))
+        parse_key_field(&key_field_name)
+            .into_iter()
+            .map(SearchFieldName)
+            .collect()
     }
 
-    fn get_key_field_config(
+    fn get_key_field_configs(
         &self,
         heaprel: &PgRelation,
-    ) -> (SearchFieldName, SearchFieldConfig, SearchFieldType) {
+    ) -> Vec<(SearchFieldName, SearchFieldConfig, SearchFieldType)> {
         // Create a map from column name to column type. We'll use this to verify that index
         // configurations passed by the user reference the correct types for each column.
         let name_type_map: HashMap<SearchFieldName, SearchFieldType> = heaprel
@@ -347,60 +528,22 @@ impl SearchIndexCreateOptions {
             })
             .collect();
 
-        let key_field_name = self.get_key_field().expect("must specify key_field");
-        let key_field_type = match name_type_map.get(&key_field_name) {
-            Some(field_type) => field_type,
-            None => panic!("key field does not exist"),
-        };
-        let key_field_config = match key_field_type {
-            SearchFieldType::I64 | SearchFieldType::U64 | SearchFieldType::F64 => {
-                SearchFieldConfig::Numeric {
-                    indexed: true,
-                    fast: true,
-                    stored: true,
-                    column: None,
-                }
-            }
-            SearchFieldType::Text => SearchFieldConfig::Text {
-                indexed: true,
-                fast: true,
-                stored: true,
-                fieldnorms: false,
-                tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
-                record: IndexRecordOption::Basic,
-                normalizer: SearchNormalizer::Raw,
-                column: None,
-            },
-            SearchFieldType::Json => SearchFieldConfig::Json {
-                indexed: true,
-                fast: true,
-                stored: true,
-                fieldnorms: false,
-                expand_dots: false,
-                tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
-                record: IndexRecordOption::Basic,
-                normalizer: SearchNormalizer::Raw,
-                column: None,
-            },
-            SearchFieldType::Range => SearchFieldConfig::Range {
-                stored: true,
-                column: None,
-            },
-            SearchFieldType::Bool => SearchFieldConfig::Boolean {
-                indexed: true,
-                fast: true,
-                stored: true,
-                column: None,
-            },
-            SearchFieldType::Date => SearchFieldConfig::Date {
-                indexed: true,
-                fast: true,
-                stored: true,
-                column: None,
-            },
-        };
+        let key_fields = self.get_key_fields();
+        if key_fields.is_empty() {
+            panic!("must specify key_field");
+        }
 
-        (key_field_name, key_field_config, *key_field_type)
+        key_fields
+            .into_iter()
+            .map(|key_field_name| {
+                let key_field_type = match name_type_map.get(&key_field_name) {
+                    Some(field_type) => *field_type,
+                    None => panic!("key field '{key_field_name}' does not exist"),
+                };
+                let key_field_config = SearchFieldConfig::key_field(&key_field_type);
+                (key_field_name, key_field_config, key_field_type)
+            })
+            .collect()
     }
 
     pub fn get_fields(
@@ -409,7 +552,7 @@ impl SearchIndexCreateOptions {
         index_info: *mut pg_sys::IndexInfo,
     ) -> Vec<(SearchFieldName, SearchFieldConfig, SearchFieldType)> {
         let tupdesc = heaprel.tuple_desc();
-        let (key_field_name, key_field_config, key_field_type) = self.get_key_field_config(heaprel);
+        let key_fields = self.get_key_field_configs(heaprel);
 
         let mut config_by_name = [
             self.text_fields_offset,
@@ -418,6 +561,7 @@ impl SearchIndexCreateOptions {
             self.json_fields_offset,
             self.range_fields_offset,
             self.datetime_fields_offset,
+            self.facet_fields_offset,
         ]
         .into_iter()
         .map(|offset| self.get_str(offset, "".to_string()))
@@ -429,6 +573,13 @@ impl SearchIndexCreateOptions {
         })
         .collect::<HashMap<_, _>>();
 
+        // text columns are indexed as facets when they're configured in `facet_fields`
+        let facet_names = self
+            .get_facet_fields()
+            .into_iter()
+            .map(|(name, _)| name.0)
+            .collect::<HashSet<_>>();
+
         let num_index_attrs = unsafe { (*index_info).ii_NumIndexAttrs };
         let mut fields_by_name = (0..num_index_attrs)
             .map(|i| {
@@ -446,11 +597,16 @@ impl SearchIndexCreateOptions {
                     column_type_oid
                 };
 
-                let field_type = SearchFieldType::try_from(&base_oid).unwrap_or_else(|err| {
+                let field_type = match SearchFieldType::try_from(&base_oid).unwrap_or_else(|err| {
                     panic!("cannot index column '{column_name}' with type {base_oid:?}: {err}")
-                });
+                }) {
+                    SearchFieldType::Text if facet_names.contains(column_name) => {
+                        SearchFieldType::Facet
+                    }
+                    field_type => field_type,
+                };
 
-                if column_name == key_field_name.0 && config_by_name.contains_key(column_name){
+                if key_fields.iter().any(|(key_field_name, _, _)| column_name == key_field_name.0) && config_by_name.contains_key(column_name){
                     panic!("cannot override BM25 configuration for key_field '{column_name}', you must use an aliased field name and 'column' configuration key");
                 }
 
@@ -469,11 +625,13 @@ impl SearchIndexCreateOptions {
             })
             .collect::<HashMap<_, _>>();
 
-        // Ensure the key_field entry has the correct default values.
-        fields_by_name.insert(
-            key_field_name.0.clone(),
-            (key_field_name, key_field_config, key_field_type),
-        );
+        // Ensure the key_field entries have the correct default values.
+        for (key_field_name, key_field_config, key_field_type) in key_fields {
+            fields_by_name.insert(
+                key_field_name.0.clone(),
+                (key_field_name, key_field_config, key_field_type),
+            );
+        }
 
         // Iterate through all the configured fields to check for fields configured that don't
         // have a matching Postgres column (for features like multiple tokenizers).
@@ -484,12 +642,18 @@ impl SearchIndexCreateOptions {
             // on the configuration, telling us which column contains the data to index.
             if let Some(column) = json_config.get("column").and_then(|c| c.as_str()) {
                 if let Some((_, _, field_type)) = fields_by_name.get(column) {
+                    let field_type = match field_type {
+                        SearchFieldType::Text if facet_names.contains(&name) => {
+                            SearchFieldType::Facet
+                        }
+                        field_type => *field_type,
+                    };
                     fields_by_name.insert(
                         name.to_string(),
                         (
                             SearchFieldName(name.to_string()),
-                            Self::json_value_to_search_field_config(field_type, json_config),
-                            *field_type,
+                            Self::json_value_to_search_field_config(&field_type, json_config),
+                            field_type,
                         ),
                     );
                 }
@@ -501,6 +665,48 @@ impl SearchIndexCreateOptions {
         fields_by_name.into_values().collect()
     }
 
+    pub fn get_merge_policy(&self) -> MergePolicyConfig {
+        let merge_policy = self.get_str(self.merge_policy_offset, "".to_string());
+        if merge_policy.is_empty() {
+            return MergePolicyConfig::default();
+        }
+        MergePolicyConfig::from_option(&merge_policy)
+            .unwrap_or_else(|err| panic!("invalid merge_policy '{merge_policy}': {err}"))
+    }
+
+    /// How the index's stored fields are compressed.  Like `docstore_blocksize`, this is recorded
+    /// in the index's settings when it's built, so changing it takes effect on the next REINDEX
+    pub fn get_docstore_compression(&self) -> Compressor {
+        let docstore_compression = self.get_str(self.docstore_compression_offset, "".to_string());
+        if docstore_compression.is_empty() {
+            return Compressor::default();
+        }
+        parse_docstore_compression(&docstore_compression).unwrap_or_else(|err| {
+            panic!("invalid docstore_compression '{docstore_compression}': {err}")
+        })
+    }
+
+    /// The size, in bytes, of the blocks the index's stored fields are compressed in
+    pub fn get_docstore_blocksize(&self) -> usize {
+        if self.docstore_blocksize <= 0 {
+            return DEFAULT_DOCSTORE_BLOCKSIZE as usize;
+        }
+        self.docstore_blocksize as usize
+    }
+
+    /// Whether inserts are added to the pending list, rather than written as new segments
+    pub fn get_fastupdate(&self) -> bool {
+        self.fastupdate
+    }
+
+    /// The size, in bytes, the pending list can grow to before it's flushed into a segment
+    pub fn get_pending_list_limit(&self) -> usize {
+        if self.pending_list_limit <= 0 {
+            return DEFAULT_PENDING_LIST_LIMIT as usize * 1024;
+        }
+        self.pending_list_limit as usize * 1024
+    }
+
     fn get_str(&self, offset: i32, default: String) -> String {
         if offset == 0 {
             default
@@ -569,6 +775,15 @@ pub unsafe fn init() {
         Some(validate_datetime_fields),
         pg_sys::AccessExclusiveLock as pg_sys::LOCKMODE,
     );
+    pg_sys::add_string_reloption(
+        RELOPT_KIND_PDB,
+        "facet_fields".as_pg_cstr(),
+        "JSON string specifying which text or ltree fields should be indexed as facets"
+            .as_pg_cstr(),
+        std::ptr::null(),
+        Some(validate_facet_fields),
+        pg_sys::AccessExclusiveLock as pg_sys::LOCKMODE,
+    );
     pg_sys::add_string_reloption(
         RELOPT_KIND_PDB,
         "key_field".as_pg_cstr(),
@@ -577,4 +792,46 @@ pub unsafe fn init() {
         Some(validate_key_field),
         pg_sys::AccessExclusiveLock as pg_sys::LOCKMODE,
     );
+    pg_sys::add_string_reloption(
+        RELOPT_KIND_PDB,
+        "merge_policy".as_pg_cstr(),
+        "The policy, and its tunables, used to merge the index's segments".as_pg_cstr(),
+        std::ptr::null(),
+        Some(validate_merge_policy),
+        pg_sys::ShareUpdateExclusiveLock as pg_sys::LOCKMODE,
+    );
+    pg_sys::add_string_reloption(
+        RELOPT_KIND_PDB,
+        "docstore_compression".as_pg_cstr(),
+        "How stored fields are compressed: none, lz4, zstd, or zstd(<level>)".as_pg_cstr(),
+        std::ptr::null(),
+        Some(validate_docstore_compression),
+        pg_sys::AccessExclusiveLock as pg_sys::LOCKMODE,
+    );
+    pg_sys::add_int_reloption(
+        RELOPT_KIND_PDB,
+        "docstore_blocksize".as_pg_cstr(),
+        "The size, in bytes, of the blocks stored fields are compressed in".as_pg_cstr(),
+        DEFAULT_DOCSTORE_BLOCKSIZE,
+        1024,
+        1024 * 1024,
+        pg_sys::AccessExclusiveLock as pg_sys::LOCKMODE,
+    );
+    pg_sys::add_bool_reloption(
+        RELOPT_KIND_PDB,
+        "fastupdate".as_pg_cstr(),
+        "Add inserted rows to a pending list instead of writing a new segment for each transaction"
+            .as_pg_cstr(),
+        false,
+        pg_sys::ShareUpdateExclusiveLock as pg_sys::LOCKMODE,
+    );
+    pg_sys::add_int_reloption(
+        RELOPT_KIND_PDB,
+        "pending_list_limit".as_pg_cstr(),
+        "The size, in kilobytes, the pending list can grow to before it's flushed".as_pg_cstr(),
+        DEFAULT_PENDING_LIST_LIMIT,
+        64,
+        MAX_PENDING_LIST_LIMIT,
+        pg_sys::ShareUpdateExclusiveLock as pg_sys::LOCKMODE,
+    );
 }
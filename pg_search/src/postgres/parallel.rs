@@ -127,7 +127,8 @@ pub unsafe fn maybe_claim_segment(scan: pg_sys::IndexScanDesc) -> Option<Segment
     } else {
         // claim the next one
         state.remaining_segments -= 1;
-        Some(state.get_segment_id(state.remaining_segments as usize))
+        state.set_claimed_by_me(state.remaining_segments);
+        Some(state.get_segment_id(state.remaining_segments))
     }
 }
 
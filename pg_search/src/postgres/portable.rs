@@ -0,0 +1,642 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Moving a bm25 index's segments to and from a plain tantivy directory on the database server.
+//!
+//! [`export_index`] writes the visible segments' files, byte for byte, alongside a `meta.json`,
+//! so the result can be opened by any tantivy tooling.  [`import_index`] goes the other way,
+//! attaching segments built elsewhere.  Their "ctid" fast field can't be right, so it's rebuilt
+//! by looking up each document's key in the table's unique index on the key field.
+
+use crate::index::channel::ChannelDirectory;
+use crate::index::external::{ExternalDirectory, META_FILEPATH};
+use crate::index::fast_fields_helper::FFType;
+use crate::index::merge_policy::AllowedMergePolicy;
+use crate::index::pending::flush_pending;
+use crate::index::reader::index::SearchIndexReader;
+use crate::index::utils::load_schema;
+use crate::index::writer::index::{SearchIndexWriter, CHANNEL_QUEUE_LEN};
+use crate::index::{BlockDirectoryType, WriterResources};
+use crate::postgres::delete::DeadDocsQuery;
+use crate::postgres::types::TantivyValue;
+use crate::postgres::utils::{categorize_fields, item_pointer_to_u64, CategorizedFieldData};
+use crate::schema::{SearchField, SearchIndexSchema};
+use anyhow::{bail, Result};
+use pgrx::{check_for_interrupts, pg_sys, PgMemoryContexts, PgOid, PgRelation, PgTupleDesc};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::ffi::CStr;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use tantivy::columnar::{ColumnType, ColumnarReader, ColumnarWriter, DynamicColumn, RowId};
+use tantivy::directory::FileSlice;
+use tantivy::directory::TerminatingWrite;
+use tantivy::index::{SegmentComponent, SegmentId, SegmentMetaInventory};
+use tantivy::schema::OwnedValue;
+use tantivy::{Directory, DocId, HasLen, Index, Segment, SegmentReader};
+use tantivy_common::BitSet;
+
+/// How much of a file to hold in memory at once while copying it
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+pub struct ExportStats {
+    pub segments: i32,
+    pub num_docs: i64,
+    pub bytes: i64,
+}
+
+pub struct ImportStats {
+    pub segments: i32,
+    pub num_docs: i64,
+    pub rows_replaced: i64,
+}
+
+/// Error unless the current user has the privileges of `role_name`, which superusers always do
+unsafe fn check_role(role_name: &CStr, action: &str) -> Result<()> {
+    let role = pg_sys::get_role_oid(role_name.as_ptr(), false);
+    if !pg_sys::has_privs_of_role(pg_sys::GetUserId(), role) {
+        bail!(
+            "must be superuser or have privileges of the {} role to {action}",
+            role_name.to_string_lossy()
+        );
+    }
+    Ok(())
+}
+
+/// Error unless the current user owns `index`, the same as REINDEX requires
+unsafe fn check_owner(index: &PgRelation) -> Result<()> {
+    #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+    let is_owner = pg_sys::pg_class_ownercheck(index.oid(), pg_sys::GetUserId());
+    #[cfg(any(feature = "pg16", feature = "pg17"))]
+    let is_owner =
+        pg_sys::object_ownercheck(pg_sys::RelationRelationId, index.oid(), pg_sys::GetUserId());
+
+    if !is_owner {
+        bail!("must be owner of index {}", index.name());
+    }
+    Ok(())
+}
+
+/// Error unless the current user can SELECT from `heap`, whose contents the index holds
+unsafe fn check_select(heap: &PgRelation) -> Result<()> {
+    let result = pg_sys::pg_class_aclcheck(
+        heap.oid(),
+        pg_sys::GetUserId(),
+        pg_sys::ACL_SELECT as pg_sys::AclMode,
+    );
+    if result != pg_sys::AclResult::ACLCHECK_OK {
+        bail!("permission denied for table {}", heap.name());
+    }
+    Ok(())
+}
+
+fn copy_file(source: &FileSlice, target: &mut dyn Write) -> std::io::Result<u64> {
+    let len = source.len();
+    for start in (0..len).step_by(COPY_CHUNK_SIZE) {
+        let end = (start + COPY_CHUNK_SIZE).min(len);
+        target.write_all(source.read_bytes_slice(start..end)?.as_slice())?;
+    }
+    Ok(len as u64)
+}
+
+/// Write the index's visible segments, and a `meta.json` describing them, to `path`, after
+/// flushing its pending list into a segment of its own.
+///
+/// Callers must hold a lock that allows writing to `index`.
+pub unsafe fn export_index(index: &PgRelation, path: &Path) -> Result<ExportStats> {
+    check_role(c"pg_write_server_files", "export an index")?;
+    check_select(
+        &index
+            .heap_relation()
+            .expect("index should have a heap relation"),
+    )?;
+
+    std::fs::create_dir_all(path)?;
+    if std::fs::read_dir(path)?.next().is_some() {
+        bail!("cannot export to `{}`: it is not empty", path.display());
+    }
+
+    // documents in the pending list aren't in any segment yet, so put them in one
+    flush_pending(index, true)?;
+    let directory = BlockDirectoryType::Mvcc.directory(index, AllowedMergePolicy::None);
+    let meta = directory.load_metas(&SegmentMetaInventory::default())?;
+
+    let mut stats = ExportStats {
+        segments: meta.segments.len() as i32,
+        num_docs: 0,
+        bytes: 0,
+    };
+    for segment_meta in &meta.segments {
+        stats.num_docs += segment_meta.num_docs() as i64;
+
+        // these are the files as stored, tantivy's footer and all
+        for file in segment_meta.list_files() {
+            check_for_interrupts!();
+            let source = FileSlice::new(directory.get_file_handle(&file)?);
+            let mut target = std::io::BufWriter::new(std::fs::File::create(path.join(&file))?);
+            stats.bytes += copy_file(&source, &mut target)? as i64;
+            target.flush()?;
+        }
+    }
+
+    let meta_json = serde_json::to_string_pretty(&meta)?;
+    std::fs::write(path.join(META_FILEPATH), meta_json)?;
+
+    Ok(stats)
+}
+
+/// The rows a key matched
+enum KeyMatch {
+    None,
+    One(u64),
+    Many,
+}
+
+/// Finds the row of each key through a unique index on the key field columns, so that matching
+/// the imported documents takes an index probe each, rather than the key of every row in memory
+struct KeyLookup {
+    snapshot: pg_sys::Snapshot,
+    scan: pg_sys::IndexScanDesc,
+    slot: *mut pg_sys::TupleTableSlot,
+    scan_keys: Vec<pg_sys::ScanKeyData>,
+
+    /// For each scan key, the position of its column in a document's key, and its type
+    key_columns: Vec<(usize, PgOid)>,
+    memctx: PgMemoryContexts,
+}
+
+impl KeyLookup {
+    /// `key_fields` are in the order of a document's key
+    unsafe fn open(
+        heap: &PgRelation,
+        index: &PgRelation,
+        key_fields: &[(SearchField, CategorizedFieldData)],
+    ) -> Result<Self> {
+        let index_attnums = (*index.rd_index)
+            .indkey
+            .values
+            .as_slice((*index.rd_index).indnatts as usize);
+        let key_attnums = key_fields
+            .iter()
+            .map(|(_, CategorizedFieldData { attno, .. })| index_attnums[*attno])
+            .collect::<Vec<_>>();
+
+        let unique_index = heap
+            .indices(pg_sys::AccessShareLock as _)
+            .find(|candidate| {
+                let rd_index = &*candidate.rd_index;
+                let attnums = rd_index
+                    .indkey
+                    .values
+                    .as_slice(rd_index.indnkeyatts as usize);
+                rd_index.indisunique
+                    && rd_index.indisvalid
+                    && pg_sys::RelationGetIndexPredicate(candidate.as_ptr()).is_null()
+                    && attnums.len() == key_attnums.len()
+                    && attnums
+                        .iter()
+                        .all(|attnum| *attnum != 0 && key_attnums.contains(attnum))
+            });
+        let Some(unique_index) = unique_index else {
+            bail!(
+                "importing into index \"{}\" requires a unique index on its key field",
+                index.name()
+            );
+        };
+
+        let nkeys = (*unique_index.rd_index).indnkeyatts as usize;
+        let attnums = (*unique_index.rd_index).indkey.values.as_slice(nkeys);
+        let mut scan_keys = vec![pg_sys::ScanKeyData::default(); nkeys];
+        let mut key_columns = Vec::with_capacity(nkeys);
+        for (i, scan_key) in scan_keys.iter_mut().enumerate() {
+            let position = key_attnums
+                .iter()
+                .position(|attnum| *attnum == attnums[i])
+                .expect("unique index column should be a key field");
+            key_columns.push((position, key_fields[position].1.base_oid));
+
+            let opfamily = *unique_index.rd_opfamily.add(i);
+            let opcintype = *unique_index.rd_opcintype.add(i);
+            let operator = pg_sys::get_opfamily_member(
+                opfamily,
+                opcintype,
+                opcintype,
+                pg_sys::BTEqualStrategyNumber as _,
+            );
+            if operator == pg_sys::InvalidOid {
+                bail!(
+                    "unique index \"{}\" has no equality operator for its column {}",
+                    unique_index.name(),
+                    i + 1
+                );
+            }
+            pg_sys::ScanKeyEntryInitialize(
+                scan_key,
+                0,
+                (i + 1) as pg_sys::AttrNumber,
+                pg_sys::BTEqualStrategyNumber as _,
+                pg_sys::InvalidOid,
+                *unique_index.rd_indcollation.add(i),
+                pg_sys::get_opcode(operator),
+                pg_sys::Datum::null(),
+            );
+        }
+
+        // with writers kept out, the latest snapshot sees every row that's still live
+        let snapshot = pg_sys::RegisterSnapshot(pg_sys::GetLatestSnapshot());
+        let scan = pg_sys::index_beginscan(
+            heap.as_ptr(),
+            unique_index.as_ptr(),
+            snapshot,
+            nkeys as _,
+            0,
+        );
+        let slot = pg_sys::MakeSingleTupleTableSlot(
+            heap.rd_att,
+            pg_sys::table_slot_callbacks(heap.as_ptr()),
+        );
+
+        Ok(Self {
+            snapshot,
+            scan,
+            slot,
+            scan_keys,
+            key_columns,
+            memctx: PgMemoryContexts::new("pg_search_import_index"),
+        })
+    }
+
+    /// Find the live row with `key`, identified by the root of its HOT chain like the index does
+    unsafe fn lookup(&mut self, key: &[TantivyValue]) -> Result<KeyMatch> {
+        self.memctx.reset();
+        for (scan_key, (position, oid)) in self.scan_keys.iter_mut().zip(&self.key_columns) {
+            let value = key[*position].clone();
+            let datum = self
+                .memctx
+                .switch_to(|_| value.try_into_datum(*oid))?
+                .expect("document key should not be NULL");
+            scan_key.sk_argument = datum;
+        }
+        pg_sys::index_rescan(
+            self.scan,
+            self.scan_keys.as_mut_ptr(),
+            self.scan_keys.len() as _,
+            std::ptr::null_mut(),
+            0,
+        );
+
+        let mut matched = KeyMatch::None;
+        loop {
+            check_for_interrupts!();
+            let tid =
+                pg_sys::index_getnext_tid(self.scan, pg_sys::ScanDirection::ForwardScanDirection);
+            if tid.is_null() {
+                break;
+            }
+
+            // fetching the row moves the scan's tid to whichever member of the chain is visible
+            let ctid = item_pointer_to_u64(*tid);
+            if !pg_sys::index_fetch_heap(self.scan, self.slot) {
+                continue;
+            }
+            matched = match matched {
+                KeyMatch::None => KeyMatch::One(ctid),
+                KeyMatch::One(_) | KeyMatch::Many => KeyMatch::Many,
+            };
+        }
+        Ok(matched)
+    }
+}
+
+impl Drop for KeyLookup {
+    fn drop(&mut self) {
+        unsafe {
+            if crate::postgres::utils::IsTransactionState() {
+                pg_sys::ExecDropSingleTupleTableSlot(self.slot);
+                pg_sys::index_endscan(self.scan);
+                pg_sys::UnregisterSnapshot(self.snapshot);
+            }
+        }
+    }
+}
+
+/// The key of `doc`, or `None` if it's a nested child document, which has no key
+fn doc_key(key_columns: &[FFType], doc: DocId) -> Option<Vec<TantivyValue>> {
+    key_columns
+        .iter()
+        .map(|column| match column {
+            FFType::Text(_) => {
+                let mut value = String::new();
+                column.string(doc, &mut value)?;
+                Some(TantivyValue(value.into()))
+            }
+            _ => Some(column.value(doc)).filter(|value| value.0 != OwnedValue::Null),
+        })
+        .collect()
+}
+
+/// The ctid of every doc in `segment_reader`, found by looking up its key.  Deleted docs get
+/// a ctid of zero, and nested children get the ctid of the parent that follows them.
+unsafe fn remapped_ctids(
+    segment_reader: &SegmentReader,
+    key_names: &[String],
+    key_lookup: &mut KeyLookup,
+) -> Result<Vec<u64>> {
+    let key_columns = key_names
+        .iter()
+        .map(|name| FFType::new(segment_reader.fast_fields(), name))
+        .collect::<Vec<_>>();
+    let alive_bitset = segment_reader.alive_bitset();
+
+    let mut remapped = vec![0; segment_reader.max_doc() as usize];
+    let mut parent_ctid = None;
+    for doc in (0..segment_reader.max_doc()).rev() {
+        check_for_interrupts!();
+        if !alive_bitset.map_or(true, |bitset| bitset.is_alive(doc)) {
+            continue;
+        }
+
+        let ctid = match doc_key(&key_columns, doc) {
+            Some(key) => match key_lookup.lookup(&key)? {
+                KeyMatch::One(ctid) => ctid,
+                KeyMatch::Many => bail!(
+                    "segment {} has a document whose key {key:?} matches more than one row",
+                    segment_reader.segment_id().short_uuid_string()
+                ),
+                KeyMatch::None => bail!(
+                    "segment {} has a document whose key {key:?} doesn't match any row",
+                    segment_reader.segment_id().short_uuid_string()
+                ),
+            },
+            None => match parent_ctid {
+                Some(ctid) => ctid,
+                None => bail!(
+                    "segment {} has a document with no key",
+                    segment_reader.segment_id().short_uuid_string()
+                ),
+            },
+        };
+
+        remapped[doc as usize] = ctid;
+        parent_ctid = Some(ctid);
+    }
+
+    Ok(remapped)
+}
+
+/// Rewrite `source`'s fast fields to `target`, with `ctids` in place of its "ctid" column
+fn write_remapped_fast_fields(
+    source: &Segment,
+    ctids: &[u64],
+    target: &mut dyn Write,
+) -> tantivy::Result<()> {
+    let columnar = ColumnarReader::open(source.open_read(SegmentComponent::FastFields)?)?;
+    let mut writer = ColumnarWriter::default();
+
+    for (column_name, handle) in columnar.list_columns()? {
+        if column_name == "ctid" {
+            continue;
+        }
+
+        writer.record_column_type(&column_name, handle.column_type(), false);
+        let name = column_name.as_str();
+        match handle.open()? {
+            DynamicColumn::Bool(column) => {
+                for doc in 0..columnar.num_rows() {
+                    for value in column.values_for_doc(doc) {
+                        writer.record_bool(doc, name, value);
+                    }
+                }
+            }
+            DynamicColumn::I64(column) => {
+                for doc in 0..columnar.num_rows() {
+                    for value in column.values_for_doc(doc) {
+                        writer.record_numerical(doc, name, value);
+                    }
+                }
+            }
+            DynamicColumn::U64(column) => {
+                for doc in 0..columnar.num_rows() {
+                    for value in column.values_for_doc(doc) {
+                        writer.record_numerical(doc, name, value);
+                    }
+                }
+            }
+            DynamicColumn::F64(column) => {
+                for doc in 0..columnar.num_rows() {
+                    for value in column.values_for_doc(doc) {
+                        writer.record_numerical(doc, name, value);
+                    }
+                }
+            }
+            DynamicColumn::IpAddr(column) => {
+                for doc in 0..columnar.num_rows() {
+                    for value in column.values_for_doc(doc) {
+                        writer.record_ip_addr(doc, name, value);
+                    }
+                }
+            }
+            DynamicColumn::DateTime(column) => {
+                for doc in 0..columnar.num_rows() {
+                    for value in column.values_for_doc(doc) {
+                        writer.record_datetime(doc, name, value);
+                    }
+                }
+            }
+            DynamicColumn::Bytes(column) => {
+                let mut value = vec![];
+                for doc in 0..columnar.num_rows() {
+                    for ord in column.term_ords(doc) {
+                        column.ord_to_bytes(ord, &mut value)?;
+                        writer.record_bytes(doc, name, &value);
+                    }
+                }
+            }
+            DynamicColumn::Str(column) => {
+                let mut value = String::new();
+                for doc in 0..columnar.num_rows() {
+                    for ord in column.term_ords(doc) {
+                        column.ord_to_str(ord, &mut value)?;
+                        writer.record_str(doc, name, &value);
+                    }
+                }
+            }
+        }
+    }
+
+    writer.record_column_type("ctid", ColumnType::U64, false);
+    for (doc, ctid) in ctids.iter().enumerate() {
+        writer.record_numerical(doc as RowId, "ctid", *ctid);
+    }
+
+    writer.serialize(ctids.len() as RowId, target)?;
+    Ok(())
+}
+
+/// Copy each of `segments` into the index behind `directory` as a new segment, and commit
+/// them all at once.  This runs off the main thread, so must not touch Postgres.
+fn attach_segments(
+    directory: ChannelDirectory,
+    segments: Vec<(Segment, Vec<u64>)>,
+) -> tantivy::Result<()> {
+    let target = Index::open(directory)?;
+    let previous_meta = target.load_metas()?;
+    let mut new_meta = previous_meta.clone();
+
+    for (source, ctids) in segments {
+        let source_meta = source.meta();
+        let mut segment_meta =
+            target.new_segment_meta(SegmentId::generate_random(), ctids.len() as u32);
+        if let Some(delete_opstamp) = source_meta.delete_opstamp() {
+            segment_meta =
+                segment_meta.with_delete_meta(source_meta.num_deleted_docs(), delete_opstamp);
+        }
+
+        let mut segment = target.segment(segment_meta.clone());
+        for component in SegmentComponent::iterator() {
+            let source_path = source_meta.relative_path(*component);
+            if matches!(component, SegmentComponent::TempStore)
+                || !source.index().directory().exists(&source_path)?
+            {
+                continue;
+            }
+
+            // tantivy checks, strips and replaces each file's footer on the way through
+            let mut writer = segment.open_write(*component)?;
+            match component {
+                SegmentComponent::FastFields => {
+                    write_remapped_fast_fields(&source, &ctids, &mut writer)?
+                }
+                _ => {
+                    copy_file(&source.open_read(*component)?, &mut writer)?;
+                }
+            }
+            writer.terminate()?;
+        }
+
+        new_meta.segments.push(segment_meta);
+    }
+
+    target
+        .directory()
+        .save_metas(&new_meta, &previous_meta, &mut ())
+}
+
+/// Attach the segments of the tantivy index at `path` to the index.  Every document's key must
+/// match exactly one live row of `heap`, found through a unique index on the key field, and
+/// whatever the index already had for those rows is deleted.
+///
+/// Callers must hold a lock that keeps writers out of `heap`.
+pub unsafe fn import_index(
+    heap: &PgRelation,
+    index: &PgRelation,
+    path: &Path,
+) -> Result<ImportStats> {
+    check_role(c"pg_read_server_files", "import an index")?;
+    check_owner(index)?;
+
+    let source = Index::open(ExternalDirectory::open(path)?)?;
+    let schema = load_schema(index.oid())?.schema;
+    if serde_json::to_value(source.schema())? != serde_json::to_value(&schema)? {
+        bail!(
+            "the schema in `{}` does not match index \"{}\"",
+            path.join(META_FILEPATH).display(),
+            index.name()
+        );
+    }
+    let schema = SearchIndexSchema::open(schema, index);
+
+    // find each document's row by its key
+    let key_names = schema
+        .key_fields()
+        .into_iter()
+        .map(|field| field.name.0)
+        .collect::<Vec<_>>();
+    let tupdesc = PgTupleDesc::from_pg_unchecked(index.rd_att);
+    let mut key_fields = categorize_fields(&tupdesc, &schema)
+        .into_iter()
+        .filter(|(field, _)| key_names.contains(&field.name.0))
+        .collect::<Vec<_>>();
+    key_fields.sort_by_key(|(field, _)| key_names.iter().position(|name| name == &field.name.0));
+    let mut key_lookup = KeyLookup::open(heap, index, &key_fields)?;
+
+    let mut segments = vec![];
+    let mut imported_ctids = FxHashSet::default();
+    let mut num_docs = 0;
+    for segment in source.searchable_segments()? {
+        let segment_reader = SegmentReader::open(&segment)?;
+        let ctids = remapped_ctids(&segment_reader, &key_names, &mut key_lookup)?;
+        imported_ctids.extend(
+            segment_reader
+                .doc_ids_alive()
+                .map(|doc| ctids[doc as usize]),
+        );
+        num_docs += segment_reader.num_docs() as i64;
+        segments.push((segment, ctids));
+    }
+    drop(key_lookup);
+
+    // delete whatever the index already has for the imported rows, which means anything in the
+    // pending list needs to be in a segment first
+    flush_pending(index, true)?;
+    let search_reader = SearchIndexReader::open(index, BlockDirectoryType::Mvcc, false)?;
+    let mut replaced = FxHashMap::<SegmentId, BitSet>::default();
+    let mut rows_replaced = FxHashSet::default();
+    for segment_reader in search_reader.segment_readers() {
+        let ctid_column = segment_reader.fast_fields().u64("ctid")?;
+        let mut bitset = BitSet::with_max_value(segment_reader.max_doc());
+        for doc in segment_reader.doc_ids_alive() {
+            check_for_interrupts!();
+            if let Some(ctid) = ctid_column.first(doc) {
+                if imported_ctids.contains(&ctid) {
+                    bitset.insert(doc);
+                    rows_replaced.insert(ctid);
+                }
+            }
+        }
+        if bitset.len() > 0 {
+            replaced.insert(segment_reader.segment_id(), bitset);
+        }
+    }
+    drop(search_reader);
+
+    if !replaced.is_empty() {
+        let mut writer =
+            SearchIndexWriter::open(index, BlockDirectoryType::Mvcc, WriterResources::Statement)?;
+        writer.delete_query(Box::new(DeadDocsQuery(Arc::new(replaced))))?;
+        writer.commit()?;
+    }
+
+    let stats = ImportStats {
+        segments: segments.len() as i32,
+        num_docs,
+        rows_replaced: rows_replaced.len() as i64,
+    };
+
+    let (req_sender, req_receiver) = crossbeam::channel::bounded(CHANNEL_QUEUE_LEN);
+    let channel_dir = ChannelDirectory::new(req_sender);
+    let mut handler = BlockDirectoryType::Mvcc.channel_request_handler(
+        index,
+        req_receiver,
+        AllowedMergePolicy::None,
+    );
+    handler
+        .wait_for(move || attach_segments(channel_dir, segments))
+        .expect("spawned thread should not fail")?;
+
+    Ok(stats)
+}
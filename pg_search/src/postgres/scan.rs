@@ -15,14 +15,45 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::index::fast_fields_helper::FFHelper;
+use crate::index::fast_fields_helper::{FFHelper, FastFieldType, WhichFastField};
 use crate::index::reader::index::{SearchIndexReader, SearchResults};
 use crate::index::BlockDirectoryType;
-use crate::postgres::options::SearchIndexCreateOptions;
+use crate::postgres::index::get_fields;
 use crate::postgres::{parallel, ScanStrategy};
 use crate::query::SearchQueryInput;
+use crate::schema::{SearchFieldConfig, SearchFieldName};
 use pgrx::pg_sys::IndexScanDesc;
 use pgrx::*;
+use tantivy::index::SegmentId;
+use tokenizers::SearchNormalizer;
+
+/// The types of the index attributes an Index Only Scan can return, when they're fast fields.  These
+/// are basically pass-by-value (non tokenized) data types and text.  We index UUID as a string, but
+/// it's beneficial to support returning it due to Parallel Index Only Scans
+const RETURNABLE_TYPES: [pg_sys::Oid; 9] = [
+    pg_sys::INT2OID,
+    pg_sys::INT4OID,
+    pg_sys::INT8OID,
+    pg_sys::FLOAT4OID,
+    pg_sys::FLOAT8OID,
+    pg_sys::BOOLOID,
+    pg_sys::UUIDOID,
+    pg_sys::TEXTOID,
+    pg_sys::VARCHAROID,
+];
+
+/// Is a value of this (returnable) type passed by reference?
+fn is_by_reference(typoid: PgOid) -> bool {
+    [pg_sys::UUIDOID, pg_sys::TEXTOID, pg_sys::VARCHAROID].contains(&typoid.value())
+}
+
+/// Does a value of this (returnable) type vary in size?
+fn is_varlena(typoid: PgOid) -> bool {
+    [pg_sys::TEXTOID, pg_sys::VARCHAROID].contains(&typoid.value())
+}
+
+/// How many documents an ordered scan collects, in score order, before it needs to search again
+const ORDERED_SCAN_CHUNK_SIZE: usize = 100;
 
 pub struct Bm25ScanState {
     need_scores: bool,
@@ -31,7 +62,16 @@ pub struct Bm25ScanState {
     search_query_input: SearchQueryInput,
     results: SearchResults,
     itup: (Vec<pg_sys::Datum>, Vec<bool>),
-    key_field_oid: PgOid,
+    returned_fields: Vec<(usize, PgOid)>,
+    varlena_fields: bool,
+    ordered: Option<OrderedScan>,
+}
+
+/// An index scan that returns rows in score order, for an `ORDER BY <=>@@` clause
+struct OrderedScan {
+    segment_ids: Vec<SegmentId>,
+    limit: usize,
+    returned: usize,
 }
 
 #[pg_guard]
@@ -47,6 +87,15 @@ pub extern "C" fn ambeginscan(
         // this one time
         (*scandesc).xs_hitupdesc = (*indexrel).rd_att;
 
+        if norderbys > 0 {
+            // it's up to us to allocate the space for the ORDER BY values we return
+            let norderbys = norderbys as usize;
+            (*scandesc).xs_orderbyvals =
+                pg_sys::palloc0(norderbys * std::mem::size_of::<pg_sys::Datum>()).cast();
+            (*scandesc).xs_orderbynulls =
+                pg_sys::palloc0(norderbys * std::mem::size_of::<bool>()).cast();
+        }
+
         scandesc
     }
 }
@@ -57,8 +106,8 @@ pub extern "C" fn amrescan(
     scan: pg_sys::IndexScanDesc,
     keys: pg_sys::ScanKey,
     nkeys: ::std::os::raw::c_int,
-    _orderbys: pg_sys::ScanKey,
-    _norderbys: ::std::os::raw::c_int,
+    orderbys: pg_sys::ScanKey,
+    norderbys: ::std::os::raw::c_int,
 ) {
     fn key_to_search_query_input(key: &pg_sys::ScanKeyData) -> SearchQueryInput {
         match ScanStrategy::try_from(key.sk_strategy).expect("`key.sk_strategy` is unrecognized") {
@@ -75,33 +124,93 @@ pub extern "C" fn amrescan(
                 SearchQueryInput::from_datum(key.sk_argument, false)
                     .expect("ScanKey.sk_argument must not be null")
             },
+            ScanStrategy::TextScore | ScanStrategy::SearchQueryInputScore => {
+                panic!("`<=>@@` can only be used in an ORDER BY clause")
+            }
+        }
+    }
+
+    fn orderby_to_search_query_input(
+        indexrel: &PgRelation,
+        orderby: &pg_sys::ScanKeyData,
+    ) -> SearchQueryInput {
+        match ScanStrategy::try_from(orderby.sk_strategy)
+            .expect("`orderby.sk_strategy` is unrecognized")
+        {
+            ScanStrategy::TextScore => unsafe {
+                // like `@@@`, the text is a query against the field on the lhs of `<=>@@`
+                let field = indexrel
+                    .tuple_desc()
+                    .get(orderby.sk_attno as usize - 1)
+                    .expect("ORDER BY attribute should exist in the index")
+                    .name()
+                    .to_string();
+                let query_string = String::from_datum(orderby.sk_argument, false)
+                    .expect("ScanKey.sk_argument must not be null");
+                SearchQueryInput::ParseWithField {
+                    field,
+                    query_string,
+                    lenient: None,
+                    conjunction_mode: None,
+                }
+            },
+            ScanStrategy::SearchQueryInputScore => unsafe {
+                SearchQueryInput::from_datum(orderby.sk_argument, false)
+                    .expect("ScanKey.sk_argument must not be null")
+            },
+            ScanStrategy::TextQuery | ScanStrategy::SearchQueryInput => {
+                panic!("`@@@` cannot be used in an ORDER BY clause")
+            }
         }
     }
 
-    let (indexrel, keys) = unsafe {
+    let (indexrel, keys, orderbys) = unsafe {
         // SAFETY:  assert the pointers we're going to use are non-null
         assert!(!scan.is_null());
         assert!(!(*scan).indexRelation.is_null());
-        assert!(!keys.is_null());
-        assert!(nkeys > 0); // Ensure there's at least one key provided for the search.
+        assert!(!keys.is_null() || nkeys == 0);
+        assert!(!orderbys.is_null() || norderbys == 0);
+        assert!(nkeys > 0 || norderbys > 0); // Ensure there's at least a search or an ordering.
 
         let indexrel = (*scan).indexRelation;
-        let keys = std::slice::from_raw_parts(keys as *const pg_sys::ScanKeyData, nkeys as usize);
+        let keys = if nkeys > 0 {
+            std::slice::from_raw_parts(keys as *const pg_sys::ScanKeyData, nkeys as usize)
+        } else {
+            &[]
+        };
+        let orderbys = if norderbys > 0 {
+            std::slice::from_raw_parts(orderbys as *const pg_sys::ScanKeyData, norderbys as usize)
+        } else {
+            &[]
+        };
 
-        (PgRelation::from_pg(indexrel), keys)
+        (PgRelation::from_pg(indexrel), keys, orderbys)
     };
 
     // build a Boolean "must" clause of all the ScanKeys
-    let mut search_query_input = key_to_search_query_input(&keys[0]);
-    for key in &keys[1..] {
-        let key = key_to_search_query_input(key);
-
-        search_query_input = SearchQueryInput::Boolean {
-            must: vec![search_query_input, key],
-            should: vec![],
+    let search_query_input =
+        keys.iter()
+            .map(key_to_search_query_input)
+            .reduce(|a, b| SearchQueryInput::Boolean {
+                must: vec![a, b],
+                should: vec![],
+                must_not: vec![],
+            });
+
+    // an ordered scan returns every document the ScanKeys match, scored only by the ORDER BY
+    // query.  those that don't match it come last, with a score of zero
+    let search_query_input = match orderbys {
+        [] => search_query_input.expect("there should be at least one ScanKey"),
+        [orderby] => SearchQueryInput::Boolean {
+            must: vec![SearchQueryInput::ConstScore {
+                query: Box::new(search_query_input.unwrap_or(SearchQueryInput::All)),
+                score: 0.0,
+            }],
+            should: vec![orderby_to_search_query_input(&indexrel, orderby)],
             must_not: vec![],
-        };
-    }
+        },
+        _ => panic!("a bm25 index scan can only be ordered by one `<=>@@` expression"),
+    };
 
     // Create the index and scan state
     let search_reader = SearchIndexReader::open(&indexrel, BlockDirectoryType::Mvcc, unsafe {
@@ -111,15 +220,33 @@ pub extern "C" fn amrescan(
     unsafe {
         parallel::maybe_init_parallel_scan(scan, &search_reader);
 
-        let options = (*(*scan).indexRelation).rd_options as *mut SearchIndexCreateOptions;
-        let key_field = (*options)
-            .get_key_field()
-            .expect("bm25 index should have a key_field")
-            .0;
-        let key_field_type = search_reader.key_field().type_.into();
+        let ordered = (!orderbys.is_empty()).then(|| {
+            let segment_ids = if (*scan).parallel_scan.is_null() {
+                search_reader
+                    .segment_readers()
+                    .iter()
+                    .map(|segment_reader| segment_reader.segment_id())
+                    .collect()
+            } else {
+                // each participant's rows must be in score order for them to be merged, so it
+                // claims every segment it can and searches them together
+                std::iter::from_fn(|| parallel::maybe_claim_segment(scan)).collect()
+            };
+            OrderedScan {
+                segment_ids,
+                limit: ORDERED_SCAN_CHUNK_SIZE,
+                returned: 0,
+            }
+        });
 
-        let need_scores = search_query_input.contains_more_like_this();
-        let results = if (*scan).parallel_scan.is_null() {
+        let need_scores = ordered.is_some() || search_query_input.contains_more_like_this();
+        let results = if let Some(ordered) = &ordered {
+            search_reader.search_top_n_in_segments(
+                &ordered.segment_ids,
+                &search_query_input,
+                ordered.limit,
+            )
+        } else if (*scan).parallel_scan.is_null() {
             // not a parallel scan
             search_reader.search(
                 need_scores,
@@ -137,19 +264,39 @@ pub extern "C" fn amrescan(
 
         let natts = (*(*scan).xs_hitupdesc).natts as usize;
         let scan_state = if (*scan).xs_want_itup {
+            // the index attributes we can return, alongside the fast fields that hold their values
+            let attrs = (*(*scan).xs_hitupdesc).attrs.as_slice(natts);
+            let (returned_fields, fast_fields): (Vec<_>, Vec<WhichFastField>) = attrs
+                .iter()
+                .enumerate()
+                .filter(|(_, attr)| RETURNABLE_TYPES.contains(&attr.atttypid))
+                .filter_map(|(attno, attr)| {
+                    let schema = search_reader.schema();
+                    if !schema.is_fast_field(attr.name()) {
+                        return None;
+                    }
+                    let field =
+                        schema.get_search_field(&SearchFieldName(attr.name().to_string()))?;
+                    Some((
+                        (attno, PgOid::from(attr.atttypid)),
+                        (field.name.0.as_str(), FastFieldType::from(field.type_)).into(),
+                    ))
+                })
+                .unzip();
+            let varlena_fields = returned_fields
+                .iter()
+                .any(|(_, typoid)| is_varlena(*typoid));
+
             Bm25ScanState {
                 need_scores,
-                fast_fields: FFHelper::with_fields(
-                    &search_reader,
-                    &[(key_field, key_field_type).into()],
-                ),
+                fast_fields: FFHelper::with_fields(&search_reader, &fast_fields),
                 reader: search_reader,
                 search_query_input,
                 results,
                 itup: (vec![pg_sys::Datum::null(); natts], vec![true; natts]),
-                key_field_oid: PgOid::from(
-                    (*(*scan).xs_hitupdesc).attrs.as_slice(natts)[0].atttypid,
-                ),
+                returned_fields,
+                varlena_fields,
+                ordered,
             }
         } else {
             Bm25ScanState {
@@ -159,7 +306,9 @@ pub extern "C" fn amrescan(
                 search_query_input,
                 results,
                 itup: (vec![], vec![]),
-                key_field_oid: PgOid::Invalid,
+                returned_fields: vec![],
+                varlena_fields: false,
+                ordered,
             }
         };
 
@@ -191,6 +340,7 @@ pub unsafe extern "C" fn amgettuple(
     };
 
     (*scan).xs_recheck = false;
+    (*scan).xs_recheckorderby = false;
 
     loop {
         match state.results.next() {
@@ -198,32 +348,47 @@ pub unsafe extern "C" fn amgettuple(
                 let ipd = &mut (*scan).xs_heaptid;
                 crate::postgres::utils::u64_to_item_pointer(scored.ctid, ipd);
 
+                if let Some(ordered) = state.ordered.as_mut() {
+                    // the most relevant rows are the "closest", so they sort first
+                    let distance = -scored.bm25;
+                    *(*scan).xs_orderbyvals = distance.into_datum().unwrap();
+                    *(*scan).xs_orderbynulls = false;
+                    ordered.returned += 1;
+                }
+
                 if (*scan).xs_want_itup {
-                    let key = state
-                        .fast_fields
-                        .value(0, doc_address)
-                        .expect("key_field should be a fast_field");
-                    match key
-                        .try_into_datum(state.key_field_oid)
-                        .expect("key_field value should convert to a Datum")
-                    {
-                        // got a valid Datum
-                        Some(key_field_datum) => {
-                            state.itup.0[0] = key_field_datum;
-                            state.itup.1[0] = false;
-                        }
+                    for (i, (attno, typoid)) in state.returned_fields.iter().enumerate() {
+                        let value = state
+                            .fast_fields
+                            .value(i, doc_address)
+                            .expect("returned field should be a fast_field");
+                        match value
+                            .try_into_datum(*typoid)
+                            .expect("returned field value should convert to a Datum")
+                        {
+                            // got a valid Datum
+                            Some(datum) => {
+                                state.itup.0[*attno] = datum;
+                                state.itup.1[*attno] = false;
+                            }
 
-                        // we got a NULL for the key_field.  Highly unlikely but definitely possible
-                        None => {
-                            state.itup.0[0] = pg_sys::Datum::null();
-                            state.itup.1[0] = true;
+                            // we got a NULL for the field
+                            None => {
+                                state.itup.0[*attno] = pg_sys::Datum::null();
+                                state.itup.1[*attno] = true;
+                            }
                         }
                     }
 
                     let values = state.itup.0.as_mut_ptr();
                     let nulls = state.itup.1.as_mut_ptr();
 
-                    if (*scan).xs_hitup.is_null() {
+                    if (*scan).xs_hitup.is_null() || state.varlena_fields {
+                        // a varlena value's size varies from row to row, so we can't reuse the
+                        // previous tuple to hold it
+                        if !(*scan).xs_hitup.is_null() {
+                            pg_sys::heap_freetuple((*scan).xs_hitup);
+                        }
                         (*scan).xs_hitup =
                             pg_sys::heap_form_tuple((*scan).xs_hitupdesc, values, nulls);
                     } else {
@@ -248,7 +413,6 @@ pub unsafe extern "C" fn amgettuple(
                                 heap_compute_data_size((*scan).xs_hitupdesc, values, nulls);
                             let td = (*(*scan).xs_hitup).t_data;
 
-                            // TODO:  seems like this could crash with a varlena "key_field" of varrying sizes per row
                             heap_fill_tuple(
                                 (*scan).xs_hitupdesc,
                                 values,
@@ -260,12 +424,24 @@ pub unsafe extern "C" fn amgettuple(
                             );
                         });
                     }
+
+                    // the tuple has its own copy of every by-reference value
+                    for (attno, typoid) in &state.returned_fields {
+                        if !state.itup.1[*attno] && is_by_reference(*typoid) {
+                            pg_sys::pfree(state.itup.0[*attno].cast_mut_ptr());
+                        }
+                    }
                 }
 
                 return true;
             }
             None => {
-                if search_next_segment(scan, state) {
+                if state.ordered.is_some() {
+                    if search_next_chunk(state) {
+                        // loop back around to start returning results from the bigger chunk
+                        continue;
+                    }
+                } else if search_next_segment(scan, state) {
                     // loop back around to start returning results from this segment
                     continue;
                 }
@@ -330,14 +506,28 @@ unsafe fn search_next_segment(scan: IndexScanDesc, state: &mut Bm25ScanState) ->
     false
 }
 
-#[pg_guard]
-pub extern "C" fn amcanreturn(indexrel: pg_sys::Relation, attno: i32) -> bool {
-    if attno != 1 {
-        // currently, we only support returning the "key_field", which will always be the first
-        // index attribute
+// an ordered scan that returned every document in its chunk may have more to return, so search
+// again for a chunk twice the size, skipping the documents that have already been returned
+fn search_next_chunk(state: &mut Bm25ScanState) -> bool {
+    let Some(ordered) = state.ordered.as_mut() else {
+        return false;
+    };
+    if ordered.returned < ordered.limit {
         return false;
     }
 
+    ordered.limit = ordered.limit.saturating_mul(2);
+    state.results = state.reader.search_top_n_in_segments(
+        &ordered.segment_ids,
+        &state.search_query_input,
+        ordered.limit,
+    );
+    state.results.nth(ordered.returned - 1);
+    true
+}
+
+#[pg_guard]
+pub extern "C" fn amcanreturn(indexrel: pg_sys::Relation, attno: i32) -> bool {
     unsafe {
         assert!(!indexrel.is_null());
         assert!(!(*indexrel).rd_att.is_null());
@@ -347,16 +537,25 @@ pub extern "C" fn amcanreturn(indexrel: pg_sys::Relation, attno: i32) -> bool {
             .get((attno - 1) as usize)
             .expect("attno should exist in index tupledesc");
 
-        // we can only return a field if it's one of the below types -- basically pass-by-value (non tokenized) data types
-        [
-            pg_sys::INT4OID,
-            pg_sys::INT8OID,
-            pg_sys::FLOAT4OID,
-            pg_sys::FLOAT8OID,
-            pg_sys::BOOLOID,
-            // we index UUID as strings, but it's beneficial to support returning due to Parallel Index Only Scans
-            pg_sys::UUIDOID,
-        ]
-        .contains(&att.atttypid)
+        if !RETURNABLE_TYPES.contains(&att.atttypid) || (*indexrel).rd_options.is_null() {
+            return false;
+        }
+
+        // the field must also be fast, and come back out of its fast field exactly as it went in,
+        // so text fields can't be normalized.  the parts of the "key_field" always qualify
+        let (fields, _) = get_fields(&PgRelation::from_pg(indexrel));
+        fields.iter().any(|(name, config, _)| {
+            name.0 == att.name()
+                && config.column().unwrap_or(&name.0) == att.name()
+                && matches!(
+                    config,
+                    SearchFieldConfig::Text {
+                        fast: true,
+                        normalizer: SearchNormalizer::Raw,
+                        ..
+                    } | SearchFieldConfig::Numeric { fast: true, .. }
+                        | SearchFieldConfig::Boolean { fast: true, .. }
+                )
+        })
     }
 }
@@ -23,6 +23,7 @@ use std::mem::{offset_of, size_of};
 use std::path::{Path, PathBuf};
 use std::slice::from_raw_parts;
 use tantivy::index::{SegmentComponent, SegmentId};
+use tantivy::schema::Schema;
 use tantivy::Opstamp;
 
 pub const MERGE_LOCK: pg_sys::BlockNumber = 0;
@@ -46,13 +47,33 @@ pub struct BM25PageSpecialData {
 // Merge lock
 // ---------------------------------------------------------
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 #[repr(C, packed)]
 pub struct MergeLockData {
     pub last_merge: pg_sys::TransactionId,
     pub num_segments: u32,
 }
 
+// ---------------------------------------------------------
+// Cleanup lock
+// ---------------------------------------------------------
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C, packed)]
+pub struct CleanupLockData {
+    /// The header block of the [`LinkedBytesList`](crate::postgres::storage::LinkedBytesList)
+    /// holding the index's planner statistics, or zero if they've never been gathered
+    pub statistics_blockno: pg_sys::BlockNumber,
+
+    /// The header block of the [`LinkedItemList`](crate::postgres::storage::LinkedItemList)
+    /// holding the index's pending list, or zero if nothing has ever been written to it
+    pub pending_blockno: pg_sys::BlockNumber,
+
+    /// Roughly how many bytes of documents have been added to the pending list since it was
+    /// last flushed
+    pub pending_bytes: u64,
+}
+
 // ---------------------------------------------------------
 // Linked lists
 // ---------------------------------------------------------
@@ -173,6 +194,74 @@ impl Default for SegmentMetaEntry {
     }
 }
 
+// ---------------------------------------------------------
+// Pending list
+// ---------------------------------------------------------
+
+/// A document inserted into the pending list of an index with `fastupdate` enabled, waiting to
+/// be flushed into a segment.
+///
+/// The document and its nested children are kept as tantivy's JSON representation, which
+/// [`TantivyDocument::parse_json`](tantivy::TantivyDocument::parse_json) reads back using the
+/// index's schema.  A flushed entry has its `xmax` set to the transaction that flushed it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PendingEntry {
+    pub xmin: pg_sys::TransactionId,
+    pub xmax: pg_sys::TransactionId,
+    pub ctid: u64,
+    pub doc: String,
+    pub nested: Vec<String>,
+}
+
+// ---------------------------------------------------------
+// Schema versions
+// ---------------------------------------------------------
+
+/// One version of the index's tantivy [`Schema`], as stored in the schema linked list
+/// starting at [`SCHEMA_START`].
+///
+/// Versions are only ever appended and each one is a strict superset of the previous version:
+/// new fields are added to the end so that the field ids of existing segments remain stable.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchemaEntry {
+    pub version: u32,
+    pub xmin: pg_sys::TransactionId,
+    pub schema: Schema,
+}
+
+/// What we could find in the schema linked list.  Indexes created before schemas were versioned
+/// store a bare [`Schema`], which we treat as version 1.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum StoredSchema {
+    Versioned(SchemaEntry),
+    Legacy(Schema),
+}
+
+impl From<StoredSchema> for SchemaEntry {
+    fn from(value: StoredSchema) -> Self {
+        match value {
+            StoredSchema::Versioned(entry) => entry,
+            StoredSchema::Legacy(schema) => SchemaEntry {
+                version: 1,
+                xmin: pg_sys::FrozenTransactionId,
+                schema,
+            },
+        }
+    }
+}
+
+impl SchemaEntry {
+    /// A schema version is usable if the transaction that wrote it committed (or is us).  We don't
+    /// use snapshot visibility here because segments written under a newer schema must always be
+    /// read with that schema
+    pub unsafe fn is_usable(&self) -> bool {
+        self.xmin == pg_sys::FrozenTransactionId
+            || pg_sys::TransactionIdIsCurrentTransactionId(self.xmin)
+            || pg_sys::TransactionIdDidCommit(self.xmin)
+    }
+}
+
 // ---------------------------------------------------------
 // Linked list entry <-> PgItem
 // ---------------------------------------------------------
@@ -204,12 +293,60 @@ impl From<PgItem> for SegmentMetaEntry {
     }
 }
 
+impl From<PendingEntry> for PgItem {
+    fn from(val: PendingEntry) -> Self {
+        let bytes: Vec<u8> =
+            bincode::serialize(&val).expect("expected to serialize valid PendingEntry");
+        let pg_bytes = unsafe { pg_sys::palloc(bytes.len()) as *mut u8 };
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), pg_bytes, bytes.len());
+        }
+        PgItem(pg_bytes as pg_sys::Item, bytes.len() as pg_sys::Size)
+    }
+}
+
+impl From<PgItem> for PendingEntry {
+    fn from(pg_item: PgItem) -> Self {
+        let PgItem(item, size) = pg_item;
+        let decoded: PendingEntry = unsafe {
+            bincode::deserialize(from_raw_parts(item as *const u8, size))
+                .expect("expected to deserialize valid PendingEntry")
+        };
+        decoded
+    }
+}
+
+impl PendingEntry {
+    /// The number of bytes the entry takes up on its page
+    pub fn item_size(&self) -> usize {
+        bincode::serialized_size(self).expect("expected to size valid PendingEntry") as usize
+    }
+}
+
 impl SegmentMetaEntry {
     /// Fake an opstamp value based on our internal `xmin` and `xmax` values
     pub fn opstamp(&self) -> Opstamp {
         ((self.xmax as u64) << 32) | (self.xmin as u64)
     }
 
+    /// The total size, in bytes, of all the segment's components
+    pub fn byte_size(&self) -> u64 {
+        [
+            self.postings,
+            self.positions,
+            self.fast_fields,
+            self.field_norms,
+            self.terms,
+            self.store,
+            self.temp_store,
+            self.delete.map(|entry| entry.file_entry),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|entry| entry.total_bytes as u64)
+        .sum()
+    }
+
     pub fn get_file_entry(&self, segment_component: SegmentComponent) -> Option<FileEntry> {
         match segment_component {
             SegmentComponent::Postings => self.postings,
@@ -393,6 +530,30 @@ impl MVCCEntry for SegmentMetaEntry {
     }
 }
 
+impl MVCCEntry for PendingEntry {
+    fn get_xmin(&self) -> pg_sys::TransactionId {
+        self.xmin
+    }
+    fn get_xmax(&self) -> pg_sys::TransactionId {
+        self.xmax
+    }
+    fn into_frozen(self, should_freeze_xmin: bool, should_freeze_xmax: bool) -> Self {
+        PendingEntry {
+            xmin: if should_freeze_xmin {
+                pg_sys::FrozenTransactionId
+            } else {
+                self.xmin
+            },
+            xmax: if should_freeze_xmax {
+                pg_sys::FrozenTransactionId
+            } else {
+                self.xmax
+            },
+            ..self
+        }
+    }
+}
+
 pub const fn bm25_max_free_space() -> usize {
     unsafe {
         (pg_sys::BLCKSZ as usize)
@@ -89,7 +89,6 @@ impl BufferMut {
         page
     }
 
-    #[allow(dead_code)]
     pub fn page(&self) -> Page {
         unsafe {
             Page {
@@ -197,6 +196,10 @@ impl Page<'_> {
         unsafe { self.pg_page.recyclable(heaprel) }
     }
 
+    pub fn is_deleted(&self) -> bool {
+        self.special::<BM25PageSpecialData>().xmax != pg_sys::InvalidTransactionId
+    }
+
     pub fn next_blockno(&self) -> pg_sys::BlockNumber {
         unsafe {
             let special = pg_sys::PageGetSpecialPointer(self.pg_page) as *mut BM25PageSpecialData;
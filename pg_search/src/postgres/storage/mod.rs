@@ -35,7 +35,9 @@
 // +-------------------------------------------------------------+
 // |                         Cleanup Lock                        |
 // +-------------------------------------------------------------+
-// | Empty                                                       |
+// | CleanupLockData                                             |
+// | [statistics_blockno: BlockNumber,                           |
+// |  pending_blockno: BlockNumber, pending_bytes: u64]          |
 // +-------------------------------------------------------------+
 
 // ---------------------------------------------------------------
@@ -17,6 +17,7 @@
 
 use crate::postgres::datetime::{datetime_components_to_tantivy_date, MICROSECONDS_IN_SECOND};
 use crate::postgres::range::RangeToTantivyValue;
+use crate::query::{nested_path_id, NESTED_PATH_KEY};
 use crate::schema::AnyEnum;
 use ordered_float::OrderedFloat;
 use pgrx::datum::datetime_support::DateTimeConversionError;
@@ -24,16 +25,17 @@ use pgrx::pg_sys::Datum;
 use pgrx::pg_sys::Oid;
 use pgrx::IntoDatum;
 use pgrx::PostgresType;
-use pgrx::{FromDatum, PgBuiltInOids, PgOid};
+use pgrx::{pg_sys, FromDatum, PgBuiltInOids, PgOid, PgTupleDesc};
 use serde::ser::{Serialize, SerializeStruct, Serializer};
 use serde::{Deserialize, Deserializer};
 use serde_json::Value;
 use std::cmp::Ordering;
+use std::ffi::CStr;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::num::ParseFloatError;
 use std::str::FromStr;
-use tantivy::schema::OwnedValue;
+use tantivy::schema::{Facet, OwnedValue};
 use thiserror::Error;
 
 #[derive(Clone, Debug, Eq, PartialEq, PostgresType)]
@@ -146,24 +148,70 @@ impl TantivyValue {
         datum: Datum,
         oid: PgOid,
     ) -> Result<Vec<Self>, TantivyValueError> {
+        // Tantivy has a limitation that prevents JSON top-level arrays from being
+        // inserted into the index. Therefore, we need to flatten the array elements
+        // individually before converting them into Tantivy values.
+        Ok(Self::json_value_to_tantivy_value(Self::json_from_datum(
+            datum, oid,
+        )?))
+    }
+
+    /// Produce one value per element of every array of objects in the JSON datum, for
+    /// `nested` fields.  Each element is wrapped back into the object path it was found at, so
+    /// that it can be searched with the same paths as the flattened parent.  Arrays nested
+    /// inside of an element are left as part of that element.
+    pub unsafe fn try_from_datum_json_nested(
+        datum: Datum,
+        oid: PgOid,
+    ) -> Result<Vec<Self>, TantivyValueError> {
+        let mut values = vec![];
+        Self::collect_nested_json_values(
+            Self::json_from_datum(datum, oid)?,
+            &mut vec![],
+            &mut values,
+        );
+        Ok(values)
+    }
+
+    fn collect_nested_json_values(value: Value, path: &mut Vec<String>, values: &mut Vec<Self>) {
+        match value {
+            Value::Array(elements) => {
+                let path_id = nested_path_id(&path.join("."));
+                for element in elements.into_iter().filter(Value::is_object) {
+                    let mut wrapped = path.iter().rev().fold(element, |inner, key| {
+                        Value::Object(serde_json::Map::from_iter([(key.clone(), inner)]))
+                    });
+                    // remember which array the element came from, so that a nested query over
+                    // one path doesn't match the elements of another
+                    if let Value::Object(map) = &mut wrapped {
+                        map.insert(NESTED_PATH_KEY.to_string(), Value::from(path_id));
+                    }
+                    values.push(TantivyValue(tantivy::schema::OwnedValue::from(wrapped)));
+                }
+            }
+            Value::Object(map) => {
+                for (key, value) in map {
+                    path.push(key);
+                    Self::collect_nested_json_values(value, path, values);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    unsafe fn json_from_datum(datum: Datum, oid: PgOid) -> Result<Value, TantivyValueError> {
         match &oid {
             PgOid::BuiltIn(builtin) => match builtin {
-                // Tantivy has a limitation that prevents JSON top-level arrays from being
-                // inserted into the index. Therefore, we need to flatten the array elements
-                // individually before converting them into Tantivy values.
                 PgBuiltInOids::JSONBOID => {
                     let pgrx_value = pgrx::JsonB::from_datum(datum, false)
                         .ok_or(TantivyValueError::DatumDeref)?;
-                    let json_value: Value =
-                        serde_json::from_slice(&serde_json::to_vec(&pgrx_value.0)?)?;
-                    Ok(Self::json_value_to_tantivy_value(json_value))
+                    Ok(serde_json::from_slice(&serde_json::to_vec(&pgrx_value.0)?)?)
                 }
                 PgBuiltInOids::JSONOID => {
                     let pgrx_value = pgrx::Json::from_datum(datum, false)
                         .ok_or(TantivyValueError::DatumDeref)?;
-                    let json_value: Value =
-                        serde_json::from_slice(&serde_json::to_vec(&pgrx_value.0)?)?;
-                    Ok(Self::json_value_to_tantivy_value(json_value))
+                    Ok(serde_json::from_slice(&serde_json::to_vec(&pgrx_value.0)?)?)
                 }
                 _ => Err(TantivyValueError::UnsupportedJsonOid(oid.value())),
             },
@@ -171,6 +219,90 @@ impl TantivyValue {
         }
     }
 
+    /// Convert a facet field's value, or each element of an array of them.  `text` values are
+    /// facet paths like `/a/b/c`, and `ltree` values are converted from their `a.b.c` form.
+    pub unsafe fn try_from_datum_facet(
+        datum: Datum,
+        oid: PgOid,
+        is_array: bool,
+    ) -> Result<Vec<Self>, TantivyValueError> {
+        if is_array {
+            let array: pgrx::Array<Datum> =
+                pgrx::Array::from_datum(datum, false).ok_or(TantivyValueError::DatumDeref)?;
+            array
+                .iter()
+                .flatten()
+                .map(|element| Self::try_from_datum_facet_value(element, oid))
+                .collect()
+        } else {
+            Ok(vec![Self::try_from_datum_facet_value(datum, oid)?])
+        }
+    }
+
+    unsafe fn try_from_datum_facet_value(
+        datum: Datum,
+        oid: PgOid,
+    ) -> Result<Self, TantivyValueError> {
+        let facet = match &oid {
+            PgOid::BuiltIn(PgBuiltInOids::TEXTOID | PgBuiltInOids::VARCHAROID) => {
+                let path = String::from_datum(datum, false).ok_or(TantivyValueError::DatumDeref)?;
+                Facet::from_text(&path)
+                    .map_err(|_| TantivyValueError::InvalidFacetPath(path.clone()))?
+            }
+            PgOid::Custom(custom) if crate::schema::is_ltree_type(*custom) => {
+                let mut output_func = pg_sys::InvalidOid;
+                let mut is_varlena = false;
+                pg_sys::getTypeOutputInfo(*custom, &mut output_func, &mut is_varlena);
+                let path = CStr::from_ptr(pg_sys::OidOutputFunctionCall(output_func, datum))
+                    .to_str()
+                    .map_err(|_| TantivyValueError::DatumDeref)?;
+                if path.is_empty() {
+                    Facet::root()
+                } else {
+                    Facet::from_path(path.split('.'))
+                }
+            }
+            _ => return Err(TantivyValueError::UnsupportedOid(oid.value())),
+        };
+        Ok(TantivyValue(OwnedValue::Facet(facet)))
+    }
+
+    /// Convert each attribute of a composite (`ROW(...)`) datum, in attribute order.  NULL
+    /// attributes are an error as they can't be part of a key.
+    pub unsafe fn try_from_datum_composite(datum: Datum) -> Result<Vec<Self>, TantivyValueError> {
+        let header = pg_sys::pg_detoast_datum(datum.cast_mut_ptr()) as pg_sys::HeapTupleHeader;
+        let typid = (*header).t_choice.t_datum.datum_typeid;
+        let typmod = (*header).t_choice.t_datum.datum_typmod;
+        let tupdesc = PgTupleDesc::from_pg(pg_sys::lookup_rowtype_tupdesc(typid, typmod));
+
+        let mut tuple = pg_sys::HeapTupleData {
+            t_len: pgrx::varlena::varsize_any(header.cast()) as _,
+            t_data: header,
+            ..Default::default()
+        };
+        let natts = tupdesc.len();
+        let mut values = vec![Datum::null(); natts];
+        let mut nulls = vec![true; natts];
+        pg_sys::heap_deform_tuple(
+            &mut tuple,
+            tupdesc.as_ptr(),
+            values.as_mut_ptr(),
+            nulls.as_mut_ptr(),
+        );
+
+        tupdesc
+            .iter()
+            .zip(values.into_iter().zip(nulls))
+            .filter(|(attribute, _)| !attribute.is_dropped())
+            .map(|(attribute, (value, isnull))| {
+                if isnull {
+                    return Err(TantivyValueError::DatumDeref);
+                }
+                TantivyValue::try_from_datum(value, attribute.type_oid())
+            })
+            .collect()
+    }
+
     pub unsafe fn try_from_datum(datum: Datum, oid: PgOid) -> Result<Self, TantivyValueError> {
         match &oid {
             PgOid::BuiltIn(builtin) => match builtin {
@@ -274,6 +406,18 @@ impl TantivyValue {
     ) -> Result<Self, TantivyValueError> {
         Self::try_from_datum(any_element.datum(), PgOid::from_untagged(any_element.oid()))
     }
+
+    /// Convert a key value, which is either a single value or, for a composite key, a `ROW(...)`
+    /// holding each part of the key
+    pub unsafe fn try_from_anyelement_key(
+        any_element: pgrx::AnyElement,
+    ) -> Result<Vec<Self>, TantivyValueError> {
+        if pg_sys::type_is_rowtype(any_element.oid()) {
+            Self::try_from_datum_composite(any_element.datum())
+        } else {
+            Ok(vec![Self::try_from_anyelement(any_element)?])
+        }
+    }
 }
 
 impl fmt::Display for TantivyValue {
@@ -1012,4 +1156,7 @@ pub enum TantivyValueError {
 
     #[error("Cannot convert TantivyValue to type {0}")]
     UnsupportedIntoConversion(String),
+
+    #[error("'{0}' is not a valid facet path, it must look like '/a/b/c'")]
+    InvalidFacetPath(String),
 }
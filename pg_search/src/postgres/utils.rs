@@ -17,7 +17,9 @@
 
 use crate::index::writer::index::IndexError;
 use crate::postgres::types::TantivyValue;
-use crate::schema::{SearchDocument, SearchField, SearchIndexSchema};
+use crate::schema::{
+    SearchDocument, SearchField, SearchFieldConfig, SearchFieldType, SearchIndexSchema,
+};
 use anyhow::{anyhow, Result};
 use chrono::{NaiveDate, NaiveTime};
 use pgrx::itemptr::{item_pointer_get_both, item_pointer_set_all};
@@ -137,7 +139,7 @@ pub fn categorize_fields(
 pub unsafe fn row_to_search_document(
     values: *mut pg_sys::Datum,
     isnull: *mut bool,
-    key_field_name: &str,
+    key_field_names: &[String],
     categorized_fields: &Vec<(SearchField, CategorizedFieldData)>,
     document: &mut SearchDocument,
 ) -> Result<(), IndexError> {
@@ -154,15 +156,19 @@ pub unsafe fn row_to_search_document(
         let datum = *values.add(*attno);
         let isnull = *isnull.add(*attno);
 
-        if key_field_name == search_field.name.as_ref() && isnull {
-            return Err(IndexError::KeyIdNull(key_field_name.to_string()));
+        if isnull && key_field_names.contains(&search_field.name.0) {
+            return Err(IndexError::KeyIdNull(search_field.name.0.clone()));
         }
 
         if isnull {
             continue;
         }
 
-        if *is_array {
+        if search_field.type_ == SearchFieldType::Facet {
+            for value in TantivyValue::try_from_datum_facet(datum, *base_oid, *is_array)? {
+                document.insert(search_field.id, value.tantivy_schema_value());
+            }
+        } else if *is_array {
             for value in TantivyValue::try_from_datum_array(datum, *base_oid)? {
                 document.insert(search_field.id, value.tantivy_schema_value());
             }
@@ -170,6 +176,14 @@ pub unsafe fn row_to_search_document(
             for value in TantivyValue::try_from_datum_json(datum, *base_oid)? {
                 document.insert(search_field.id, value.tantivy_schema_value());
             }
+            if matches!(
+                search_field.config,
+                SearchFieldConfig::Json { nested: true, .. }
+            ) {
+                for value in TantivyValue::try_from_datum_json_nested(datum, *base_oid)? {
+                    document.insert_nested(search_field.id, value.tantivy_schema_value());
+                }
+            }
         } else {
             document.insert(
                 search_field.id,
@@ -216,23 +230,18 @@ pub fn convert_pg_date_string(typeoid: PgOid, date_string: &str) -> tantivy::Dat
             tantivy::DateTime::from_timestamp_micros(micros)
         }
         PgOid::BuiltIn(PgBuiltInOids::TIMESTAMPOID | PgBuiltInOids::TSRANGEOID) => {
-            // Since [`pgrx::Timestamp`]s are tied to the Postgres instance's timezone,
-            // to figure out *which* timezone it's actually in, we convert to a
-            // [`pgrx::TimestampWithTimeZone`].
-            // Once the offset is known, we can create and return a [`chrono::NaiveDateTime`]
-            // with the appropriate offset.
+            // a `timestamp` is indexed as though it were UTC, so it's read the same way here,
+            // rather than through the session's time zone, where some times don't exist
             let t = pgrx::datum::Timestamp::from_str(date_string)
                 .expect("must be a valid postgres timestamp");
-            let twtz: datum::TimestampWithTimeZone = t.into();
-            let (seconds, _micros, _nanos) = convert_pgrx_seconds_to_chrono(twtz.second())
+            let (seconds, _micros, _nanos) = convert_pgrx_seconds_to_chrono(t.second())
                 .expect("must not overflow converting pgrx seconds");
-            let micros =
-                NaiveDate::from_ymd_opt(twtz.year(), twtz.month().into(), twtz.day().into())
-                    .expect("must be able to convert date timestamp")
-                    .and_hms_opt(twtz.hour().into(), twtz.minute().into(), seconds)
-                    .expect("must be able to parse timestamp format")
-                    .and_utc()
-                    .timestamp_micros();
+            let micros = NaiveDate::from_ymd_opt(t.year(), t.month().into(), t.day().into())
+                .expect("must be able to convert date timestamp")
+                .and_hms_opt(t.hour().into(), t.minute().into(), seconds)
+                .expect("must be able to parse timestamp format")
+                .and_utc()
+                .timestamp_micros();
             tantivy::DateTime::from_timestamp_micros(micros)
         }
         PgOid::BuiltIn(PgBuiltInOids::TIMESTAMPTZOID | pg_sys::BuiltinOid::TSTZRANGEOID) => {
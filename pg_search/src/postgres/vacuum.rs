@@ -15,6 +15,14 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::gucs;
+use crate::index::merge_policy::{ForceMergePolicy, MergeLock};
+use crate::index::pending::vacuum_pending;
+use crate::index::reader::index::SearchIndexReader;
+use crate::index::statistics::{load_statistics, save_statistics, IndexStatistics};
+use crate::index::writer::index::SearchIndexWriter;
+use crate::index::{BlockDirectoryType, WriterResources};
+use crate::postgres::merge_worker::ForceMergeGuard;
 use crate::postgres::storage::buffer::BufferManager;
 use pgrx::*;
 
@@ -24,10 +32,49 @@ pub extern "C" fn amvacuumcleanup(
     stats: *mut pg_sys::IndexBulkDeleteResult,
 ) -> *mut pg_sys::IndexBulkDeleteResult {
     let info = unsafe { PgBox::from_pg(info) };
+
+    if !info.analyze_only {
+        unsafe {
+            let index_relation = PgRelation::from_pg(info.index);
+            vacuum_pending(&index_relation)
+                .expect("amvacuumcleanup: should be able to vacuum the pending list");
+            rewrite_deleted_segments(&index_relation);
+        }
+    }
+
+    let num_index_tuples = unsafe {
+        let index_relation = PgRelation::from_pg(info.index);
+        match SearchIndexReader::open(&index_relation, BlockDirectoryType::Mvcc, false) {
+            Ok(search_reader) => {
+                let num_rows = search_reader.num_rows();
+                refresh_statistics(&index_relation, search_reader, num_rows, info.analyze_only);
+                Some(num_rows as f64)
+            }
+            Err(e) => {
+                pgrx::warning!(
+                    "could not open index \"{}\" to count its rows: {e}",
+                    index_relation.name()
+                );
+                None
+            }
+        }
+    };
+
     if info.analyze_only {
         return stats;
     }
 
+    let mut stats = if stats.is_null() {
+        // ambulkdelete wasn't called because there was nothing to delete
+        unsafe {
+            PgBox::<pg_sys::IndexBulkDeleteResult>::from_pg(
+                pg_sys::palloc0(std::mem::size_of::<pg_sys::IndexBulkDeleteResult>()).cast(),
+            )
+        }
+    } else {
+        unsafe { PgBox::from_pg(stats) }
+    };
+
     // return all recyclable pages to the free space map
     unsafe {
         let index_relation = PgRelation::from_pg(info.index);
@@ -38,12 +85,19 @@ pub extern "C" fn amvacuumcleanup(
         let heap_oid = pg_sys::IndexGetRelation(index_oid, false);
         let heap_relation = pg_sys::RelationIdGetRelation(heap_oid);
 
+        stats.num_pages = nblocks;
+        stats.pages_deleted = 0;
+        stats.pages_free = 0;
         for blockno in 0..nblocks {
             check_for_interrupts!();
             let buffer = bman.get_buffer(blockno);
             let page = buffer.page();
 
+            if page.is_deleted() {
+                stats.pages_deleted += 1;
+            }
             if page.is_recyclable(heap_relation) {
+                stats.pages_free += 1;
                 bman.record_free_index_page(buffer);
             }
         }
@@ -51,6 +105,65 @@ pub extern "C" fn amvacuumcleanup(
         pg_sys::IndexFreeSpaceMapVacuum(info.index);
     }
 
-    // TODO: Update stats
-    stats
+    match num_index_tuples {
+        Some(num_index_tuples) => {
+            stats.num_index_tuples = num_index_tuples;
+            stats.estimated_count = false;
+        }
+        None => {
+            stats.num_index_tuples = info.num_heap_tuples;
+            stats.estimated_count = true;
+        }
+    }
+    stats.into_pg()
+}
+
+/// Gather the statistics the planner uses to estimate our selectivity.  `ANALYZE`, including
+/// autovacuum's, always does, while a plain `VACUUM` only does when the index doesn't have any
+/// yet or enough rows have come and gone since they were gathered.  Failing to gather them
+/// shouldn't fail the `VACUUM`, so that's only a warning.
+unsafe fn refresh_statistics(
+    index_relation: &PgRelation,
+    search_reader: SearchIndexReader,
+    num_rows: u64,
+    analyze: bool,
+) {
+    let index_oid = index_relation.oid();
+    if !analyze && load_statistics(index_oid).is_some_and(|previous| !previous.is_stale(num_rows)) {
+        return;
+    }
+
+    let statistics = IndexStatistics::collect(&search_reader);
+    drop(search_reader);
+    if let Err(e) = statistics.and_then(|statistics| save_statistics(index_oid, &statistics)) {
+        pgrx::warning!(
+            "could not gather statistics for index \"{}\": {e}",
+            index_relation.name()
+        );
+    }
+}
+
+/// Rewrite every segment with at least `paradedb.vacuum_deleted_docs_ratio` of its documents
+/// deleted, so that the space they take up can be reclaimed by the next VACUUM.  If a merge is
+/// already running then we leave it be -- it's rewriting segments too
+unsafe fn rewrite_deleted_segments(index_relation: &PgRelation) {
+    let Some(merge_lock) = MergeLock::acquire_for_merge(index_relation.oid()) else {
+        return;
+    };
+
+    let _guard = ForceMergeGuard::enter();
+    let policy = ForceMergePolicy {
+        max_segments: None,
+        deleted_docs_ratio: Some(gucs::vacuum_deleted_docs_ratio()),
+        max_merges: None,
+    };
+    SearchIndexWriter::open(
+        index_relation,
+        BlockDirectoryType::Mvcc,
+        WriterResources::ForceMerge(policy),
+    )
+    .and_then(|writer| writer.commit())
+    .expect("amvacuumcleanup: should be able to rewrite segments with deleted docs");
+
+    drop(merge_lock);
 }
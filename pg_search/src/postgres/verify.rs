@@ -0,0 +1,542 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Structural checks for a bm25 index, in the spirit of `amcheck`.
+//!
+//! Every block of the index should belong to exactly one structure -- the lock blocks, the
+//! schema, settings and statistics lists, the segment meta entries list, or the file of a live
+//! segment -- or else be marked deleted.  [`verify_structure`] walks all of them and reports
+//! whatever doesn't hold up, and [`verify_heap_all_indexed`] cross-checks the index against the
+//! heap.  Callers are expected to hold a lock that keeps writers out of the index.
+
+use crate::index::reader::index::SearchIndexReader;
+use crate::index::utils::load_schema;
+use crate::index::BlockDirectoryType;
+use crate::postgres::storage::block::{
+    BM25PageSpecialData, CleanupLockData, LinkedList, LinkedListData, MergeLockData, PendingEntry,
+    SegmentMetaEntry, CLEANUP_LOCK, MERGE_LOCK, SCHEMA_START, SEGMENT_METAS_START, SETTINGS_START,
+};
+use crate::postgres::storage::buffer::BufferManager;
+use crate::postgres::storage::LinkedBytesList;
+use crate::postgres::utils::item_pointer_to_u64;
+use anyhow::Result;
+use pgrx::{check_for_interrupts, pg_sys, PgRelation};
+use rustc_hash::{FxHashMap, FxHashSet};
+use tantivy::index::SegmentComponent;
+use tantivy::query::{EnableScoring, ExistsQuery, Query};
+use tantivy::{DocSet, IndexSettings, TERMINATED};
+
+/// A problem found with the index, and the block it was found on, if any
+pub type Problem = (Option<pg_sys::BlockNumber>, String);
+
+struct Verifier {
+    bman: BufferManager,
+    relation_oid: pg_sys::Oid,
+    nblocks: pg_sys::BlockNumber,
+    owners: Vec<Option<String>>,
+    next_xid: pg_sys::TransactionId,
+    problems: Vec<Problem>,
+}
+
+impl Verifier {
+    fn report(&mut self, blockno: Option<pg_sys::BlockNumber>, message: String) {
+        self.problems.push((blockno, message));
+    }
+
+    /// Record that `blockno` belongs to `owner`, returning false if it can't, in which case the
+    /// caller shouldn't read it
+    fn claim(&mut self, blockno: pg_sys::BlockNumber, owner: &str) -> bool {
+        if blockno == pg_sys::InvalidBlockNumber || blockno >= self.nblocks {
+            let nblocks = self.nblocks;
+            self.report(
+                None,
+                format!("{owner} points to block {blockno}, but the index has {nblocks} blocks"),
+            );
+            return false;
+        }
+
+        match &self.owners[blockno as usize] {
+            Some(existing) if existing == owner => {
+                self.report(Some(blockno), format!("{owner} has a cycle"));
+                false
+            }
+            Some(existing) => {
+                let existing = existing.clone();
+                self.report(
+                    Some(blockno),
+                    format!("block is owned by both {existing} and {owner}"),
+                );
+                false
+            }
+            None => {
+                self.owners[blockno as usize] = Some(owner.to_string());
+                true
+            }
+        }
+    }
+
+    unsafe fn check_xid(
+        &mut self,
+        blockno: pg_sys::BlockNumber,
+        what: &str,
+        xid: pg_sys::TransactionId,
+    ) {
+        if pg_sys::TransactionIdIsNormal(xid)
+            && pg_sys::TransactionIdFollowsOrEquals(xid, self.next_xid)
+        {
+            self.report(
+                Some(blockno),
+                format!("{what} {xid} is newer than any transaction"),
+            );
+        }
+    }
+
+    /// Follow a chain of blocks by their `next_blockno`, returning the blocks visited
+    unsafe fn walk_chain(
+        &mut self,
+        owner: &str,
+        start_blockno: pg_sys::BlockNumber,
+    ) -> Vec<pg_sys::BlockNumber> {
+        let mut blocks = vec![];
+        let mut blockno = start_blockno;
+        while blockno != pg_sys::InvalidBlockNumber {
+            check_for_interrupts!();
+            if !self.claim(blockno, owner) {
+                break;
+            }
+
+            let buffer = self.bman.get_buffer(blockno);
+            let page = buffer.page();
+            if page.header().pd_upper == 0 {
+                self.report(
+                    Some(blockno),
+                    format!("block of {owner} was never initialized"),
+                );
+                break;
+            }
+            let xmax = page.special::<BM25PageSpecialData>().xmax;
+            let next_blockno = page.next_blockno();
+            let is_deleted = page.is_deleted();
+            drop(buffer);
+
+            self.check_xid(blockno, "page xmax", xmax);
+            if is_deleted {
+                self.report(Some(blockno), format!("block of {owner} is marked deleted"));
+            }
+
+            blocks.push(blockno);
+            blockno = next_blockno;
+        }
+        blocks
+    }
+
+    /// Walk a [`LinkedBytesList`], checking its header against its chain of data blocks and, if
+    /// it has one, the block list that indexes them
+    unsafe fn walk_bytes_list(&mut self, owner: &str, header_blockno: pg_sys::BlockNumber) {
+        if !self.claim(header_blockno, &format!("{owner} header")) {
+            return;
+        }
+        let metadata = self
+            .bman
+            .get_buffer(header_blockno)
+            .page_contents::<LinkedListData>();
+
+        let blocks = self.walk_chain(owner, metadata.start_blockno);
+        if blocks.last() != Some(&{ metadata.last_blockno }) {
+            let last_blockno = metadata.last_blockno;
+            self.report(
+                Some(header_blockno),
+                format!(
+                    "{owner} header says its last block is {last_blockno}, but its chain ends at {:?}",
+                    blocks.last()
+                ),
+            );
+        }
+        if blocks.len() != metadata.npages as usize {
+            let npages = metadata.npages;
+            self.report(
+                Some(header_blockno),
+                format!(
+                    "{owner} header says it has {npages} blocks, but its chain has {}",
+                    blocks.len()
+                ),
+            );
+        }
+
+        let blocklist_start = metadata.blocklist_start;
+        if blocklist_start != 0 && blocklist_start != pg_sys::InvalidBlockNumber {
+            self.walk_chain(&format!("{owner} block list"), blocklist_start);
+            let list = LinkedBytesList::open(self.relation_oid, header_blockno);
+            if let Some(ord) =
+                (0..blocks.len()).find(|&ord| list.block_for_ord(ord) != Some(blocks[ord]))
+            {
+                self.report(
+                    Some(blocklist_start),
+                    format!("{owner} block list disagrees with its chain at block #{ord}"),
+                );
+            }
+        }
+    }
+
+    /// Mark the blocks of a dead segment's file as accounted for, so long as they haven't
+    /// already been marked deleted, and so possibly reused
+    unsafe fn walk_dead_file(&mut self, owner: &str, header_blockno: pg_sys::BlockNumber) {
+        if !self.claim_if_undeleted(header_blockno, owner) {
+            return;
+        }
+        let metadata = self
+            .bman
+            .get_buffer(header_blockno)
+            .page_contents::<LinkedListData>();
+
+        for start_blockno in [metadata.start_blockno, metadata.blocklist_start] {
+            let mut blockno = start_blockno;
+            while self.claim_if_undeleted(blockno, owner) {
+                blockno = self.bman.get_buffer(blockno).page().next_blockno();
+            }
+        }
+    }
+
+    unsafe fn claim_if_undeleted(&mut self, blockno: pg_sys::BlockNumber, owner: &str) -> bool {
+        if blockno >= self.nblocks || self.owners[blockno as usize].is_some() {
+            // this also catches `InvalidBlockNumber`, and a cycle
+            return false;
+        }
+
+        let buffer = self.bman.get_buffer(blockno);
+        let page = buffer.page();
+        if page.header().pd_upper == 0 || page.is_deleted() {
+            return false;
+        }
+        self.owners[blockno as usize] = Some(owner.to_string());
+        true
+    }
+}
+
+/// Walk every structure in the index and report any problems found along the way
+pub unsafe fn verify_structure(index: &PgRelation) -> Result<Vec<Problem>> {
+    let relation_oid = index.oid();
+    let nblocks =
+        pg_sys::RelationGetNumberOfBlocksInFork(index.as_ptr(), pg_sys::ForkNumber::MAIN_FORKNUM);
+    let mut verifier = Verifier {
+        bman: BufferManager::new(relation_oid),
+        relation_oid,
+        nblocks,
+        owners: vec![None; nblocks as usize],
+        next_xid: pg_sys::ReadNextFullTransactionId().value as pg_sys::TransactionId,
+        problems: vec![],
+    };
+
+    // lock blocks
+    if verifier.claim(MERGE_LOCK, "merge lock") {
+        let metadata = verifier
+            .bman
+            .get_buffer(MERGE_LOCK)
+            .page_contents::<MergeLockData>();
+        verifier.check_xid(MERGE_LOCK, "last merge", metadata.last_merge);
+    }
+    let mut statistics_blockno = pg_sys::InvalidBlockNumber;
+    let mut pending_blockno = pg_sys::InvalidBlockNumber;
+    if verifier.claim(CLEANUP_LOCK, "cleanup lock") {
+        let metadata = verifier
+            .bman
+            .get_buffer(CLEANUP_LOCK)
+            .page_contents::<CleanupLockData>();
+        statistics_blockno = metadata.statistics_blockno;
+        pending_blockno = metadata.pending_blockno;
+    }
+
+    // metadata lists
+    verifier.walk_bytes_list("schema", SCHEMA_START);
+    if let Err(e) = load_schema(relation_oid) {
+        verifier.report(Some(SCHEMA_START), format!("schema cannot be read: {e}"));
+    }
+    verifier.walk_bytes_list("settings", SETTINGS_START);
+    let settings = LinkedBytesList::open(relation_oid, SETTINGS_START).read_all();
+    if let Err(e) = serde_json::from_slice::<IndexSettings>(&settings) {
+        verifier.report(
+            Some(SETTINGS_START),
+            format!("settings cannot be read: {e}"),
+        );
+    }
+    if statistics_blockno != 0 && statistics_blockno != pg_sys::InvalidBlockNumber {
+        verifier.walk_bytes_list("statistics", statistics_blockno);
+    }
+    if pending_blockno != 0
+        && pending_blockno != pg_sys::InvalidBlockNumber
+        && verifier.claim(pending_blockno, "pending list header")
+    {
+        let start_blockno = verifier
+            .bman
+            .get_buffer(pending_blockno)
+            .page_contents::<LinkedListData>()
+            .start_blockno;
+        for blockno in verifier.walk_chain("pending list", start_blockno) {
+            let buffer = verifier.bman.get_buffer(blockno);
+            let page = buffer.page();
+            let entries = (pg_sys::FirstOffsetNumber..=page.max_offset_number())
+                .filter_map(|offsetno| page.read_item::<PendingEntry>(offsetno))
+                .map(|(entry, _)| entry)
+                .collect::<Vec<_>>();
+            drop(buffer);
+
+            for entry in entries {
+                let heap_blockno = (entry.ctid >> 16) as pg_sys::BlockNumber;
+                let heap_offno = entry.ctid as u16;
+                let what = format!("pending document for ({heap_blockno},{heap_offno})");
+                verifier.check_xid(blockno, &format!("{what} xmin"), entry.xmin);
+                verifier.check_xid(blockno, &format!("{what} xmax"), entry.xmax);
+            }
+        }
+    }
+
+    // segment meta entries, and the files of every segment that's still in use
+    if verifier.claim(SEGMENT_METAS_START, "segment meta entries header") {
+        let start_blockno = verifier
+            .bman
+            .get_buffer(SEGMENT_METAS_START)
+            .page_contents::<LinkedListData>()
+            .start_blockno;
+        let mut entries = vec![];
+        for blockno in verifier.walk_chain("segment meta entries", start_blockno) {
+            let buffer = verifier.bman.get_buffer(blockno);
+            let page = buffer.page();
+            entries.extend(
+                (pg_sys::FirstOffsetNumber..=page.max_offset_number())
+                    .filter_map(|offsetno| page.read_item::<SegmentMetaEntry>(offsetno))
+                    .map(|(entry, _)| (blockno, entry)),
+            );
+        }
+
+        let mut segment_ids = FxHashSet::default();
+        let mut dead_entries = vec![];
+        for (blockno, entry) in entries {
+            let segment = entry.segment_id.short_uuid_string();
+            verifier.check_xid(blockno, &format!("segment {segment} xmin"), entry.xmin);
+            verifier.check_xid(blockno, &format!("segment {segment} xmax"), entry.xmax);
+            if entry.xmin == pg_sys::InvalidTransactionId {
+                verifier.report(Some(blockno), format!("segment {segment} has no xmin"));
+            }
+
+            let is_live = !pg_sys::TransactionIdDidAbort(entry.xmin)
+                && (entry.xmax == pg_sys::InvalidTransactionId
+                    || !pg_sys::TransactionIdDidCommit(entry.xmax));
+            if !is_live {
+                dead_entries.push(entry);
+                continue;
+            }
+            if !segment_ids.insert(entry.segment_id) {
+                verifier.report(
+                    Some(blockno),
+                    format!("segment {segment} has more than one live entry"),
+                );
+                continue;
+            }
+
+            for component in SegmentComponent::iterator() {
+                if let Some(file_entry) = entry.get_file_entry(*component) {
+                    let owner = format!("segment {segment} {component:?} file");
+                    verifier.walk_bytes_list(&owner, file_entry.staring_block);
+                }
+            }
+        }
+
+        // dead entries share files with the live entries that replaced them, so they go last
+        for entry in dead_entries {
+            let segment = entry.segment_id.short_uuid_string();
+            for component in SegmentComponent::iterator() {
+                if let Some(file_entry) = entry.get_file_entry(*component) {
+                    let owner = format!("dead segment {segment} {component:?} file");
+                    verifier.walk_dead_file(&owner, file_entry.staring_block);
+                }
+            }
+        }
+    }
+
+    // everything else should be free
+    for blockno in 0..nblocks {
+        check_for_interrupts!();
+        if verifier.owners[blockno as usize].is_some() {
+            continue;
+        }
+        let buffer = verifier.bman.get_buffer(blockno);
+        let page = buffer.page();
+        let is_free = page.header().pd_upper == 0 || page.is_deleted();
+        drop(buffer);
+
+        if !is_free {
+            verifier.report(
+                Some(blockno),
+                "block isn't part of any structure and isn't marked deleted".into(),
+            );
+        }
+    }
+
+    Ok(verifier.problems)
+}
+
+struct HeapCheckState {
+    live_docs: FxHashMap<u64, u32>,
+    checked: FxHashSet<u64>,
+    problems: Vec<Problem>,
+}
+
+/// Check that every heap tuple the index should contain has exactly one live document
+pub unsafe fn verify_heap_all_indexed(
+    heap: &PgRelation,
+    index: &PgRelation,
+) -> Result<Vec<Problem>> {
+    let search_reader = SearchIndexReader::open(index, BlockDirectoryType::Mvcc, false)?;
+
+    // nested child documents share their parent's ctid, but never have a key
+    let key_field_name = search_reader.key_field().name.0;
+    let parents = ExistsQuery::new(key_field_name, false).weight(
+        EnableScoring::disabled_from_searcher(search_reader.searcher()),
+    )?;
+
+    let mut live_docs = FxHashMap::<u64, u32>::default();
+    for segment_reader in search_reader.segment_readers() {
+        let ctids = segment_reader.fast_fields().u64("ctid")?;
+        let alive_bitset = segment_reader.alive_bitset();
+        let mut scorer = parents.scorer(segment_reader, 1.0)?;
+        while scorer.doc() != TERMINATED {
+            check_for_interrupts!();
+            let doc_id = scorer.doc();
+            if alive_bitset.map_or(true, |bitset| bitset.is_alive(doc_id)) {
+                if let Some(ctid) = ctids.first(doc_id) {
+                    *live_docs.entry(ctid).or_default() += 1;
+                }
+            }
+            scorer.advance();
+        }
+    }
+    drop(search_reader);
+
+    let mut state = HeapCheckState {
+        live_docs,
+        checked: Default::default(),
+        problems: vec![],
+    };
+    let index_info = pg_sys::BuildIndexInfo(index.as_ptr());
+    pg_sys::IndexBuildHeapScan(
+        heap.as_ptr(),
+        index.as_ptr(),
+        index_info,
+        Some(heap_check_callback),
+        &mut state,
+    );
+
+    Ok(state.problems)
+}
+
+#[pgrx::pg_guard]
+unsafe extern "C" fn heap_check_callback(
+    _indexrel: pg_sys::Relation,
+    ctid: pg_sys::ItemPointer,
+    _values: *mut pg_sys::Datum,
+    _isnull: *mut bool,
+    _tuple_is_alive: bool,
+    state: *mut std::os::raw::c_void,
+) {
+    check_for_interrupts!();
+    let state = (state as *mut HeapCheckState)
+        .as_mut()
+        .expect("HeapCheckState pointer should not be null");
+
+    // every member of a HOT chain is reported with the chain's root ctid
+    let ctid = item_pointer_to_u64(*ctid);
+    if !state.checked.insert(ctid) {
+        return;
+    }
+
+    let blockno = (ctid >> 16) as pg_sys::BlockNumber;
+    let offno = ctid as u16;
+    match state.live_docs.get(&ctid).copied().unwrap_or(0) {
+        1 => {}
+        0 => state.problems.push((
+            None,
+            format!("heap tuple ({blockno},{offno}) has no live document"),
+        )),
+        n => state.problems.push((
+            None,
+            format!("heap tuple ({blockno},{offno}) has {n} live documents"),
+        )),
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::*;
+    use pgrx::prelude::*;
+
+    fn create_index() -> PgRelation {
+        Spi::run("CREATE TABLE t (id SERIAL, data TEXT);").unwrap();
+        Spi::run("INSERT INTO t (data) SELECT md5(x::text) FROM generate_series(1, 100) x;")
+            .unwrap();
+        Spi::run("CREATE INDEX t_idx ON t USING bm25(id, data) WITH (key_field = 'id')").unwrap();
+        let relation_oid: pg_sys::Oid =
+            Spi::get_one("SELECT oid FROM pg_class WHERE relname = 't_idx' AND relkind = 'i';")
+                .expect("spi should succeed")
+                .unwrap();
+        unsafe { PgRelation::with_lock(relation_oid, pg_sys::AccessShareLock as _) }
+    }
+
+    #[pg_test]
+    unsafe fn test_verify_structure_block_owned_twice() {
+        let index = create_index();
+        assert_eq!(verify_structure(&index).unwrap(), vec![]);
+
+        // the statistics have never been gathered, so point them at the merge lock
+        let mut bman = BufferManager::new(index.oid());
+        let mut buffer = bman.get_buffer_mut(CLEANUP_LOCK);
+        let mut page = buffer.page_mut();
+        page.contents_mut::<CleanupLockData>().statistics_blockno = MERGE_LOCK;
+        drop(buffer);
+
+        assert_eq!(
+            verify_structure(&index).unwrap(),
+            vec![(
+                Some(MERGE_LOCK),
+                "block is owned by both merge lock and statistics header".to_string()
+            )]
+        );
+    }
+
+    #[pg_test]
+    unsafe fn test_verify_structure_cycle() {
+        let index = create_index();
+        let metadata = BufferManager::new(index.oid())
+            .get_buffer(SCHEMA_START)
+            .page_contents::<LinkedListData>();
+
+        // link the schema's last block back to its first
+        let mut bman = BufferManager::new(index.oid());
+        let mut buffer = bman.get_buffer_mut(metadata.last_blockno);
+        let mut page = buffer.page_mut();
+        page.special_mut::<BM25PageSpecialData>().next_blockno = metadata.start_blockno;
+        drop(buffer);
+
+        assert_eq!(
+            verify_structure(&index).unwrap(),
+            vec![(
+                Some(metadata.start_blockno),
+                "schema has a cycle".to_string()
+            )]
+        );
+    }
+}
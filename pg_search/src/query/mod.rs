@@ -15,9 +15,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+mod nested;
 mod range;
 
+pub use nested::{nested_path_id, NESTED_PATH_KEY};
+
 use crate::postgres::utils::convert_pg_date_string;
+use crate::query::nested::NestedQuery;
 use crate::query::range::{Comparison, RangeField};
 use crate::schema::IndexRecordOption;
 use anyhow::Result;
@@ -37,7 +41,7 @@ use tantivy::{
         TermSetQuery,
     },
     query_grammar::Occur,
-    schema::{Field, FieldType, OwnedValue, DATE_TIME_PRECISION_INDEXED},
+    schema::{Facet, Field, FieldType, OwnedValue, DATE_TIME_PRECISION_INDEXED},
     Searcher, Term,
 };
 use thiserror::Error;
@@ -76,6 +80,10 @@ pub enum SearchQueryInput {
     Exists {
         field: String,
     },
+    Facet {
+        field: String,
+        path: String,
+    },
     FastFieldRangeWeight {
         field: String,
         #[serde(
@@ -116,6 +124,10 @@ pub enum SearchQueryInput {
         document_fields: Option<Vec<(String, tantivy::schema::OwnedValue)>>,
         document_id: Option<tantivy::schema::OwnedValue>,
     },
+    Nested {
+        path: String,
+        query: Box<SearchQueryInput>,
+    },
     Parse {
         query_string: String,
         lenient: Option<bool>,
@@ -213,6 +225,10 @@ pub enum SearchQueryInput {
         slop: Option<u32>,
         max_expansions: Option<u32>,
     },
+    SearchAfter {
+        sort_values: Vec<tantivy::schema::OwnedValue>,
+        key: Vec<tantivy::schema::OwnedValue>,
+    },
     Term {
         field: Option<String>,
         value: tantivy::schema::OwnedValue,
@@ -245,11 +261,38 @@ impl SearchQueryInput {
             SearchQueryInput::DisjunctionMax { disjuncts, .. } => {
                 disjuncts.iter().any(Self::contains_more_like_this)
             }
+            SearchQueryInput::Nested { query, .. } => Self::contains_more_like_this(query),
             SearchQueryInput::WithIndex { query, .. } => Self::contains_more_like_this(query),
             SearchQueryInput::MoreLikeThis { .. } => true,
             _ => false,
         }
     }
+
+    /// Remove a [`SearchQueryInput::SearchAfter`] cursor from this query, returning its sort
+    /// values and key.
+    ///
+    /// The cursor isn't a query by itself:  it's honored by the "top N" collector, which knows the
+    /// `ORDER BY` it's relative to.  Only a cursor that's required to match, either as the whole
+    /// query or as a `must` clause, can be taken.  It's replaced with [`SearchQueryInput::All`].
+    pub fn take_search_after(
+        &mut self,
+    ) -> Option<(
+        Vec<tantivy::schema::OwnedValue>,
+        Vec<tantivy::schema::OwnedValue>,
+    )> {
+        match self {
+            SearchQueryInput::SearchAfter { sort_values, key } => {
+                let cursor = (std::mem::take(sort_values), std::mem::take(key));
+                *self = SearchQueryInput::All;
+                Some(cursor)
+            }
+            SearchQueryInput::Boolean { must, .. } => {
+                must.iter_mut().find_map(|query| query.take_search_after())
+            }
+            SearchQueryInput::WithIndex { query, .. } => query.take_search_after(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -285,6 +328,8 @@ pub trait AsFieldType<T> {
 
     fn key_field(&self) -> (FieldType, PgOid, Field);
 
+    fn key_fields(&self) -> Vec<(FieldType, PgOid, Field)>;
+
     fn as_field_type(&self, from: &T) -> Option<(FieldType, PgOid, Field)>;
 
     fn is_field_type(&self, from: &T, value: &OwnedValue) -> bool {
@@ -554,6 +599,18 @@ impl SearchQueryInput {
             }
             Self::Empty => Ok(Box::new(EmptyQuery)),
             Self::Exists { field } => Ok(Box::new(ExistsQuery::new(field, false))),
+            Self::Facet { field, path } => {
+                let field = field_lookup
+                    .as_facet(&field)
+                    .ok_or(QueryError::WrongFieldType(field))?;
+                // facet terms are indexed for every ancestor of a document's facets, so a term
+                // matches the whole subtree below `path`
+                let term = Term::from_facet(field, &facet_from_path(&path)?);
+                Ok(Box::new(TermQuery::new(
+                    term,
+                    IndexRecordOption::Basic.into(),
+                )))
+            }
             Self::FastFieldRangeWeight {
                 field,
                 lower_bound,
@@ -707,10 +764,34 @@ impl SearchQueryInput {
 
                 match (document_id, document_fields) {
                     (Some(key_value), None) => {
-                        let (field_type, _, field) = field_lookup.key_field();
-                        let term = value_to_term(field, &key_value, &field_type, None, false)?;
-                        let query: Box<dyn Query> =
-                            Box::new(TermQuery::new(term, IndexRecordOption::Basic.into()));
+                        let key_fields = field_lookup.key_fields();
+                        let key_values = match key_value {
+                            // a composite key is given as a ROW(...) of all its parts
+                            OwnedValue::Array(parts) if key_fields.len() > 1 => parts,
+                            key_value => vec![key_value],
+                        };
+                        if key_values.len() != key_fields.len() {
+                            return Err(Box::new(QueryError::KeyPartsMismatch(
+                                key_fields.len(),
+                                key_values.len(),
+                            )));
+                        }
+
+                        let mut key_terms = vec![];
+                        for ((field_type, _, field), key_value) in key_fields.iter().zip(key_values)
+                        {
+                            let term = value_to_term(*field, &key_value, field_type, None, false)?;
+                            key_terms.push((
+                                Occur::Must,
+                                Box::new(TermQuery::new(term, IndexRecordOption::Basic.into()))
+                                    as Box<dyn Query>,
+                            ));
+                        }
+                        let query: Box<dyn Query> = if key_terms.len() == 1 {
+                            key_terms.pop().unwrap().1
+                        } else {
+                            Box::new(BooleanQuery::new(key_terms))
+                        };
                         let addresses = searcher.search(&query, &DocSetCollector)?;
                         let disjuncts: Vec<Box<dyn Query>> = addresses
                             .into_iter()
@@ -748,6 +829,29 @@ impl SearchQueryInput {
                     }
                 }
             }
+            Self::Nested { path, query } => {
+                let (field, path) = split_field_and_path(&path);
+                let (field_type, _, field) = field_lookup
+                    .as_field_type(&field)
+                    .filter(|(field_type, _, _)| matches!(field_type, FieldType::JsonObject(_)))
+                    .ok_or(QueryError::WrongFieldType(field))?;
+                let path_term = value_to_term(
+                    field,
+                    &OwnedValue::I64(nested_path_id(&path.unwrap_or_default())),
+                    &field_type,
+                    Some(NESTED_PATH_KEY),
+                    false,
+                )?;
+                let key_field_name = searcher
+                    .schema()
+                    .get_field_name(field_lookup.key_field().2)
+                    .to_string();
+                Ok(Box::new(NestedQuery::new(
+                    query.into_tantivy_query(field_lookup, parser, searcher)?,
+                    path_term,
+                    key_field_name,
+                )))
+            }
             Self::PhrasePrefix {
                 field,
                 phrases,
@@ -1583,6 +1687,7 @@ impl SearchQueryInput {
 
                 Ok(Box::new(TermSetQuery::new(terms)))
             }
+            Self::SearchAfter { .. } => Err(QueryError::SearchAfterWithoutTopN.into()),
             Self::WithIndex { query, .. } => {
                 query.into_tantivy_query(field_lookup, parser, searcher)
             }
@@ -1722,6 +1827,17 @@ pub fn split_field_and_path(field: &str) -> (String, Option<String>) {
     }
 }
 
+/// Parse a facet path, given either as `/a/b/c` or in `ltree` form as `a.b.c`
+pub fn facet_from_path(path: &str) -> Result<Facet, Box<dyn std::error::Error>> {
+    if path.starts_with('/') {
+        Ok(Facet::from_text(path).map_err(|_| QueryError::InvalidFacetPath(path.to_string()))?)
+    } else if path.is_empty() {
+        Ok(Facet::root())
+    } else {
+        Ok(Facet::from_path(path.split('.')))
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Error)]
 enum QueryError {
@@ -1735,6 +1851,12 @@ enum QueryError {
     NonIndexedField(String),
     #[error("wrong type given for field")]
     FieldTypeMismatch,
+    #[error("the index's key_field has {0} parts but {1} values were given")]
+    KeyPartsMismatch(usize, usize),
+    #[error("paradedb.search_after() requires an ORDER BY ... LIMIT over fast fields or paradedb.score() that the index can sort by")]
+    SearchAfterWithoutTopN,
+    #[error("'{0}' is not a valid facet path")]
+    InvalidFacetPath(String),
     #[error("could not build regex with pattern '{1}': {0}")]
     RegexError(#[source] tantivy::TantivyError, String),
     #[error(
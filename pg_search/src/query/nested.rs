@@ -0,0 +1,173 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use tantivy::query::{
+    BitSetDocSet, BooleanQuery, ConstScoreQuery, EnableScoring, ExistsQuery, Explanation, Query,
+    Scorer, TermQuery, Weight,
+};
+use tantivy::query_grammar::Occur;
+use tantivy::schema::IndexRecordOption;
+use tantivy::{DocId, DocSet, Score, SegmentReader, TantivyError, Term, TERMINATED};
+use tantivy_common::BitSet;
+
+/// The key, at the root of a nested child document's JSON field, that records which array the
+/// child came from
+pub const NESTED_PATH_KEY: &str = "_paradedb_nested_path";
+
+/// A stable identifier for the path of a nested array, relative to its JSON field.  It's
+/// indexed under [`NESTED_PATH_KEY`] as a number, so that it's not subject to the field's
+/// tokenizer.
+pub fn nested_path_id(path: &str) -> i64 {
+    // FNV-1a, which unlike the std hashers is guaranteed to never change
+    let hash = path.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    (hash & i64::MAX as u64) as i64
+}
+
+/// Matches parent documents that have at least one nested child document matching the wrapped
+/// query.
+///
+/// Children are indexed immediately before their parent and don't have a value for the index's
+/// key field, while every parent does.  So the parent of a child is the first document after it
+/// that has a key.  Only children of the array at the query's path are considered.
+#[derive(Clone, Debug)]
+pub struct NestedQuery {
+    child_query: Box<dyn Query>,
+    parent_query: Box<dyn Query>,
+}
+
+impl NestedQuery {
+    pub fn new(query: Box<dyn Query>, path_term: Term, key_field_name: String) -> Self {
+        let parent_query: Box<dyn Query> = Box::new(ExistsQuery::new(key_field_name, false));
+        let child_query = Box::new(BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (
+                Occur::Must,
+                Box::new(ConstScoreQuery::new(
+                    Box::new(TermQuery::new(path_term, IndexRecordOption::Basic)),
+                    0.0,
+                )),
+            ),
+            (Occur::MustNot, parent_query.clone()),
+        ]));
+
+        Self {
+            child_query,
+            parent_query,
+        }
+    }
+}
+
+impl Query for NestedQuery {
+    fn weight(&self, enable_scoring: EnableScoring<'_>) -> tantivy::Result<Box<dyn Weight>> {
+        Ok(Box::new(NestedWeight {
+            child_weight: self.child_query.weight(enable_scoring)?,
+            parent_weight: self.parent_query.weight(enable_scoring)?,
+        }))
+    }
+
+    fn query_terms<'a>(&'a self, visitor: &mut dyn FnMut(&'a Term, bool)) {
+        self.child_query.query_terms(visitor)
+    }
+}
+
+struct NestedWeight {
+    child_weight: Box<dyn Weight>,
+    parent_weight: Box<dyn Weight>,
+}
+
+impl Weight for NestedWeight {
+    fn scorer(&self, reader: &SegmentReader, boost: Score) -> tantivy::Result<Box<dyn Scorer>> {
+        // deleted parents are included here too, so that their (also deleted) children don't
+        // get attributed to the next parent
+        let mut parents = BitSet::with_max_value(reader.max_doc());
+        let mut parent_scorer = self.parent_weight.scorer(reader, 1.0)?;
+        while parent_scorer.doc() != TERMINATED {
+            parents.insert(parent_scorer.doc());
+            parent_scorer.advance();
+        }
+        let mut parents = BitSetDocSet::from(parents);
+
+        let mut matches: Vec<(DocId, Score)> = vec![];
+        let mut child_scorer = self.child_weight.scorer(reader, boost)?;
+        while child_scorer.doc() != TERMINATED {
+            let child = child_scorer.doc();
+            let parent = if parents.doc() > child {
+                parents.doc()
+            } else {
+                parents.seek(child + 1)
+            };
+            if parent == TERMINATED {
+                // a child without a parent can only be the result of a partially written block
+                break;
+            }
+
+            // a parent scores as its best matching child
+            let score = child_scorer.score();
+            match matches.last_mut() {
+                Some((doc, best)) if *doc == parent => *best = best.max(score),
+                _ => matches.push((parent, score)),
+            }
+            child_scorer.advance();
+        }
+
+        Ok(Box::new(NestedScorer { matches, cursor: 0 }))
+    }
+
+    fn explain(&self, reader: &SegmentReader, doc: DocId) -> tantivy::Result<Explanation> {
+        let mut scorer = self.scorer(reader, 1.0)?;
+        if scorer.seek(doc) != doc {
+            return Err(TantivyError::InvalidArgument(format!(
+                "Document #({doc}) does not match"
+            )));
+        }
+        Ok(Explanation::new("NestedQuery", scorer.score()))
+    }
+}
+
+struct NestedScorer {
+    matches: Vec<(DocId, Score)>,
+    cursor: usize,
+}
+
+impl DocSet for NestedScorer {
+    fn advance(&mut self) -> DocId {
+        self.cursor += 1;
+        self.doc()
+    }
+
+    fn doc(&self) -> DocId {
+        self.matches
+            .get(self.cursor)
+            .map(|(doc, _)| *doc)
+            .unwrap_or(TERMINATED)
+    }
+
+    fn size_hint(&self) -> u32 {
+        self.matches.len() as u32
+    }
+}
+
+impl Scorer for NestedScorer {
+    fn score(&mut self) -> Score {
+        self.matches
+            .get(self.cursor)
+            .map(|(_, score)| *score)
+            .unwrap_or_default()
+    }
+}
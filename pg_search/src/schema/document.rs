@@ -22,6 +22,9 @@ use tantivy::TantivyDocument;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SearchDocument {
     pub doc: TantivyDocument,
+    /// One child document per element of a `nested` JSON field.  These are written immediately
+    /// before `doc` so that a nested query can find the parent of a matching element.
+    pub nested: Vec<TantivyDocument>,
 }
 
 impl SearchDocument {
@@ -29,6 +32,13 @@ impl SearchDocument {
     pub fn insert(&mut self, SearchFieldId(key): SearchFieldId, value: OwnedValue) {
         self.doc.add_field_value(key, &value)
     }
+
+    #[inline(always)]
+    pub fn insert_nested(&mut self, SearchFieldId(key): SearchFieldId, value: OwnedValue) {
+        let mut child = TantivyDocument::new();
+        child.add_field_value(key, &value);
+        self.nested.push(child);
+    }
 }
 
 impl From<SearchDocument> for TantivyDocument {
@@ -28,9 +28,11 @@ use serde_json::json;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use tantivy::schema::{
-    DateOptions, Field, JsonObjectOptions, NumericOptions, Schema, TextFieldIndexing, TextOptions,
+    DateOptions, FacetOptions, Field, JsonObjectOptions, NumericOptions, Schema, SchemaBuilder,
+    TextFieldIndexing, TextOptions,
 };
 use thiserror::Error;
+use tokenizers::manager::SearchTokenizerFilters;
 use tokenizers::{SearchNormalizer, SearchTokenizer};
 
 use crate::postgres::index::get_fields;
@@ -61,6 +63,7 @@ pub enum SearchFieldType {
     Json,
     Date,
     Range,
+    Facet,
 }
 
 impl TryFrom<&PgOid> for SearchFieldType {
@@ -96,6 +99,8 @@ impl TryFrom<&PgOid> for SearchFieldType {
             PgOid::Custom(custom) => {
                 if unsafe { pgrx::pg_sys::type_is_enum(*custom) } {
                     Ok(SearchFieldType::F64)
+                } else if unsafe { is_ltree_type(*custom) } {
+                    Ok(SearchFieldType::Facet)
                 } else {
                     Err(SearchIndexSchemaError::InvalidPgOid(*pg_oid))
                 }
@@ -105,6 +110,16 @@ impl TryFrom<&PgOid> for SearchFieldType {
     }
 }
 
+/// Is `oid` the `ltree` type from the `ltree` contrib extension?  As an extension type, it
+/// doesn't have a fixed oid, so it's identified by name.
+pub unsafe fn is_ltree_type(oid: pgrx::pg_sys::Oid) -> bool {
+    let type_name = std::ffi::CStr::from_ptr(pgrx::pg_sys::format_type_be(oid));
+    type_name
+        .to_str()
+        .map(|type_name| type_name.rsplit('.').next() == Some("ltree"))
+        .unwrap_or(false)
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 pub enum SearchFieldConfig {
     Text {
@@ -143,6 +158,8 @@ pub enum SearchFieldConfig {
         #[serde(default)]
         normalizer: SearchNormalizer,
         #[serde(default)]
+        nested: bool,
+        #[serde(default)]
         column: Option<String>,
     },
     Range {
@@ -181,6 +198,12 @@ pub enum SearchFieldConfig {
         #[serde(default)]
         column: Option<String>,
     },
+    Facet {
+        #[serde(default = "default_as_false")]
+        stored: bool,
+        #[serde(default)]
+        column: Option<String>,
+    },
 }
 
 impl SearchFieldConfig {
@@ -307,6 +330,13 @@ impl SearchFieldConfig {
             None => Ok(true),
         }?;
 
+        let nested = match obj.get("nested") {
+            Some(v) => v
+                .as_bool()
+                .ok_or_else(|| anyhow::anyhow!("'nested' field should be a boolean")),
+            None => Ok(false),
+        }?;
+
         let column = match obj.get("column") {
             Some(v) => v
                 .as_str()
@@ -324,6 +354,7 @@ impl SearchFieldConfig {
             tokenizer,
             record,
             normalizer,
+            nested,
             column,
         })
     }
@@ -477,6 +508,29 @@ impl SearchFieldConfig {
         })
     }
 
+    pub fn facet_from_json(value: serde_json::Value) -> Result<Self> {
+        let obj = value
+            .as_object()
+            .context("Expected a JSON object for Facet configuration")?;
+
+        let stored = match obj.get("stored") {
+            Some(v) => v
+                .as_bool()
+                .ok_or_else(|| anyhow::anyhow!("'stored' field should be a boolean")),
+            None => Ok(false),
+        }?;
+
+        let column = match obj.get("column") {
+            Some(v) => v
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("'column' field should be a string"))
+                .map(|s| Some(s.to_string())),
+            None => Ok(None),
+        }?;
+
+        Ok(SearchFieldConfig::Facet { stored, column })
+    }
+
     pub fn column(&self) -> Option<&String> {
         match self {
             Self::Text { column, .. }
@@ -484,7 +538,8 @@ impl SearchFieldConfig {
             | Self::Range { column, .. }
             | Self::Numeric { column, .. }
             | Self::Boolean { column, .. }
-            | Self::Date { column, .. } => column.as_ref(),
+            | Self::Date { column, .. }
+            | Self::Facet { column, .. } => column.as_ref(),
         }
     }
 }
@@ -514,6 +569,61 @@ impl SearchFieldConfig {
     pub fn default_date() -> Self {
         Self::from_json(json!({"Date": {}}))
     }
+
+    /// The configuration every `key_field` column is indexed with, regardless of what the index
+    /// options say.  Keys must be matched exactly and read back quickly, so they are untokenized
+    /// fast fields.
+    pub fn key_field(field_type: &SearchFieldType) -> Self {
+        match field_type {
+            SearchFieldType::I64 | SearchFieldType::U64 | SearchFieldType::F64 => {
+                SearchFieldConfig::Numeric {
+                    indexed: true,
+                    fast: true,
+                    stored: false,
+                    column: None,
+                }
+            }
+            SearchFieldType::Text => SearchFieldConfig::Text {
+                indexed: true,
+                fast: true,
+                stored: false,
+                fieldnorms: false,
+                tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
+                record: IndexRecordOption::Basic,
+                normalizer: SearchNormalizer::Raw,
+                column: None,
+            },
+            SearchFieldType::Json => SearchFieldConfig::Json {
+                indexed: true,
+                fast: true,
+                stored: false,
+                fieldnorms: false,
+                expand_dots: false,
+                tokenizer: SearchTokenizer::Raw(SearchTokenizerFilters::default()),
+                record: IndexRecordOption::Basic,
+                normalizer: SearchNormalizer::Raw,
+                nested: false,
+                column: None,
+            },
+            SearchFieldType::Range => SearchFieldConfig::Range {
+                stored: false,
+                column: None,
+            },
+            SearchFieldType::Bool => SearchFieldConfig::Boolean {
+                indexed: true,
+                fast: true,
+                stored: false,
+                column: None,
+            },
+            SearchFieldType::Date => SearchFieldConfig::Date {
+                indexed: true,
+                fast: true,
+                stored: false,
+                column: None,
+            },
+            SearchFieldType::Facet => panic!("an ltree column cannot be used as the key_field"),
+        }
+    }
 }
 
 impl From<SearchFieldConfig> for TextOptions {
@@ -662,6 +772,23 @@ impl From<SearchFieldConfig> for DateOptions {
     }
 }
 
+impl From<SearchFieldConfig> for FacetOptions {
+    fn from(config: SearchFieldConfig) -> Self {
+        let mut facet_options = FacetOptions::default();
+        match config {
+            SearchFieldConfig::Facet { stored, .. } => {
+                if stored {
+                    facet_options = facet_options.set_stored();
+                }
+            }
+            _ => {
+                panic!("attempted to convert non-facet search field config to tantivy facet config")
+            }
+        }
+        facet_options
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub struct SearchField {
     /// The id of the field, stored in the index.
@@ -684,8 +811,9 @@ impl From<&SearchField> for Field {
 pub struct SearchIndexSchema {
     /// The fields that are stored in the index.
     pub fields: Vec<SearchField>,
-    /// The index of the key field in the fields vector.
-    pub key: usize,
+    /// The indexes of the key fields in the fields vector, in key order.  There is more than
+    /// one when the index uses a composite key.
+    pub keys: Vec<usize>,
     /// The underlying tantivy schema
     #[into]
     pub schema: Schema,
@@ -697,23 +825,14 @@ pub struct SearchIndexSchema {
 impl SearchIndexSchema {
     pub fn new(
         fields: Vec<(SearchFieldName, SearchFieldConfig, SearchFieldType)>,
-        key_index: usize,
+        key_indexes: Vec<usize>,
     ) -> Result<Self, SearchIndexSchemaError> {
         let mut builder = Schema::builder();
         let mut search_fields = vec![];
 
         for (name, config, field_type) in fields {
-            let id: SearchFieldId = match field_type {
-                SearchFieldType::Text => builder.add_text_field(name.as_ref(), config.clone()),
-                SearchFieldType::I64 => builder.add_i64_field(name.as_ref(), config.clone()),
-                SearchFieldType::U64 => builder.add_u64_field(name.as_ref(), config.clone()),
-                SearchFieldType::F64 => builder.add_f64_field(name.as_ref(), config.clone()),
-                SearchFieldType::Bool => builder.add_bool_field(name.as_ref(), config.clone()),
-                SearchFieldType::Json => builder.add_json_field(name.as_ref(), config.clone()),
-                SearchFieldType::Range => builder.add_json_field(name.as_ref(), config.clone()),
-                SearchFieldType::Date => builder.add_date_field(name.as_ref(), config.clone()),
-            }
-            .into();
+            let id: SearchFieldId =
+                Self::add_field(&mut builder, &name, &config, field_type).into();
 
             search_fields.push(SearchField {
                 id,
@@ -728,36 +847,98 @@ impl SearchIndexSchema {
         builder.add_u64_field("ctid", tantivy::schema::INDEXED | tantivy::schema::FAST);
 
         Ok(Self {
-            key: key_index,
+            keys: key_indexes,
             schema: builder.build(),
             lookup: Self::build_lookup(&search_fields).into(),
             fields: search_fields,
         })
     }
 
+    /// Open the [`SearchIndexSchema`] for an existing index, using the tantivy `schema` stored
+    /// in the index.
+    ///
+    /// Fields that are configured on the index but aren't yet in `schema` (ie, they were added
+    /// with `ALTER INDEX` and no writer has synchronized the stored schema yet) are left out.
     pub fn open(schema: Schema, index_relation: &PgRelation) -> Self {
-        let (fields, key_index) = unsafe { get_fields(index_relation) };
+        let (fields, key_indexes) = unsafe { get_fields(index_relation) };
+        let key_names = key_indexes
+            .iter()
+            .map(|key_index| fields[*key_index].0.clone())
+            .collect::<Vec<_>>();
         let search_fields = fields
             .iter()
-            .map(|(field_name, field_config, field_type)| {
-                let field = schema.get_field(field_name.0.as_str()).unwrap();
-                SearchField {
+            .filter_map(|(field_name, field_config, field_type)| {
+                let field = schema.get_field(field_name.0.as_str()).ok()?;
+                Some(SearchField {
                     id: SearchFieldId(field),
                     name: field_name.clone(),
                     config: field_config.clone(),
                     type_: *field_type,
-                }
+                })
             })
             .collect::<Vec<_>>();
+        let key_indexes = key_names
+            .iter()
+            .map(|key_name| {
+                search_fields
+                    .iter()
+                    .position(|field| &field.name == key_name)
+                    .expect("key field should be in the stored schema")
+            })
+            .collect();
 
         Self {
-            key: key_index,
+            keys: key_indexes,
             schema,
             lookup: Self::build_lookup(&search_fields).into(),
             fields: search_fields,
         }
     }
 
+    /// Returns a copy of the stored tantivy `schema` with any of the configured `fields` it
+    /// doesn't already contain appended to the end, or [`None`] if there's nothing to add.
+    ///
+    /// Fields that already exist keep the configuration they were created with, as segments
+    /// have already been written using it.  Changing them requires a REINDEX.
+    pub fn with_new_fields(
+        schema: &Schema,
+        fields: &[(SearchFieldName, SearchFieldConfig, SearchFieldType)],
+    ) -> Option<Schema> {
+        let mut builder = Schema::builder();
+        for (_, field_entry) in schema.fields() {
+            builder.add_field(field_entry.clone());
+        }
+
+        let mut added = false;
+        for (name, config, field_type) in fields {
+            if schema.get_field(name.0.as_str()).is_err() {
+                Self::add_field(&mut builder, name, config, *field_type);
+                added = true;
+            }
+        }
+
+        added.then(|| builder.build())
+    }
+
+    fn add_field(
+        builder: &mut SchemaBuilder,
+        name: &SearchFieldName,
+        config: &SearchFieldConfig,
+        field_type: SearchFieldType,
+    ) -> Field {
+        match field_type {
+            SearchFieldType::Text => builder.add_text_field(name.as_ref(), config.clone()),
+            SearchFieldType::I64 => builder.add_i64_field(name.as_ref(), config.clone()),
+            SearchFieldType::U64 => builder.add_u64_field(name.as_ref(), config.clone()),
+            SearchFieldType::F64 => builder.add_f64_field(name.as_ref(), config.clone()),
+            SearchFieldType::Bool => builder.add_bool_field(name.as_ref(), config.clone()),
+            SearchFieldType::Json => builder.add_json_field(name.as_ref(), config.clone()),
+            SearchFieldType::Range => builder.add_json_field(name.as_ref(), config.clone()),
+            SearchFieldType::Date => builder.add_date_field(name.as_ref(), config.clone()),
+            SearchFieldType::Facet => builder.add_facet_field(name.as_ref(), config.clone()),
+        }
+    }
+
     fn build_lookup(search_fields: &[SearchField]) -> HashMap<SearchFieldName, usize> {
         let mut lookup = HashMap::new();
         search_fields
@@ -770,21 +951,43 @@ impl SearchIndexSchema {
         lookup
     }
 
+    /// The first (or only) field of the key
     pub fn key_field(&self) -> SearchField {
-        self.fields
-            .get(self.key)
+        self.key_fields()
+            .into_iter()
+            .next()
             .expect("key field should be present on search schema")
-            .clone()
+    }
+
+    /// Every field of the key, in key order
+    pub fn key_fields(&self) -> Vec<SearchField> {
+        self.keys
+            .iter()
+            .map(|key| {
+                self.fields
+                    .get(*key)
+                    .expect("key field should be present on search schema")
+                    .clone()
+            })
+            .collect()
     }
 
     pub fn is_key_field(&self, name: &str) -> bool {
-        self.key_field().name.0 == name
+        self.key_fields().iter().any(|field| field.name.0 == name)
+    }
+
+    /// Does any field index the elements of its JSON arrays as nested documents?
+    pub fn has_nested_fields(&self) -> bool {
+        self.fields
+            .iter()
+            .any(|field| matches!(field.config, SearchFieldConfig::Json { nested: true, .. }))
     }
 
     #[inline(always)]
     pub fn new_document(&self) -> SearchDocument {
         SearchDocument {
             doc: tantivy::TantivyDocument::new(),
+            nested: vec![],
         }
     }
 
@@ -975,6 +1178,18 @@ impl AsFieldType<String> for (&PgRelation, &SearchIndexSchema) {
         (field_type, self.typeoid(&search_field), field)
     }
 
+    fn key_fields(&self) -> Vec<(tantivy::schema::FieldType, PgOid, Field)> {
+        self.1
+            .key_fields()
+            .iter()
+            .map(|search_field| {
+                let field = search_field.id.0;
+                let field_type = self.1.schema.get_field_entry(field).field_type().clone();
+                (field_type, self.typeoid(search_field), field)
+            })
+            .collect()
+    }
+
     fn fields(&self) -> Vec<(tantivy::schema::FieldType, PgOid, Field)> {
         let indexrel = self.0;
         let typeoid_lookup: HashMap<String, PgOid> = indexrel
@@ -20,6 +20,22 @@ mod fixtures;
 use fixtures::*;
 use rstest::*;
 use sqlx::PgConnection;
+use std::time::{Duration, Instant};
+
+/// Merges happen in a background worker after the writing transaction commits, so poll for a
+/// while until the index reaches the segment count we expect
+fn wait_for_segment_count(conn: &mut PgConnection, expected: usize) -> usize {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    loop {
+        let nsegments = "SELECT COUNT(*) FROM paradedb.index_info('idxtest_table');"
+            .fetch_one::<(i64,)>(conn)
+            .0 as usize;
+        if nsegments == expected || Instant::now() > deadline {
+            return nsegments;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
 
 #[rstest]
 fn vacuum_full(mut conn: PgConnection) {
@@ -83,10 +99,49 @@ fn segment_count_matches_available_parallelism(mut conn: PgConnection) {
         "INSERT INTO test_table (value) SELECT md5(random()::text)".execute(&mut conn);
     }
 
+    let nsegments = wait_for_segment_count(&mut conn, expected_segments + 1);
+    assert_eq!(nsegments, expected_segments + 1);
+}
+
+#[rstest]
+fn merges_wait_for_commit(mut conn: PgConnection) {
+    r#"
+        SET maintenance_work_mem = '1GB';
+        DROP TABLE IF EXISTS test_table;
+        CREATE TABLE test_table (id SERIAL PRIMARY KEY, value TEXT NOT NULL);
+        INSERT INTO test_table (value) SELECT md5(random()::text) FROM generate_series(1, 100000);
+
+        CREATE INDEX idxtest_table ON public.test_table
+        USING bm25 (id, value)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "value": {}
+            }'
+        );
+    "#
+    .execute(&mut conn);
+
+    // inserts never merge themselves, and the background worker can't see the new segments
+    // until the transaction that wrote them commits
+    let expected_segments: usize = std::thread::available_parallelism().unwrap().into();
+    "BEGIN".execute(&mut conn);
+    for _ in 0..10 {
+        "INSERT INTO test_table (value) SELECT md5(random()::text)".execute(&mut conn);
+    }
     let nsegments = "SELECT COUNT(*) FROM paradedb.index_info('idxtest_table');"
         .fetch_one::<(i64,)>(&mut conn)
         .0 as usize;
+    assert_eq!(nsegments, expected_segments + 10);
+    "COMMIT".execute(&mut conn);
+
+    let nsegments = wait_for_segment_count(&mut conn, expected_segments + 1);
     assert_eq!(nsegments, expected_segments + 1);
+
+    let count = "SELECT COUNT(*) FROM test_table WHERE id @@@ paradedb.all()"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(count, 100010);
 }
 
 #[rstest]
@@ -152,9 +207,7 @@ fn vacuum_restores_segment_count(mut conn: PgConnection) {
     "VACUUM test_table".execute(&mut conn);
 
     let expected_segments: usize = std::thread::available_parallelism().unwrap().into();
-    let nsegments = "SELECT COUNT(*) FROM paradedb.index_info('idxtest_table');"
-        .fetch_one::<(i64,)>(&mut conn)
-        .0 as usize;
+    let nsegments = wait_for_segment_count(&mut conn, expected_segments + 1);
     assert_eq!(nsegments, expected_segments + 1);
 }
 
@@ -192,3 +245,256 @@ fn bulk_insert_merge_behavior(mut conn: PgConnection) {
         .0 as usize;
     assert_eq!(nsegments, expected_segments + 1);
 }
+
+#[rstest]
+fn no_merge_policy(mut conn: PgConnection) {
+    r#"
+        DROP TABLE IF EXISTS test_table;
+        CREATE TABLE test_table (id SERIAL PRIMARY KEY, value TEXT NOT NULL);
+
+        CREATE INDEX idxtest_table ON public.test_table
+        USING bm25 (id, value)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "value": {}
+            }',
+            merge_policy = 'no_merge'
+        );
+    "#
+    .execute(&mut conn);
+
+    let num_inserts = std::thread::available_parallelism().unwrap().get() + 10;
+    for _ in 0..num_inserts {
+        "INSERT INTO test_table (value) SELECT md5(random()::text)".execute(&mut conn);
+    }
+
+    // give a merge worker the chance to misbehave, if one were launched
+    std::thread::sleep(Duration::from_secs(1));
+    let nsegments = "SELECT COUNT(*) FROM paradedb.index_info('idxtest_table');"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0 as usize;
+    assert_eq!(nsegments, num_inserts);
+}
+
+#[rstest]
+fn size_tiered_merge_policy(mut conn: PgConnection) {
+    r#"
+        DROP TABLE IF EXISTS test_table;
+        CREATE TABLE test_table (id SERIAL PRIMARY KEY, value TEXT NOT NULL);
+
+        CREATE INDEX idxtest_table ON public.test_table
+        USING bm25 (id, value)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "value": {}
+            }',
+            merge_policy = '{"type": "size_tiered", "merge_factor": 2}'
+        );
+    "#
+    .execute(&mut conn);
+
+    // every segment is well below the floor size, so they're all in the lowest tier and any
+    // two of them are merged together
+    for _ in 0..10 {
+        "INSERT INTO test_table (value) SELECT md5(random()::text)".execute(&mut conn);
+    }
+
+    let nsegments = wait_for_segment_count(&mut conn, 1);
+    assert_eq!(nsegments, 1);
+
+    let count = "SELECT COUNT(*) FROM test_table WHERE id @@@ paradedb.all()"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(count, 10);
+}
+
+#[rstest]
+fn invalid_merge_policy(mut conn: PgConnection) {
+    "CREATE TABLE test_table (id SERIAL PRIMARY KEY, value TEXT NOT NULL)".execute(&mut conn);
+
+    for (merge_policy, expected) in [
+        ("tiered", "unknown variant `tiered`"),
+        (
+            r#"{"type": "size_tiered", "merge_factor": 1}"#,
+            "merge_factor must be at least 2",
+        ),
+        (
+            r#"{"type": "n_plus_one", "n": 4, "m": 2}"#,
+            "unknown field `m`",
+        ),
+    ] {
+        let result = format!(
+            r#"CREATE INDEX idxtest_table ON test_table USING bm25 (id, value)
+               WITH (key_field = 'id', text_fields = '{{"value": {{}}}}', merge_policy = '{merge_policy}')"#
+        )
+        .execute_result(&mut conn);
+        match result {
+            Ok(_) => panic!("merge_policy '{merge_policy}' should be rejected"),
+            Err(err) => assert!(err.to_string().contains(expected), "{err}"),
+        }
+    }
+}
+
+#[rstest]
+fn force_merge(mut conn: PgConnection) {
+    r#"
+        DROP TABLE IF EXISTS test_table;
+        CREATE TABLE test_table (id SERIAL PRIMARY KEY, value TEXT NOT NULL);
+
+        CREATE INDEX idxtest_table ON public.test_table
+        USING bm25 (id, value)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "value": {}
+            }',
+            merge_policy = 'no_merge'
+        );
+    "#
+    .execute(&mut conn);
+
+    for _ in 0..10 {
+        "INSERT INTO test_table (value) SELECT md5(random()::text) FROM generate_series(1, 100)"
+            .execute(&mut conn);
+    }
+    "DELETE FROM test_table WHERE id % 2 = 0".execute(&mut conn);
+    "VACUUM test_table".execute(&mut conn);
+
+    let (segments_before, segments_after, deleted_docs_removed) =
+        "SELECT * FROM paradedb.merge('idxtest_table', max_segments => 1, expunge_deletes => true)"
+            .fetch_one::<(i32, i32, i64)>(&mut conn);
+    assert_eq!(segments_before, 10);
+    assert_eq!(segments_after, 1);
+    assert_eq!(deleted_docs_removed, 500);
+
+    let (nsegments, num_deleted) =
+        "SELECT COUNT(*), SUM(num_deleted)::bigint FROM paradedb.index_info('idxtest_table')"
+            .fetch_one::<(i64, i64)>(&mut conn);
+    assert_eq!(nsegments, 1);
+    assert_eq!(num_deleted, 0);
+
+    let count = "SELECT COUNT(*) FROM test_table WHERE id @@@ paradedb.all()"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(count, 500);
+
+    let result = "SELECT * FROM paradedb.merge('idxtest_table', max_segments => 0)"
+        .execute_result(&mut conn);
+    match result {
+        Ok(_) => panic!("max_segments => 0 should be rejected"),
+        Err(err) => assert!(
+            err.to_string().contains("max_segments must be at least 1"),
+            "{err}"
+        ),
+    }
+}
+
+#[rstest]
+fn vacuum_rewrites_deleted_segments(mut conn: PgConnection) {
+    r#"
+        DROP TABLE IF EXISTS test_table;
+        CREATE TABLE test_table (id SERIAL PRIMARY KEY, value TEXT NOT NULL) WITH (autovacuum_enabled = false);
+
+        CREATE INDEX idxtest_table ON public.test_table
+        USING bm25 (id, value)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "value": {}
+            }',
+            merge_policy = 'no_merge'
+        );
+    "#
+    .execute(&mut conn);
+
+    for _ in 0..10 {
+        "INSERT INTO test_table (value) SELECT md5(random()::text) FROM generate_series(1, 100)"
+            .execute(&mut conn);
+    }
+
+    // half of every segment is deleted, which is past the default threshold
+    "DELETE FROM test_table WHERE id % 2 = 0".execute(&mut conn);
+    "VACUUM test_table".execute(&mut conn);
+
+    let (nsegments, num_deleted) =
+        "SELECT COUNT(*), SUM(num_deleted)::bigint FROM paradedb.index_info('idxtest_table')"
+            .fetch_one::<(i64, i64)>(&mut conn);
+    assert_eq!(nsegments, 10);
+    assert_eq!(num_deleted, 0);
+
+    let reltuples = "SELECT reltuples::bigint FROM pg_class WHERE relname = 'idxtest_table'"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(reltuples, 500);
+
+    // a fifth of every segment is deleted, which isn't
+    "SET paradedb.vacuum_deleted_docs_ratio = 0.5".execute(&mut conn);
+    "DELETE FROM test_table WHERE id % 10 = 1".execute(&mut conn);
+    "VACUUM test_table".execute(&mut conn);
+
+    let num_deleted = "SELECT SUM(num_deleted)::bigint FROM paradedb.index_info('idxtest_table')"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(num_deleted, 100);
+
+    let reltuples = "SELECT reltuples::bigint FROM pg_class WHERE relname = 'idxtest_table'"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(reltuples, 400);
+
+    let count = "SELECT COUNT(*) FROM test_table WHERE id @@@ paradedb.all()"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(count, 400);
+}
+
+#[rstest]
+fn vacuum_only_deletes_from_segments_with_dead_rows(mut conn: PgConnection) {
+    r#"
+        DROP TABLE IF EXISTS test_table;
+        CREATE TABLE test_table (id SERIAL PRIMARY KEY, value TEXT NOT NULL) WITH (autovacuum_enabled = false);
+
+        CREATE INDEX idxtest_table ON public.test_table
+        USING bm25 (id, value)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "value": {}
+            }',
+            merge_policy = 'no_merge'
+        );
+    "#
+    .execute(&mut conn);
+
+    for _ in 0..4 {
+        "INSERT INTO test_table (value) SELECT md5(random()::text) FROM generate_series(1, 1000)"
+            .execute(&mut conn);
+    }
+
+    // mark every heap page all-visible, and then dirty only those of the last segment
+    "VACUUM test_table".execute(&mut conn);
+    "SET paradedb.vacuum_deleted_docs_ratio = 1".execute(&mut conn);
+    "DELETE FROM test_table WHERE id > 3990".execute(&mut conn);
+    "VACUUM test_table".execute(&mut conn);
+
+    let num_deleted = "SELECT num_deleted::bigint FROM paradedb.index_info('idxtest_table') ORDER BY num_deleted DESC"
+        .fetch::<(i64,)>(&mut conn)
+        .into_iter()
+        .map(|(num_deleted,)| num_deleted)
+        .collect::<Vec<_>>();
+    assert_eq!(num_deleted, vec![10, 0, 0, 0]);
+
+    let count = "SELECT COUNT(*) FROM test_table WHERE id @@@ paradedb.all()"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(count, 3990);
+
+    // nothing left to delete
+    "VACUUM test_table".execute(&mut conn);
+    let num_deleted = "SELECT SUM(num_deleted)::bigint FROM paradedb.index_info('idxtest_table')"
+        .fetch_one::<(i64,)>(&mut conn)
+        .0;
+    assert_eq!(num_deleted, 10);
+}
@@ -107,6 +107,51 @@ fn includes_segment_count(mut conn: PgConnection) {
     assert!(plan.get("Segment Count").is_some());
 }
 
+#[rstest]
+fn explain_analyze_instrumentation(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    "SET enable_indexscan TO off;".execute(&mut conn);
+
+    let (plan, ) = "EXPLAIN (ANALYZE, FORMAT JSON) SELECT * FROM paradedb.bm25_search WHERE description @@@ 'shoes'".fetch_one::<(Value,)>(&mut conn);
+    let plan = plan.pointer("/0/Plan/Plans/0").unwrap();
+    eprintln!("{plan:#?}");
+
+    // every segment is listed as "<segment id>: <matches>"
+    let segment_matches = plan
+        .get("Segment Matches")
+        .and_then(Value::as_str)
+        .expect("Segment Matches should be reported");
+    let total_matches: u64 = segment_matches
+        .split(", ")
+        .map(|segment| segment.rsplit_once(": ").unwrap().1.parse::<u64>().unwrap())
+        .sum();
+    assert_eq!(total_matches, 3);
+
+    assert_eq!(plan.get("Visibility Checks"), Some(&Value::from(3)));
+    assert!(plan.get("Heap Fetches").is_some());
+    assert!(plan.get("Tantivy Time").is_some());
+    assert!(plan.get("Heap Time").is_some());
+    // only reported under VERBOSE, like the virtual tuples
+    assert_eq!(plan.get("Invisible Tuples"), None);
+
+    let (plan, ) = "EXPLAIN (ANALYZE, VERBOSE, FORMAT JSON) SELECT * FROM paradedb.bm25_search WHERE description @@@ 'shoes'".fetch_one::<(Value,)>(&mut conn);
+    let plan = plan.pointer("/0/Plan/Plans/0").unwrap();
+    assert_eq!(plan.get("Invisible Tuples"), Some(&Value::from(0)));
+
+    let (plan, ) = "EXPLAIN (ANALYZE, TIMING OFF, FORMAT JSON) SELECT id, paradedb.score(id) FROM paradedb.bm25_search WHERE description @@@ 'shoes' ORDER BY paradedb.score(id) DESC LIMIT 2".fetch_one::<(Value,)>(&mut conn);
+    let plan = plan.pointer("/0/Plan/Plans/0").unwrap();
+    eprintln!("{plan:#?}");
+    assert_eq!(
+        plan.get("   Invisible Tuple Retries"),
+        Some(&Value::from(0))
+    );
+    assert_eq!(plan.get("   Final Chunk Size"), Some(&Value::from(2)));
+    assert_eq!(plan.get("Tantivy Time"), None);
+}
+
 #[rstest]
 fn field_on_left(mut conn: PgConnection) {
     SimpleProductsTable::setup().execute(&mut conn);
@@ -516,3 +561,506 @@ fn cte_issue_1951(mut conn: PgConnection) {
     "#.fetch_result::<(i32, )>(&mut conn).expect("query failed");
     assert_eq!(results.len(), 1);
 }
+
+#[rstest]
+fn stored_fields_project(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE stored_docs (id SERIAL PRIMARY KEY, description TEXT, rating INT);
+    INSERT INTO stored_docs (description, rating) VALUES
+        ('Ergonomic metal keyboard', 4),
+        ('Plastic keyboard', 3),
+        ('Running shoes', 5);
+    CREATE INDEX stored_docs_idx ON stored_docs
+    USING bm25 (id, description, rating)
+    WITH (
+        key_field = 'id',
+        text_fields = '{"description": {"stored": true}}',
+        numeric_fields = '{"rating": {"stored": true}}'
+    );
+    "#
+    .execute(&mut conn);
+
+    let rows: Vec<(i32, Value, Value)> = r#"
+    SELECT id, paradedb.stored(description), paradedb.stored(rating)
+    FROM stored_docs WHERE description @@@ 'keyboard' ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(
+        rows,
+        vec![
+            (
+                1,
+                Value::String("Ergonomic metal keyboard".into()),
+                Value::Number(Number::from(4))
+            ),
+            (
+                2,
+                Value::String("Plastic keyboard".into()),
+                Value::Number(Number::from(3))
+            ),
+        ]
+    );
+
+    let (document,): (Value,) =
+        "SELECT paradedb.stored_document('stored_docs_idx', 3)".fetch_one(&mut conn);
+    assert_eq!(
+        document["description"],
+        Value::String("Running shoes".into())
+    );
+    assert_eq!(document["rating"], Value::Number(Number::from(5)));
+
+    // in a join, another table's column isn't read from this index, even at the same position
+    r#"
+    CREATE TABLE stored_notes (id INT, note TEXT);
+    INSERT INTO stored_notes VALUES (1, 'first'), (2, 'second');
+    "#
+    .execute(&mut conn);
+    let rows: Vec<(i32, Option<Value>)> = r#"
+    SELECT d.id, paradedb.stored(n.note)
+    FROM stored_docs d JOIN stored_notes n ON n.id = d.id
+    WHERE d.description @@@ 'keyboard' ORDER BY d.id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1, None), (2, None)]);
+
+    let result =
+        "SELECT id, paradedb.stored(rating + 1) FROM stored_docs WHERE description @@@ 'keyboard'"
+            .fetch_result::<(i32, Option<Value>)>(&mut conn);
+    match result {
+        Ok(_) => panic!("`paradedb.stored()` of an expression should be rejected"),
+        Err(err) => assert!(
+            err.to_string()
+                .contains("`paradedb.stored()`'s argument must be a column"),
+            "{err}"
+        ),
+    }
+
+    // deleted rows are no longer visible
+    "DELETE FROM stored_docs WHERE id = 3".execute(&mut conn);
+    let (document,): (Option<Value>,) =
+        "SELECT paradedb.stored_document('stored_docs_idx', 3)".fetch_one(&mut conn);
+    assert_eq!(document, None);
+}
+
+#[rstest]
+fn stored_array_fields_stay_arrays(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE stored_tags (id SERIAL PRIMARY KEY, description TEXT, tags TEXT[]);
+    INSERT INTO stored_tags (description, tags) VALUES
+        ('Plastic keyboard', ARRAY['office']),
+        ('Metal keyboard', ARRAY['office', 'gaming']);
+    CREATE INDEX stored_tags_idx ON stored_tags
+    USING bm25 (id, description, tags)
+    WITH (
+        key_field = 'id',
+        text_fields = '{"description": {"stored": true}, "tags": {"stored": true}}'
+    );
+    "#
+    .execute(&mut conn);
+
+    let rows: Vec<(i32, Value)> = r#"
+    SELECT id, paradedb.stored(tags)
+    FROM stored_tags WHERE description @@@ 'keyboard' ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(
+        rows,
+        vec![
+            (1, serde_json::json!(["office"])),
+            (2, serde_json::json!(["office", "gaming"])),
+        ]
+    );
+
+    let (document,): (Value,) =
+        "SELECT paradedb.stored_document('stored_tags_idx', 1)".fetch_one(&mut conn);
+    assert_eq!(
+        document["description"],
+        Value::String("Plastic keyboard".into())
+    );
+    assert_eq!(document["tags"], serde_json::json!(["office"]));
+}
+
+#[rstest]
+fn pushdown_predicates_on_indexed_fields(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    SimpleProductsTable::setup().execute(&mut conn);
+    "UPDATE paradedb.bm25_search SET rating = NULL WHERE id = 5".execute(&mut conn);
+
+    let queries = [
+        "description @@@ 'shoes' AND rating < 4",
+        "description @@@ 'shoes' AND 4 > rating",
+        "description @@@ 'shoes' AND rating <> 4",
+        "description @@@ 'shoes' AND rating BETWEEN 2 AND 4",
+        "description @@@ 'keyboard' OR rating = 5",
+        "description @@@ 'shoes' AND rating IN (1, 3, 5)",
+        "description @@@ 'shoes' AND in_stock",
+        "description @@@ 'shoes' AND NOT in_stock",
+        "description @@@ 'shoes' AND rating IS NULL",
+        "description @@@ 'shoes' AND rating IS NOT NULL",
+        "description @@@ 'shoes' AND rating >= 2 AND in_stock AND id <> 4",
+    ];
+
+    for query in queries {
+        let (plan,) = format!(
+            "EXPLAIN (FORMAT JSON) SELECT id FROM paradedb.bm25_search WHERE {query} ORDER BY paradedb.score(id) DESC, id LIMIT 5"
+        )
+        .fetch_one::<(Value,)>(&mut conn);
+        assert!(
+            format!("{plan:?}").contains("ParadeDB Scan"),
+            "`{query}` should use the custom scan: {plan:#?}"
+        );
+
+        let sql = format!("SELECT id FROM paradedb.bm25_search WHERE {query} ORDER BY id");
+        let pushed_down: Vec<(i32,)> = sql.clone().fetch(&mut conn);
+
+        "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+        let expected: Vec<(i32,)> = sql.fetch(&mut conn);
+        "RESET paradedb.enable_custom_scan".execute(&mut conn);
+
+        assert_eq!(pushed_down, expected, "`{query}` returned the wrong rows");
+    }
+
+    // the LIMIT is applied after the predicates, inside the index
+    let rows: Vec<(i32, i32)> = r#"
+    SELECT id, rating FROM paradedb.bm25_search
+    WHERE description @@@ 'shoes' AND rating < 4
+    ORDER BY paradedb.score(id) DESC, id LIMIT 1
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows.len(), 1);
+    assert!(rows[0].1 < 4);
+}
+
+#[rstest]
+fn prepared_statement_generic_plan(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    SimpleProductsTable::setup().execute(&mut conn);
+    "SET plan_cache_mode TO force_generic_plan".execute(&mut conn);
+
+    r#"
+    PREPARE text_query(text) AS
+    SELECT id FROM paradedb.bm25_search WHERE description @@@ $1 ORDER BY id;
+
+    PREPARE builder_query(text) AS
+    SELECT id FROM paradedb.bm25_search WHERE id @@@ paradedb.term('description', $1) ORDER BY id;
+
+    PREPARE query_input(paradedb.searchqueryinput) AS
+    SELECT id, paradedb.score(id) FROM paradedb.bm25_search WHERE id @@@ $1 ORDER BY id;
+    "#
+    .execute(&mut conn);
+
+    for (statement, expected) in [
+        ("EXECUTE text_query('keyboard')", vec![(1,), (2,)]),
+        ("EXECUTE text_query('shoes')", vec![(3,), (4,), (5,)]),
+        ("EXECUTE builder_query('keyboard')", vec![(1,), (2,)]),
+        ("EXECUTE builder_query('shoes')", vec![(3,), (4,), (5,)]),
+    ] {
+        let (plan,) = format!("EXPLAIN (FORMAT JSON) {statement}").fetch_one::<(Value,)>(&mut conn);
+        assert!(
+            format!("{plan:?}").contains("ParadeDB Scan"),
+            "`{statement}` should use the custom scan: {plan:#?}"
+        );
+
+        let rows: Vec<(i32,)> = statement.fetch(&mut conn);
+        assert_eq!(rows, expected, "`{statement}` returned the wrong rows");
+    }
+
+    let rows: Vec<(i32, f32)> =
+        "EXECUTE query_input(paradedb.term('description', 'keyboard'))".fetch(&mut conn);
+    assert_eq!(
+        rows.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert!(rows.iter().all(|(_, score)| *score > 0.0));
+}
+
+#[rstest]
+fn prepared_statement_pushdown_parameters(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    SimpleProductsTable::setup().execute(&mut conn);
+    "SET plan_cache_mode TO force_generic_plan".execute(&mut conn);
+
+    r#"
+    PREPARE filtered(text, int, int[]) AS
+    SELECT id FROM paradedb.bm25_search
+    WHERE description @@@ $1 AND rating < $2 AND id = ANY($3)
+    ORDER BY id;
+
+    PREPARE in_stock(text, bool) AS
+    SELECT id FROM paradedb.bm25_search WHERE description @@@ $1 AND in_stock = $2 ORDER BY id;
+    "#
+    .execute(&mut conn);
+
+    for (statement, literal) in [
+        (
+            "EXECUTE filtered('shoes', 4, ARRAY[3, 4, 5])",
+            "description @@@ 'shoes' AND rating < 4 AND id = ANY(ARRAY[3, 4, 5])",
+        ),
+        (
+            "EXECUTE filtered('keyboard', 5, ARRAY[1, 2, 3])",
+            "description @@@ 'keyboard' AND rating < 5 AND id = ANY(ARRAY[1, 2, 3])",
+        ),
+        (
+            "EXECUTE filtered('shoes', NULL, ARRAY[3, 4, 5])",
+            "description @@@ 'shoes' AND rating < NULL AND id = ANY(ARRAY[3, 4, 5])",
+        ),
+        (
+            "EXECUTE filtered('shoes', 10, NULL)",
+            "description @@@ 'shoes' AND rating < 10 AND id = ANY(NULL::int[])",
+        ),
+        (
+            "EXECUTE in_stock('shoes', true)",
+            "description @@@ 'shoes' AND in_stock = true",
+        ),
+        (
+            "EXECUTE in_stock('shoes', false)",
+            "description @@@ 'shoes' AND in_stock = false",
+        ),
+    ] {
+        let (plan,) = format!("EXPLAIN (FORMAT JSON) {statement}").fetch_one::<(Value,)>(&mut conn);
+        assert!(
+            format!("{plan:?}").contains("ParadeDB Scan"),
+            "`{statement}` should use the custom scan: {plan:#?}"
+        );
+        let rows: Vec<(i32,)> = statement.fetch(&mut conn);
+
+        "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+        let expected: Vec<(i32,)> =
+            format!("SELECT id FROM paradedb.bm25_search WHERE {literal} ORDER BY id")
+                .fetch(&mut conn);
+        "RESET paradedb.enable_custom_scan".execute(&mut conn);
+
+        assert_eq!(rows, expected, "`{statement}` returned the wrong rows");
+    }
+}
+
+#[rstest]
+fn aggregate_pushdown(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    r#"
+    CALL paradedb.create_bm25_test_table(table_name => 'mock_items', schema_name => 'public');
+
+    CREATE INDEX mock_items_idx ON mock_items
+    USING bm25 (id, description, category, rating, in_stock)
+    WITH (
+        key_field='id',
+        text_fields='{
+            "description": {},
+            "category": {"fast": true, "normalizer": "raw"}
+        }',
+        numeric_fields='{"rating": {}}',
+        boolean_fields='{"in_stock": {}}'
+    );
+
+    UPDATE mock_items SET rating = NULL WHERE id = 1;
+    DELETE FROM mock_items WHERE id = 2;
+    "#
+    .execute(&mut conn);
+
+    let queries = [
+        "SELECT category, count(*), sum(rating), avg(rating)::text, min(rating), max(rating) FROM mock_items WHERE description @@@ 'shoes OR keyboard OR plastic' GROUP BY category ORDER BY category",
+        "SELECT in_stock, count(rating) * 2 AS doubled FROM mock_items WHERE description @@@ 'shoes OR keyboard' AND rating > 2 GROUP BY in_stock ORDER BY in_stock",
+        "SELECT count(*) FROM mock_items WHERE description @@@ 'shoes' GROUP BY category, in_stock ORDER BY category, in_stock",
+        "SELECT count(*), max(rating) FROM mock_items WHERE description @@@ 'shoes'",
+        "SELECT count(*), max(rating) FROM mock_items WHERE description @@@ 'does-not-exist'",
+    ];
+
+    for query in queries {
+        let (plan,) = format!("EXPLAIN (FORMAT JSON) {query}").fetch_one::<(Value,)>(&mut conn);
+        assert!(
+            format!("{plan:?}").contains("ParadeDB Aggregate Scan"),
+            "`{query}` should use the aggregate scan: {plan:#?}"
+        );
+
+        let sql = format!("SELECT row_to_json(q)::text FROM ({query}) q");
+        let pushed_down: Vec<(String,)> = sql.clone().fetch(&mut conn);
+
+        "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+        let expected: Vec<(String,)> = sql.fetch(&mut conn);
+        "RESET paradedb.enable_custom_scan".execute(&mut conn);
+
+        assert_eq!(pushed_down, expected, "`{query}` returned the wrong groups");
+    }
+
+    // `description` isn't a fast field, so Postgres does the grouping itself
+    let (plan,) = "EXPLAIN (FORMAT JSON) SELECT description, count(*) FROM mock_items WHERE description @@@ 'shoes' GROUP BY description"
+        .fetch_one::<(Value,)>(&mut conn);
+    assert!(!format!("{plan:?}").contains("ParadeDB Aggregate Scan"));
+}
+
+#[rstest]
+fn mixed_fast_fields(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    r#"
+    CALL paradedb.create_bm25_test_table(table_name => 'mock_items', schema_name => 'public');
+
+    CREATE INDEX mock_items_idx ON mock_items
+    USING bm25 (id, description, category, rating, in_stock)
+    WITH (
+        key_field='id',
+        text_fields='{
+            "description": {"fast": true},
+            "category": {"fast": true, "normalizer": "raw"}
+        }',
+        numeric_fields='{"rating": {}}',
+        boolean_fields='{"in_stock": {}}'
+    );
+    "#
+    .execute(&mut conn);
+    "VACUUM mock_items".execute(&mut conn);
+    "SET enable_indexscan TO off".execute(&mut conn);
+
+    let query = "SELECT id, description, category, rating, in_stock, paradedb.score(id) FROM mock_items WHERE description @@@ 'shoes OR keyboard'";
+    let (plan,) = format!("EXPLAIN (FORMAT JSON) {query}").fetch_one::<(Value,)>(&mut conn);
+    let plan = plan.pointer("/0/Plan/Plans/0").unwrap();
+    assert_eq!(
+        plan.get("Exec Method"),
+        Some(&Value::from("MixedFastFieldExecState")),
+        "{plan:#?}"
+    );
+
+    let mut fast: Vec<(i32, String, String, i32, bool, f32)> = query.fetch(&mut conn);
+    fast.sort_by_key(|row| row.0);
+    assert!(fast.iter().all(|row| row.5 > 0.0));
+
+    "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+    let expected: Vec<(i32, String, String, i32, bool)> = "SELECT id, description, category, rating, in_stock FROM mock_items WHERE description @@@ 'shoes OR keyboard' ORDER BY id"
+        .fetch(&mut conn);
+    assert_eq!(
+        fast.into_iter()
+            .map(|(id, description, category, rating, in_stock, _)| (
+                id,
+                description,
+                category,
+                rating,
+                in_stock
+            ))
+            .collect::<Vec<_>>(),
+        expected
+    );
+}
+
+#[rstest]
+fn pushdown_text_and_datetime_predicates(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    r#"
+    CREATE TABLE events (
+        id SERIAL PRIMARY KEY,
+        description TEXT,
+        category VARCHAR,
+        label TEXT,
+        day DATE,
+        at TIMESTAMP(0),
+        at_tz TIMESTAMPTZ(0),
+        at_precise TIMESTAMP
+    );
+    INSERT INTO events (description, category, label, day, at, at_tz, at_precise) VALUES
+        ('red shoes', 'Footwear', 'Sale', '2024-01-01', '2024-01-01 10:00:00', '2024-01-01 10:00:00+00', '2024-01-01 10:00:00.5'),
+        ('blue shoes', 'footwear', 'sale', '2024-01-02', '2024-01-02 10:00:00', '2024-01-02 10:00:00+00', '2024-01-02 10:00:00.5'),
+        ('green shoes', 'Apparel', NULL, '2024-01-03', '2024-01-03 10:00:00', '2024-01-03 10:00:00+00', '2024-01-03 10:00:00.5'),
+        ('old shoes', NULL, 'Sale', NULL, NULL, NULL, NULL);
+    CREATE INDEX events_idx ON events
+    USING bm25 (id, description, category, label, day, at, at_tz, at_precise)
+    WITH (
+        key_field = 'id',
+        text_fields = '{
+            "description": {},
+            "category": {"fast": true, "tokenizer": {"type": "raw", "lowercase": false}},
+            "label": {"fast": true}
+        }'
+    );
+    CREATE COLLATION case_insensitive (provider = icu, locale = 'und-u-ks-level2', deterministic = false);
+    "#
+    .execute(&mut conn);
+
+    let pushed_down = [
+        "description @@@ 'shoes' AND category = 'Footwear'",
+        "description @@@ 'shoes' AND 'footwear' = category",
+        "description @@@ 'shoes' AND category <> 'Footwear'",
+        "description @@@ 'shoes' AND category IN ('Footwear', 'Apparel')",
+        "description @@@ 'shoes' AND category IS NULL",
+        "description @@@ 'shoes' AND category IS NOT NULL",
+        "description @@@ 'shoes' AND day = '2024-01-02'",
+        "description @@@ 'shoes' AND day > '2024-01-01'",
+        "description @@@ 'shoes' AND day <= '2024-01-02'",
+        "description @@@ 'shoes' AND day <> '2024-01-02'",
+        "description @@@ 'shoes' AND day IN ('2024-01-01', '2024-01-03')",
+        "description @@@ 'shoes' AND at = '2024-01-02 10:00:00'",
+        "description @@@ 'shoes' AND at > '2024-01-01 10:00:00'",
+        "description @@@ 'shoes' AND at BETWEEN '2024-01-01 10:00:00' AND '2024-01-02 10:00:00'",
+        "description @@@ 'shoes' AND at_tz < '2024-01-03 10:00:00+00'",
+        "description @@@ 'shoes' AND at_tz >= '2024-01-02 11:00:00+01'",
+        "description @@@ 'shoes' AND at IS NULL",
+    ];
+    let not_pushed_down = [
+        // the index can't tell how a collation sorts text
+        "description @@@ 'shoes' AND category < 'G'",
+        // `label` is lowercased by its tokenizer
+        "description @@@ 'shoes' AND label = 'Sale'",
+        // a nondeterministic collation considers other strings equal too
+        "description @@@ 'shoes' AND category = 'footwear' COLLATE case_insensitive",
+        // the index would truncate `at_precise`'s fractional seconds
+        "description @@@ 'shoes' AND at_precise > '2024-01-01 10:00:00'",
+        // nor can it find a fractional second
+        "description @@@ 'shoes' AND at < '2024-01-02 10:00:00.5'::timestamp",
+    ];
+
+    for (query, expect_pushdown) in pushed_down
+        .into_iter()
+        .map(|query| (query, true))
+        .chain(not_pushed_down.into_iter().map(|query| (query, false)))
+    {
+        let (plan,) =
+            format!("EXPLAIN (FORMAT JSON) SELECT id FROM events WHERE {query} ORDER BY id")
+                .fetch_one::<(Value,)>(&mut conn);
+        assert_eq!(
+            format!("{plan:?}").contains("ParadeDB Scan"),
+            expect_pushdown,
+            "`{query}` was planned wrong: {plan:#?}"
+        );
+
+        let sql = format!("SELECT id FROM events WHERE {query} ORDER BY id");
+        let rows: Vec<(i32,)> = sql.clone().fetch(&mut conn);
+
+        "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+        let expected: Vec<(i32,)> = sql.fetch(&mut conn);
+        "RESET paradedb.enable_custom_scan".execute(&mut conn);
+
+        assert_eq!(rows, expected, "`{query}` returned the wrong rows");
+    }
+}
+
+#[rstest]
+fn numeric_predicates_are_not_pushed_down(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    r#"
+    CREATE TABLE priced (id SERIAL PRIMARY KEY, description TEXT, price NUMERIC);
+    INSERT INTO priced (description, price) VALUES
+        ('red shoes', 99.99999999999999999),
+        ('blue shoes', 100),
+        ('green shoes', 42.5);
+    CREATE INDEX priced_idx ON priced
+    USING bm25 (id, description, price) WITH (key_field = 'id');
+    "#
+    .execute(&mut conn);
+
+    // `99.99999999999999999` and `100` are the same `f64`, so only Postgres can tell them apart
+    let query = "description @@@ 'shoes' AND price < 100";
+    let (plan,) = format!("EXPLAIN (FORMAT JSON) SELECT id FROM priced WHERE {query} ORDER BY id")
+        .fetch_one::<(Value,)>(&mut conn);
+    assert!(
+        !format!("{plan:?}").contains("ParadeDB Scan"),
+        "`{query}` should not be pushed down: {plan:#?}"
+    );
+
+    let rows: Vec<(i32,)> =
+        format!("SELECT id FROM priced WHERE {query} ORDER BY id").fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (3,)]);
+}
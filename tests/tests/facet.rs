@@ -0,0 +1,123 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use rstest::*;
+use sqlx::PgConnection;
+
+#[rstest]
+fn text_facet_field(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE products (
+        id serial8,
+        name text,
+        category text
+    );
+
+    INSERT INTO products (name, category) VALUES
+        ('running shoes', '/apparel/shoes/running'),
+        ('hiking boots', '/apparel/shoes/hiking'),
+        ('rain jacket', '/apparel/jackets'),
+        ('headlamp', '/gear/lighting');
+
+    CREATE INDEX products_idx ON products
+    USING bm25 (id, name, category) WITH (key_field='id', facet_fields='{"category": {}}');
+    "#
+    .execute(&mut conn);
+
+    let rows: Vec<(String, String)> =
+        "SELECT name, field_type FROM paradedb.schema('products_idx') WHERE name = 'category'"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![("category".into(), "Facet".into())]);
+
+    // a facet query matches the whole subtree
+    let rows: Vec<(i64,)> =
+        "SELECT id FROM products WHERE id @@@ paradedb.facet('category', '/apparel/shoes') ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (2,)]);
+
+    let rows: Vec<(i64,)> =
+        "SELECT id FROM products WHERE id @@@ paradedb.facet('category', '/apparel') ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (2,), (3,)]);
+
+    // counts are for the direct children of the requested facet
+    let rows: Vec<(String, i64)> =
+        "SELECT facet, count FROM paradedb.facet_counts('products_idx', 'category')"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![("/apparel".into(), 3), ("/gear".into(), 1)]);
+
+    let rows: Vec<(String, i64)> = r#"
+    SELECT facet, count FROM paradedb.facet_counts(
+        'products_idx', 'category',
+        query => paradedb.parse('name:shoes OR name:jacket'),
+        path => '/apparel'
+    )
+    "#
+    .fetch(&mut conn);
+    assert_eq!(
+        rows,
+        vec![("/apparel/jackets".into(), 1), ("/apparel/shoes".into(), 1)]
+    );
+
+    // deleted rows aren't counted
+    "DELETE FROM products WHERE id = 4".execute(&mut conn);
+    let rows: Vec<(String, i64)> =
+        "SELECT facet, count FROM paradedb.facet_counts('products_idx', 'category')"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![("/apparel".into(), 3)]);
+}
+
+#[rstest]
+fn ltree_facet_field(mut conn: PgConnection) {
+    r#"
+    CREATE EXTENSION IF NOT EXISTS ltree;
+
+    CREATE TABLE products (
+        id serial8,
+        name text,
+        category ltree
+    );
+
+    INSERT INTO products (name, category) VALUES
+        ('running shoes', 'apparel.shoes.running'),
+        ('hiking boots', 'apparel.shoes.hiking'),
+        ('headlamp', 'gear.lighting');
+
+    CREATE INDEX products_idx ON products
+    USING bm25 (id, name, category) WITH (key_field='id');
+    "#
+    .execute(&mut conn);
+
+    let rows: Vec<(i64,)> =
+        "SELECT id FROM products WHERE id @@@ paradedb.facet('category', 'apparel.shoes') ORDER BY id"
+            .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (2,)]);
+
+    let rows: Vec<(String, i64)> =
+        "SELECT facet, count FROM paradedb.facet_counts('products_idx', 'category', path => 'apparel.shoes')"
+            .fetch(&mut conn);
+    assert_eq!(
+        rows,
+        vec![
+            ("/apparel/shoes/hiking".into(), 1),
+            ("/apparel/shoes/running".into(), 1)
+        ]
+    );
+}
@@ -0,0 +1,154 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use rstest::*;
+use sqlx::PgConnection;
+
+// the short id `paradedb.index_info()` reports for the in-memory pending list segment
+const PENDING_SEGNO: &str = "ffffffff";
+
+fn segments(conn: &mut PgConnection) -> Vec<String> {
+    "SELECT segno FROM paradedb.index_info('idxpending') ORDER BY segno"
+        .fetch::<(String,)>(conn)
+        .into_iter()
+        .map(|(segno,)| segno)
+        .collect()
+}
+
+fn count(conn: &mut PgConnection, query: &str) -> i64 {
+    let (count,) =
+        format!("SELECT count(*) FROM pending WHERE body @@@ '{query}'").fetch_one::<(i64,)>(conn);
+    count
+}
+
+#[rstest]
+fn pending_list_is_searchable(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE pending (id SERIAL PRIMARY KEY, body TEXT NOT NULL)
+            WITH (autovacuum_enabled = false);
+        CREATE INDEX idxpending ON pending
+        USING bm25 (id, body)
+        WITH (key_field = 'id', fastupdate = true);
+    "#
+    .execute(&mut conn);
+    let initial = segments(&mut conn);
+
+    for i in 0..20 {
+        format!("INSERT INTO pending (body) VALUES ('small write number {i}')").execute(&mut conn);
+    }
+
+    // every insert went to the pending list, which is searched as a single extra segment
+    let with_pending = segments(&mut conn);
+    assert_eq!(with_pending.len(), initial.len() + 1);
+    assert!(with_pending.contains(&PENDING_SEGNO.to_string()));
+    assert_eq!(count(&mut conn, "small"), 20);
+    assert_eq!(count(&mut conn, "13"), 1);
+
+    "UPDATE pending SET body = 'changed' WHERE id = 1".execute(&mut conn);
+    "DELETE FROM pending WHERE id = 2".execute(&mut conn);
+    assert_eq!(count(&mut conn, "small"), 18);
+    assert_eq!(count(&mut conn, "changed"), 1);
+
+    // an aborted insert never becomes visible
+    "BEGIN; INSERT INTO pending (body) VALUES ('aborted'); ROLLBACK".execute(&mut conn);
+    assert_eq!(count(&mut conn, "aborted"), 0);
+
+    let problems = "SELECT * FROM paradedb.verify_index('idxpending', heapallindexed => true)"
+        .fetch::<(Option<i64>, String)>(&mut conn);
+    assert_eq!(problems, vec![]);
+
+    // vacuum moves the pending list into a real segment
+    "VACUUM pending".execute(&mut conn);
+    let after_vacuum = segments(&mut conn);
+    assert!(!after_vacuum.contains(&PENDING_SEGNO.to_string()));
+    assert_eq!(count(&mut conn, "small"), 18);
+    assert_eq!(count(&mut conn, "changed"), 1);
+    assert_eq!(count(&mut conn, "aborted"), 0);
+
+    let problems = "SELECT * FROM paradedb.verify_index('idxpending', heapallindexed => true)"
+        .fetch::<(Option<i64>, String)>(&mut conn);
+    assert_eq!(problems, vec![]);
+}
+
+#[rstest]
+fn pending_list_flushes_at_limit(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE pending (id SERIAL PRIMARY KEY, body TEXT NOT NULL)
+            WITH (autovacuum_enabled = false);
+        CREATE INDEX idxpending ON pending
+        USING bm25 (id, body)
+        WITH (key_field = 'id', fastupdate = true, pending_list_limit = 64);
+    "#
+    .execute(&mut conn);
+    let initial = segments(&mut conn);
+
+    // roughly 2kB per document, so the 64kB limit is crossed a few times
+    for _ in 0..100 {
+        "INSERT INTO pending (body) SELECT string_agg(md5(x::text), ' ') FROM generate_series(1, 60) x"
+            .execute(&mut conn);
+    }
+
+    let flushed = segments(&mut conn)
+        .into_iter()
+        .filter(|segno| segno != PENDING_SEGNO)
+        .count();
+    assert!(flushed > initial.len());
+    assert!(flushed < initial.len() + 100);
+    assert_eq!(count(&mut conn, "c4ca4238a0b923820dcc509a6f75849b"), 100);
+
+    // with fastupdate turned off, writes go straight to new segments
+    "ALTER INDEX idxpending SET (fastupdate = false); VACUUM pending".execute(&mut conn);
+    "INSERT INTO pending (body) VALUES ('direct')".execute(&mut conn);
+    assert!(!segments(&mut conn).contains(&PENDING_SEGNO.to_string()));
+    assert_eq!(count(&mut conn, "direct"), 1);
+}
+
+#[rstest]
+fn pending_list_at_largest_limit(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE pending (id SERIAL PRIMARY KEY, body TEXT NOT NULL)
+            WITH (autovacuum_enabled = false);
+        CREATE INDEX idxpending ON pending
+        USING bm25 (id, body)
+        WITH (key_field = 'id', fastupdate = true, pending_list_limit = 65536);
+    "#
+    .execute(&mut conn);
+    let initial = segments(&mut conn);
+
+    // roughly 2kB per document, so about 10MB of pending list without reaching the limit
+    for _ in 0..20 {
+        "INSERT INTO pending (body) SELECT string_agg(md5((x * y)::text), ' ') FROM generate_series(1, 60) x, generate_series(1, 250) y GROUP BY y"
+            .execute(&mut conn);
+    }
+
+    // every document is in the one pending list segment
+    let with_pending = segments(&mut conn);
+    assert_eq!(with_pending.len(), initial.len() + 1);
+    assert!(with_pending.contains(&PENDING_SEGNO.to_string()));
+    assert_eq!(count(&mut conn, "c4ca4238a0b923820dcc509a6f75849b"), 20);
+    let (all,) =
+        "SELECT count(*) FROM pending WHERE id @@@ paradedb.all()".fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(all, 5000);
+
+    // the pending list is indexed again by every search, so it can't be allowed to grow without bound
+    let result =
+        "ALTER INDEX idxpending SET (pending_list_limit = 65537)".execute_result(&mut conn);
+    assert!(result.is_err());
+}
@@ -139,3 +139,76 @@ fn explain_row_estimate(mut conn: PgConnection) {
             || plan_rows == Some(&Value::Number(Number::from(3)))
     );
 }
+
+#[rstest]
+fn analyze_statistics_row_estimate(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    SimpleProductsTable::setup().execute(&mut conn);
+    "ANALYZE paradedb.bm25_search".execute(&mut conn);
+    "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+
+    let plan_rows = |plan: Value| {
+        plan.pointer("/0/Plan/Plan Rows")
+            .and_then(Value::as_f64)
+            .expect("plan should have a row estimate")
+    };
+
+    // a term that's among the most common in its field is estimated exactly
+    let (plan,) =
+        "EXPLAIN (FORMAT JSON) SELECT * FROM paradedb.bm25_search WHERE description @@@ 'shoes'"
+            .fetch_one::<(Value,)>(&mut conn);
+    assert_eq!(plan_rows(plan), 3.0);
+
+    // ranges over a fast field are estimated from its histogram
+    let (actual,) = "SELECT count(*) FROM paradedb.bm25_search WHERE rating >= 4"
+        .fetch_one::<(i64,)>(&mut conn);
+    let (plan,) = "EXPLAIN (FORMAT JSON) SELECT * FROM paradedb.bm25_search WHERE id @@@ paradedb.range(field => 'rating', range => int4range(4, NULL))"
+        .fetch_one::<(Value,)>(&mut conn);
+    let estimate = plan_rows(plan);
+    assert!(
+        (estimate - actual as f64).abs() <= 3.0,
+        "estimated {estimate} rows but there are {actual}"
+    );
+}
+
+#[rstest]
+fn vacuum_refreshes_stale_statistics(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    SimpleProductsTable::setup().execute(&mut conn);
+    "ANALYZE paradedb.bm25_search".execute(&mut conn);
+    "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+
+    let estimate = |conn: &mut PgConnection| {
+        let (plan,) =
+            "EXPLAIN (FORMAT JSON) SELECT * FROM paradedb.bm25_search WHERE description @@@ 'shoes'"
+                .fetch_one::<(Value,)>(conn);
+        plan.pointer("/0/Plan/Plan Rows")
+            .and_then(Value::as_f64)
+            .expect("plan should have a row estimate")
+    };
+    let insert_shoes = |conn: &mut PgConnection, count: i32| {
+        format!(
+            r#"
+            INSERT INTO paradedb.bm25_search (description, category, rating, in_stock, metadata, created_at, last_updated_date, latest_available_time)
+            SELECT 'new shoes', category, rating, in_stock, metadata, created_at, last_updated_date, latest_available_time
+            FROM paradedb.bm25_search, generate_series(1, {count}) WHERE id = 1
+            "#
+        )
+        .execute(conn);
+    };
+    assert_eq!(estimate(&mut conn), 3.0);
+
+    // a VACUUM after a small change keeps the statistics, but ANALYZE always gathers them again
+    insert_shoes(&mut conn, 1);
+    "VACUUM paradedb.bm25_search".execute(&mut conn);
+    assert_eq!(estimate(&mut conn), 3.0);
+    "ANALYZE paradedb.bm25_search".execute(&mut conn);
+    assert_eq!(estimate(&mut conn), 4.0);
+
+    // once enough rows have been added, VACUUM gathers them too
+    insert_shoes(&mut conn, 10);
+    "VACUUM paradedb.bm25_search".execute(&mut conn);
+    assert_eq!(estimate(&mut conn), 14.0);
+}
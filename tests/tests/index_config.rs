@@ -587,3 +587,104 @@ fn custom_enum_parse(mut conn: PgConnection) {
 
     assert_eq!(rows, vec![(1, "Item 1".into())]);
 }
+
+#[rstest]
+fn add_field_without_reindex(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE paradedb.index_config(id INTEGER, description TEXT);
+    INSERT INTO paradedb.index_config VALUES (1, 'Running shoes'), (2, 'Hiking boots');
+    "#
+    .execute(&mut conn);
+
+    r#"
+    CREATE INDEX index_config_index ON paradedb.index_config
+    USING bm25 (id, description)
+    WITH (key_field='id');
+    "#
+    .execute(&mut conn);
+
+    let (version,): (i32,) = r#"
+    SELECT paradedb.add_field(
+        'paradedb.index_config_index',
+        'description_stem',
+        '{"column": "description", "tokenizer": {"type": "default", "stemmer": "English"}}'
+    )"#
+    .fetch_one(&mut conn);
+    assert_eq!(version, 2);
+
+    let rows: Vec<(String, String)> =
+        "SELECT name, field_type FROM paradedb.schema('paradedb.index_config_index')"
+            .fetch(&mut conn);
+    assert!(rows.contains(&("description_stem".into(), "Str".into())));
+
+    // existing segments don't have the new field, but new ones do
+    "INSERT INTO paradedb.index_config VALUES (3, 'Trail running shoes')".execute(&mut conn);
+    let rows: Vec<(i32,)> = "SELECT id FROM paradedb.index_config WHERE id @@@ paradedb.parse('description_stem:run') ORDER BY id"
+        .fetch(&mut conn);
+    assert_eq!(rows, vec![(3,)]);
+
+    // the original field is unaffected
+    let rows: Vec<(i32,)> = "SELECT id FROM paradedb.index_config WHERE id @@@ paradedb.parse('description:shoes') ORDER BY id"
+        .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (3,)]);
+
+    match r#"SELECT paradedb.add_field('paradedb.index_config_index', 'description_stem', '{"column": "description"}')"#
+        .execute_result(&mut conn)
+    {
+        Ok(_) => panic!("should fail when the field already exists"),
+        Err(err) => assert!(
+            err.to_string().contains("already exists"),
+            "{}",
+            fmt_err(err)
+        ),
+    };
+}
+
+#[rstest]
+fn docstore_compression(mut conn: PgConnection) {
+    "CALL paradedb.create_bm25_test_table(table_name => 'index_config', schema_name => 'paradedb')"
+        .execute(&mut conn);
+
+    for compression in ["none", "lz4", "zstd", "zstd(19)"] {
+        format!(
+            r#"CREATE INDEX index_config_index ON paradedb.index_config
+            USING bm25 (id, description)
+            WITH (key_field='id', docstore_compression='{compression}', docstore_blocksize=4096)"#
+        )
+        .execute(&mut conn);
+
+        // stored fields are read back through the docstore
+        "INSERT INTO paradedb.index_config (description) VALUES ('Durable hiking shoes')"
+            .execute(&mut conn);
+        let rows: Vec<(String,)> = "SELECT paradedb.snippet(description) FROM paradedb.index_config WHERE description @@@ 'shoes' ORDER BY id"
+            .fetch(&mut conn);
+        assert!(
+            rows.contains(&("Durable hiking <b>shoes</b>".into(),)),
+            "{compression}: {rows:?}"
+        );
+
+        "DROP INDEX paradedb.index_config_index".execute(&mut conn);
+    }
+
+    for (option, expected) in [
+        (
+            "docstore_compression='gzip'",
+            "expected one of none, lz4, zstd",
+        ),
+        (
+            "docstore_compression='zstd(0)'",
+            "zstd level must be between 1 and 22",
+        ),
+        ("docstore_blocksize=100", "out of bounds"),
+    ] {
+        match format!(
+            r#"CREATE INDEX index_config_index ON paradedb.index_config
+            USING bm25 (id, description) WITH (key_field='id', {option})"#
+        )
+        .execute_result(&mut conn)
+        {
+            Ok(_) => panic!("{option} should be rejected"),
+            Err(err) => assert!(err.to_string().contains(expected), "{}", fmt_err(err)),
+        };
+    }
+}
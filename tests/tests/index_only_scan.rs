@@ -36,3 +36,59 @@ fn custom_scan_on_key_field(mut conn: PgConnection) {
         Some(&Value::String(String::from("ParadeDB Scan")))
     );
 }
+
+#[rstest]
+fn index_only_scan_on_fast_fields(mut conn: PgConnection) {
+    use serde_json::Value;
+
+    r#"
+        CALL paradedb.create_bm25_test_table(table_name => 'bm25_search', schema_name => 'paradedb');
+
+        CREATE INDEX bm25_search_idx ON paradedb.bm25_search
+        USING bm25 (id, description, category, rating, in_stock)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "description": {},
+                "category": {"fast": true}
+            }',
+            numeric_fields = '{
+                "rating": {}
+            }',
+            boolean_fields = '{
+                "in_stock": {}
+            }'
+        );
+    "#
+    .execute(&mut conn);
+    "VACUUM paradedb.bm25_search".execute(&mut conn);
+
+    let query = "SELECT id, rating, category, in_stock FROM paradedb.bm25_search WHERE description @@@ 'shoes OR keyboard' ORDER BY id";
+    let expected: Vec<(i32, i32, String, bool)> = query.fetch(&mut conn);
+    assert!(!expected.is_empty());
+
+    r#"
+        SET paradedb.enable_custom_scan TO off;
+        SET enable_seqscan TO off;
+        SET enable_bitmapscan TO off;
+    "#
+    .execute(&mut conn);
+
+    // every column comes from a fast field, so the heap isn't visited
+    let (plan,) =
+        format!("EXPLAIN (ANALYZE, FORMAT JSON) {query}").fetch_one::<(Value,)>(&mut conn);
+    let plan = format!("{plan:?}");
+    assert!(plan.contains("Index Only Scan"), "{plan}");
+    assert!(plan.contains("\"Heap Fetches\": Number(0)"), "{plan}");
+
+    let actual: Vec<(i32, i32, String, bool)> = query.fetch(&mut conn);
+    assert_eq!(actual, expected);
+
+    // `description` isn't a fast field, so it has to be read from the heap
+    let (plan,) = "EXPLAIN (FORMAT JSON) SELECT id, description FROM paradedb.bm25_search WHERE description @@@ 'shoes'"
+        .fetch_one::<(Value,)>(&mut conn);
+    assert!(
+        !format!("{plan:?}").contains("Index Only Scan"),
+        "{plan:#?}"
+    );
+}
@@ -65,3 +65,102 @@ fn simple_jsonb_string_array_crash(mut conn: PgConnection) {
     "#
     .execute(&mut conn);
 }
+
+#[rstest]
+fn nested_json_array_of_objects(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE products (
+        id serial8,
+        attrs jsonb
+    );
+
+    INSERT INTO products (attrs) VALUES
+        ('{"variants": [{"color": "red", "size": "large"}, {"color": "blue", "size": "small"}]}'),
+        ('{"variants": [{"color": "red", "size": "small"}]}'),
+        ('{"variants": []}');
+
+    CREATE INDEX products_idx ON products
+    USING bm25 (id, attrs) WITH (key_field='id', json_fields='{"attrs": {"nested": true}}');
+    "#
+    .execute(&mut conn);
+
+    // the flattened parent still matches terms from different array elements
+    let rows: Vec<(i64,)> = r#"
+    SELECT id FROM products WHERE id @@@ paradedb.boolean(must => ARRAY[
+        paradedb.term('attrs.variants.color', 'red'),
+        paradedb.term('attrs.variants.size', 'small')
+    ]) ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (2,)]);
+
+    // but a nested query requires both terms to match the same element
+    let rows: Vec<(i64,)> = r#"
+    SELECT id FROM products WHERE id @@@ paradedb.nested('attrs.variants', paradedb.boolean(must => ARRAY[
+        paradedb.term('attrs.variants.color', 'red'),
+        paradedb.term('attrs.variants.size', 'small')
+    ])) ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(2,)]);
+
+    // child documents are never returned on their own
+    let rows: Vec<(i64,)> = r#"
+    SELECT id FROM products WHERE id @@@ paradedb.all() ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,), (2,), (3,)]);
+
+    // and go away with their parent
+    "UPDATE products SET attrs = '{\"variants\": [{\"color\": \"green\", \"size\": \"small\"}]}' WHERE id = 2"
+        .execute(&mut conn);
+    let rows: Vec<(i64,)> = r#"
+    SELECT id FROM products WHERE id @@@ paradedb.nested('attrs.variants', paradedb.term('attrs.variants.color', 'red')) ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,)]);
+}
+
+#[rstest]
+fn nested_json_query_is_limited_to_its_path(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE products (
+        id serial8,
+        attrs jsonb
+    );
+
+    INSERT INTO products (attrs) VALUES
+        ('{"variants": [{"color": "red"}], "bundles": [{"color": "blue"}]}'),
+        ('{"variants": [{"color": "blue"}, {"color": "green"}]}'),
+        ('{"bundles": [{"color": "red"}]}');
+
+    CREATE INDEX products_idx ON products
+    USING bm25 (id, attrs) WITH (key_field='id', json_fields='{"attrs": {"nested": true}}');
+    "#
+    .execute(&mut conn);
+
+    // children of other arrays don't match, even if the inner query names their fields
+    let rows: Vec<(i64,)> = r#"
+    SELECT id FROM products WHERE id @@@ paradedb.nested('attrs.variants', paradedb.term('attrs.bundles.color', 'blue')) ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![]);
+
+    let rows: Vec<(i64,)> = r#"
+    SELECT id FROM products WHERE id @@@ paradedb.nested('attrs.bundles', paradedb.term('attrs.bundles.color', 'blue')) ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1,)]);
+
+    let rows: Vec<(i64,)> = r#"
+    SELECT id FROM products WHERE id @@@ paradedb.nested('attrs.variants', paradedb.term('attrs.variants.color', 'blue')) ORDER BY id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(2,)]);
+
+    // and child documents aren't counted as rows of the index
+    "VACUUM products".execute(&mut conn);
+    let (reltuples,) = "SELECT reltuples FROM pg_class WHERE relname = 'products_idx'"
+        .fetch_one::<(f32,)>(&mut conn);
+    assert_eq!(reltuples, 3.0);
+}
@@ -833,3 +833,86 @@ fn timetz_key(mut conn: PgConnection) {
     .fetch_collect(&mut conn);
     assert_eq!(rows.len(), 6);
 }
+
+#[rstest]
+fn composite_key(mut conn: PgConnection) {
+    r#"
+    CREATE TABLE tenant_docs (
+        tenant_id INT NOT NULL,
+        doc_id INT NOT NULL,
+        description TEXT,
+        PRIMARY KEY (tenant_id, doc_id)
+    );
+    INSERT INTO tenant_docs VALUES
+        (1, 1, 'red running shoes'),
+        (1, 2, 'blue running shoes'),
+        (2, 1, 'red hiking boots'),
+        (2, 2, 'green garden hose');
+    CREATE INDEX tenant_docs_idx ON tenant_docs
+    USING bm25 (tenant_id, doc_id, description)
+    WITH (key_field = '(tenant_id, doc_id)', text_fields = '{"description": {"stored": true}}');
+    "#
+    .execute(&mut conn);
+
+    let rows: Vec<(i32, i32)> = r#"
+    SELECT tenant_id, doc_id FROM tenant_docs
+    WHERE tenant_id @@@ paradedb.parse('description:red') ORDER BY tenant_id, doc_id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1, 1), (2, 1)]);
+
+    // both key parts come back from an index-only scan
+    "SET paradedb.enable_custom_scan TO off; SET enable_seqscan TO off; SET enable_bitmapscan TO off"
+        .execute(&mut conn);
+    let rows: Vec<(i32, i32)> = r#"
+    SELECT tenant_id, doc_id FROM tenant_docs
+    WHERE tenant_id @@@ paradedb.parse('description:shoes') ORDER BY tenant_id, doc_id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1, 1), (1, 2)]);
+    "RESET paradedb.enable_custom_scan; RESET enable_seqscan; RESET enable_bitmapscan"
+        .execute(&mut conn);
+
+    // evaluated row by row, rows that share the first key part are still told apart
+    "SET paradedb.enable_custom_scan TO off; SET enable_indexscan TO off; SET enable_bitmapscan TO off"
+        .execute(&mut conn);
+    let rows: Vec<(i32, i32)> = r#"
+    SELECT tenant_id, doc_id FROM tenant_docs
+    WHERE tenant_id @@@ paradedb.parse('description:blue') ORDER BY tenant_id, doc_id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1, 2)]);
+    let rows: Vec<(i32, i32)> = r#"
+    SELECT tenant_id, doc_id FROM tenant_docs
+    WHERE description @@@ 'red' AND NOT description @@@ 'boots' ORDER BY tenant_id, doc_id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1, 1)]);
+    "RESET paradedb.enable_custom_scan; RESET enable_indexscan; RESET enable_bitmapscan"
+        .execute(&mut conn);
+
+    // the custom scan returns the same rows
+    let rows: Vec<(i32, i32)> = r#"
+    SELECT tenant_id, doc_id FROM tenant_docs
+    WHERE tenant_id @@@ paradedb.parse('description:blue') ORDER BY tenant_id, doc_id
+    "#
+    .fetch(&mut conn);
+    assert_eq!(rows, vec![(1, 2)]);
+
+    // the key parts are passed as a ROW(...)
+    let rows: Vec<(i32, i32)> = r#"
+    SELECT tenant_id, doc_id FROM tenant_docs
+    WHERE tenant_id @@@ paradedb.more_like_this(
+        document_id => ROW(2, 1),
+        min_term_frequency => 1,
+        min_doc_frequency => 1
+    )
+    ORDER BY tenant_id, doc_id
+    "#
+    .fetch(&mut conn);
+    assert!(rows.contains(&(2, 1)));
+
+    let (document,): (serde_json::Value,) =
+        "SELECT paradedb.stored_document('tenant_docs_idx', ROW(1, 2))".fetch_one(&mut conn);
+    assert_eq!(document["description"], "blue running shoes");
+}
@@ -77,3 +77,27 @@ fn dont_do_parallel_index_scan(mut conn: PgConnection) {
     .fetch::<(i64,)>(&mut conn);
     assert_eq!(count, vec![(3,)]);
 }
+
+#[rstest]
+fn explain_analyze_parallel_custom_scan_under_limit(mut conn: PgConnection) {
+    SimpleProductsTable::setup().execute(&mut conn);
+
+    r#"
+        set enable_indexscan to off;
+        set parallel_setup_cost to 0;
+        set parallel_tuple_cost to 0;
+    "#
+    .execute(&mut conn);
+
+    // when the scan runs in parallel, the Limit shuts down its Gather, and with it the custom
+    // scan, as soon as it has its rows, and then the whole plan is shut down again at the end
+    for _ in 0..3 {
+        let (plan, ) = "EXPLAIN (ANALYZE, FORMAT JSON) select id from paradedb.bm25_search where description @@@ 'shoes' limit 1;".fetch_one::<(Value,)>(&mut conn);
+        eprintln!("{plan:#?}");
+        assert!(format!("{plan:?}").contains("Custom Scan"), "{plan:#?}");
+    }
+
+    let rows = "select id from paradedb.bm25_search where description @@@ 'shoes' limit 1;"
+        .fetch::<(i32,)>(&mut conn);
+    assert_eq!(rows.len(), 1);
+}
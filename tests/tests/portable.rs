@@ -0,0 +1,227 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use rstest::*;
+use sqlx::PgConnection;
+
+#[rstest]
+fn export_then_import_after_rows_move(mut conn: PgConnection) {
+    let path = std::env::temp_dir().join(format!("pg_search_export_{}", rand::random::<u64>()));
+    let path = path.display();
+
+    r#"
+        CREATE TABLE portable (id SERIAL PRIMARY KEY, value TEXT NOT NULL)
+            WITH (autovacuum_enabled = false);
+        INSERT INTO portable (value) SELECT md5(x::text) FROM generate_series(1, 1000) x;
+
+        CREATE INDEX idxportable ON portable
+        USING bm25 (id, value)
+        WITH (key_field = 'id', text_fields = '{"value": {"tokenizer": {"type": "keyword"}}}');
+
+        DELETE FROM portable WHERE id % 2 = 0;
+        VACUUM portable;
+    "#
+    .execute(&mut conn);
+
+    let (segments, num_docs, _) =
+        format!("SELECT * FROM paradedb.export_index('idxportable', '{path}')")
+            .fetch_one::<(i32, i64, i64)>(&mut conn);
+    assert!(segments >= 1);
+    assert_eq!(num_docs, 500);
+
+    // exporting over an earlier export is refused
+    let result = format!("SELECT * FROM paradedb.export_index('idxportable', '{path}')")
+        .execute_result(&mut conn);
+    assert!(result.is_err());
+
+    // rewrite the heap, so that every row's ctid changes from what was exported
+    "VACUUM FULL portable".execute(&mut conn);
+
+    let (_, num_docs, rows_replaced) =
+        format!("SELECT * FROM paradedb.import_index('idxportable', '{path}')")
+            .fetch_one::<(i32, i64, i64)>(&mut conn);
+    assert_eq!(num_docs, 500);
+    assert_eq!(rows_replaced, 500);
+
+    let problems = "SELECT * FROM paradedb.verify_index('idxportable', heapallindexed => true)"
+        .fetch::<(Option<i64>, String)>(&mut conn);
+    assert_eq!(problems, vec![]);
+
+    let (id,) = "SELECT id FROM portable WHERE value @@@ md5('501')".fetch_one::<(i32,)>(&mut conn);
+    assert_eq!(id, 501);
+
+    let (count,) =
+        "SELECT count(*) FROM portable WHERE id @@@ paradedb.all()".fetch_one::<(i64,)>(&mut conn);
+    assert_eq!(count, 500);
+
+    // a key that's no longer in the table can't be matched to a row
+    "DELETE FROM portable WHERE id = 501; VACUUM portable".execute(&mut conn);
+    let result = format!("SELECT * FROM paradedb.import_index('idxportable', '{path}')")
+        .execute_result(&mut conn);
+    assert!(result.is_err());
+
+    let _ = std::fs::remove_dir_all(path.to_string());
+}
+
+#[rstest]
+fn export_and_import_check_privileges(mut conn: PgConnection) {
+    let path = std::env::temp_dir().join(format!("pg_search_export_{}", rand::random::<u64>()));
+    let path = path.display();
+    let role = format!("portable_user_{}", rand::random::<u32>());
+
+    format!(
+        r#"
+        CREATE TABLE portable (id SERIAL PRIMARY KEY, value TEXT NOT NULL);
+        INSERT INTO portable (value) SELECT md5(x::text) FROM generate_series(1, 100) x;
+
+        CREATE INDEX idxportable ON portable
+        USING bm25 (id, value)
+        WITH (key_field = 'id', text_fields = '{{"value": {{}}}}');
+
+        CREATE ROLE {role};
+        GRANT pg_read_server_files, pg_write_server_files TO {role};
+        GRANT USAGE ON SCHEMA paradedb TO {role};
+        SET ROLE {role};
+    "#
+    )
+    .execute(&mut conn);
+
+    // the index holds the table's contents, which this role can't read
+    let result = format!("SELECT * FROM paradedb.export_index('idxportable', '{path}')")
+        .execute_result(&mut conn);
+    match result {
+        Ok(_) => panic!("exporting without SELECT on the table should fail"),
+        Err(err) => assert!(
+            err.to_string()
+                .contains("permission denied for table portable"),
+            "{err}"
+        ),
+    }
+
+    "RESET ROLE".execute(&mut conn);
+    format!("GRANT SELECT ON portable TO {role}; SET ROLE {role}").execute(&mut conn);
+    let (_, num_docs, _) = format!("SELECT * FROM paradedb.export_index('idxportable', '{path}')")
+        .fetch_one::<(i32, i64, i64)>(&mut conn);
+    assert_eq!(num_docs, 100);
+
+    // replacing the index's contents is as privileged as rebuilding it
+    let result = format!("SELECT * FROM paradedb.import_index('idxportable', '{path}')")
+        .execute_result(&mut conn);
+    match result {
+        Ok(_) => panic!("importing into an index the role doesn't own should fail"),
+        Err(err) => assert!(
+            err.to_string()
+                .contains("must be owner of index idxportable"),
+            "{err}"
+        ),
+    }
+
+    format!("RESET ROLE; DROP TABLE portable; DROP OWNED BY {role}; DROP ROLE {role}")
+        .execute(&mut conn);
+    let _ = std::fs::remove_dir_all(path.to_string());
+}
+
+#[rstest]
+fn import_requires_unique_key_index(mut conn: PgConnection) {
+    let path = std::env::temp_dir().join(format!("pg_search_export_{}", rand::random::<u64>()));
+    let path = path.display();
+
+    r#"
+        CREATE TABLE portable (id INT NOT NULL, value TEXT NOT NULL);
+        INSERT INTO portable (id, value) SELECT x, md5(x::text) FROM generate_series(1, 100) x;
+
+        CREATE INDEX idxportable ON portable
+        USING bm25 (id, value)
+        WITH (key_field = 'id', text_fields = '{"value": {}}');
+    "#
+    .execute(&mut conn);
+
+    let (_, num_docs, _) = format!("SELECT * FROM paradedb.export_index('idxportable', '{path}')")
+        .fetch_one::<(i32, i64, i64)>(&mut conn);
+    assert_eq!(num_docs, 100);
+
+    // each document's row is found through a unique index on the key field
+    let result = format!("SELECT * FROM paradedb.import_index('idxportable', '{path}')")
+        .execute_result(&mut conn);
+    match result {
+        Ok(_) => panic!("importing without a unique index on the key field should fail"),
+        Err(err) => assert!(
+            err.to_string().contains(
+                "importing into index \"idxportable\" requires a unique index on its key field"
+            ),
+            "{err}"
+        ),
+    }
+
+    "CREATE UNIQUE INDEX portable_id ON portable (id)".execute(&mut conn);
+    let (_, num_docs, rows_replaced) =
+        format!("SELECT * FROM paradedb.import_index('idxportable', '{path}')")
+            .fetch_one::<(i32, i64, i64)>(&mut conn);
+    assert_eq!(num_docs, 100);
+    assert_eq!(rows_replaced, 100);
+
+    let problems = "SELECT * FROM paradedb.verify_index('idxportable', heapallindexed => true)"
+        .fetch::<(Option<i64>, String)>(&mut conn);
+    assert_eq!(problems, vec![]);
+
+    let _ = std::fs::remove_dir_all(path.to_string());
+}
+
+#[rstest]
+fn export_includes_pending_list(mut conn: PgConnection) {
+    let path = std::env::temp_dir().join(format!("pg_search_export_{}", rand::random::<u64>()));
+    let path = path.display();
+
+    r#"
+        CREATE TABLE portable (id SERIAL PRIMARY KEY, value TEXT NOT NULL)
+            WITH (autovacuum_enabled = false);
+        INSERT INTO portable (value) SELECT md5(x::text) FROM generate_series(1, 100) x;
+
+        CREATE INDEX idxportable ON portable
+        USING bm25 (id, value)
+        WITH (key_field = 'id', text_fields = '{"value": {"tokenizer": {"type": "keyword"}}}', fastupdate = true);
+    "#
+    .execute(&mut conn);
+
+    // these go to the pending list rather than a segment
+    for x in 101..=120 {
+        format!("INSERT INTO portable (value) VALUES (md5('{x}'))").execute(&mut conn);
+    }
+
+    let (_, num_docs, _) = format!("SELECT * FROM paradedb.export_index('idxportable', '{path}')")
+        .fetch_one::<(i32, i64, i64)>(&mut conn);
+    assert_eq!(num_docs, 120);
+
+    "VACUUM FULL portable".execute(&mut conn);
+    let (_, num_docs, rows_replaced) =
+        format!("SELECT * FROM paradedb.import_index('idxportable', '{path}')")
+            .fetch_one::<(i32, i64, i64)>(&mut conn);
+    assert_eq!(num_docs, 120);
+    assert_eq!(rows_replaced, 120);
+
+    let problems = "SELECT * FROM paradedb.verify_index('idxportable', heapallindexed => true)"
+        .fetch::<(Option<i64>, String)>(&mut conn);
+    assert_eq!(problems, vec![]);
+
+    let (id,) = "SELECT id FROM portable WHERE value @@@ md5('113')".fetch_one::<(i32,)>(&mut conn);
+    assert_eq!(id, 113);
+
+    let _ = std::fs::remove_dir_all(path.to_string());
+}
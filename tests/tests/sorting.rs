@@ -164,3 +164,319 @@ fn sort_by_row_return_scores(mut conn: PgConnection) {
     assert_eq!(plan.get("   Sort Field"), None);
     assert_eq!(plan.get("Scores"), Some(&Value::Bool(true)));
 }
+
+#[rstest]
+fn sort_by_multiple_fields(mut conn: PgConnection) {
+    // ensure our custom scan wins against our small test table
+    r#"
+        SET enable_indexscan TO off;
+        CALL paradedb.create_bm25_test_table(table_name => 'bm25_search', schema_name => 'paradedb');
+
+        CREATE INDEX bm25_search_idx ON paradedb.bm25_search
+        USING bm25 (id, description, category, rating, in_stock, created_at)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "description": {},
+                "category": {
+                    "fast": true,
+                    "normalizer": "raw"
+                }
+            }',
+            numeric_fields = '{
+                "rating": {}
+            }',
+            boolean_fields = '{
+                "in_stock": {}
+            }',
+            datetime_fields = '{
+                "created_at": {}
+            }'
+        );
+
+        UPDATE paradedb.bm25_search SET rating = NULL WHERE id IN (1, 3);
+    "#.execute(&mut conn);
+
+    let queries = [
+        "SELECT id, rating, category FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes OR plastic' ORDER BY rating, category DESC, id LIMIT 7",
+        "SELECT id, in_stock, created_at FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes OR plastic' ORDER BY in_stock DESC, created_at, id DESC LIMIT 4",
+    ];
+
+    for query in queries {
+        let (plan,) = format!("EXPLAIN (FORMAT JSON) {query}").fetch_one::<(Value,)>(&mut conn);
+        assert!(
+            format!("{plan:?}").contains("Sort Keys"),
+            "`{query}` should sort by a compound key: {plan:#?}"
+        );
+
+        let sql = format!("SELECT row_to_json(q)::text FROM ({query}) q");
+        let pushed_down: Vec<(String,)> = sql.clone().fetch(&mut conn);
+
+        "SET paradedb.enable_custom_scan TO off".execute(&mut conn);
+        let expected: Vec<(String,)> = sql.fetch(&mut conn);
+        "RESET paradedb.enable_custom_scan".execute(&mut conn);
+
+        assert_eq!(pushed_down, expected, "`{query}` returned the wrong rows");
+    }
+
+    // scores can be one of the keys too
+    let query = "SELECT paradedb.score(id), rating FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes OR plastic' ORDER BY paradedb.score(id) DESC, rating DESC LIMIT 5";
+    let (plan,) = format!("EXPLAIN (FORMAT JSON) {query}").fetch_one::<(Value,)>(&mut conn);
+    assert!(format!("{plan:?}").contains("Sort Keys"), "{plan:#?}");
+    let rows: Vec<(f32, Option<i32>)> = query.fetch(&mut conn);
+    assert_eq!(rows.len(), 5);
+    for pair in rows.windows(2) {
+        let ((score_a, rating_a), (score_b, rating_b)) = (pair[0], pair[1]);
+        assert!(score_a >= score_b);
+        if score_a == score_b {
+            // NULLs sort first when descending
+            assert!(rating_a.is_none() || rating_b.is_some_and(|b| rating_a.unwrap() >= b));
+        }
+    }
+
+    // `description` isn't a fast field, so the whole sort can't be pushed down
+    let (plan,) = "EXPLAIN (FORMAT JSON) SELECT id FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes' ORDER BY rating, description LIMIT 5"
+        .fetch_one::<(Value,)>(&mut conn);
+    assert!(!format!("{plan:?}").contains("Sort Keys"));
+    assert!(!format!("{plan:?}").contains("Sort Field"));
+}
+
+#[rstest]
+fn search_after_pagination(mut conn: PgConnection) {
+    r#"
+        SET enable_indexscan TO off;
+        CALL paradedb.create_bm25_test_table(table_name => 'bm25_search', schema_name => 'paradedb');
+
+        CREATE INDEX bm25_search_idx ON paradedb.bm25_search
+        USING bm25 (id, description, rating)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "description": {}
+            }',
+            numeric_fields = '{
+                "rating": {}
+            }'
+        );
+    "#
+    .execute(&mut conn);
+
+    let expected: Vec<(i32, i32)> = "SELECT id, rating FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes OR plastic' ORDER BY rating DESC, id"
+        .fetch(&mut conn);
+    assert!(expected.len() > 6);
+
+    // walk every page with a cursor made from the last row of the page before
+    let mut paged: Vec<(i32, i32)> = vec![];
+    loop {
+        let cursor = match paged.last() {
+            Some((id, rating)) => format!("AND id @@@ paradedb.search_after('[{rating}]', {id})"),
+            None => String::new(),
+        };
+        let query = format!("SELECT id, rating FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes OR plastic' {cursor} ORDER BY rating DESC, id LIMIT 3");
+
+        let (plan,) = format!("EXPLAIN (FORMAT JSON) {query}").fetch_one::<(Value,)>(&mut conn);
+        assert!(format!("{plan:?}").contains("Top N Limit"), "{plan:#?}");
+
+        let page: Vec<(i32, i32)> = query.fetch(&mut conn);
+        if page.is_empty() {
+            break;
+        }
+        paged.extend(page);
+    }
+    assert_eq!(paged, expected);
+
+    // OFFSET is still honored, and agrees with the cursor
+    let offset: Vec<(i32, i32)> = "SELECT id, rating FROM paradedb.bm25_search WHERE description @@@ 'keyboard OR shoes OR plastic' ORDER BY rating DESC, id LIMIT 3 OFFSET 3"
+        .fetch(&mut conn);
+    assert_eq!(offset, expected[3..6]);
+
+    // without an ORDER BY the index can sort by, there's nothing for the cursor to follow
+    let result = "SELECT id FROM paradedb.bm25_search WHERE description @@@ 'keyboard' AND id @@@ paradedb.search_after('[]', 1) ORDER BY description"
+        .execute_result(&mut conn);
+    assert!(result.is_err());
+}
+
+#[rstest]
+fn search_after_score_ties(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE tied (id SERIAL PRIMARY KEY, description TEXT);
+        INSERT INTO tied (description)
+            SELECT 'blue running shoes' FROM generate_series(1, 5)
+            UNION ALL SELECT 'shoes' FROM generate_series(1, 3);
+        CREATE INDEX tied_idx ON tied
+        USING bm25 (id, description)
+        WITH (key_field = 'id');
+    "#
+    .execute(&mut conn);
+
+    let expected: Vec<(i32, f32)> = "SELECT id, paradedb.score(id) FROM tied WHERE description @@@ 'shoes OR blue' ORDER BY paradedb.score(id) DESC, id"
+        .fetch(&mut conn);
+    assert_eq!(expected.len(), 8);
+
+    // pages of 2 split the runs of equal scores, so the cursor's score must compare equal to the
+    // score it was printed from
+    let mut paged: Vec<(i32, f32)> = vec![];
+    loop {
+        let cursor = match paged.last() {
+            Some((id, score)) => format!("AND id @@@ paradedb.search_after('[{score}]', {id})"),
+            None => String::new(),
+        };
+        let page: Vec<(i32, f32)> = format!("SELECT id, paradedb.score(id) FROM tied WHERE description @@@ 'shoes OR blue' {cursor} ORDER BY paradedb.score(id) DESC, id LIMIT 2")
+            .fetch(&mut conn);
+        if page.is_empty() {
+            break;
+        }
+        paged.extend(page);
+    }
+    assert_eq!(paged, expected);
+}
+
+#[rstest]
+fn order_by_score_operator(mut conn: PgConnection) {
+    r#"
+        CALL paradedb.create_bm25_test_table(table_name => 'bm25_search', schema_name => 'paradedb');
+
+        CREATE INDEX bm25_search_idx ON paradedb.bm25_search
+        USING bm25 (id, description, rating)
+        WITH (
+            key_field = 'id',
+            text_fields = '{
+                "description": {}
+            }',
+            numeric_fields = '{
+                "rating": {}
+            }'
+        );
+    "#
+    .execute(&mut conn);
+
+    // the scores the custom scan computes for the same query
+    let scores: Vec<(i32, f32)> = "SELECT id, paradedb.score(id) FROM paradedb.bm25_search WHERE description @@@ 'shoes OR keyboard'"
+        .fetch(&mut conn);
+    assert!(scores.len() > 3);
+
+    // the plain bm25 index scan returns rows in score order itself
+    r#"
+        SET paradedb.enable_custom_scan TO off;
+        SET enable_seqscan TO off;
+        SET enable_bitmapscan TO off;
+    "#
+    .execute(&mut conn);
+
+    let query = "SELECT id, description <=>@@ 'shoes OR keyboard' FROM paradedb.bm25_search WHERE description @@@ 'shoes OR keyboard' ORDER BY description <=>@@ 'shoes OR keyboard'";
+    let (plan,) = format!("EXPLAIN (FORMAT JSON) {query}").fetch_one::<(Value,)>(&mut conn);
+    let plan = format!("{plan:?}");
+    assert!(plan.contains("Index Scan"), "{plan}");
+    assert!(!plan.contains("\"Sort\""), "{plan}");
+
+    let ordered: Vec<(i32, f32)> = query.fetch(&mut conn);
+    assert_eq!(ordered.len(), scores.len());
+    assert!(ordered.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    for (id, distance) in &ordered {
+        let (_, score) = scores.iter().find(|(scored, _)| scored == id).unwrap();
+        assert_eq!(*distance, -score);
+    }
+
+    // without a WHERE clause every row is returned, the ones that match the query first
+    let (count,) = "SELECT COUNT(*) FROM paradedb.bm25_search".fetch_one::<(i64,)>(&mut conn);
+    let all: Vec<(i32, f32)> = "SELECT id, description <=>@@ 'shoes OR keyboard' FROM paradedb.bm25_search ORDER BY description <=>@@ 'shoes OR keyboard'"
+        .fetch(&mut conn);
+    assert_eq!(all.len() as i64, count);
+    assert_eq!(
+        all.iter()
+            .take(scores.len())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>(),
+        ordered.iter().map(|(id, _)| *id).collect::<Vec<_>>()
+    );
+    assert!(all[scores.len()..]
+        .iter()
+        .all(|(_, distance)| *distance == 0.0));
+
+    // it works as the inner side of a join, too
+    let top: Vec<(i32,)> = r#"
+        SELECT s.id FROM (VALUES ('shoes'), ('keyboard')) AS q(term)
+        CROSS JOIN LATERAL (
+            SELECT id FROM paradedb.bm25_search ORDER BY description <=>@@ q.term LIMIT 1
+        ) s
+    "#
+    .fetch(&mut conn);
+    assert_eq!(top.len(), 2);
+
+    // outside of an ordered index scan, the operator scores each row itself
+    "SET enable_indexscan TO off".execute(&mut conn);
+    let (plan,) = format!("EXPLAIN (FORMAT JSON) {query}").fetch_one::<(Value,)>(&mut conn);
+    let plan = format!("{plan:?}");
+    assert!(plan.contains("\"Sort\""), "{plan}");
+
+    let mut sorted: Vec<(i32, f32)> = query.fetch(&mut conn);
+    assert!(sorted.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    let mut ordered = ordered;
+    sorted.sort_by_key(|(id, _)| *id);
+    ordered.sort_by_key(|(id, _)| *id);
+    assert_eq!(sorted, ordered);
+
+    let sorted: Vec<(i32, f32)> = "SELECT id, description <=>@@ 'shoes OR keyboard' FROM paradedb.bm25_search ORDER BY description <=>@@ 'shoes OR keyboard'"
+        .fetch(&mut conn);
+    assert_eq!(sorted.len() as i64, count);
+    assert!(sorted[..scores.len()]
+        .iter()
+        .all(|scored| ordered.contains(scored)));
+    assert!(sorted[scores.len()..]
+        .iter()
+        .all(|(_, distance)| *distance == 0.0));
+
+    // and with a query that's only known at execution
+    let top_sorted: Vec<(i32,)> = r#"
+        SELECT s.id FROM (VALUES ('shoes'), ('keyboard')) AS q(term)
+        CROSS JOIN LATERAL (
+            SELECT id FROM paradedb.bm25_search ORDER BY description <=>@@ q.term LIMIT 1
+        ) s
+    "#
+    .fetch(&mut conn);
+    assert_eq!(top_sorted.len(), top.len());
+}
+
+#[rstest]
+fn top_n_by_field_across_segments(mut conn: PgConnection) {
+    r#"
+        SET enable_indexscan TO off;
+        CREATE TABLE feed (id SERIAL PRIMARY KEY, body TEXT NOT NULL, rank BIGINT NOT NULL);
+        CREATE INDEX idxfeed ON feed
+        USING bm25 (id, body, rank)
+        WITH (key_field = 'id', numeric_fields = '{"rank": {"fast": true}}');
+    "#
+    .execute(&mut conn);
+
+    // each statement writes its own segment, holding a range of ranks above the previous one's
+    for batch in 0..8 {
+        format!(
+            "INSERT INTO feed (body, rank) SELECT 'post', {batch} * 100 + x - 400 FROM generate_series(1, 100) x"
+        )
+        .execute(&mut conn);
+    }
+    // and this moves the best row into a segment of its own, out of order with the rest
+    "UPDATE feed SET rank = 10000 WHERE id = 150".execute(&mut conn);
+
+    let (plan,) = "EXPLAIN (FORMAT JSON) SELECT id FROM feed WHERE body @@@ 'post' ORDER BY rank DESC LIMIT 5"
+        .fetch_one::<(Value,)>(&mut conn);
+    assert!(
+        format!("{plan:?}").contains("TopNScanExecState"),
+        "{plan:?}"
+    );
+
+    let newest: Vec<(i32,)> =
+        "SELECT id FROM feed WHERE body @@@ 'post' ORDER BY rank DESC LIMIT 5".fetch(&mut conn);
+    assert_eq!(newest, vec![(150,), (800,), (799,), (798,), (797,)]);
+
+    let oldest: Vec<(i32,)> =
+        "SELECT id FROM feed WHERE body @@@ 'post' ORDER BY rank ASC LIMIT 3".fetch(&mut conn);
+    assert_eq!(oldest, vec![(1,), (2,), (3,)]);
+
+    // segments that are skipped still count when the limit reaches past them
+    let (expected,) = "SELECT array_agg(id ORDER BY rank DESC) FROM (SELECT id, rank FROM feed ORDER BY rank DESC LIMIT 250) t"
+        .fetch_one::<(Vec<i32>,)>(&mut conn);
+    let (actual,) = "SELECT array_agg(id ORDER BY rank DESC) FROM (SELECT id, rank FROM feed WHERE body @@@ 'post' ORDER BY rank DESC LIMIT 250) t"
+        .fetch_one::<(Vec<i32>,)>(&mut conn);
+    assert_eq!(actual, expected);
+}
@@ -0,0 +1,90 @@
+// Copyright (c) 2023-2025 Retake, Inc.
+//
+// This file is part of ParadeDB - Postgres for Search and Analytics
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+mod fixtures;
+
+use fixtures::*;
+use rstest::*;
+use sqlx::PgConnection;
+
+fn problems(conn: &mut PgConnection, heapallindexed: bool) -> Vec<(Option<i64>, String)> {
+    format!("SELECT * FROM paradedb.verify_index('idxverify', heapallindexed => {heapallindexed})")
+        .fetch::<(Option<i64>, String)>(conn)
+}
+
+#[rstest]
+fn verify_index_after_writes(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE verify (id SERIAL PRIMARY KEY, value TEXT NOT NULL, counter INT NOT NULL DEFAULT 0)
+            WITH (autovacuum_enabled = false);
+        INSERT INTO verify (value) SELECT md5(x::text) FROM generate_series(1, 1000) x;
+
+        CREATE INDEX idxverify ON verify
+        USING bm25 (id, value)
+        WITH (key_field = 'id', text_fields = '{"value": {}}');
+    "#
+    .execute(&mut conn);
+    assert_eq!(problems(&mut conn, true), vec![]);
+
+    for _ in 0..5 {
+        "INSERT INTO verify (value) SELECT md5(random()::text) FROM generate_series(1, 100)"
+            .execute(&mut conn);
+    }
+    "UPDATE verify SET value = md5(value) WHERE id % 7 = 0".execute(&mut conn);
+    "DELETE FROM verify WHERE id % 5 = 0".execute(&mut conn);
+    assert_eq!(problems(&mut conn, true), vec![]);
+
+    // the unindexed column can be updated in place, leaving HOT chains behind
+    "UPDATE verify SET counter = counter + 1 WHERE id % 3 = 0".execute(&mut conn);
+    "VACUUM verify".execute(&mut conn);
+    assert_eq!(problems(&mut conn, true), vec![]);
+
+    "SELECT * FROM paradedb.merge('idxverify', max_segments => 1, expunge_deletes => true)"
+        .execute(&mut conn);
+    "VACUUM verify".execute(&mut conn);
+    assert_eq!(problems(&mut conn, true), vec![]);
+}
+
+#[rstest]
+fn verify_index_missing_document(mut conn: PgConnection) {
+    r#"
+        CREATE TABLE verify (id SERIAL PRIMARY KEY, value TEXT NOT NULL);
+        INSERT INTO verify (value) SELECT md5(x::text) FROM generate_series(1, 100) x;
+
+        CREATE INDEX idxverify ON verify
+        USING bm25 (id, value)
+        WITH (key_field = 'id', text_fields = '{"value": {}}');
+    "#
+    .execute(&mut conn);
+
+    // an index that isn't ready for inserts is skipped when the row is written
+    r#"
+        UPDATE pg_index SET indisready = false WHERE indexrelid = 'idxverify'::regclass;
+        INSERT INTO verify (value) VALUES ('not indexed');
+        UPDATE pg_index SET indisready = true WHERE indexrelid = 'idxverify'::regclass;
+    "#
+    .execute(&mut conn);
+    let (ctid,) = "SELECT ctid::text FROM verify WHERE value = 'not indexed'"
+        .fetch_one::<(String,)>(&mut conn);
+
+    // the structure is still sound, so only the heap check finds anything
+    assert_eq!(problems(&mut conn, false), vec![]);
+    assert_eq!(
+        problems(&mut conn, true),
+        vec![(None, format!("heap tuple {ctid} has no live document"))]
+    );
+}
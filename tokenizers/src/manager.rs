@@ -406,6 +406,26 @@ impl SearchTokenizer {
         }
     }
 
+    /// The longest value, in bytes, that this tokenizer indexes as a single unchanged token.
+    /// `None` if it splits or alters what it's given, as every tokenizer but `raw` with
+    /// `lowercase` turned off does.
+    pub fn max_unchanged_token_len(&self) -> Option<usize> {
+        match self {
+            SearchTokenizer::Raw(filters)
+                if filters.lowercase == Some(false) && filters.stemmer.is_none() =>
+            {
+                // `remove_long` drops tokens that are at least as long as its limit
+                Some(
+                    filters
+                        .remove_long
+                        .unwrap_or(DEFAULT_REMOVE_TOKEN_LENGTH)
+                        .saturating_sub(1),
+                )
+            }
+            _ => None,
+        }
+    }
+
     fn filters(&self) -> &SearchTokenizerFilters {
         match self {
             SearchTokenizer::Default(filters) => filters,
@@ -576,6 +596,33 @@ mod tests {
         );
     }
 
+    #[rstest]
+    fn test_max_unchanged_token_len() {
+        let unchanged = |json: &str| {
+            SearchTokenizer::from_json_value(&serde_json::from_str(json).unwrap())
+                .unwrap()
+                .max_unchanged_token_len()
+        };
+
+        assert_eq!(
+            unchanged(r#"{"type": "raw", "lowercase": false}"#),
+            Some(254)
+        );
+        assert_eq!(
+            unchanged(r#"{"type": "raw", "lowercase": false, "remove_long": 1000}"#),
+            Some(999)
+        );
+        assert_eq!(unchanged(r#"{"type": "raw"}"#), None);
+        assert_eq!(
+            unchanged(r#"{"type": "raw", "lowercase": false, "stemmer": "English"}"#),
+            None
+        );
+        assert_eq!(
+            unchanged(r#"{"type": "whitespace", "lowercase": false}"#),
+            None
+        );
+    }
+
     #[rstest]
     fn test_search_normalizer() {
         assert_eq!(SearchNormalizer::Lowercase.name(), "lowercase");